@@ -0,0 +1,621 @@
+//! Encoding of instructions, and expansion of pseudo-instructions.
+//!
+//! Pseudo-instructions use `$at` as a scratch register. Their size only
+//! depends on the kind and the value of literal operands, never on the
+//! address of labels, so that the first pass can lay out the code.
+
+use crate::parse::{Operand, Statement};
+
+/// `$at`, reserved to the assembler.
+const AT: u8 = 1;
+const RA: u8 = 31;
+
+/// Conditions of `c.cond.fmt`, in encoding order.
+const CONDITIONS: [&str; 16] = [
+  "f", "un", "eq", "ueq", "olt", "ult", "ole", "ule", "sf", "ngle", "seq", "ngl", "lt", "nge",
+  "le", "ngt",
+];
+
+/// Encode the instruction `statement` located at `pc`, possibly as several
+/// words. `resolve` gives the address of a label.
+pub fn encode(
+  statement: &Statement,
+  pc: u32,
+  resolve: &dyn Fn(&str) -> Option<u32>,
+) -> Result<Vec<u32>, String> {
+  let mut encoder = Encoder {
+    pc,
+    resolve,
+    words: Vec::new(),
+  };
+
+  encoder.instruction(&statement.name, &statement.operands)?;
+  Ok(encoder.words)
+}
+
+struct Encoder<'a> {
+  pc: u32,
+  resolve: &'a dyn Fn(&str) -> Option<u32>,
+  words: Vec<u32>,
+}
+
+impl Encoder<'_> {
+  fn instruction(&mut self, name: &str, ops: &[Operand]) -> Result<(), String> {
+    match name {
+      // three registers
+      "add" | "addu" | "sub" | "subu" | "and" | "or" | "xor" | "nor" | "slt" | "sltu" | "movz"
+      | "movn" => {
+        let funct = match name {
+          "add" => 0x20,
+          "addu" => 0x21,
+          "sub" => 0x22,
+          "subu" => 0x23,
+          "and" => 0x24,
+          "or" => 0x25,
+          "xor" => 0x26,
+          "nor" => 0x27,
+          "slt" => 0x2a,
+          "sltu" => 0x2b,
+          "movz" => 0xa,
+          _ => 0xb,
+        };
+
+        arity(ops, 3)?;
+        self.r(reg(&ops[1])?, reg(&ops[2])?, reg(&ops[0])?, 0, funct);
+      }
+      "sllv" | "srlv" | "srav" => {
+        let funct = match name {
+          "sllv" => 0x4,
+          "srlv" => 0x6,
+          _ => 0x7,
+        };
+
+        arity(ops, 3)?;
+        self.r(reg(&ops[2])?, reg(&ops[1])?, reg(&ops[0])?, 0, funct);
+      }
+      "sll" | "srl" | "sra" => {
+        let funct = match name {
+          "sll" => 0x0,
+          "srl" => 0x2,
+          _ => 0x3,
+        };
+
+        arity(ops, 3)?;
+        let shamt = ranged(int(&ops[2])?, 0, 31)?;
+        self.r(0, reg(&ops[1])?, reg(&ops[0])?, shamt, funct);
+      }
+      "mul" => {
+        arity(ops, 3)?;
+        let (rd, rs, rt) = (reg(&ops[0])?, reg(&ops[1])?, reg(&ops[2])?);
+        self.emit((0x1c << 26) | r(rs, rt, rd, 0, 0x2));
+      }
+      "clz" | "clo" => {
+        arity(ops, 2)?;
+        let funct = if name == "clz" { 0x20 } else { 0x21 };
+        let (rd, rs) = (reg(&ops[0])?, reg(&ops[1])?);
+        self.emit((0x1c << 26) | r(rs, 0, rd, 0, funct));
+      }
+      "madd" | "maddu" | "msub" | "msubu" => {
+        let funct = match name {
+          "madd" => 0x0,
+          "maddu" => 0x1,
+          "msub" => 0x4,
+          _ => 0x5,
+        };
+
+        arity(ops, 2)?;
+        let (rs, rt) = (reg(&ops[0])?, reg(&ops[1])?);
+        self.emit((0x1c << 26) | r(rs, rt, 0, 0, funct));
+      }
+      "mult" | "multu" | "div" | "divu" | "tge" | "tgeu" | "tlt" | "tltu" | "teq" | "tne" => {
+        let funct = match name {
+          "mult" => 0x18,
+          "multu" => 0x19,
+          "div" => 0x1a,
+          "divu" => 0x1b,
+          "tge" => 0x30,
+          "tgeu" => 0x31,
+          "tlt" => 0x32,
+          "tltu" => 0x33,
+          "teq" => 0x34,
+          _ => 0x36,
+        };
+
+        arity(ops, 2)?;
+        self.r(reg(&ops[0])?, reg(&ops[1])?, 0, 0, funct);
+      }
+      "mfhi" | "mflo" => {
+        arity(ops, 1)?;
+        let funct = if name == "mfhi" { 0x10 } else { 0x12 };
+        self.r(0, 0, reg(&ops[0])?, 0, funct);
+      }
+      "mthi" | "mtlo" => {
+        arity(ops, 1)?;
+        let funct = if name == "mthi" { 0x11 } else { 0x13 };
+        self.r(reg(&ops[0])?, 0, 0, 0, funct);
+      }
+      "jr" => {
+        arity(ops, 1)?;
+        self.r(reg(&ops[0])?, 0, 0, 0, 0x8);
+      }
+      "jalr" => match ops {
+        [rs] => self.r(reg(rs)?, 0, RA, 0, 0x9),
+        [rd, rs] => self.r(reg(rs)?, 0, reg(rd)?, 0, 0x9),
+        _ => return Err(operand_count(1, ops)),
+      },
+      "syscall" => {
+        arity(ops, 0)?;
+        self.r(0, 0, 0, 0, 0xc);
+      }
+      "break" => {
+        let code = match ops {
+          [] => 0,
+          [code] => ranged(int(code)?, 0, 0xfffff)?,
+          _ => return Err(operand_count(1, ops)),
+        };
+
+        self.emit((code << 6) | 0xd);
+      }
+      "nop" => {
+        arity(ops, 0)?;
+        self.emit(0);
+      }
+
+      // immediates
+      "addi" | "addiu" | "slti" | "sltiu" => {
+        let opcode = match name {
+          "addi" => 0x8,
+          "addiu" => 0x9,
+          "slti" => 0xa,
+          _ => 0xb,
+        };
+
+        arity(ops, 3)?;
+        let imm = signed16(int(&ops[2])?)?;
+        self.i(opcode, reg(&ops[1])?, reg(&ops[0])?, imm);
+      }
+      "andi" | "ori" | "xori" => {
+        let opcode = match name {
+          "andi" => 0xc,
+          "ori" => 0xd,
+          _ => 0xe,
+        };
+
+        arity(ops, 3)?;
+        let imm = ranged(int(&ops[2])?, 0, 0xffff)?;
+        self.i(opcode, reg(&ops[1])?, reg(&ops[0])?, imm);
+      }
+      "lui" => {
+        arity(ops, 2)?;
+        let imm = ranged(int(&ops[1])?, -0x8000, 0xffff)?;
+        self.i(0xf, 0, reg(&ops[0])?, imm);
+      }
+      "tgei" | "tgeiu" | "tlti" | "tltiu" | "teqi" | "tnei" => {
+        let code = match name {
+          "tgei" => 0x8,
+          "tgeiu" => 0x9,
+          "tlti" => 0xa,
+          "tltiu" => 0xb,
+          "teqi" => 0xc,
+          _ => 0xe,
+        };
+
+        arity(ops, 2)?;
+        let imm = signed16(int(&ops[1])?)?;
+        self.i(0x1, reg(&ops[0])?, code, imm);
+      }
+
+      // branches and jumps
+      "beq" | "bne" => {
+        arity(ops, 3)?;
+        let opcode = if name == "beq" { 0x4 } else { 0x5 };
+        let (rs, rt) = (reg(&ops[0])?, reg(&ops[1])?);
+        self.branch(opcode, rs, rt, &ops[2])?;
+      }
+      "blez" | "bgtz" => {
+        arity(ops, 2)?;
+        let opcode = if name == "blez" { 0x6 } else { 0x7 };
+        self.branch(opcode, reg(&ops[0])?, 0, &ops[1])?;
+      }
+      "bltz" | "bgez" | "bltzal" | "bgezal" => {
+        let code = match name {
+          "bltz" => 0x0,
+          "bgez" => 0x1,
+          "bltzal" => 0x10,
+          _ => 0x11,
+        };
+
+        arity(ops, 2)?;
+        self.branch(0x1, reg(&ops[0])?, code, &ops[1])?;
+      }
+      "j" | "jal" => {
+        arity(ops, 1)?;
+        let opcode = if name == "j" { 0x2 } else { 0x3 };
+        let target = self.target(&ops[0])?;
+
+        if target % 4 != 0 || (target ^ self.next()) & 0xf000_0000 != 0 {
+          return Err(format!("cannot jump to {target:#010x} from here"));
+        }
+
+        self.emit((opcode << 26) | ((target >> 2) & 0x3ff_ffff));
+      }
+
+      // memory
+      "lb" | "lh" | "lwl" | "lw" | "lbu" | "lhu" | "lwr" | "sb" | "sh" | "swl" | "sw" | "swr"
+      | "ll" | "sc" => {
+        let opcode = match name {
+          "lb" => 0x20,
+          "lh" => 0x21,
+          "lwl" => 0x22,
+          "lw" => 0x23,
+          "lbu" => 0x24,
+          "lhu" => 0x25,
+          "lwr" => 0x26,
+          "sb" => 0x28,
+          "sh" => 0x29,
+          "swl" => 0x2a,
+          "sw" => 0x2b,
+          "swr" => 0x2e,
+          "ll" => 0x30,
+          _ => 0x38,
+        };
+
+        arity(ops, 2)?;
+        self.memory(opcode, reg(&ops[0])?, &ops[1])?;
+      }
+      "lwc1" | "ldc1" | "swc1" | "sdc1" | "l.s" | "l.d" | "s.s" | "s.d" => {
+        let opcode = match name {
+          "lwc1" | "l.s" => 0x31,
+          "ldc1" | "l.d" => 0x35,
+          "swc1" | "s.s" => 0x39,
+          _ => 0x3d,
+        };
+
+        arity(ops, 2)?;
+        self.memory(opcode, freg(&ops[0])?, &ops[1])?;
+      }
+
+      // coprocessor 0
+      "mfc0" | "mtc0" => {
+        arity(ops, 2)?;
+        let rs = if name == "mfc0" { 0x0 } else { 0x4 };
+        self.cop(0x10, rs, reg(&ops[0])?, reg(&ops[1])?, 0, 0);
+      }
+      "tlbr" | "tlbwi" | "tlbwr" | "tlbp" | "eret" => {
+        let funct = match name {
+          "tlbr" => 0x1,
+          "tlbwi" => 0x2,
+          "tlbwr" => 0x6,
+          "tlbp" => 0x8,
+          _ => 0x18,
+        };
+
+        arity(ops, 0)?;
+        self.cop(0x10, 0x10, 0, 0, 0, funct);
+      }
+
+      // coprocessor 1
+      "mfc1" | "mtc1" => {
+        arity(ops, 2)?;
+        let rs = if name == "mfc1" { 0x0 } else { 0x4 };
+        self.cop(0x11, rs, reg(&ops[0])?, freg(&ops[1])?, 0, 0);
+      }
+      "cfc1" | "ctc1" => {
+        arity(ops, 2)?;
+        let rs = if name == "cfc1" { 0x2 } else { 0x6 };
+        self.cop(0x11, rs, reg(&ops[0])?, reg(&ops[1])?, 0, 0);
+      }
+      "bc1f" | "bc1t" => {
+        let (cc, target) = match ops {
+          [target] => (0, target),
+          [cc, target] => (ranged(int(cc)?, 0, 7)?, target),
+          _ => return Err(operand_count(1, ops)),
+        };
+
+        let tf = if name == "bc1f" { 0 } else { 1 };
+        self.branch(0x11, 0x8, (cc << 2 | tf) as u8, target)?;
+      }
+      _ if name.starts_with("c.") => self.compare(name, ops)?,
+      _ if name.contains('.') => self.float(name, ops)?,
+
+      _ => self.pseudo(name, ops)?,
+    }
+
+    Ok(())
+  }
+
+  fn pseudo(&mut self, name: &str, ops: &[Operand]) -> Result<(), String> {
+    match name {
+      "move" => {
+        arity(ops, 2)?;
+        self.r(0, reg(&ops[1])?, reg(&ops[0])?, 0, 0x21);
+      }
+      "neg" | "negu" => {
+        arity(ops, 2)?;
+        let funct = if name == "neg" { 0x22 } else { 0x23 };
+        self.r(0, reg(&ops[1])?, reg(&ops[0])?, 0, funct);
+      }
+      "not" => {
+        arity(ops, 2)?;
+        self.r(reg(&ops[1])?, 0, reg(&ops[0])?, 0, 0x27);
+      }
+      "subi" | "subiu" => {
+        arity(ops, 3)?;
+        let opcode = if name == "subi" { 0x8 } else { 0x9 };
+        let imm = signed16(-int(&ops[2])?)?;
+        self.i(opcode, reg(&ops[1])?, reg(&ops[0])?, imm);
+      }
+      "li" => {
+        arity(ops, 2)?;
+        let rt = reg(&ops[0])?;
+        let value = ranged(int(&ops[1])?, i32::MIN as i64, u32::MAX as i64)?;
+        self.load_immediate(rt, value);
+      }
+      "la" => {
+        arity(ops, 2)?;
+        let rt = reg(&ops[0])?;
+
+        match &ops[1] {
+          Operand::Memory(offset, base) => self.i(0x9, *base, rt, signed16(*offset)?),
+          target => {
+            let addr = self.target(target)?;
+            self.i(0xf, 0, AT, addr >> 16);
+            self.i(0xd, AT, rt, addr & 0xffff);
+          }
+        }
+      }
+      "b" => {
+        arity(ops, 1)?;
+        self.branch(0x4, 0, 0, &ops[0])?;
+      }
+      "bal" => {
+        arity(ops, 1)?;
+        self.branch(0x1, 0, 0x11, &ops[0])?;
+      }
+      "beqz" | "bnez" => {
+        arity(ops, 2)?;
+        let opcode = if name == "beqz" { 0x4 } else { 0x5 };
+        self.branch(opcode, reg(&ops[0])?, 0, &ops[1])?;
+      }
+      "blt" | "bltu" | "ble" | "bleu" | "bgt" | "bgtu" | "bge" | "bgeu" => {
+        arity(ops, 3)?;
+        let rs = reg(&ops[0])?;
+        let rt = match &ops[1] {
+          Operand::Int(value) => {
+            self.load_immediate(AT, ranged(*value, i32::MIN as i64, u32::MAX as i64)?);
+            AT
+          }
+          rt => reg(rt)?,
+        };
+
+        // `$at` is set when the branch is taken for `blt` and `bgt`, when it
+        // is not for `bge` and `ble`
+        let funct = if name.ends_with('u') { 0x2b } else { 0x2a };
+        let (rs, rt, opcode) = match name.trim_end_matches('u') {
+          "blt" => (rs, rt, 0x5),
+          "bge" => (rs, rt, 0x4),
+          "bgt" => (rt, rs, 0x5),
+          _ => (rt, rs, 0x4),
+        };
+
+        self.r(rs, rt, AT, 0, funct);
+        self.branch(opcode, AT, 0, &ops[2])?;
+      }
+
+      _ => return Err(format!("unknown instruction `{name}`")),
+    }
+
+    Ok(())
+  }
+
+  /// `c.cond.fmt [cc,] fs, ft`
+  fn compare(&mut self, name: &str, ops: &[Operand]) -> Result<(), String> {
+    let unknown = || format!("unknown instruction `{name}`");
+
+    let (cond, fmt) = name[2..].rsplit_once('.').ok_or_else(unknown)?;
+    let cond = CONDITIONS
+      .iter()
+      .position(|c| *c == cond)
+      .ok_or_else(unknown)? as u32;
+    let fmt = match fmt {
+      "s" => 0x10,
+      "d" => 0x11,
+      _ => return Err(unknown()),
+    };
+
+    let (cc, fs, ft) = match ops {
+      [fs, ft] => (0, fs, ft),
+      [cc, fs, ft] => (ranged(int(cc)?, 0, 7)?, fs, ft),
+      _ => return Err(operand_count(2, ops)),
+    };
+
+    self.cop(
+      0x11,
+      fmt,
+      freg(ft)?,
+      freg(fs)?,
+      (cc << 2) as u8,
+      0x30 | cond,
+    );
+    Ok(())
+  }
+
+  /// Floating point arithmetic and conversions: `op.fmt fd, fs[, ft]`.
+  fn float(&mut self, name: &str, ops: &[Operand]) -> Result<(), String> {
+    let unknown = || format!("unknown instruction `{name}`");
+
+    let (op, fmt) = name.rsplit_once('.').ok_or_else(unknown)?;
+    let fmt = match fmt {
+      "s" => 0x10,
+      "d" => 0x11,
+      "w" => 0x14,
+      _ => return Err(unknown()),
+    };
+
+    let (funct, operands) = match op {
+      "add" => (0x0, 3),
+      "sub" => (0x1, 3),
+      "mul" => (0x2, 3),
+      "div" => (0x3, 3),
+      "sqrt" => (0x4, 2),
+      "abs" => (0x5, 2),
+      "mov" => (0x6, 2),
+      "neg" => (0x7, 2),
+      "round.w" => (0xc, 2),
+      "trunc.w" => (0xd, 2),
+      "ceil.w" => (0xe, 2),
+      "floor.w" => (0xf, 2),
+      "cvt.s" if fmt != 0x10 => (0x20, 2),
+      "cvt.d" if fmt != 0x11 => (0x21, 2),
+      "cvt.w" if fmt != 0x14 => (0x24, 2),
+      _ => return Err(unknown()),
+    };
+
+    // words are only ever converted
+    if fmt == 0x14 && !op.starts_with("cvt.") {
+      return Err(unknown());
+    }
+
+    arity(ops, operands)?;
+    let (fd, fs) = (freg(&ops[0])?, freg(&ops[1])?);
+    let ft = match ops.get(2) {
+      Some(ft) => freg(ft)?,
+      None => 0,
+    };
+
+    self.cop(0x11, fmt, ft, fs, fd, funct);
+    Ok(())
+  }
+
+  /// Load `value` into `rt` in one instruction when it fits in 16 bits, two
+  /// otherwise.
+  fn load_immediate(&mut self, rt: u8, value: u32) {
+    if (value as i32) >= -0x8000 && (value as i32) < 0x8000 {
+      self.i(0x9, 0, rt, value & 0xffff);
+    } else if value <= 0xffff {
+      self.i(0xd, 0, rt, value);
+    } else {
+      self.i(0xf, 0, AT, value >> 16);
+      self.i(0xd, AT, rt, value & 0xffff);
+    }
+  }
+
+  /// A load or a store. Direct accesses to a label go through `$at`.
+  fn memory(&mut self, opcode: u32, rt: u8, operand: &Operand) -> Result<(), String> {
+    match operand {
+      Operand::Memory(offset, base) => self.i(opcode, *base, rt, signed16(*offset)?),
+      Operand::Int(addr) => {
+        let addr = ranged(*addr, i32::MIN as i64, u32::MAX as i64)? as u32;
+        self.absolute(opcode, rt, addr);
+      }
+      target @ Operand::Label(..) => {
+        let addr = self.target(target)?;
+        self.absolute(opcode, rt, addr);
+      }
+      other => return Err(format!("expected a memory operand, found {}", other.kind())),
+    }
+
+    Ok(())
+  }
+
+  fn absolute(&mut self, opcode: u32, rt: u8, addr: u32) {
+    // the offset is sign extended
+    self.i(0xf, 0, AT, addr.wrapping_add(0x8000) >> 16);
+    self.i(opcode, AT, rt, addr & 0xffff);
+  }
+
+  fn branch(&mut self, opcode: u32, rs: u8, rt: u8, target: &Operand) -> Result<(), String> {
+    let target = self.target(target)?;
+    let offset = target.wrapping_sub(self.next()) as i32;
+
+    if offset % 4 != 0 || !(-0x8000..0x8000).contains(&(offset >> 2)) {
+      return Err(format!("branch target {target:#010x} is out of reach"));
+    }
+
+    self.i(opcode, rs, rt, (offset >> 2) as u32 & 0xffff);
+    Ok(())
+  }
+
+  /// Address designated by a label or an integer.
+  fn target(&self, operand: &Operand) -> Result<u32, String> {
+    match operand {
+      Operand::Label(name, offset) => match (self.resolve)(name) {
+        Some(addr) => Ok(addr.wrapping_add(*offset as u32)),
+        None => Err(format!("undefined label `{name}`")),
+      },
+      Operand::Int(addr) => Ok(ranged(*addr, 0, u32::MAX as i64)?),
+      other => Err(format!("expected a label, found {}", other.kind())),
+    }
+  }
+
+  /// Address of the instruction following the one being emitted.
+  fn next(&self) -> u32 {
+    self.pc.wrapping_add(4 * self.words.len() as u32 + 4)
+  }
+
+  fn emit(&mut self, word: u32) {
+    self.words.push(word);
+  }
+
+  fn r(&mut self, rs: u8, rt: u8, rd: u8, shamt: u32, funct: u32) {
+    self.emit(r(rs, rt, rd, shamt, funct));
+  }
+
+  fn i(&mut self, opcode: u32, rs: u8, rt: u8, imm: u32) {
+    self.emit((opcode << 26) | ((rs as u32) << 21) | ((rt as u32) << 16) | (imm & 0xffff));
+  }
+
+  fn cop(&mut self, opcode: u32, rs: u32, rt: u8, rd: u8, shamt: u8, funct: u32) {
+    self.emit((opcode << 26) | (rs << 21) | r(0, rt, rd, shamt as u32, funct));
+  }
+}
+
+fn r(rs: u8, rt: u8, rd: u8, shamt: u32, funct: u32) -> u32 {
+  ((rs as u32) << 21) | ((rt as u32) << 16) | ((rd as u32) << 11) | (shamt << 6) | funct
+}
+
+fn arity(ops: &[Operand], expected: usize) -> Result<(), String> {
+  match ops.len() == expected {
+    true => Ok(()),
+    false => Err(operand_count(expected, ops)),
+  }
+}
+
+fn operand_count(expected: usize, ops: &[Operand]) -> String {
+  format!("expected {expected} operand(s), found {}", ops.len())
+}
+
+fn reg(operand: &Operand) -> Result<u8, String> {
+  match operand {
+    Operand::Register(n) => Ok(*n),
+    other => Err(format!("expected a register, found {}", other.kind())),
+  }
+}
+
+fn freg(operand: &Operand) -> Result<u8, String> {
+  match operand {
+    Operand::FloatRegister(n) => Ok(*n),
+    other => Err(format!(
+      "expected a floating point register, found {}",
+      other.kind()
+    )),
+  }
+}
+
+fn int(operand: &Operand) -> Result<i64, String> {
+  match operand {
+    Operand::Int(n) => Ok(*n),
+    other => Err(format!("expected an integer, found {}", other.kind())),
+  }
+}
+
+fn ranged(value: i64, min: i64, max: i64) -> Result<u32, String> {
+  match (min..=max).contains(&value) {
+    true => Ok(value as u32),
+    false => Err(format!("{value} is out of range, expected {min}..={max}")),
+  }
+}
+
+fn signed16(value: i64) -> Result<u32, String> {
+  ranged(value, -0x8000, 0x7fff).map(|v| v & 0xffff)
+}
@@ -0,0 +1,422 @@
+//! Assembler for MIPS source, in the dialect of MARS and SPIM.
+//!
+//! ```asm
+//! .data
+//! msg: .asciiz "hello world"
+//! .text
+//! main:
+//!   la $a0, msg
+//!   li $v0, 4
+//!   syscall
+//! ```
+//!
+//! Supported directives are `.text`, `.data`, `.ktext`, `.kdata`, `.extern`,
+//! `.word`, `.half`, `.byte`, `.float`, `.double`, `.ascii`, `.asciiz`,
+//! `.space`, `.align` and `.globl`, which is ignored. Besides the instructions
+//! the CPU implements, common pseudo-instructions are expanded: `li`, `la`,
+//! `move`, `neg`, `not`, `subi`, `b`, `bal`, `beqz`, `bnez`, the `blt` family
+//! and loads and stores to a label.
+//!
+//! Assembling takes two passes. The first lays out every section and collects
+//! the labels, the second encodes the instructions.
+
+use mips_cpu::mem::{self, EXTERN_START};
+use mips_program::{ProgramData, Section};
+use parse::{Operand, Statement};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+mod encode;
+mod parse;
+
+/// Size of the `.data` section, up to the heap.
+const DATA_SIZE: u32 = 0x30000;
+/// Size of the `.extern` section.
+const EXTERN_SIZE: u32 = 0x10000;
+
+/// An error, and the line it was found on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+  /// Line number, starting at 1.
+  pub line: usize,
+  pub message: String,
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+impl std::error::Error for Error {}
+
+/// An assembled program.
+#[derive(Debug, Clone)]
+pub struct Assembly {
+  pub program: ProgramData,
+  /// Source line of each instruction word, by address.
+  lines: BTreeMap<u32, usize>,
+}
+
+impl Assembly {
+  /// Line the instruction at `addr` was assembled from.
+  pub fn line_at(&self, addr: u32) -> Option<usize> {
+    self.lines.get(&addr).copied()
+  }
+
+  /// Address of the first instruction assembled from `line`.
+  pub fn addr_of_line(&self, line: usize) -> Option<u32> {
+    self
+      .lines
+      .iter()
+      .find_map(|(addr, l)| (*l == line).then_some(*addr))
+  }
+
+  /// Every instruction word address, along with its source line, by address.
+  pub fn lines(&self) -> impl Iterator<Item = (u32, usize)> + '_ {
+    self.lines.iter().map(|(addr, line)| (*addr, *line))
+  }
+}
+
+/// Assemble `source`. Returns every error found, by line.
+pub fn assemble(source: &str) -> Result<Assembly, Vec<Error>> {
+  let mut errors = Vec::new();
+  let mut lines = Vec::new();
+
+  for (i, line) in source.lines().enumerate() {
+    match parse::parse_line(line) {
+      Ok(parsed) => lines.push((i + 1, parsed)),
+      Err(message) => errors.push(Error {
+        line: i + 1,
+        message,
+      }),
+    }
+  }
+
+  let mut layout = Layout::default();
+  for (line, parsed) in &lines {
+    if let Err(message) = layout.line(parsed) {
+      errors.push(Error {
+        line: *line,
+        message,
+      });
+    }
+  }
+  layout.flush_labels();
+
+  let mut output = Layout {
+    known: Some(layout.symbols),
+    ..Layout::default()
+  };
+
+  // lines which failed already fail the same way, and emit nothing either
+  let failed = errors.iter().map(|e| e.line).collect::<HashSet<_>>();
+  for (line, parsed) in lines.iter().filter(|(line, _)| !failed.contains(line)) {
+    if let Err(message) = output.line(parsed) {
+      errors.push(Error {
+        line: *line,
+        message,
+      });
+    }
+
+    for addr in std::mem::take(&mut output.emitted) {
+      output.lines.insert(addr, *line);
+    }
+  }
+
+  if !errors.is_empty() {
+    errors.sort_by_key(|e| e.line);
+    return Err(errors);
+  }
+
+  let mut builder = ProgramData::builder()
+    .text(output.text.bytes)
+    .data(output.data.bytes)
+    .ktext(output.ktext.bytes)
+    .kdata(output.kdata.bytes);
+
+  let mut labels = output.symbols.into_iter().collect::<Vec<_>>();
+  labels.sort_by_key(|(_, (_, addr))| *addr);
+
+  for (name, (section, addr)) in labels {
+    builder = builder.label(section, name, (addr - start(section)) as usize);
+  }
+
+  Ok(Assembly {
+    program: builder.build(),
+    lines: output.lines,
+  })
+}
+
+fn start(section: Section) -> u32 {
+  *mem::section_range(section).start()
+}
+
+/// Content of a section.
+#[derive(Debug, Default)]
+struct Segment {
+  bytes: Vec<u8>,
+  /// Bytes reserved without content, for `.extern`.
+  reserved: u32,
+}
+
+impl Segment {
+  fn len(&self) -> u32 {
+    self.bytes.len() as u32 + self.reserved
+  }
+
+  fn align(&mut self, alignment: u32) {
+    while self.len() % alignment != 0 {
+      self.bytes.push(0);
+    }
+  }
+}
+
+/// Sections being laid out, and the labels found.
+#[derive(Debug)]
+struct Layout {
+  section: Section,
+  text: Segment,
+  data: Segment,
+  ktext: Segment,
+  kdata: Segment,
+  r#extern: Segment,
+  symbols: HashMap<String, (Section, u32)>,
+  /// Every label of the program, in the second pass. In the first one
+  /// addresses are not known yet, labels stand for the instruction using
+  /// them.
+  known: Option<HashMap<String, (Section, u32)>>,
+  /// Labels waiting for the next item, to take its address once aligned.
+  pending: Vec<String>,
+  /// Addresses of the instruction words emitted by the last line.
+  emitted: Vec<u32>,
+  lines: BTreeMap<u32, usize>,
+}
+
+impl Default for Layout {
+  fn default() -> Self {
+    Layout {
+      section: Section::Text,
+      text: Segment::default(),
+      data: Segment::default(),
+      ktext: Segment::default(),
+      kdata: Segment::default(),
+      r#extern: Segment::default(),
+      symbols: HashMap::new(),
+      known: None,
+      pending: Vec::new(),
+      emitted: Vec::new(),
+      lines: BTreeMap::new(),
+    }
+  }
+}
+
+impl Layout {
+  fn segment(&mut self) -> &mut Segment {
+    match self.section {
+      Section::Text => &mut self.text,
+      Section::KText => &mut self.ktext,
+      Section::KData => &mut self.kdata,
+      Section::Extern => &mut self.r#extern,
+      Section::Data | Section::Heap => &mut self.data,
+    }
+  }
+
+  /// Address of the next byte of the current section.
+  fn cursor(&mut self) -> u32 {
+    start(self.section) + self.segment().len()
+  }
+
+  fn line(&mut self, line: &parse::Line) -> Result<(), String> {
+    self.emitted.clear();
+
+    for label in &line.labels {
+      if self.symbols.contains_key(label) || self.pending.contains(label) {
+        return Err(format!("label `{label}` is defined twice"));
+      }
+
+      self.pending.push(label.clone());
+    }
+
+    let Some(statement) = &line.statement else {
+      return Ok(());
+    };
+
+    match statement.name.strip_prefix('.') {
+      Some(directive) => self.directive(directive, &statement.operands),
+      None => self.instruction(statement),
+    }
+  }
+
+  fn instruction(&mut self, statement: &Statement) -> Result<(), String> {
+    if !matches!(self.section, Section::Text | Section::KText) {
+      return Err("instructions belong in .text or .ktext".to_owned());
+    }
+
+    self.segment().align(4);
+    self.flush_labels();
+
+    let pc = self.cursor();
+    let resolve = |name: &str| match &self.known {
+      Some(known) => known.get(name).map(|(_, addr)| *addr),
+      None => Some(pc),
+    };
+    let words = encode::encode(statement, pc, &resolve)?;
+
+    for (i, word) in words.iter().enumerate() {
+      self.emitted.push(pc + 4 * i as u32);
+      self.segment().bytes.extend_from_slice(&word.to_le_bytes());
+    }
+
+    self.check_size()
+  }
+
+  fn directive(&mut self, name: &str, ops: &[Operand]) -> Result<(), String> {
+    match name {
+      "text" | "data" | "ktext" | "kdata" => {
+        if !ops.is_empty() {
+          return Err(format!(".{name} does not take an address"));
+        }
+
+        self.flush_labels();
+        self.section = match name {
+          "text" => Section::Text,
+          "data" => Section::Data,
+          "ktext" => Section::KText,
+          _ => Section::KData,
+        };
+      }
+      "globl" | "global" => (),
+      "extern" => {
+        let [Operand::Label(label, 0), Operand::Int(size)] = ops else {
+          return Err(".extern expects a label and a size".to_owned());
+        };
+
+        if self.symbols.contains_key(label) {
+          return Err(format!("label `{label}` is defined twice"));
+        }
+
+        let addr = EXTERN_START + self.r#extern.len();
+        self.symbols.insert(label.clone(), (Section::Extern, addr));
+        self.r#extern.reserved += *size as u32;
+        self.check_extern()?;
+      }
+      "align" => {
+        let [Operand::Int(n @ 0..=3)] = ops else {
+          return Err(".align expects 0, 1, 2 or 3".to_owned());
+        };
+
+        self.segment().align(1 << n);
+      }
+      "space" => {
+        let [Operand::Int(n @ 0..)] = ops else {
+          return Err(".space expects a size".to_owned());
+        };
+
+        self.flush_labels();
+        let segment = self.segment();
+        segment.bytes.resize(segment.bytes.len() + *n as usize, 0);
+      }
+      "ascii" | "asciiz" => {
+        self.flush_labels();
+
+        for op in ops {
+          let Operand::Str(s) = op else {
+            return Err(format!(".{name} expects strings"));
+          };
+
+          let segment = self.segment();
+          segment.bytes.extend_from_slice(s);
+          if name == "asciiz" {
+            segment.bytes.push(0);
+          }
+        }
+      }
+      "byte" | "half" | "word" => {
+        let size = match name {
+          "byte" => 1,
+          "half" => 2,
+          _ => 4,
+        };
+
+        self.segment().align(size);
+        self.flush_labels();
+
+        for op in ops {
+          let value = match op {
+            Operand::Int(n) => *n,
+            Operand::Label(label, offset) if size == 4 => match &self.known {
+              Some(known) => match known.get(label) {
+                Some((_, addr)) => *addr as i64 + offset,
+                None => return Err(format!("undefined label `{label}`")),
+              },
+              None => 0,
+            },
+            other => return Err(format!("expected an integer, found {}", other.kind())),
+          };
+
+          let min = -(1 << (size * 8 - 1));
+          let max = (1i64 << (size * 8)) - 1;
+          if !(min..=max).contains(&value) {
+            return Err(format!("{value} does not fit in a {name}"));
+          }
+
+          let bytes = (value as u32).to_le_bytes();
+          self
+            .segment()
+            .bytes
+            .extend_from_slice(&bytes[..size as usize]);
+        }
+      }
+      "float" | "double" => {
+        self.segment().align(if name == "float" { 4 } else { 8 });
+        self.flush_labels();
+
+        for op in ops {
+          let value = match op {
+            Operand::Float(f) => *f,
+            Operand::Int(n) => *n as f64,
+            other => return Err(format!("expected a number, found {}", other.kind())),
+          };
+
+          let segment = self.segment();
+          match name {
+            "float" => segment.bytes.extend((value as f32).to_le_bytes()),
+            _ => segment.bytes.extend(value.to_le_bytes()),
+          }
+        }
+      }
+      _ => return Err(format!("unknown directive `.{name}`")),
+    }
+
+    self.check_size()
+  }
+
+  /// Give the pending labels the current address.
+  fn flush_labels(&mut self) {
+    let section = self.section;
+    let addr = self.cursor();
+
+    for label in std::mem::take(&mut self.pending) {
+      self.symbols.insert(label, (section, addr));
+    }
+  }
+
+  fn check_size(&mut self) -> Result<(), String> {
+    match self.section {
+      Section::Data if self.data.len() > DATA_SIZE => Err(format!(
+        ".data is full, it holds up to {DATA_SIZE:#x} bytes"
+      )),
+      _ => Ok(()),
+    }
+  }
+
+  fn check_extern(&self) -> Result<(), String> {
+    match self.r#extern.len() > EXTERN_SIZE {
+      true => Err(format!(
+        ".extern is full, it holds up to {EXTERN_SIZE:#x} bytes"
+      )),
+      false => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,252 @@
+//! Splits a line of source into labels and a statement with its operands.
+
+use mips_cpu::register::NAMES;
+use std::iter::Peekable;
+use std::str;
+
+type Chars<'a> = Peekable<str::Chars<'a>>;
+
+/// A line of source, once comments are stripped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Line {
+  pub labels: Vec<String>,
+  pub statement: Option<Statement>,
+}
+
+/// An instruction, or a directive when `name` starts with a dot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+  pub name: String,
+  pub operands: Vec<Operand>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+  Register(u8),
+  FloatRegister(u8),
+  Int(i64),
+  Float(f64),
+  Str(Vec<u8>),
+  /// A label, with an optional offset: `array+8`.
+  Label(String, i64),
+  /// `offset(base)`, or `(base)`.
+  Memory(i64, u8),
+}
+
+impl Operand {
+  /// Short description, for error messages.
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Operand::Register(_) => "a register",
+      Operand::FloatRegister(_) => "a floating point register",
+      Operand::Int(_) => "an integer",
+      Operand::Float(_) => "a floating point number",
+      Operand::Str(_) => "a string",
+      Operand::Label(..) => "a label",
+      Operand::Memory(..) => "a memory operand",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Ident(String),
+  Register(String),
+  Int(i64),
+  Float(f64),
+  Str(Vec<u8>),
+  Comma,
+  Colon,
+  LParen,
+  RParen,
+  Plus,
+  Minus,
+}
+
+pub fn parse_line(line: &str) -> Result<Line, String> {
+  let mut tokens = tokenize(line)?.into_iter().peekable();
+  let mut parsed = Line::default();
+
+  while let Some(token) = tokens.next() {
+    let Token::Ident(name) = token else {
+      return Err("expected an instruction or a label".to_owned());
+    };
+
+    if tokens.peek() == Some(&Token::Colon) {
+      tokens.next();
+      parsed.labels.push(name);
+      continue;
+    }
+
+    let rest = tokens.collect::<Vec<_>>();
+    let operands = match rest.is_empty() {
+      true => Vec::new(),
+      false => rest
+        .split(|t| *t == Token::Comma)
+        .map(operand)
+        .collect::<Result<_, _>>()?,
+    };
+
+    parsed.statement = Some(Statement { name, operands });
+    break;
+  }
+
+  Ok(parsed)
+}
+
+fn operand(tokens: &[Token]) -> Result<Operand, String> {
+  use Token::*;
+
+  match tokens {
+    [] => Err("missing operand".to_owned()),
+    [Register(name)] => register(name),
+    [Int(n)] => Ok(Operand::Int(*n)),
+    [Minus, Int(n)] => Ok(Operand::Int(-n)),
+    [Float(f)] => Ok(Operand::Float(*f)),
+    [Minus, Float(f)] => Ok(Operand::Float(-f)),
+    [Str(s)] => Ok(Operand::Str(s.clone())),
+    [Ident(name)] => Ok(Operand::Label(name.clone(), 0)),
+    [Ident(name), Plus, Int(n)] => Ok(Operand::Label(name.clone(), *n)),
+    [Ident(name), Minus, Int(n)] => Ok(Operand::Label(name.clone(), -n)),
+    [LParen, Register(base), RParen] => Ok(Operand::Memory(0, base_register(base)?)),
+    [Int(n), LParen, Register(base), RParen] => Ok(Operand::Memory(*n, base_register(base)?)),
+    [Minus, Int(n), LParen, Register(base), RParen] => {
+      Ok(Operand::Memory(-n, base_register(base)?))
+    }
+    _ => Err("malformed operand".to_owned()),
+  }
+}
+
+fn base_register(name: &str) -> Result<u8, String> {
+  match register(name)? {
+    Operand::Register(n) => Ok(n),
+    _ => Err(format!("`${name}` cannot be used as a base register")),
+  }
+}
+
+/// Resolve a register from its name without the `$`: an ABI name, a number
+/// or `f` followed by a number.
+fn register(name: &str) -> Result<Operand, String> {
+  if let Some(n) = NAMES.iter().position(|n| *n == name) {
+    return Ok(Operand::Register(n as u8));
+  }
+
+  let number = |digits: &str| digits.parse::<u8>().ok().filter(|n| *n < 32);
+
+  if name == "s8" {
+    Ok(Operand::Register(30))
+  } else if let Some(n) = number(name) {
+    Ok(Operand::Register(n))
+  } else if let Some(n) = name.strip_prefix('f').and_then(number) {
+    Ok(Operand::FloatRegister(n))
+  } else {
+    Err(format!("unknown register `${name}`"))
+  }
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+  let mut tokens = Vec::new();
+  let mut chars = line.chars().peekable();
+
+  while let Some(&c) = chars.peek() {
+    match c {
+      '#' => break,
+      c if c.is_whitespace() => {
+        chars.next();
+      }
+      ',' | ':' | '(' | ')' | '+' | '-' => {
+        chars.next();
+        tokens.push(match c {
+          ',' => Token::Comma,
+          ':' => Token::Colon,
+          '(' => Token::LParen,
+          ')' => Token::RParen,
+          '+' => Token::Plus,
+          _ => Token::Minus,
+        });
+      }
+      '$' => {
+        chars.next();
+        tokens.push(Token::Register(take_word(&mut chars)));
+      }
+      '"' => {
+        chars.next();
+        tokens.push(Token::Str(quoted(&mut chars, '"')?));
+      }
+      '\'' => {
+        chars.next();
+        let bytes = quoted(&mut chars, '\'')?;
+
+        match bytes[..] {
+          [byte] => tokens.push(Token::Int(byte as i64)),
+          _ => return Err("a character literal holds a single character".to_owned()),
+        }
+      }
+      c if c.is_ascii_digit() => tokens.push(number(&take_word(&mut chars))?),
+      c if c.is_alphabetic() || c == '_' || c == '.' => {
+        tokens.push(Token::Ident(take_word(&mut chars)))
+      }
+      c => return Err(format!("unexpected character `{c}`")),
+    }
+  }
+
+  Ok(tokens)
+}
+
+fn take_word(chars: &mut Chars) -> String {
+  let mut word = String::new();
+
+  while let Some(&c) = chars.peek() {
+    if !(c.is_alphanumeric() || c == '_' || c == '.') {
+      break;
+    }
+
+    word.push(c);
+    chars.next();
+  }
+
+  word
+}
+
+fn number(word: &str) -> Result<Token, String> {
+  let int = if let Some(hex) = word.strip_prefix("0x") {
+    i64::from_str_radix(hex, 16)
+  } else if let Some(bin) = word.strip_prefix("0b") {
+    i64::from_str_radix(bin, 2)
+  } else {
+    word.parse()
+  };
+
+  match int {
+    Ok(n) => Ok(Token::Int(n)),
+    Err(_) => word
+      .parse()
+      .map(Token::Float)
+      .map_err(|_| format!("invalid number `{word}`")),
+  }
+}
+
+/// Read up to the closing `quote`, processing escape sequences.
+fn quoted(chars: &mut Chars, quote: char) -> Result<Vec<u8>, String> {
+  let mut bytes = Vec::new();
+
+  loop {
+    let c = match chars.next() {
+      None => return Err("unterminated string".to_owned()),
+      Some(c) if c == quote => return Ok(bytes),
+      Some('\\') => match chars.next() {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some(c @ ('\\' | '\'' | '"')) => c,
+        Some(c) => return Err(format!("unknown escape sequence `\\{c}`")),
+        None => return Err("unterminated string".to_owned()),
+      },
+      Some(c) => c,
+    };
+
+    let mut buffer = [0; 4];
+    bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+  }
+}
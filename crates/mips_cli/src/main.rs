@@ -0,0 +1,222 @@
+use mips_cpu::console::Stdio;
+use mips_cpu::convention::ConventionChecker;
+use mips_cpu::environment::Environment;
+use mips_cpu::exception::Exception;
+use mips_cpu::fs::Host;
+use mips_cpu::predict::{BranchSimulator, PredictorKind};
+use mips_cpu::syscall::{self, Outcome};
+use mips_cpu::uninit::UninitChecker;
+use mips_cpu::Cpu;
+use mips_program::ProgramData;
+use std::cell::RefCell;
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::time::Duration;
+
+const USAGE: &str = "usage: mips_cli <text.bin> [options]
+
+options:
+  --max-cycles <n>       give up after n cycles (default 1000000)
+  --predictor <kind>     simulate a branch predictor: taken, not-taken, 1bit,
+                         2bit, gshare or btb
+  --predictor-bits <n>   predictor tables hold 2^n entries (default 10)
+  --writable-text        allow stores to .text, for self-modifying code
+  --strict-memory        report loads from memory never written
+  --check-convention     report violations of the calling convention
+  --fs-root <dir>        directory file system calls are confined to
+                         (default: the current directory)
+  --deterministic        derive the time from the cycle count, seed random
+                         number generators with their id and skip sleeps";
+
+/// Why the run ended.
+enum Stop {
+  Exit(i32),
+  Exception(Exception),
+  Syscall(syscall::SyscallError),
+  CycleLimit,
+}
+
+struct Options {
+  text_path: String,
+  max_cycles: u64,
+  predictor: Option<PredictorKind>,
+  predictor_bits: u32,
+  writable_text: bool,
+  strict_memory: bool,
+  check_convention: bool,
+  fs_root: String,
+  deterministic: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+  let mut options = Options {
+    text_path: args.next().ok_or("missing program")?,
+    max_cycles: 1_000_000,
+    predictor: None,
+    predictor_bits: 10,
+    writable_text: false,
+    strict_memory: false,
+    check_convention: false,
+    fs_root: ".".to_owned(),
+    deterministic: false,
+  };
+
+  while let Some(arg) = args.next() {
+    let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+
+    match arg.as_str() {
+      "--max-cycles" => {
+        options.max_cycles = value()?.parse().map_err(|e| format!("--max-cycles: {e}"))?;
+      }
+      "--predictor" => {
+        options.predictor = Some(value()?.parse().map_err(|e| format!("{e}"))?);
+      }
+      "--predictor-bits" => {
+        options.predictor_bits = value()?
+          .parse()
+          .ok()
+          .filter(|bits| (1..=24).contains(bits))
+          .ok_or("--predictor-bits: expected a number between 1 and 24")?;
+      }
+      "--writable-text" => options.writable_text = true,
+      "--strict-memory" => options.strict_memory = true,
+      "--check-convention" => options.check_convention = true,
+      "--fs-root" => options.fs_root = value()?,
+      "--deterministic" => options.deterministic = true,
+      _ => return Err(format!("unknown option {arg}")),
+    }
+  }
+
+  Ok(options)
+}
+
+/// Run `cpu`, servicing system calls, until the program ends or `max_cycles`
+/// have run.
+fn run(cpu: &mut Cpu, max_cycles: u64) -> Stop {
+  while cpu.cycles() < max_cycles {
+    match cpu.cycle() {
+      Ok(()) => (),
+      Err(Exception::Syscall) => match syscall::service(cpu, &mut Stdio) {
+        // reading stdin blocks, input is never awaited
+        Ok(Outcome::Continue | Outcome::AwaitInput) => (),
+        Ok(Outcome::Sleep(_)) if cpu.environment().is_deterministic() => (),
+        Ok(Outcome::Sleep(millis)) => std::thread::sleep(Duration::from_millis(millis as u64)),
+        Ok(Outcome::Exit(code)) => return Stop::Exit(code),
+        Err(e) => return Stop::Syscall(e),
+      },
+      Err(e) => return Stop::Exception(e),
+    }
+  }
+
+  Stop::CycleLimit
+}
+
+fn main() -> ExitCode {
+  let options = match parse_args(std::env::args().skip(1)) {
+    Ok(options) => options,
+    Err(e) => {
+      eprintln!("{e}\n\n{USAGE}");
+      return ExitCode::FAILURE;
+    }
+  };
+
+  let text = match std::fs::read(&options.text_path) {
+    Ok(text) => text,
+    Err(e) => {
+      eprintln!("cannot read {}: {e}", options.text_path);
+      return ExitCode::FAILURE;
+    }
+  };
+
+  let program = ProgramData::builder()
+    .text(text)
+    .writable_text(options.writable_text)
+    .build();
+  let program = Rc::new(program);
+  let mut cpu = Cpu::new(Rc::clone(&program));
+  cpu.set_file_system(Host::new(&options.fs_root));
+
+  if options.deterministic {
+    *cpu.environment_mut() = Environment::deterministic();
+  }
+
+  let branches = options.predictor.map(|kind| {
+    let simulator = BranchSimulator::new(&program, kind.build(options.predictor_bits));
+    let simulator = Rc::new(RefCell::new(simulator));
+    cpu.add_observer(simulator.clone());
+    simulator
+  });
+
+  let uninit = options.strict_memory.then(|| {
+    cpu.memory_mut().set_strict(true);
+    let checker = Rc::new(RefCell::new(UninitChecker::new(&program)));
+    cpu.add_observer(checker.clone());
+    checker
+  });
+
+  let convention = options.check_convention.then(|| {
+    let checker = ConventionChecker::new(&program, cpu.registers());
+    let checker = Rc::new(RefCell::new(checker));
+    cpu.add_observer(checker.clone());
+    checker
+  });
+
+  let stop = run(&mut cpu, options.max_cycles);
+  let (pc, cycles) = (cpu.registers().pc, cpu.cycles());
+
+  let status = match stop {
+    Stop::Exit(code) => {
+      eprintln!("exited with code {code} after {cycles} cycles");
+      exit_code(code)
+    }
+    Stop::Exception(Exception::AddrLoadFetch) if cpu.memory().fetch(pc).is_err() => {
+      eprintln!("dropped off the program at {pc:#010x} after {cycles} cycles");
+      ExitCode::FAILURE
+    }
+    Stop::Exception(exception) => {
+      eprintln!("stopped by {exception:?} at {pc:#010x} after {cycles} cycles");
+      ExitCode::FAILURE
+    }
+    Stop::Syscall(e) => {
+      eprintln!("{e} at {pc:#010x} after {cycles} cycles");
+      ExitCode::FAILURE
+    }
+    Stop::CycleLimit => {
+      eprintln!("cycle limit reached at {pc:#010x} after {cycles} cycles");
+      ExitCode::FAILURE
+    }
+  };
+
+  if let Some(branches) = branches {
+    if let Err(e) = branches.borrow().write_report(std::io::stdout().lock()) {
+      eprintln!("{e}");
+      return ExitCode::FAILURE;
+    }
+  }
+
+  if let Some(uninit) = uninit {
+    if let Err(e) = uninit.borrow().write_report(std::io::stdout().lock()) {
+      eprintln!("{e}");
+      return ExitCode::FAILURE;
+    }
+  }
+
+  if let Some(convention) = convention {
+    if let Err(e) = convention.borrow().write_report(std::io::stdout().lock()) {
+      eprintln!("{e}");
+      return ExitCode::FAILURE;
+    }
+  }
+
+  status
+}
+
+/// The status of the process for a program exiting with `code`. Codes which
+/// do not fit a status byte still fail, instead of wrapping around to 0.
+fn exit_code(code: i32) -> ExitCode {
+  match code {
+    0 => ExitCode::SUCCESS,
+    1..=255 => ExitCode::from(code as u8),
+    _ => ExitCode::from(u8::MAX),
+  }
+}
@@ -0,0 +1,442 @@
+//! L1 cache simulation, for studying the hit rate of access patterns.
+//!
+//! Caches only keep statistics: memory always holds the up to date values,
+//! whatever the write policy. A `CacheSimulator` registered as an observer
+//! feeds instruction fetches to an I-cache and data accesses to a D-cache.
+
+use crate::mem::{Access, AccessKind};
+use crate::observer::CpuObserver;
+use crate::symbols::Symbols;
+use mips_program::ProgramData;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::{error, fmt};
+
+/// Block replacement policy of set associative caches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replacement {
+  /// Evict the least recently used block.
+  Lru,
+  /// Evict the block which was brought in first.
+  Fifo,
+  /// Evict a pseudo-random block. The sequence is the same on every run.
+  Random,
+}
+
+/// What happens to memory when a store hits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WritePolicy {
+  /// Every store is written to memory.
+  WriteThrough,
+  /// Stores mark the block dirty, it is written back to memory on eviction.
+  WriteBack,
+}
+
+/// Geometry and policies of a cache. The default matches MARS's Data Cache
+/// Simulator: 8 direct mapped blocks of 4 words, LRU, write-back with write
+/// allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+  /// Total capacity in bytes.
+  pub size: u32,
+  /// Block size in bytes.
+  pub block_size: u32,
+  /// Number of blocks per set, 1 for a direct mapped cache.
+  pub associativity: u32,
+  pub replacement: Replacement,
+  pub write_policy: WritePolicy,
+  /// Whether a store miss brings the block into the cache.
+  pub write_allocate: bool,
+}
+
+impl Default for CacheConfig {
+  fn default() -> Self {
+    CacheConfig {
+      size: 128,
+      block_size: 16,
+      associativity: 1,
+      replacement: Replacement::Lru,
+      write_policy: WritePolicy::WriteBack,
+      write_allocate: true,
+    }
+  }
+}
+
+impl CacheConfig {
+  /// Number of sets.
+  pub fn sets(&self) -> u32 {
+    self.size / (self.block_size * self.associativity)
+  }
+
+  /// Check the geometry is consistent.
+  pub fn validate(&self) -> Result<(), ConfigError> {
+    if !self.block_size.is_power_of_two() || self.block_size < 4 {
+      return Err(ConfigError::BlockSize(self.block_size));
+    }
+
+    if self.associativity == 0 {
+      return Err(ConfigError::Associativity(self.associativity));
+    }
+
+    let set_size = self.block_size.saturating_mul(self.associativity);
+
+    if self.size == 0 || self.size % set_size != 0 || !(self.size / set_size).is_power_of_two() {
+      return Err(ConfigError::Size(self.size));
+    }
+
+    Ok(())
+  }
+}
+
+/// Inconsistent cache geometry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+  /// Block sizes must be powers of two, of at least one word.
+  BlockSize(u32),
+  /// Associativity must be at least 1.
+  Associativity(u32),
+  /// The size must be a power of two number of sets.
+  Size(u32),
+}
+
+impl fmt::Display for ConfigError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ConfigError::BlockSize(n) => write!(
+        f,
+        "invalid block size {n}: must be a power of two of at least 4 bytes"
+      ),
+      ConfigError::Associativity(n) => write!(f, "invalid associativity {n}"),
+      ConfigError::Size(n) => write!(
+        f,
+        "invalid cache size {n}: must be a power of two number of sets"
+      ),
+    }
+  }
+}
+
+impl error::Error for ConfigError {}
+
+/// Hit and miss counts of a cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+  pub hits: u64,
+  pub misses: u64,
+  /// Blocks read from memory.
+  pub block_reads: u64,
+  /// Dirty blocks written back to memory on eviction.
+  pub writebacks: u64,
+  /// Stores written straight to memory, by write-through or by a store miss
+  /// without write allocation.
+  pub memory_writes: u64,
+}
+
+impl CacheStats {
+  pub fn accesses(&self) -> u64 {
+    self.hits + self.misses
+  }
+
+  /// Hit rate in percent, zero if there was no access.
+  pub fn hit_rate(&self) -> f64 {
+    match self.accesses() {
+      0 => 0.0,
+      total => self.hits as f64 * 100.0 / total as f64,
+    }
+  }
+
+  fn add(&mut self, other: &CacheStats) {
+    self.hits += other.hits;
+    self.misses += other.misses;
+    self.block_reads += other.block_reads;
+    self.writebacks += other.writebacks;
+    self.memory_writes += other.memory_writes;
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Line {
+  valid: bool,
+  dirty: bool,
+  tag: u32,
+  /// Clock value of the last access, for LRU.
+  used: u64,
+  /// Clock value when the block was brought in, for FIFO.
+  loaded: u64,
+}
+
+/// A single cache.
+#[derive(Debug, Clone)]
+pub struct Cache {
+  config: CacheConfig,
+  /// `associativity` lines per set.
+  lines: Vec<Line>,
+  clock: u64,
+  /// State of the xorshift generator used for random replacement.
+  seed: u64,
+  stats: CacheStats,
+}
+
+impl Cache {
+  pub fn new(config: CacheConfig) -> Result<Cache, ConfigError> {
+    config.validate()?;
+
+    Ok(Cache {
+      lines: vec![Line::default(); (config.sets() * config.associativity) as usize],
+      config,
+      clock: 0,
+      seed: 0x2545f4914f6cdd1d,
+      stats: CacheStats::default(),
+    })
+  }
+
+  pub fn config(&self) -> &CacheConfig {
+    &self.config
+  }
+
+  pub fn stats(&self) -> &CacheStats {
+    &self.stats
+  }
+
+  /// Simulate an access to `addr`. Returns whether it hit.
+  pub fn access(&mut self, addr: u32, kind: AccessKind) -> bool {
+    let stats = self.access_stats(addr, kind);
+    self.stats.add(&stats);
+    stats.hits == 1
+  }
+
+  /// Simulate an access, returning its own contribution to the statistics.
+  fn access_stats(&mut self, addr: u32, kind: AccessKind) -> CacheStats {
+    self.clock += 1;
+
+    let block = addr / self.config.block_size;
+    let set = (block % self.config.sets()) as usize;
+    let tag = block / self.config.sets();
+    let ways = self.config.associativity as usize;
+    let clock = self.clock;
+    let write_back = self.config.write_policy == WritePolicy::WriteBack;
+
+    let mut stats = CacheStats::default();
+    let range = set * ways..(set + 1) * ways;
+
+    if let Some(line) = self.lines[range.clone()]
+      .iter_mut()
+      .find(|l| l.valid && l.tag == tag)
+    {
+      stats.hits = 1;
+      line.used = clock;
+
+      if kind == AccessKind::Store {
+        match write_back {
+          true => line.dirty = true,
+          false => stats.memory_writes = 1,
+        }
+      }
+
+      return stats;
+    }
+
+    stats.misses = 1;
+
+    if kind == AccessKind::Store && !self.config.write_allocate {
+      stats.memory_writes = 1;
+      return stats;
+    }
+
+    let victim = range.start + self.victim(&range);
+    let line = &mut self.lines[victim];
+
+    if line.valid && line.dirty {
+      stats.writebacks = 1;
+    }
+
+    stats.block_reads = 1;
+    *line = Line {
+      valid: true,
+      dirty: false,
+      tag,
+      used: clock,
+      loaded: clock,
+    };
+
+    if kind == AccessKind::Store {
+      match write_back {
+        true => line.dirty = true,
+        false => stats.memory_writes = 1,
+      }
+    }
+
+    stats
+  }
+
+  /// Index within the set `range` of the line to replace.
+  fn victim(&mut self, range: &std::ops::Range<usize>) -> usize {
+    let set = &self.lines[range.clone()];
+
+    if let Some(free) = set.iter().position(|l| !l.valid) {
+      return free;
+    }
+
+    match self.config.replacement {
+      Replacement::Lru => min_index(set.iter().map(|l| l.used)),
+      Replacement::Fifo => min_index(set.iter().map(|l| l.loaded)),
+      Replacement::Random => {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed % set.len() as u64) as usize
+      }
+    }
+  }
+}
+
+fn min_index(values: impl Iterator<Item = u64>) -> usize {
+  values
+    .enumerate()
+    .min_by_key(|(_, v)| *v)
+    .map_or(0, |(i, _)| i)
+}
+
+/// Per label statistics of both caches.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LabelStats {
+  pub icache: CacheStats,
+  pub dcache: CacheStats,
+}
+
+/// Simulates an instruction cache and a data cache, to be registered as an
+/// observer. Either cache may be left out.
+///
+/// Statistics are also attributed to the label of the instruction issuing the
+/// access.
+pub struct CacheSimulator {
+  symbols: Symbols,
+  icache: Option<Cache>,
+  dcache: Option<Cache>,
+  labels: HashMap<String, LabelStats>,
+}
+
+impl CacheSimulator {
+  pub fn new(
+    program: &ProgramData,
+    icache: Option<CacheConfig>,
+    dcache: Option<CacheConfig>,
+  ) -> Result<CacheSimulator, ConfigError> {
+    Ok(CacheSimulator {
+      symbols: Symbols::from_program(program),
+      icache: icache.map(Cache::new).transpose()?,
+      dcache: dcache.map(Cache::new).transpose()?,
+      labels: HashMap::new(),
+    })
+  }
+
+  pub fn icache(&self) -> Option<&Cache> {
+    self.icache.as_ref()
+  }
+
+  pub fn dcache(&self) -> Option<&Cache> {
+    self.dcache.as_ref()
+  }
+
+  /// Statistics per label, sorted by label name.
+  pub fn by_label(&self) -> Vec<(&str, &LabelStats)> {
+    let mut labels = self
+      .labels
+      .iter()
+      .map(|(name, stats)| (name.as_str(), stats))
+      .collect::<Vec<_>>();
+
+    labels.sort_by_key(|(name, _)| *name);
+    labels
+  }
+
+  /// Write a human readable report.
+  pub fn write_report(&self, mut out: impl Write) -> io::Result<()> {
+    for (name, cache) in [("I-cache", &self.icache), ("D-cache", &self.dcache)] {
+      let Some(cache) = cache else {
+        continue;
+      };
+
+      let config = cache.config();
+      let stats = cache.stats();
+
+      writeln!(
+        out,
+        "{name}: {} bytes, {} byte blocks, {}-way, {:?}, {:?}, {}",
+        config.size,
+        config.block_size,
+        config.associativity,
+        config.replacement,
+        config.write_policy,
+        match config.write_allocate {
+          true => "write-allocate",
+          false => "no-write-allocate",
+        },
+      )?;
+      writeln!(
+        out,
+        "  accesses {}, hits {}, misses {} ({:.2}% hit rate)",
+        stats.accesses(),
+        stats.hits,
+        stats.misses,
+        stats.hit_rate(),
+      )?;
+      writeln!(
+        out,
+        "  block reads {}, writebacks {}, memory writes {}",
+        stats.block_reads, stats.writebacks, stats.memory_writes,
+      )?;
+    }
+
+    writeln!(out, "\nby label:")?;
+    for (label, stats) in self.by_label() {
+      writeln!(
+        out,
+        "  {label:<24} I {:>10} acc {:>6.2}%  D {:>10} acc {:>6.2}%",
+        stats.icache.accesses(),
+        stats.icache.hit_rate(),
+        stats.dcache.accesses(),
+        stats.dcache.hit_rate(),
+      )?;
+    }
+
+    Ok(())
+  }
+
+  fn label_stats(&mut self, pc: u32) -> &mut LabelStats {
+    let name = self.symbols.label_name(pc);
+
+    if !self.labels.contains_key(name) {
+      self.labels.insert(name.to_owned(), LabelStats::default());
+    }
+
+    // inserted right above
+    #[allow(clippy::unwrap_used)]
+    self.labels.get_mut(name).unwrap()
+  }
+
+  fn data_access(&mut self, pc: u32, access: &Access) {
+    if let Some(cache) = &mut self.dcache {
+      let stats = cache.access_stats(access.addr, access.kind);
+      cache.stats.add(&stats);
+      self.label_stats(pc).dcache.add(&stats);
+    }
+  }
+}
+
+impl CpuObserver for CacheSimulator {
+  fn on_fetch(&mut self, pc: u32, _word: u32) {
+    if let Some(cache) = &mut self.icache {
+      let stats = cache.access_stats(pc, AccessKind::Load);
+      cache.stats.add(&stats);
+      self.label_stats(pc).icache.add(&stats);
+    }
+  }
+
+  fn on_memory_read(&mut self, pc: u32, access: &Access) {
+    self.data_access(pc, access);
+  }
+
+  fn on_memory_write(&mut self, pc: u32, access: &Access) {
+    self.data_access(pc, access);
+  }
+}
@@ -0,0 +1,76 @@
+//! Consoles the input and output system calls go through.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+/// Text input and output of a running program.
+///
+/// Input is line based: `read_char` takes the first character of a line.
+pub trait Console {
+  /// Print program output.
+  fn write(&mut self, text: &str);
+
+  /// Next line of input, without its line terminator. Returns `None` when no
+  /// input is available yet, the system call is then retried once there is.
+  fn read_line(&mut self) -> Option<String>;
+}
+
+/// Console on the standard input and output of the process. Reading blocks,
+/// the end of input reads as empty lines.
+#[derive(Debug, Default)]
+pub struct Stdio;
+
+impl Console for Stdio {
+  fn write(&mut self, text: &str) {
+    let mut stdout = io::stdout().lock();
+    // output is best effort, like print!
+    let _ = stdout.write_all(text.as_bytes());
+    let _ = stdout.flush();
+  }
+
+  fn read_line(&mut self) -> Option<String> {
+    let mut line = String::new();
+    // errors read as the end of input
+    let _ = io::stdin().lock().read_line(&mut line);
+
+    let len = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(len);
+    Some(line)
+  }
+}
+
+/// Console held in memory: output is collected and input is taken from lines
+/// queued beforehand.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Buffered {
+  output: String,
+  input: VecDeque<String>,
+}
+
+impl Buffered {
+  /// Everything written so far.
+  pub fn output(&self) -> &str {
+    &self.output
+  }
+
+  /// Queue a line of input.
+  pub fn push_input(&mut self, line: impl Into<String>) {
+    self.input.push_back(line.into());
+  }
+
+  /// Whether queued input is left.
+  pub fn has_input(&self) -> bool {
+    !self.input.is_empty()
+  }
+}
+
+impl Console for Buffered {
+  fn write(&mut self, text: &str) {
+    self.output.push_str(text);
+  }
+
+  fn read_line(&mut self) -> Option<String> {
+    self.input.pop_front()
+  }
+}
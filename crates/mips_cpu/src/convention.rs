@@ -0,0 +1,283 @@
+//! Checks of the O32 calling convention, over the calls a program makes.
+//!
+//! A `ConventionChecker` registered as an observer follows calls (`jal`,
+//! `jalr`, `bgezal`, `bltzal`) and returns (`jr $ra`), as the profiler does.
+//! It flags:
+//!
+//! - callee-saved registers (`$s0`..`$s7`, `$sp`, `$fp`) holding another
+//!   value on return than on the call,
+//! - reads of `$t` and `$a` registers after a call returned, before the
+//!   caller wrote them again; system calls are left out, they read every
+//!   argument register,
+//! - `$sp` not word aligned, or on a call not a multiple of 8 bytes away from
+//!   its initial value; MARS starts it on a word boundary only,
+//! - writes to `$k0` and `$k1` by user code, in `kuseg`.
+
+use crate::decode::{self, Class};
+use crate::disasm;
+use crate::mmu::Segment;
+use crate::observer::CpuObserver;
+use crate::register::{self, Registers};
+use crate::symbols::Symbols;
+use mips_program::ProgramData;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const SP: u8 = 29;
+
+/// Registers a function must restore before returning.
+const CALLEE_SAVED: [u8; 10] = [16, 17, 18, 19, 20, 21, 22, 23, 29, 30];
+
+/// Mask of the `$a0`..`$a3`, `$t0`..`$t9` registers, which a call may clobber.
+const CALLER_SAVED: u32 = 0xf0 | 0xff00 | 0x0300_0000;
+
+/// Registers reserved to the kernel.
+const KERNEL: [u8; 2] = [26, 27];
+
+/// A breach of the calling convention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+  /// A function returned with a callee-saved register changed.
+  NotRestored {
+    reg: u8,
+    /// Entry point of the function.
+    function: u32,
+    expected: u32,
+    found: u32,
+  },
+  /// A caller-saved register was read after a call, without being written
+  /// since.
+  ClobberedRead {
+    reg: u8,
+    /// The call which may have clobbered it.
+    call: u32,
+  },
+  /// `$sp` is not aligned on `alignment` bytes. Doubleword alignment is
+  /// counted from the `$sp` the checker started with.
+  MisalignedStack { sp: u32, alignment: u32 },
+  /// User code wrote a register reserved to the kernel.
+  KernelRegister { reg: u8 },
+}
+
+/// A violation, and the instruction which committed it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+  pub pc: u32,
+  pub word: u32,
+  pub kind: ViolationKind,
+}
+
+/// A call which did not return yet.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+  call: u32,
+  function: u32,
+  /// Values of the `CALLEE_SAVED` registers on the call.
+  saved: [u32; CALLEE_SAVED.len()],
+}
+
+/// Follows the calls of a program and collects calling convention
+/// violations, to be registered as an observer.
+pub struct ConventionChecker {
+  symbols: Symbols,
+  /// Values of the regular registers.
+  registers: [u32; 32],
+  /// `$sp` when the checker started, which stack frames are aligned from.
+  stack_base: u32,
+  /// Word fetched during the current cycle.
+  word: u32,
+  call: bool,
+  ret: bool,
+  frames: Vec<Frame>,
+  /// Caller-saved registers not written since `clobbered_by`, the last call
+  /// of the current function returned, as a mask.
+  clobbered: u32,
+  clobbered_by: u32,
+  violations: Vec<Violation>,
+}
+
+impl ConventionChecker {
+  /// A checker starting from the current values of `registers`.
+  pub fn new(program: &ProgramData, registers: &Registers) -> ConventionChecker {
+    let mut values = [0; 32];
+    for (n, value) in values.iter_mut().enumerate() {
+      *value = registers.r(n).map_or(0, |r| *r);
+    }
+
+    ConventionChecker {
+      symbols: Symbols::from_program(program),
+      registers: values,
+      stack_base: values[SP as usize],
+      word: 0,
+      call: false,
+      ret: false,
+      frames: Vec::new(),
+      clobbered: 0,
+      clobbered_by: 0,
+      violations: Vec::new(),
+    }
+  }
+
+  /// Every violation, in program order.
+  pub fn violations(&self) -> &[Violation] {
+    &self.violations
+  }
+
+  /// Number of calls which did not return yet.
+  pub fn depth(&self) -> usize {
+    self.frames.len()
+  }
+
+  /// Write the violations, grouped by instruction and kind.
+  pub fn write_report(&self, mut out: impl Write) -> io::Result<()> {
+    writeln!(
+      out,
+      "{} calling convention violation(s)",
+      self.violations.len()
+    )?;
+
+    let mut sites = HashMap::<(u32, String), (u32, u64)>::new();
+    for violation in &self.violations {
+      let key = (violation.pc, self.describe(violation.kind));
+      sites.entry(key).or_insert((violation.word, 0)).1 += 1;
+    }
+
+    let mut sites = sites.into_iter().collect::<Vec<_>>();
+    sites.sort();
+
+    for ((pc, description), (word, count)) in sites {
+      writeln!(
+        out,
+        "  {pc:#010x} <{}>  {:<24} {description}, {count} time(s)",
+        self.symbols.location(pc),
+        disasm::disassemble(word, pc),
+      )?;
+    }
+
+    Ok(())
+  }
+
+  /// The violation, with addresses shown as `label+offset`.
+  fn describe(&self, kind: ViolationKind) -> String {
+    match kind {
+      ViolationKind::NotRestored {
+        reg,
+        function,
+        expected,
+        found,
+      } => format!(
+        "{} not restored by <{}>: {expected:#x} became {found:#x}",
+        register::name(reg),
+        self.symbols.location(function)
+      ),
+      ViolationKind::ClobberedRead { reg, call } => format!(
+        "{} read after the call at <{}> without being reloaded",
+        register::name(reg),
+        self.symbols.location(call)
+      ),
+      ViolationKind::MisalignedStack { sp, alignment } => {
+        format!("$sp {sp:#010x} is not aligned on {alignment} bytes")
+      }
+      ViolationKind::KernelRegister { reg } => {
+        format!("{} is reserved to the kernel", register::name(reg))
+      }
+    }
+  }
+
+  fn report(&mut self, pc: u32, kind: ViolationKind) {
+    self.violations.push(Violation {
+      pc,
+      word: self.word,
+      kind,
+    });
+  }
+
+  /// Check that `$sp` is a multiple of `alignment` bytes away from `base`.
+  fn check_alignment(&mut self, pc: u32, alignment: u32, base: u32) {
+    let sp = self.registers[SP as usize];
+
+    if sp.wrapping_sub(base) % alignment != 0 {
+      self.report(pc, ViolationKind::MisalignedStack { sp, alignment });
+    }
+  }
+
+  fn saved(&self) -> [u32; CALLEE_SAVED.len()] {
+    CALLEE_SAVED.map(|n| self.registers[n as usize])
+  }
+}
+
+impl CpuObserver for ConventionChecker {
+  fn on_fetch(&mut self, pc: u32, word: u32) {
+    self.word = word;
+    self.call = decode::is_call(word);
+    self.ret = decode::is_return(word);
+
+    if decode::classify(word) != Class::Syscall {
+      for reg in decode::registers_read(word) {
+        if self.clobbered & mask(reg) != 0 {
+          // once per clobbered value
+          self.clobbered &= !mask(reg);
+          let call = self.clobbered_by;
+          self.report(pc, ViolationKind::ClobberedRead { reg, call });
+        }
+      }
+    }
+  }
+
+  fn on_register_write(&mut self, pc: u32, reg: u8, value: u32) {
+    if reg as usize >= self.registers.len() {
+      return;
+    }
+
+    self.registers[reg as usize] = value;
+    self.clobbered &= !mask(reg);
+
+    if reg == SP {
+      self.check_alignment(pc, 4, 0);
+    }
+
+    if KERNEL.contains(&reg) && Segment::of(pc) == Segment::Kuseg {
+      self.report(pc, ViolationKind::KernelRegister { reg });
+    }
+  }
+
+  fn on_branch(&mut self, pc: u32, target: u32, taken: bool) {
+    if taken && self.call {
+      self.check_alignment(pc, 8, self.stack_base);
+
+      self.frames.push(Frame {
+        call: pc,
+        function: target,
+        saved: self.saved(),
+      });
+      self.clobbered = 0;
+    } else if taken && self.ret {
+      let Some(frame) = self.frames.pop() else {
+        return;
+      };
+
+      for (i, found) in self.saved().into_iter().enumerate() {
+        let expected = frame.saved[i];
+
+        if found != expected {
+          let kind = ViolationKind::NotRestored {
+            reg: CALLEE_SAVED[i],
+            function: frame.function,
+            expected,
+            found,
+          };
+          self.report(pc, kind);
+        }
+      }
+
+      // whatever the caller had clobbered before is clobbered again
+      self.clobbered = CALLER_SAVED;
+      self.clobbered_by = frame.call;
+    }
+  }
+}
+
+/// Bit of a regular register in a register mask, zero for other registers.
+fn mask(reg: u8) -> u32 {
+  1u32.checked_shl(reg as u32).unwrap_or(0)
+}
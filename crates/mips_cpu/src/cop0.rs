@@ -0,0 +1,196 @@
+use crate::exception::Exception;
+use crate::mmu::{self, TlbEntry};
+use serde::{Deserialize, Serialize};
+
+/// Register number of `Index`, the TLB entry read or written by `tlbr` and
+/// `tlbwi`.
+pub const INDEX: u32 = 0;
+/// Register number of `Random`, the TLB entry written by `tlbwr`.
+pub const RANDOM: u32 = 1;
+/// Register number of `EntryLo`, the physical half of a TLB entry.
+pub const ENTRY_LO: u32 = 2;
+/// Register number of `Context`, which points to the page table entry of the
+/// last TLB miss.
+pub const CONTEXT: u32 = 4;
+/// Register number of `BadVAddr`, which holds the address an address error
+/// was raised for.
+pub const BAD_VADDR: u32 = 8;
+/// Register number of `EntryHi`, the virtual half of a TLB entry.
+pub const ENTRY_HI: u32 = 10;
+/// Register number of `Status`.
+pub const STATUS: u32 = 12;
+/// Register number of `Cause`, which holds the code of the last exception.
+pub const CAUSE: u32 = 13;
+/// Register number of `EPC`, the address of the instruction which raised the
+/// last exception.
+pub const EPC: u32 = 14;
+
+/// `Status` bit set while an exception is being handled.
+pub const STATUS_EXL: u32 = 1 << 1;
+/// `Status` bit set when running in user mode.
+pub const STATUS_UM: u32 = 1 << 4;
+
+/// `Index` bit set by `tlbp` when no entry matched.
+pub const INDEX_PROBE_FAILURE: u32 = 1 << 31;
+/// Mask of the TLB entry index in `Index` and `Random`.
+const INDEX_MASK: u32 = 0x3f << 8;
+/// Bits of `EntryLo` which hold a value.
+const ENTRY_LO_WRITABLE: u32 = 0xffffff00;
+/// Bits of `EntryHi` which hold a value.
+const ENTRY_HI_WRITABLE: u32 = mmu::ENTRY_HI_VPN | mmu::ENTRY_HI_ASID;
+/// Page table base in `Context`, the rest is set on TLB misses.
+const CONTEXT_PTE_BASE: u32 = 0xffe00000;
+
+/// Mask of the exception code in `Cause`.
+const CAUSE_EXC_CODE: u32 = 0x1f << 2;
+/// Bits of `Cause` which software may write (software interrupts).
+const CAUSE_WRITABLE: u32 = 0x3 << 8;
+
+/// Coprocessor 0, the system control coprocessor.
+///
+/// Only the registers involved in exception handling and in managing the TLB
+/// are implemented. The TLB itself belongs to the `MemoryMap`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cop0 {
+  pub index: u32,
+  pub random: u32,
+  pub entry_lo: u32,
+  pub context: u32,
+  pub bad_vaddr: u32,
+  pub entry_hi: u32,
+  pub status: u32,
+  pub cause: u32,
+  pub epc: u32,
+}
+
+impl Cop0 {
+  /// Same initial values as MARS: user mode, interrupts enabled.
+  pub fn init() -> Cop0 {
+    Cop0 {
+      index: 0,
+      random: (mmu::TLB_ENTRIES as u32 - 1) << 8,
+      entry_lo: 0,
+      context: 0,
+      bad_vaddr: 0,
+      entry_hi: 0,
+      status: 0x0000ff11,
+      cause: 0,
+      epc: 0,
+    }
+  }
+
+  /// Read register `n`, as done by `mfc0`. Returns `None` for registers which
+  /// are not implemented.
+  pub fn read(&self, n: u32) -> Option<u32> {
+    match n {
+      INDEX => Some(self.index),
+      RANDOM => Some(self.random),
+      ENTRY_LO => Some(self.entry_lo),
+      CONTEXT => Some(self.context),
+      BAD_VADDR => Some(self.bad_vaddr),
+      ENTRY_HI => Some(self.entry_hi),
+      STATUS => Some(self.status),
+      CAUSE => Some(self.cause),
+      EPC => Some(self.epc),
+      _ => None,
+    }
+  }
+
+  /// Write register `n`, as done by `mtc0`. Read-only bits are left untouched.
+  /// Returns `false` for registers which are not implemented or read-only.
+  pub fn write(&mut self, n: u32, value: u32) -> bool {
+    match n {
+      INDEX => self.index = (self.index & !INDEX_MASK) | (value & INDEX_MASK),
+      ENTRY_LO => self.entry_lo = value & ENTRY_LO_WRITABLE,
+      CONTEXT => self.context = (self.context & !CONTEXT_PTE_BASE) | (value & CONTEXT_PTE_BASE),
+      ENTRY_HI => self.entry_hi = value & ENTRY_HI_WRITABLE,
+      STATUS => self.status = value,
+      CAUSE => self.cause = (self.cause & !CAUSE_WRITABLE) | (value & CAUSE_WRITABLE),
+      EPC => self.epc = value,
+      _ => return false,
+    }
+
+    true
+  }
+
+  /// Whether the processor runs in kernel mode: while handling an exception,
+  /// or when `Status` does not select user mode.
+  pub fn is_kernel(&self) -> bool {
+    self.status & STATUS_EXL != 0 || self.status & STATUS_UM == 0
+  }
+
+  /// TLB entry selected by `Index`.
+  pub fn indexed(&self) -> usize {
+    ((self.index & INDEX_MASK) >> 8) as usize
+  }
+
+  /// TLB entry selected by `Random`.
+  pub fn randomly_indexed(&self) -> usize {
+    ((self.random & INDEX_MASK) >> 8) as usize
+  }
+
+  /// The TLB entry formed by `EntryHi` and `EntryLo`.
+  pub fn entry(&self) -> TlbEntry {
+    TlbEntry {
+      hi: self.entry_hi,
+      lo: self.entry_lo,
+    }
+  }
+
+  /// Load `EntryHi` and `EntryLo` from a TLB entry, as done by `tlbr`.
+  pub fn set_entry(&mut self, entry: TlbEntry) {
+    self.entry_hi = entry.hi & ENTRY_HI_WRITABLE;
+    self.entry_lo = entry.lo & ENTRY_LO_WRITABLE;
+  }
+
+  /// Record the result of `tlbp`.
+  pub fn set_probe(&mut self, index: Option<usize>) {
+    self.index = match index {
+      Some(i) => (i as u32) << 8,
+      None => INDEX_PROBE_FAILURE | (self.index & INDEX_MASK),
+    };
+  }
+
+  /// Advance `Random` by one cycle: it counts down from the last TLB entry to
+  /// `TLB_WIRED`, then wraps around.
+  pub fn tick(&mut self) {
+    let random = match self.randomly_indexed() {
+      i if i <= mmu::TLB_WIRED => mmu::TLB_ENTRIES - 1,
+      i => i - 1,
+    };
+
+    self.random = (random as u32) << 8;
+  }
+
+  /// Record an exception raised by the instruction at `pc`. `bad_addr` is the
+  /// faulting address of address errors and TLB exceptions.
+  ///
+  /// `EPC` is left untouched by exceptions raised while handling another.
+  pub fn raise(&mut self, exception: Exception, pc: u32, bad_addr: Option<u32>) {
+    self.cause = (self.cause & !CAUSE_EXC_CODE) | ((exception as u32) << 2);
+
+    if self.status & STATUS_EXL == 0 {
+      self.epc = pc;
+    }
+
+    self.status |= STATUS_EXL;
+
+    if let Some(addr) = bad_addr {
+      self.bad_vaddr = addr;
+    }
+
+    if let (Some(addr), true) = (bad_addr, exception.is_tlb()) {
+      let vpn = addr & mmu::ENTRY_HI_VPN;
+
+      self.context = (self.context & CONTEXT_PTE_BASE) | ((vpn >> 10) & !CONTEXT_PTE_BASE);
+      self.entry_hi = vpn | (self.entry_hi & mmu::ENTRY_HI_ASID);
+    }
+  }
+
+  /// Return from an exception, as done by `eret`. Returns the address to
+  /// resume at.
+  pub fn eret(&mut self) -> u32 {
+    self.status &= !STATUS_EXL;
+    self.epc
+  }
+}
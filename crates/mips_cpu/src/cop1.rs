@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+/// Value of the floating point implementation register: single, double and
+/// word formats are implemented.
+pub const FIR: u32 = (1 << 16) | (1 << 17) | (1 << 20);
+
+/// FCSR register number, for `cfc1`/`ctc1`.
+pub const FCSR: u32 = 31;
+/// FIR register number, for `cfc1`.
+pub const FIR_NUMBER: u32 = 0;
+
+/// IEEE 754 exception bits, as laid out in the flags, enables and cause fields
+/// of FCSR.
+pub mod flag {
+  pub const INEXACT: u32 = 1 << 0;
+  pub const UNDERFLOW: u32 = 1 << 1;
+  pub const OVERFLOW: u32 = 1 << 2;
+  pub const DIV_BY_ZERO: u32 = 1 << 3;
+  pub const INVALID: u32 = 1 << 4;
+  /// Unimplemented operation. Only exists in the cause field, and always
+  /// traps.
+  pub const UNIMPLEMENTED: u32 = 1 << 5;
+}
+
+/// Offset of the flags field (sticky exception bits) in FCSR.
+const FLAGS_SHIFT: u32 = 2;
+/// Offset of the enables field in FCSR.
+const ENABLES_SHIFT: u32 = 7;
+/// Offset of the cause field in FCSR.
+const CAUSE_SHIFT: u32 = 12;
+
+/// Rounding mode, from the two lowest bits of FCSR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+  Nearest,
+  TowardZero,
+  TowardPositive,
+  TowardNegative,
+}
+
+impl RoundingMode {
+  /// Round `value` to an integer according to this mode. Ties round to even.
+  pub fn round(self, value: f64) -> f64 {
+    match self {
+      RoundingMode::Nearest => {
+        let rounded = value.round();
+
+        // `f64::round` rounds ties away from zero
+        if (value - value.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+          rounded - value.signum()
+        } else {
+          rounded
+        }
+      }
+      RoundingMode::TowardZero => value.trunc(),
+      RoundingMode::TowardPositive => value.ceil(),
+      RoundingMode::TowardNegative => value.floor(),
+    }
+  }
+}
+
+/// Coprocessor 1, the floating point unit (R2010 FPA).
+///
+/// Holds 32 single precision registers `$f0`..`$f31`. Double precision values
+/// span an even/odd register pair, the even register holding the low word.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cop1 {
+  fpr: [u32; 32],
+  /// Floating point control and status register.
+  pub fcsr: u32,
+  /// Registers written since the last call to `clear_written`, one bit per
+  /// register and bit 32 for FCSR.
+  #[serde(skip)]
+  written: u64,
+}
+
+impl Cop1 {
+  pub fn init() -> Cop1 {
+    Cop1 {
+      fpr: [0; 32],
+      fcsr: 0,
+      written: 0,
+    }
+  }
+
+  /// Raw bits of register `n`.
+  pub fn word(&self, n: usize) -> u32 {
+    self.fpr[n]
+  }
+
+  pub fn set_word(&mut self, n: usize, value: u32) {
+    self.fpr[n] = value;
+    self.written |= 1 << n;
+  }
+
+  pub fn single(&self, n: usize) -> f32 {
+    f32::from_bits(self.fpr[n])
+  }
+
+  pub fn set_single(&mut self, n: usize, value: f32) {
+    self.set_word(n, value.to_bits());
+  }
+
+  /// Double precision value held by the pair starting at `n`, which must be
+  /// even.
+  pub fn double(&self, n: usize) -> f64 {
+    f64::from_bits(self.fpr[n] as u64 | (self.fpr[n + 1] as u64) << 32)
+  }
+
+  pub fn set_double(&mut self, n: usize, value: f64) {
+    let bits = value.to_bits();
+    self.set_word(n, bits as u32);
+    self.set_word(n + 1, (bits >> 32) as u32);
+  }
+
+  pub fn set_fcsr(&mut self, value: u32) {
+    self.fcsr = value;
+    self.written |= 1 << 32;
+  }
+
+  /// Condition flag `cc` (`0..8`), as set by `c.cond.fmt`.
+  pub fn condition(&self, cc: u32) -> bool {
+    self.fcsr & condition_bit(cc) != 0
+  }
+
+  pub fn set_condition(&mut self, cc: u32, value: bool) {
+    match value {
+      true => self.set_fcsr(self.fcsr | condition_bit(cc)),
+      false => self.set_fcsr(self.fcsr & !condition_bit(cc)),
+    }
+  }
+
+  pub fn rounding_mode(&self) -> RoundingMode {
+    match self.fcsr & 0x3 {
+      0 => RoundingMode::Nearest,
+      1 => RoundingMode::TowardZero,
+      2 => RoundingMode::TowardPositive,
+      _ => RoundingMode::TowardNegative,
+    }
+  }
+
+  /// Report the IEEE exceptions raised by an operation, as `flag` bits.
+  ///
+  /// Returns `false` if one of them is enabled (or unimplemented), in which
+  /// case the operation must trap without writing its result. Otherwise the
+  /// exceptions are accumulated in the flags field.
+  pub fn signal(&mut self, causes: u32) -> bool {
+    self.set_fcsr((self.fcsr & !(0x3f << CAUSE_SHIFT)) | (causes << CAUSE_SHIFT));
+
+    let enables = (self.fcsr >> ENABLES_SHIFT) & 0x1f;
+
+    if causes & (enables | flag::UNIMPLEMENTED) != 0 {
+      return false;
+    }
+
+    self.fcsr |= (causes & 0x1f) << FLAGS_SHIFT;
+    true
+  }
+
+  /// Registers written since the last call to `clear_written`, see `written`.
+  pub(crate) fn written(&self) -> u64 {
+    self.written
+  }
+
+  pub(crate) fn clear_written(&mut self) {
+    self.written = 0;
+  }
+}
+
+/// FCSR bit of condition flag `cc`. Flag 0 is bit 23, the others start at bit
+/// 25.
+fn condition_bit(cc: u32) -> u32 {
+  match cc {
+    0 => 1 << 23,
+    cc => 1 << (24 + cc),
+  }
+}
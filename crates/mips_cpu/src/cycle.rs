@@ -20,9 +20,11 @@ impl From<Exception> for Next {
   }
 }
 
-pub use compute::perform_cycle;
+pub use compute::{execute, perform_cycle};
 
 /// Actual code performing each instruction.
 mod compute;
 /// Operations on instructions.
-mod data;
+pub(crate) mod data;
+/// Coprocessor 1 instructions.
+mod fpu;
@@ -1,41 +1,42 @@
-use crate::cycle::{data, Next};
+use crate::cycle::{data, fpu, Next};
 use crate::exception::Exception;
 use crate::mem::MemoryMap;
-use crate::register::Registers;
-use std::cell::RefMut;
+use crate::register::{RegisterMut, Registers};
 
-/// Parses instructions in format `i rd, rs, rt`
-fn parse_arithm_r(instr: u32, reg: &Registers) -> (RefMut<u32>, RefMut<u32>, RefMut<u32>) {
+/// Parses instructions in format `i rd, rs, rt`. Source registers are read
+/// before the destination is borrowed, they may be the same register.
+fn parse_arithm_r(instr: u32, reg: &Registers) -> (RegisterMut, u32, u32) {
   // data::isolate_r* cannot return values higher or equal to 32
   #[allow(clippy::unwrap_used)]
-  let rd = reg.r(data::isolate_rd(instr) as usize).unwrap();
+  let rs = *reg.r(data::isolate_rs(instr) as usize).unwrap();
   #[allow(clippy::unwrap_used)]
-  let rs = reg.r(data::isolate_rs(instr) as usize).unwrap();
+  let rt = *reg.r(data::isolate_rt(instr) as usize).unwrap();
   #[allow(clippy::unwrap_used)]
-  let rt = reg.r(data::isolate_rt(instr) as usize).unwrap();
+  let rd = reg.r(data::isolate_rd(instr) as usize).unwrap();
 
   (rd, rs, rt)
 }
 
-/// Parses instructions in format `i rt, rs, imm16`
-fn parse_arithm_i(instr: u32, reg: &Registers) -> (RefMut<u32>, RefMut<u32>, u16) {
+/// Parses instructions in format `i rt, rs, imm16`. `rs` is read before `rt`
+/// is borrowed, they may be the same register.
+fn parse_arithm_i(instr: u32, reg: &Registers) -> (RegisterMut, u32, u16) {
   // data::isolate_r* cannot return values higher or equal to 32
   #[allow(clippy::unwrap_used)]
-  let rt = reg.r(data::isolate_rt(instr) as usize).unwrap();
+  let rs = *reg.r(data::isolate_rs(instr) as usize).unwrap();
   #[allow(clippy::unwrap_used)]
-  let rs = reg.r(data::isolate_rs(instr) as usize).unwrap();
+  let rt = reg.r(data::isolate_rt(instr) as usize).unwrap();
   let imm16 = data::isolate_imm16(instr);
 
   (rt, rs, imm16)
 }
 
 /// Parses instructions in format `i rs, rt`
-fn parse_trap_r(instr: u32, reg: &Registers) -> (RefMut<u32>, RefMut<u32>) {
+fn parse_trap_r(instr: u32, reg: &Registers) -> (u32, u32) {
   // data::isolate_r* cannot return values higher or equal to 32
   #[allow(clippy::unwrap_used)]
-  let rs = reg.r(data::isolate_rs(instr) as usize).unwrap();
+  let rs = *reg.r(data::isolate_rs(instr) as usize).unwrap();
   #[allow(clippy::unwrap_used)]
-  let rt = reg.r(data::isolate_rt(instr) as usize).unwrap();
+  let rt = *reg.r(data::isolate_rt(instr) as usize).unwrap();
 
   (rs, rt)
 }
@@ -44,11 +45,15 @@ fn parse_trap_r(instr: u32, reg: &Registers) -> (RefMut<u32>, RefMut<u32>) {
 /// function does NOT write to the program counter, the caller is responsible
 /// for updating the PC depending on the cycle result.
 pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next {
-  let instr = match memory.load_word(registers.pc) {
-    Ok(v) => v,
-    Err(e) => return Next::Exception(e),
-  };
+  match memory.fetch(registers.pc) {
+    Ok(instr) => execute(instr, memory, registers),
+    Err(e) => Next::Exception(e),
+  }
+}
 
+/// Execute an instruction which was already fetched from the current program
+/// counter. Same contract as `perform_cycle`.
+pub fn execute(instr: u32, memory: &mut MemoryMap, registers: &mut Registers) -> Next {
   // instruction flow: according to this documentation
   // https://www.math.unipd.it/~sperduti/ARCHITETTURE-1/mips32.pdf
 
@@ -61,7 +66,7 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
     0x2 => {
       // j target
       let target = data::isolate_target_26(instr);
-      Next::Branch(target)
+      Next::Branch(data::jump_target(registers.pc, target))
     }
 
     0x3 => {
@@ -72,15 +77,15 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       #[allow(clippy::unwrap_used)]
       registers.link(31).unwrap();
 
-      Next::Branch(target)
+      Next::Branch(data::jump_target(registers.pc, target))
     }
 
     0x4 => {
       // beq rs, rt, offset
       let (rt, rs, offset) = parse_arithm_i(instr, registers);
-      let addr = data::add_ihalf_to_uword(registers.pc, offset);
+      let addr = data::branch_target(registers.pc, offset);
 
-      if *rt == *rs {
+      if *rt == rs {
         Next::Branch(addr)
       } else {
         Next::Forward
@@ -90,9 +95,9 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
     0x5 => {
       // bne rs, rt, offset
       let (rt, rs, offset) = parse_arithm_i(instr, registers);
-      let addr = data::add_ihalf_to_uword(registers.pc, offset);
+      let addr = data::branch_target(registers.pc, offset);
 
-      if *rt != *rs {
+      if *rt != rs {
         Next::Branch(addr)
       } else {
         Next::Forward
@@ -103,10 +108,10 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       // blez rs, offset
 
       let (_, rs, offset) = parse_arithm_i(instr, registers);
-      let addr = data::add_ihalf_to_uword(registers.pc, offset);
+      let addr = data::branch_target(registers.pc, offset);
 
       // lez signed comparison
-      if *rs == 0 || *rs >= (1 << 31) {
+      if rs == 0 || rs >= (1 << 31) {
         Next::Branch(addr)
       } else {
         Next::Forward
@@ -117,10 +122,10 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       // bgtz rs, offset
 
       let (_, rs, offset) = parse_arithm_i(instr, registers);
-      let addr = data::add_ihalf_to_uword(registers.pc, offset);
+      let addr = data::branch_target(registers.pc, offset);
 
       // gtz signed comparison
-      if (1..1 << 31).contains(&*rs) {
+      if (1..1 << 31).contains(&rs) {
         Next::Branch(addr)
       } else {
         Next::Forward
@@ -131,9 +136,9 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       // addi rt, rs, imm16
       let (mut rt, rs, imm16) = parse_arithm_i(instr, registers);
 
-      let addend0 = *rs;
+      let addend0 = rs;
       let addend1 = data::sign_extend(16, imm16 as u32);
-      let sum = addend0 + addend1;
+      let sum = u32::wrapping_add(addend0, addend1);
 
       if data::twos_complement_overflowed(addend0, addend1, sum) {
         return Next::Exception(Exception::Overflow);
@@ -148,7 +153,7 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       // addiu rt, rs, imm16
       let (mut rt, rs, imm16) = parse_arithm_i(instr, registers);
 
-      *rt = *rs + data::sign_extend(16, imm16 as u32);
+      *rt = u32::wrapping_add(rs, data::sign_extend(16, imm16 as u32));
       Next::Forward
     }
 
@@ -156,7 +161,7 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       // slti rt, rs, imm16
       let (mut rt, rs, imm16) = parse_arithm_i(instr, registers);
 
-      *rt = (*rs - data::sign_extend(16, imm16 as u32)) >> 31;
+      *rt = ((rs as i32) < (data::sign_extend(16, imm16 as u32) as i32)) as u32;
       Next::Forward
     }
 
@@ -164,7 +169,7 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       // sltiu rt, rs, imm16
       let (mut rt, rs, imm16) = parse_arithm_i(instr, registers);
 
-      *rt = (*rs < data::sign_extend(16, imm16 as u32)) as u32;
+      *rt = (rs < data::sign_extend(16, imm16 as u32)) as u32;
       Next::Forward
     }
 
@@ -172,7 +177,7 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       // andi rt, rs, imm16
       let (mut rt, rs, imm16) = parse_arithm_i(instr, registers);
 
-      *rt = *rs & imm16 as u32;
+      *rt = rs & imm16 as u32;
       Next::Forward
     }
 
@@ -180,7 +185,7 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       // ori rt, rs, imm16
       let (mut rt, rs, imm16) = parse_arithm_i(instr, registers);
 
-      *rt = *rs | imm16 as u32;
+      *rt = rs | imm16 as u32;
       Next::Forward
     }
 
@@ -188,7 +193,7 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       // xori rt, rs, imm16
       let (mut rt, rs, imm16) = parse_arithm_i(instr, registers);
 
-      *rt = *rs ^ imm16 as u32;
+      *rt = rs ^ imm16 as u32;
       Next::Forward
     }
 
@@ -202,10 +207,14 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       Next::Forward
     }
 
+    0x10 => handle_cop0(instr, memory, registers),
+    0x11 => fpu::handle_cop1(instr, registers),
+    0x1c => handle_special2(instr, registers),
+
     0x20 => {
       // lb rt, offset(rs)
       let (mut rt, rs, offset) = parse_arithm_i(instr, registers);
-      let addr = data::add_ihalf_to_uword(*rs, offset);
+      let addr = data::add_ihalf_to_uword(rs, offset);
 
       match memory.load_byte(addr) {
         Ok(b) => {
@@ -219,7 +228,7 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
     0x21 => {
       // lh rt, offset(rs)
       let (mut rt, rs, offset) = parse_arithm_i(instr, registers);
-      let addr = data::add_ihalf_to_uword(*rs, offset);
+      let addr = data::add_ihalf_to_uword(rs, offset);
 
       match memory.load_halfword(addr) {
         Ok(h) => {
@@ -230,10 +239,32 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       }
     }
 
-    0x23 => {
-      // lw rt, offset(rs)
+    0x22 | 0x26 => {
+      // lwl rt, offset(rs) / lwr rt, offset(rs)
+      let (mut rt, rs, offset) = parse_arithm_i(instr, registers);
+      let addr = data::add_ihalf_to_uword(rs, offset);
+
+      match memory.load_word(addr & !0x3) {
+        Ok(w) => {
+          // little endian: lwl fills the high bytes of rt with the bytes of
+          // the word up to `addr`, lwr the low bytes with those from `addr`
+          *rt = if opcode == 0x22 {
+            let shift = 8 * (3 - addr % 4);
+            (w << shift) | (*rt & !(u32::MAX << shift))
+          } else {
+            let shift = 8 * (addr % 4);
+            (w >> shift) | (*rt & !(u32::MAX >> shift))
+          };
+          Next::Forward
+        }
+        Err(e) => Next::Exception(e),
+      }
+    }
+
+    0x23 | 0x30 => {
+      // lw rt, offset(rs) / ll rt, offset(rs)
       let (mut rt, rs, offset) = parse_arithm_i(instr, registers);
-      let addr = data::add_ihalf_to_uword(*rs, offset);
+      let addr = data::add_ihalf_to_uword(rs, offset);
 
       match memory.load_word(addr) {
         Ok(w) => {
@@ -247,7 +278,7 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
     0x24 => {
       // lbu rt, offset(rs)
       let (mut rt, rs, offset) = parse_arithm_i(instr, registers);
-      let addr = data::add_ihalf_to_uword(*rs, offset);
+      let addr = data::add_ihalf_to_uword(rs, offset);
 
       match memory.load_byte(addr) {
         Ok(b) => {
@@ -261,7 +292,7 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
     0x25 => {
       // lhu rt, offset(rs)
       let (mut rt, rs, offset) = parse_arithm_i(instr, registers);
-      let addr = data::add_ihalf_to_uword(*rs, offset);
+      let addr = data::add_ihalf_to_uword(rs, offset);
 
       match memory.load_halfword(addr) {
         Ok(h) => {
@@ -272,7 +303,83 @@ pub fn perform_cycle(memory: &mut MemoryMap, registers: &mut Registers) -> Next
       }
     }
 
-    _ => unimplemented!(),
+    0x28 => {
+      // sb rt, offset(rs)
+      let (rt, rs, offset) = parse_arithm_i(instr, registers);
+      let addr = data::add_ihalf_to_uword(rs, offset);
+
+      match memory.store_byte(addr, *rt as u8) {
+        Ok(()) => Next::Forward,
+        Err(e) => Next::Exception(e),
+      }
+    }
+
+    0x29 => {
+      // sh rt, offset(rs)
+      let (rt, rs, offset) = parse_arithm_i(instr, registers);
+      let addr = data::add_ihalf_to_uword(rs, offset);
+
+      match memory.store_halfword(addr, *rt as u16) {
+        Ok(()) => Next::Forward,
+        Err(e) => Next::Exception(e),
+      }
+    }
+
+    0x2a | 0x2e => {
+      // swl rt, offset(rs) / swr rt, offset(rs)
+      let (rt, rs, offset) = parse_arithm_i(instr, registers);
+      let addr = data::add_ihalf_to_uword(rs, offset);
+
+      // the counterparts of lwl and lwr, the bytes stay within one word
+      let (start, bytes) = if opcode == 0x2a {
+        let shift = 8 * (3 - addr % 4);
+        (
+          addr & !0x3,
+          (*rt >> shift).to_le_bytes()[..=(addr % 4) as usize].to_vec(),
+        )
+      } else {
+        (addr, rt.to_le_bytes()[..(4 - addr % 4) as usize].to_vec())
+      };
+
+      let stored = (start..)
+        .zip(bytes)
+        .try_for_each(|(addr, byte)| memory.store_byte(addr, byte));
+
+      match stored {
+        Ok(()) => Next::Forward,
+        Err(e) => Next::Exception(e),
+      }
+    }
+
+    0x2b => {
+      // sw rt, offset(rs)
+      let (rt, rs, offset) = parse_arithm_i(instr, registers);
+      let addr = data::add_ihalf_to_uword(rs, offset);
+
+      match memory.store_word(addr, *rt) {
+        Ok(()) => Next::Forward,
+        Err(e) => Next::Exception(e),
+      }
+    }
+
+    0x38 => {
+      // sc rt, offset(rs)
+      let (mut rt, rs, offset) = parse_arithm_i(instr, registers);
+      let addr = data::add_ihalf_to_uword(rs, offset);
+
+      // nothing else runs between ll and sc, the store always succeeds
+      match memory.store_word(addr, *rt) {
+        Ok(()) => {
+          *rt = 1;
+          Next::Forward
+        }
+        Err(e) => Next::Exception(e),
+      }
+    }
+
+    0x31 | 0x35 | 0x39 | 0x3d => fpu::handle_transfer(opcode, instr, memory, registers),
+
+    _ => Next::Exception(Exception::ReservedInstruction),
   }
 }
 
@@ -285,7 +392,15 @@ fn handle_opcode_zero(instr: u32, _memory: &mut MemoryMap, registers: &mut Regis
       let (mut rd, _, rt) = parse_arithm_r(instr, registers);
       let shamt = data::isolate_shamt(instr);
 
-      *rd = *rt << shamt;
+      *rd = rt << shamt;
+    }
+
+    0x2 => {
+      // srl rd, rt, shamt
+      let (mut rd, _, rt) = parse_arithm_r(instr, registers);
+      let shamt = data::isolate_shamt(instr);
+
+      *rd = rt >> shamt;
     }
 
     0x3 => {
@@ -293,41 +408,56 @@ fn handle_opcode_zero(instr: u32, _memory: &mut MemoryMap, registers: &mut Regis
       let (mut rd, _, rt) = parse_arithm_r(instr, registers);
       let shamt = data::isolate_shamt(instr);
 
-      *rd = (*rt >> shamt) | (*rt & (1 << 31))
+      *rd = ((rt as i32) >> shamt) as u32;
     }
 
     0x4 => {
       // sllv rd, rt, rs
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
 
-      *rd = *rt << *rs;
+      // only the low 5 bits of rs count
+      *rd = rt << (rs & 0x1f);
+    }
+
+    0x6 => {
+      // srlv rd, rt, rs
+      let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
+
+      *rd = rt >> (rs & 0x1f);
+    }
+
+    0x7 => {
+      // srav rd, rt, rs
+      let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
+
+      *rd = ((rt as i32) >> (rs & 0x1f)) as u32;
     }
 
     0x8 => {
       // jr rs
       #[allow(clippy::unwrap_used)]
-      let rs = registers.r(data::isolate_rs(instr) as usize).unwrap();
+      let rs = *registers.r(data::isolate_rs(instr) as usize).unwrap();
 
-      return Next::Branch(*rs);
+      return Next::Branch(rs);
     }
 
     0x9 => {
       // jalr rs, rd
       #[allow(clippy::unwrap_used)]
-      let rs = registers.r(data::isolate_rs(instr) as usize).unwrap();
+      let rs = *registers.r(data::isolate_rs(instr) as usize).unwrap();
 
       #[allow(clippy::unwrap_used)]
       registers.link(data::isolate_rd(instr) as usize).unwrap();
 
-      return Next::Branch(*rs);
+      return Next::Branch(rs);
     }
 
     0xa => {
       // movz rd, rs, rt
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
 
-      if *rt == 0 {
-        *rd = *rs;
+      if rt == 0 {
+        *rd = rs;
       }
     }
 
@@ -335,11 +465,21 @@ fn handle_opcode_zero(instr: u32, _memory: &mut MemoryMap, registers: &mut Regis
       // movz rd, rs, rt
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
 
-      if *rt != 0 {
-        *rd = *rs;
+      if rt != 0 {
+        *rd = rs;
       }
     }
 
+    0xc => {
+      // syscall
+      return Next::Exception(Exception::Syscall);
+    }
+
+    0xd => {
+      // break
+      return Next::Exception(Exception::Breakpoint);
+    }
+
     0x10 => {
       // mfhi rd
       #[allow(clippy::unwrap_used)]
@@ -352,7 +492,7 @@ fn handle_opcode_zero(instr: u32, _memory: &mut MemoryMap, registers: &mut Regis
       // mthi rs
       #[allow(clippy::unwrap_used)]
       let rs_value = *registers.r(data::isolate_rs(instr) as usize).unwrap();
-      registers.hi = rs_value;
+      registers.set_hi(rs_value);
     }
 
     0x12 => {
@@ -367,25 +507,56 @@ fn handle_opcode_zero(instr: u32, _memory: &mut MemoryMap, registers: &mut Regis
       // mtlo rs
       #[allow(clippy::unwrap_used)]
       let rs_value = *registers.r(data::isolate_rs(instr) as usize).unwrap();
-      registers.lo = rs_value;
+      registers.set_lo(rs_value);
+    }
+
+    0x18 => {
+      // mult rs, rt
+      let (_, rs, rt) = parse_arithm_r(instr, registers);
+
+      let product = rs as i32 as i64 * rt as i32 as i64;
+      registers.set_hi((product >> 32) as u32);
+      registers.set_lo(product as u32);
     }
 
     0x19 => {
       // multu
       let (_, rs, rt) = parse_arithm_r(instr, registers);
 
-      let (lo, hi) = u32::widening_mul(*rs, *rt);
-      drop((rs, rt));
-      registers.hi = hi;
-      registers.lo = lo;
+      let (lo, hi) = u32::widening_mul(rs, rt);
+      registers.set_hi(hi);
+      registers.set_lo(lo);
+    }
+
+    0x1a => {
+      // div rs, rt
+      let (_, rs, rt) = parse_arithm_r(instr, registers);
+      let (rs, rt) = (rs as i32, rt as i32);
+
+      // the result of a division by zero is unpredictable, HI and LO are
+      // left as they are
+      if rt != 0 {
+        registers.set_hi(rs.wrapping_rem(rt) as u32);
+        registers.set_lo(rs.wrapping_div(rt) as u32);
+      }
+    }
+
+    0x1b => {
+      // divu rs, rt
+      let (_, rs, rt) = parse_arithm_r(instr, registers);
+
+      if rt != 0 {
+        registers.set_hi(rs % rt);
+        registers.set_lo(rs / rt);
+      }
     }
 
     0x20 => {
       // add
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
-      let result = u32::wrapping_add(*rs, *rt);
+      let result = u32::wrapping_add(rs, rt);
 
-      if data::twos_complement_overflowed(*rs, *rt, result) {
+      if data::twos_complement_overflowed(rs, rt, result) {
         return Next::Exception(Exception::Overflow);
       }
 
@@ -395,15 +566,15 @@ fn handle_opcode_zero(instr: u32, _memory: &mut MemoryMap, registers: &mut Regis
     0x21 => {
       // addu
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
-      *rd = u32::wrapping_add(*rs, *rt);
+      *rd = u32::wrapping_add(rs, rt);
     }
 
     0x22 => {
       // sub
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
-      let result = u32::wrapping_sub(*rs, *rt);
+      let result = u32::wrapping_sub(rs, rt);
 
-      if data::twos_complement_overflowed(*rs, *rt, result) {
+      if data::twos_complement_overflowed(rs, rt, result) {
         return Next::Exception(Exception::Overflow);
       }
 
@@ -413,38 +584,72 @@ fn handle_opcode_zero(instr: u32, _memory: &mut MemoryMap, registers: &mut Regis
     0x23 => {
       // subu
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
-      *rd = u32::wrapping_sub(*rs, *rt);
+      *rd = u32::wrapping_sub(rs, rt);
     }
 
     0x24 => {
       // and
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
-      *rd = *rs & *rt;
+      *rd = rs & rt;
     }
 
     0x25 => {
       // or
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
-      *rd = *rs | *rt;
+      *rd = rs | rt;
     }
 
     0x26 => {
       // xor
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
-      *rd = *rs ^ *rt;
+      *rd = rs ^ rt;
     }
 
     0x27 => {
       // nor
       let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
-      *rd = !(*rs | *rt);
+      *rd = !(rs | rt);
+    }
+
+    0x2a => {
+      // slt
+      let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
+      *rd = ((rs as i32) < (rt as i32)) as u32;
+    }
+
+    0x2b => {
+      // sltu
+      let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
+      *rd = (rs < rt) as u32;
+    }
+
+    0x30 => {
+      // tge rs, rt
+      let (rs, rt) = parse_trap_r(instr, registers);
+
+      if rs as i32 >= rt as i32 {
+        return Next::Exception(Exception::Trap);
+      } else {
+        return Next::Forward;
+      }
     }
 
     0x31 => {
       // tgeu rs, rt
       let (rs, rt) = parse_trap_r(instr, registers);
 
-      if *rs >= *rt {
+      if rs >= rt {
+        return Next::Exception(Exception::Trap);
+      } else {
+        return Next::Forward;
+      }
+    }
+
+    0x32 => {
+      // tlt rs, rt
+      let (rs, rt) = parse_trap_r(instr, registers);
+
+      if (rs as i32) < rt as i32 {
         return Next::Exception(Exception::Trap);
       } else {
         return Next::Forward;
@@ -455,7 +660,7 @@ fn handle_opcode_zero(instr: u32, _memory: &mut MemoryMap, registers: &mut Regis
       // tltu rs, rt
       let (rs, rt) = parse_trap_r(instr, registers);
 
-      if *rs < *rt {
+      if rs < rt {
         return Next::Exception(Exception::Trap);
       } else {
         return Next::Forward;
@@ -466,7 +671,7 @@ fn handle_opcode_zero(instr: u32, _memory: &mut MemoryMap, registers: &mut Regis
       // teq rs, rt
       let (rs, rt) = parse_trap_r(instr, registers);
 
-      if *rs == *rt {
+      if rs == rt {
         return Next::Exception(Exception::Trap);
       } else {
         return Next::Forward;
@@ -477,29 +682,33 @@ fn handle_opcode_zero(instr: u32, _memory: &mut MemoryMap, registers: &mut Regis
       // tneq rs, rt
       let (rs, rt) = parse_trap_r(instr, registers);
 
-      if *rs != *rt {
+      if rs != rt {
         return Next::Exception(Exception::Trap);
       } else {
         return Next::Forward;
       }
     }
 
-    _ => todo!(),
+    _ => return Next::Exception(Exception::ReservedInstruction),
   }
 
   Next::Forward
 }
 
 fn handle_opcode_one(instr: u32, _memory: &mut MemoryMap, registers: &mut Registers) -> Next {
-  let (rt, rs, imm16) = parse_arithm_i(instr, registers);
+  // data::isolate_rs cannot return values higher or equal to 32
+  #[allow(clippy::unwrap_used)]
+  let rs = *registers.r(data::isolate_rs(instr) as usize).unwrap();
+  let imm16 = data::isolate_imm16(instr);
 
-  match *rt {
+  // for REGIMM instructions, the rt field selects the operation
+  match data::isolate_rt(instr) {
     0x0 => {
       // bltz rs, offset
 
       // signed  ltz comparison
-      if *rs >= (1 << 31) {
-        Next::Branch(data::add_ihalf_to_uword(registers.pc, imm16))
+      if rs >= (1 << 31) {
+        Next::Branch(data::branch_target(registers.pc, imm16))
       } else {
         Next::Forward
       }
@@ -509,8 +718,8 @@ fn handle_opcode_one(instr: u32, _memory: &mut MemoryMap, registers: &mut Regist
       // bgez rs, offset
 
       // signed comparison
-      if *rs < (1 << 31) {
-        Next::Branch(data::add_ihalf_to_uword(registers.pc, imm16))
+      if rs < (1 << 31) {
+        Next::Branch(data::branch_target(registers.pc, imm16))
       } else {
         Next::Forward
       }
@@ -520,11 +729,11 @@ fn handle_opcode_one(instr: u32, _memory: &mut MemoryMap, registers: &mut Regist
       // bltzal rs, offset
 
       // signed  ltz comparison
-      if *rs >= (1 << 31) {
+      if rs >= (1 << 31) {
         #[allow(clippy::unwrap_used)]
         registers.link(31).unwrap();
 
-        Next::Branch(data::add_ihalf_to_uword(registers.pc, imm16))
+        Next::Branch(data::branch_target(registers.pc, imm16))
       } else {
         Next::Forward
       }
@@ -534,16 +743,149 @@ fn handle_opcode_one(instr: u32, _memory: &mut MemoryMap, registers: &mut Regist
       // bgezal rs, offset
 
       // signed comparison
-      if *rs < (1 << 31) {
+      if rs < (1 << 31) {
         #[allow(clippy::unwrap_used)]
         registers.link(31).unwrap();
 
-        Next::Branch(data::add_ihalf_to_uword(registers.pc, imm16))
+        Next::Branch(data::branch_target(registers.pc, imm16))
       } else {
         Next::Forward
       }
     }
 
-    _ => unimplemented!(),
+    code @ (0x8..=0xc | 0xe) => {
+      // tgei, tgeiu, tlti, tltiu, teqi, tnei rs, imm16
+      let imm = data::sign_extend(16, imm16 as u32);
+
+      let trap = match code {
+        0x8 => rs as i32 >= imm as i32,
+        0x9 => rs >= imm,
+        0xa => (rs as i32) < imm as i32,
+        0xb => rs < imm,
+        0xc => rs == imm,
+        _ => rs != imm,
+      };
+
+      if trap {
+        Next::Exception(Exception::Trap)
+      } else {
+        Next::Forward
+      }
+    }
+
+    _ => Next::Exception(Exception::ReservedInstruction),
   }
 }
+
+/// Execute a SPECIAL2 instruction (opcode `0x1c`), selected by the function
+/// field.
+fn handle_special2(instr: u32, registers: &mut Registers) -> Next {
+  let funct = data::isolate_funct(instr);
+
+  match funct {
+    0x0 | 0x1 | 0x4 | 0x5 => {
+      // madd, maddu, msub, msubu rs, rt
+      let (_, rs, rt) = parse_arithm_r(instr, registers);
+
+      let product = match funct {
+        0x0 | 0x4 => (rs as i32 as i64 * rt as i32 as i64) as u64,
+        _ => rs as u64 * rt as u64,
+      };
+
+      let accumulator = (registers.hi as u64) << 32 | registers.lo as u64;
+      let result = match funct {
+        0x0 | 0x1 => accumulator.wrapping_add(product),
+        _ => accumulator.wrapping_sub(product),
+      };
+
+      registers.set_hi((result >> 32) as u32);
+      registers.set_lo(result as u32);
+    }
+
+    0x2 => {
+      // mul rd, rs, rt
+      let (mut rd, rs, rt) = parse_arithm_r(instr, registers);
+      *rd = rs.wrapping_mul(rt);
+    }
+
+    0x20 => {
+      // clz rd, rs
+      let (mut rd, rs, _) = parse_arithm_r(instr, registers);
+      *rd = rs.leading_zeros();
+    }
+
+    0x21 => {
+      // clo rd, rs
+      let (mut rd, rs, _) = parse_arithm_r(instr, registers);
+      *rd = rs.leading_ones();
+    }
+
+    _ => return Next::Exception(Exception::ReservedInstruction),
+  }
+
+  Next::Forward
+}
+
+fn handle_cop0(instr: u32, memory: &mut MemoryMap, registers: &mut Registers) -> Next {
+  let rt = data::isolate_rt(instr) as usize;
+  let rd = data::isolate_rd(instr);
+
+  match data::isolate_rs(instr) {
+    0x0 => {
+      // mfc0 rt, rd
+      let value = registers.cop0.read(rd).unwrap_or(0);
+
+      // data::isolate_rt cannot return values higher or equal to 32
+      #[allow(clippy::unwrap_used)]
+      let mut rt = registers.r(rt).unwrap();
+      *rt = value;
+    }
+
+    0x4 => {
+      // mtc0 rt, rd
+      #[allow(clippy::unwrap_used)]
+      let value = *registers.r(rt).unwrap();
+      registers.cop0.write(rd, value);
+    }
+
+    0x10 => match data::isolate_funct(instr) {
+      0x1 => {
+        // tlbr
+        let cop0 = &mut registers.cop0;
+        if let Some(entry) = memory.mmu().entry(cop0.indexed()) {
+          cop0.set_entry(entry);
+        }
+      }
+
+      0x2 => {
+        // tlbwi
+        let cop0 = &registers.cop0;
+        memory.mmu_mut().set_entry(cop0.indexed(), cop0.entry());
+      }
+
+      0x6 => {
+        // tlbwr
+        let cop0 = &registers.cop0;
+        memory
+          .mmu_mut()
+          .set_entry(cop0.randomly_indexed(), cop0.entry());
+      }
+
+      0x8 => {
+        // tlbp
+        let cop0 = &mut registers.cop0;
+        let index = memory.mmu().probe(cop0.entry_hi, cop0.entry_hi);
+        cop0.set_probe(index);
+      }
+
+      // eret
+      0x18 => return Next::Branch(registers.cop0.eret()),
+
+      _ => return Next::Exception(Exception::ReservedInstruction),
+    },
+
+    _ => return Next::Exception(Exception::ReservedInstruction),
+  }
+
+  Next::Forward
+}
@@ -36,15 +36,25 @@ pub fn twos_complement_overflowed(n1: u32, n2: u32, r: u32) -> bool {
   (n1 ^ n2) < 0x80000000 && (n1 ^ r) > 0x7fffffff
 }
 
+/// Sign extend the `base` lowest bits of `buf` to a whole word.
 pub fn sign_extend(base: usize, buf: u32) -> u32 {
-  let ext = 0xffffu32 * (buf >> (base - 1));
-  buf | ext << base
+  let shift = 32 - base;
+  (((buf << shift) as i32) >> shift) as u32
 }
 
+/// Add a signed half word to a word, wrapping around the address space.
 pub fn add_ihalf_to_uword(word: u32, half: u16) -> u32 {
-  if half > (u16::MAX >> 1) {
-    word - (!(half as u32) + 1)
-  } else {
-    word - half as u32
-  }
+  word.wrapping_add(sign_extend(16, half as u32))
+}
+
+/// Target of a branch at `pc` with the given offset, counted in words from the
+/// next instruction.
+pub fn branch_target(pc: u32, offset: u16) -> u32 {
+  pc.wrapping_add(4)
+    .wrapping_add(sign_extend(16, offset as u32) << 2)
+}
+
+/// Target of a jump at `pc`, within the 256MB region of the next instruction.
+pub fn jump_target(pc: u32, target: u32) -> u32 {
+  (pc.wrapping_add(4) & 0xf0000000) | (target << 2)
 }
@@ -0,0 +1,563 @@
+use crate::cop1::{self, flag, Cop1, RoundingMode};
+use crate::cycle::{data, Next};
+use crate::exception::Exception;
+use crate::mem::MemoryMap;
+use crate::register::Registers;
+
+/// Values of the `fmt` field (in place of `rs`) selecting the operand format.
+const FMT_S: u32 = 0x10;
+const FMT_D: u32 = 0x11;
+const FMT_W: u32 = 0x14;
+
+/// Value stored by conversions to word of NaN or out of range values.
+const INVALID_WORD: u32 = 0x7fffffff;
+
+/// Floating point precision of an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Precision {
+  Single,
+  Double,
+}
+
+impl Precision {
+  fn read(self, cop1: &Cop1, n: usize) -> f64 {
+    match self {
+      Precision::Single => cop1.single(n) as f64,
+      Precision::Double => cop1.double(n),
+    }
+  }
+
+  fn write(self, cop1: &mut Cop1, n: usize, value: f64) {
+    match self {
+      Precision::Single => cop1.set_single(n, value as f32),
+      Precision::Double => cop1.set_double(n, value),
+    }
+  }
+
+  /// Round `value` to this precision.
+  fn round(self, value: f64) -> f64 {
+    match self {
+      Precision::Single => value as f32 as f64,
+      Precision::Double => value,
+    }
+  }
+
+  /// The next value of this precision after `value`, toward positive
+  /// infinity.
+  fn next_up(self, value: f64) -> f64 {
+    match self {
+      Precision::Single => {
+        let value = value as f32;
+        let bits = value.to_bits();
+
+        let next = match value {
+          v if v.is_nan() || v == f32::INFINITY => value,
+          v if v == 0.0 => f32::from_bits(1),
+          v if v > 0.0 => f32::from_bits(bits + 1),
+          _ => f32::from_bits(bits - 1),
+        };
+
+        next as f64
+      }
+      Precision::Double => {
+        let bits = value.to_bits();
+
+        match value {
+          v if v.is_nan() || v == f64::INFINITY => value,
+          v if v == 0.0 => f64::from_bits(1),
+          v if v > 0.0 => f64::from_bits(bits + 1),
+          _ => f64::from_bits(bits - 1),
+        }
+      }
+    }
+  }
+
+  fn next_down(self, value: f64) -> f64 {
+    -self.next_up(-value)
+  }
+
+  fn max(self) -> f64 {
+    match self {
+      Precision::Single => f32::MAX as f64,
+      Precision::Double => f64::MAX,
+    }
+  }
+
+  fn min_positive(self) -> f64 {
+    match self {
+      Precision::Single => f32::MIN_POSITIVE as f64,
+      Precision::Double => f64::MIN_POSITIVE,
+    }
+  }
+
+  /// Double precision values must be held by even registers.
+  fn valid_register(self, n: usize) -> bool {
+    self == Precision::Single || n % 2 == 0
+  }
+}
+
+/// Parses the `ft`, `fs` and `fd` fields of COP1 instructions.
+fn parse_fpr(instr: u32) -> (usize, usize, usize) {
+  (
+    data::isolate_rt(instr) as usize,
+    data::isolate_rd(instr) as usize,
+    data::isolate_shamt(instr) as usize,
+  )
+}
+
+/// Report `causes` to FCSR, then run `write` unless one of them traps.
+fn complete(cop1: &mut Cop1, causes: u32, write: impl FnOnce(&mut Cop1)) -> Next {
+  if !cop1.signal(causes) {
+    return Next::Exception(Exception::FloatingPoint);
+  }
+
+  write(cop1);
+  Next::Forward
+}
+
+/// Trap on an encoding the FPU does not implement, such as a double precision
+/// operand in an odd register.
+fn unimplemented_operation(cop1: &mut Cop1) -> Next {
+  complete(cop1, flag::UNIMPLEMENTED, |_| ())
+}
+
+/// Execute a COP1 instruction (opcode `0x11`).
+pub fn handle_cop1(instr: u32, registers: &mut Registers) -> Next {
+  let (ft, fs, _) = parse_fpr(instr);
+
+  match data::isolate_rs(instr) {
+    0x0 => {
+      // mfc1 rt, fs
+      let value = registers.cop1.word(fs);
+
+      // data::isolate_rt cannot return values higher or equal to 32
+      #[allow(clippy::unwrap_used)]
+      let mut rt = registers.r(ft).unwrap();
+      *rt = value;
+      Next::Forward
+    }
+
+    0x2 => {
+      // cfc1 rt, fs
+      let value = match fs as u32 {
+        cop1::FIR_NUMBER => cop1::FIR,
+        cop1::FCSR => registers.cop1.fcsr,
+        _ => 0,
+      };
+
+      #[allow(clippy::unwrap_used)]
+      let mut rt = registers.r(ft).unwrap();
+      *rt = value;
+      Next::Forward
+    }
+
+    0x4 => {
+      // mtc1 rt, fs
+      #[allow(clippy::unwrap_used)]
+      let rt = *registers.r(ft).unwrap();
+      registers.cop1.set_word(fs, rt);
+      Next::Forward
+    }
+
+    0x6 => {
+      // ctc1 rt, fs
+      #[allow(clippy::unwrap_used)]
+      let rt = *registers.r(ft).unwrap();
+
+      if fs as u32 == cop1::FCSR {
+        registers.cop1.set_fcsr(rt);
+      }
+
+      Next::Forward
+    }
+
+    0x8 => {
+      // bc1f cc, offset / bc1t cc, offset
+      let cc = (instr >> 18) & 0x7;
+      let on_true = instr & (1 << 16) != 0;
+
+      if registers.cop1.condition(cc) == on_true {
+        Next::Branch(data::branch_target(
+          registers.pc,
+          data::isolate_imm16(instr),
+        ))
+      } else {
+        Next::Forward
+      }
+    }
+
+    FMT_S => handle_float(instr, Precision::Single, &mut registers.cop1),
+    FMT_D => handle_float(instr, Precision::Double, &mut registers.cop1),
+    FMT_W => handle_word(instr, &mut registers.cop1),
+
+    _ => unimplemented_operation(&mut registers.cop1),
+  }
+}
+
+/// Instructions operating on single or double precision values.
+fn handle_float(instr: u32, p: Precision, cop1: &mut Cop1) -> Next {
+  let (ft, fs, fd) = parse_fpr(instr);
+  let funct = data::isolate_funct(instr);
+
+  match funct {
+    0x0..=0x3 => {
+      // add.fmt, sub.fmt, mul.fmt, div.fmt fd, fs, ft
+      if ![ft, fs, fd].iter().all(|n| p.valid_register(*n)) {
+        return unimplemented_operation(cop1);
+      }
+
+      let (a, b) = (p.read(cop1, fs), p.read(cop1, ft));
+      let (result, causes) = arithmetic(p, cop1.rounding_mode(), funct, a, b);
+
+      complete(cop1, causes, |cop1| p.write(cop1, fd, result))
+    }
+
+    0x4 => {
+      // sqrt.fmt fd, fs
+      if ![fs, fd].iter().all(|n| p.valid_register(*n)) {
+        return unimplemented_operation(cop1);
+      }
+
+      let a = p.read(cop1, fs);
+      let (result, causes) = arithmetic(p, cop1.rounding_mode(), funct, a, 0.0);
+
+      complete(cop1, causes, |cop1| p.write(cop1, fd, result))
+    }
+
+    0x5..=0x7 => {
+      // abs.fmt, mov.fmt, neg.fmt fd, fs
+      if ![fs, fd].iter().all(|n| p.valid_register(*n)) {
+        return unimplemented_operation(cop1);
+      }
+
+      // operate on the bits, the sign is in the highest word
+      let high = match p {
+        Precision::Single => 0,
+        Precision::Double => 1,
+      };
+
+      let mut sign_word = cop1.word(fs + high);
+      match funct {
+        0x5 => sign_word &= !(1 << 31),
+        0x7 => sign_word ^= 1 << 31,
+        _ => (),
+      }
+
+      if p == Precision::Double {
+        cop1.set_word(fd, cop1.word(fs));
+      }
+      cop1.set_word(fd + high, sign_word);
+
+      Next::Forward
+    }
+
+    0xc..=0xf | 0x24 => {
+      // round.w.fmt, trunc.w.fmt, ceil.w.fmt, floor.w.fmt, cvt.w.fmt fd, fs
+      if !p.valid_register(fs) {
+        return unimplemented_operation(cop1);
+      }
+
+      let mode = match funct {
+        0xc => RoundingMode::Nearest,
+        0xd => RoundingMode::TowardZero,
+        0xe => RoundingMode::TowardPositive,
+        0xf => RoundingMode::TowardNegative,
+        _ => cop1.rounding_mode(),
+      };
+
+      let (result, causes) = to_word(p.read(cop1, fs), mode);
+
+      complete(cop1, causes, |cop1| cop1.set_word(fd, result))
+    }
+
+    0x20 => {
+      // cvt.s.d fd, fs
+      if p != Precision::Double || !p.valid_register(fs) {
+        return unimplemented_operation(cop1);
+      }
+
+      let value = cop1.double(fs);
+      let (result, causes) = match value.is_nan() {
+        // converting a NaN is an invalid operation, it stays a NaN
+        true => (value, flag::INVALID),
+        false => round(
+          Precision::Single,
+          cop1.rounding_mode(),
+          value,
+          0.0,
+          value.is_finite(),
+        ),
+      };
+
+      complete(cop1, causes, |cop1| cop1.set_single(fd, result as f32))
+    }
+
+    0x21 => {
+      // cvt.d.s fd, fs
+      if p != Precision::Single || !Precision::Double.valid_register(fd) {
+        return unimplemented_operation(cop1);
+      }
+
+      // every single precision value is exactly representable
+      let value = cop1.single(fs) as f64;
+      cop1.set_double(fd, value);
+      Next::Forward
+    }
+
+    0x30..=0x3f => {
+      // c.cond.fmt cc, fs, ft
+      if ![fs, ft].iter().all(|n| p.valid_register(*n)) {
+        return unimplemented_operation(cop1);
+      }
+
+      let (a, b) = (p.read(cop1, fs), p.read(cop1, ft));
+      let cc = (instr >> 8) & 0x7;
+      let (result, causes) = compare(funct & 0xf, a, b);
+
+      complete(cop1, causes, |cop1| cop1.set_condition(cc, result))
+    }
+
+    _ => unimplemented_operation(cop1),
+  }
+}
+
+/// Instructions converting from a word (`W` format).
+fn handle_word(instr: u32, cop1: &mut Cop1) -> Next {
+  let (_, fs, fd) = parse_fpr(instr);
+  let value = cop1.word(fs) as i32 as f64;
+
+  match data::isolate_funct(instr) {
+    0x20 => {
+      // cvt.s.w fd, fs
+      let (result, causes) = round(Precision::Single, cop1.rounding_mode(), value, 0.0, true);
+
+      complete(cop1, causes, |cop1| cop1.set_single(fd, result as f32))
+    }
+
+    0x21 => {
+      // cvt.d.w fd, fs
+      if !Precision::Double.valid_register(fd) {
+        return unimplemented_operation(cop1);
+      }
+
+      cop1.set_double(fd, value);
+      Next::Forward
+    }
+
+    _ => unimplemented_operation(cop1),
+  }
+}
+
+/// Compute `add`, `sub`, `mul`, `div` or `sqrt` (selected by `funct`) at
+/// precision `p` with rounding `mode`, returning the rounded result and the
+/// IEEE exceptions raised.
+///
+/// Single precision operations are carried out in double precision first,
+/// which holds the result closely enough to round it correctly.
+fn arithmetic(p: Precision, mode: RoundingMode, funct: u32, a: f64, b: f64) -> (f64, u32) {
+  // exact result is `result + error`, or as close as a double gets
+  let (result, error) = match funct {
+    0x0 => two_sum(a, b),
+    0x1 => two_sum(a, -b),
+    0x2 => {
+      let r = a * b;
+      (r, a.mul_add(b, -r))
+    }
+    0x3 => {
+      let r = a / b;
+      (r, (-r).mul_add(b, a) / b)
+    }
+    _ => {
+      let r = a.sqrt();
+      let error = match r == 0.0 {
+        true => 0.0,
+        false => -r.mul_add(r, -a) / (2.0 * r),
+      };
+      (r, error)
+    }
+  };
+
+  let operands_nan = a.is_nan() || (funct != 0x4 && b.is_nan());
+
+  if result.is_nan() {
+    let causes = match operands_nan {
+      true => 0,
+      false => flag::INVALID,
+    };
+
+    return (result, causes);
+  }
+
+  if funct == 0x3 && b == 0.0 && a.is_finite() {
+    return (result, flag::DIV_BY_ZERO);
+  }
+
+  let finite = a.is_finite() && (funct == 0x4 || b.is_finite());
+  let error = match error.is_finite() {
+    true => error,
+    false => 0.0,
+  };
+
+  round(p, mode, result, error, finite)
+}
+
+/// Round the value `value + error` to precision `p` with `mode`, returning
+/// the result and the IEEE exceptions raised. `value` is the double closest
+/// to the value, `error` what it lacks. `finite_operands` tells whether the
+/// value was computed from finite operands only, in which case an infinite
+/// value means the operation overflowed.
+fn round(
+  p: Precision,
+  mode: RoundingMode,
+  value: f64,
+  error: f64,
+  finite_operands: bool,
+) -> (f64, u32) {
+  let nearest = p.round(value);
+
+  if nearest.is_infinite() {
+    if !finite_operands {
+      return (nearest, 0);
+    }
+
+    // directed modes stop at the largest finite value on one side
+    let max = p.max().copysign(nearest);
+    let result = match mode {
+      RoundingMode::Nearest => nearest,
+      RoundingMode::TowardZero => max,
+      RoundingMode::TowardPositive if nearest < 0.0 => max,
+      RoundingMode::TowardNegative if nearest > 0.0 => max,
+      _ => nearest,
+    };
+
+    return (result, flag::OVERFLOW | flag::INEXACT);
+  }
+
+  // how far the value is above `nearest`, only its sign matters
+  let above = (value - nearest) + error;
+
+  let result = match mode {
+    RoundingMode::Nearest => nearest,
+    RoundingMode::TowardZero if nearest > 0.0 && above < 0.0 => p.next_down(nearest),
+    RoundingMode::TowardZero if nearest < 0.0 && above > 0.0 => p.next_up(nearest),
+    RoundingMode::TowardPositive if above > 0.0 => p.next_up(nearest),
+    RoundingMode::TowardNegative if above < 0.0 => p.next_down(nearest),
+    _ => nearest,
+  };
+
+  let causes = if above == 0.0 {
+    0
+  } else if result.is_infinite() && finite_operands {
+    flag::OVERFLOW | flag::INEXACT
+  } else if result.abs() < p.min_positive() {
+    flag::UNDERFLOW | flag::INEXACT
+  } else {
+    flag::INEXACT
+  };
+
+  (result, causes)
+}
+
+/// Sum of `a` and `b`, along with the rounding error of the sum.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+  let sum = a + b;
+  let b_virtual = sum - a;
+  let a_virtual = sum - b_virtual;
+
+  (sum, (a - a_virtual) + (b - b_virtual))
+}
+
+/// Convert `value` to a word with the given rounding mode.
+fn to_word(value: f64, mode: RoundingMode) -> (u32, u32) {
+  let rounded = mode.round(value);
+
+  if value.is_nan() || rounded < i32::MIN as f64 || rounded > i32::MAX as f64 {
+    return (INVALID_WORD, flag::INVALID);
+  }
+
+  let causes = match rounded != value {
+    true => flag::INEXACT,
+    false => 0,
+  };
+
+  (rounded as i32 as u32, causes)
+}
+
+/// Evaluate the comparison `cond` (the 4 lowest bits of `c.cond.fmt`'s funct)
+/// on `a` and `b`.
+///
+/// Bit 0 accepts unordered operands, bit 1 equal ones and bit 2 `a < b`. Bit 3
+/// marks signaling comparisons, which raise an invalid operation on unordered
+/// operands.
+fn compare(cond: u32, a: f64, b: f64) -> (bool, u32) {
+  let unordered = a.is_nan() || b.is_nan();
+
+  let result =
+    (cond & 0x1 != 0 && unordered) || (cond & 0x2 != 0 && a == b) || (cond & 0x4 != 0 && a < b);
+
+  let causes = match unordered && cond & 0x8 != 0 {
+    true => flag::INVALID,
+    false => 0,
+  };
+
+  (result, causes)
+}
+
+/// `lwc1`, `ldc1`, `swc1` and `sdc1`, selected by `opcode`.
+pub fn handle_transfer(
+  opcode: u32,
+  instr: u32,
+  memory: &mut MemoryMap,
+  registers: &mut Registers,
+) -> Next {
+  let (ft, _, _) = parse_fpr(instr);
+
+  // data::isolate_rs cannot return values higher or equal to 32
+  #[allow(clippy::unwrap_used)]
+  let base = *registers.r(data::isolate_rs(instr) as usize).unwrap();
+  let addr = data::add_ihalf_to_uword(base, data::isolate_imm16(instr));
+  let double = matches!(opcode, 0x35 | 0x3d);
+
+  if double && !Precision::Double.valid_register(ft) {
+    return unimplemented_operation(&mut registers.cop1);
+  }
+
+  let result = match opcode {
+    0x31 => {
+      // lwc1 ft, offset(base)
+      memory
+        .load_word(addr)
+        .map(|w| registers.cop1.set_word(ft, w))
+    }
+
+    0x35 => {
+      // ldc1 ft, offset(base)
+      if addr % 8 != 0 {
+        Err(memory.fault(addr, Exception::AddrLoadFetch))
+      } else {
+        memory.load_word(addr).and_then(|low| {
+          let high = memory.load_word(addr + 4)?;
+          registers.cop1.set_word(ft, low);
+          registers.cop1.set_word(ft + 1, high);
+          Ok(())
+        })
+      }
+    }
+
+    0x39 => {
+      // swc1 ft, offset(base)
+      memory.store_word(addr, registers.cop1.word(ft))
+    }
+
+    _ => {
+      // sdc1 ft, offset(base)
+      let (low, high) = (registers.cop1.word(ft), registers.cop1.word(ft + 1));
+      memory.store_doubleword(addr, low, high)
+    }
+  };
+
+  match result {
+    Ok(()) => Next::Forward,
+    Err(e) => Next::Exception(e),
+  }
+}
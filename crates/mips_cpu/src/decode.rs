@@ -0,0 +1,202 @@
+use crate::cycle::data;
+use crate::register::{F0, FCSR, HI, LO};
+
+/// Broad category of an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+  /// Arithmetic, logic, shifts, moves and multiplications.
+  Alu,
+  /// Floating point arithmetic, conversions, comparisons and moves between
+  /// the FPU and regular registers.
+  Float,
+  /// Conditional, PC-relative branch.
+  Branch,
+  /// Unconditional jump, direct or through a register.
+  Jump,
+  Load,
+  Store,
+  Syscall,
+  /// Conditional traps and `break`.
+  Trap,
+  /// Anything else, including words which do not decode.
+  Other,
+}
+
+impl Class {
+  /// Whether instructions of this class may redirect control flow.
+  pub fn is_control_transfer(self) -> bool {
+    matches!(self, Class::Branch | Class::Jump)
+  }
+}
+
+pub fn classify(instr: u32) -> Class {
+  match data::isolate_opcode(instr) {
+    0x0 => match data::isolate_funct(instr) {
+      0x8 | 0x9 => Class::Jump,
+      0xc => Class::Syscall,
+      0xd | 0x30..=0x36 => Class::Trap,
+      0x0..=0x7 | 0xa | 0xb | 0x10..=0x13 | 0x18..=0x1b | 0x20..=0x27 | 0x2a | 0x2b => Class::Alu,
+      _ => Class::Other,
+    },
+    0x1 => match data::isolate_rt(instr) {
+      0x0 | 0x1 | 0x10 | 0x11 => Class::Branch,
+      0x8..=0xe => Class::Trap,
+      _ => Class::Other,
+    },
+    0x2 | 0x3 => Class::Jump,
+    0x4..=0x7 => Class::Branch,
+    0x8..=0xf | 0x1c => Class::Alu,
+    // eret
+    0x10 if data::isolate_rs(instr) == 0x10 && data::isolate_funct(instr) == 0x18 => Class::Jump,
+    0x11 => match data::isolate_rs(instr) {
+      0x8 => Class::Branch,
+      _ => Class::Float,
+    },
+    0x20..=0x26 | 0x30 | 0x31 | 0x35 => Class::Load,
+    0x28..=0x2b | 0x2e | 0x38 | 0x39 | 0x3d => Class::Store,
+    _ => Class::Other,
+  }
+}
+
+/// Target of a PC-relative branch or a direct jump located at `pc`. Returns
+/// `None` for other instructions, including jumps through a register.
+pub fn static_target(instr: u32, pc: u32) -> Option<u32> {
+  match data::isolate_opcode(instr) {
+    0x2 | 0x3 => Some(data::jump_target(pc, data::isolate_target_26(instr))),
+    _ if classify(instr) == Class::Branch => {
+      Some(data::branch_target(pc, data::isolate_imm16(instr)))
+    }
+    _ => None,
+  }
+}
+
+/// Whether the instruction links a return address into `$ra` before jumping,
+/// as function calls do.
+pub fn is_call(instr: u32) -> bool {
+  match data::isolate_opcode(instr) {
+    0x0 => data::isolate_funct(instr) == 0x9,
+    0x1 => matches!(data::isolate_rt(instr), 0x10 | 0x11),
+    0x3 => true,
+    _ => false,
+  }
+}
+
+/// Whether the instruction is `jr $ra`, as function returns are.
+pub fn is_return(instr: u32) -> bool {
+  data::isolate_opcode(instr) == 0x0
+    && data::isolate_funct(instr) == 0x8
+    && data::isolate_rs(instr) == 31
+}
+
+/// Registers read by the instruction, as numbered in `register`. `$zero` is
+/// left out.
+pub fn registers_read(instr: u32) -> Vec<u8> {
+  dependencies(instr).0
+}
+
+/// Registers written by the instruction, as numbered in `register`. `$zero` is
+/// left out, and so are the exception flags FPU operations accumulate in FCSR.
+pub fn registers_written(instr: u32) -> Vec<u8> {
+  dependencies(instr).1
+}
+
+/// Registers read and written by an instruction.
+fn dependencies(instr: u32) -> (Vec<u8>, Vec<u8>) {
+  let rs = data::isolate_rs(instr) as u8;
+  let rt = data::isolate_rt(instr) as u8;
+  let rd = data::isolate_rd(instr) as u8;
+  let funct = data::isolate_funct(instr);
+
+  let (read, written): (Vec<u8>, Vec<u8>) = match data::isolate_opcode(instr) {
+    0x0 => match funct {
+      0x0 | 0x2 | 0x3 => (vec![rt], vec![rd]),
+      0x4 | 0x6 | 0x7 | 0xa | 0xb | 0x20..=0x27 | 0x2a | 0x2b => (vec![rs, rt], vec![rd]),
+      0x8 => (vec![rs], vec![]),
+      0x9 => (vec![rs], vec![rd]),
+      // arguments of the system call, and its result
+      0xc => (vec![2, 4, 5, 6, 7], vec![2]),
+      0x10 => (vec![HI], vec![rd]),
+      0x11 => (vec![rs], vec![HI]),
+      0x12 => (vec![LO], vec![rd]),
+      0x13 => (vec![rs], vec![LO]),
+      0x18..=0x1b => (vec![rs, rt], vec![HI, LO]),
+      0x30..=0x36 => (vec![rs, rt], vec![]),
+      _ => (vec![], vec![]),
+    },
+    0x1 => match rt {
+      0x10 | 0x11 => (vec![rs], vec![31]),
+      _ => (vec![rs], vec![]),
+    },
+    0x3 => (vec![], vec![31]),
+    0x4 | 0x5 => (vec![rs, rt], vec![]),
+    0x6 | 0x7 => (vec![rs], vec![]),
+    0x8..=0xe => (vec![rs], vec![rt]),
+    0xf => (vec![], vec![rt]),
+    0x10 => match rs {
+      0x0 => (vec![], vec![rt]),
+      0x4 => (vec![rt], vec![]),
+      _ => (vec![], vec![]),
+    },
+    0x11 => cop1_dependencies(instr),
+    0x1c => match funct {
+      0x0 | 0x1 | 0x4 | 0x5 => (vec![rs, rt, HI, LO], vec![HI, LO]),
+      0x2 => (vec![rs, rt], vec![rd]),
+      0x20 | 0x21 => (vec![rs], vec![rd]),
+      _ => (vec![], vec![]),
+    },
+    // lwl and lwr merge into the previous value
+    0x22 | 0x26 => (vec![rs, rt], vec![rt]),
+    0x20 | 0x21 | 0x23..=0x25 | 0x30 => (vec![rs], vec![rt]),
+    0x28..=0x2b | 0x2e => (vec![rs, rt], vec![]),
+    0x38 => (vec![rs, rt], vec![rt]),
+    0x31 => (vec![rs], vec![F0 + rt]),
+    0x35 => (vec![rs], fpr(rt, true)),
+    0x39 => (vec![rs, F0 + rt], vec![]),
+    0x3d => ([vec![rs], fpr(rt, true)].concat(), vec![]),
+    _ => (vec![], vec![]),
+  };
+
+  let keep = |regs: Vec<u8>| regs.into_iter().filter(|r| *r != 0).collect();
+  (keep(read), keep(written))
+}
+
+fn cop1_dependencies(instr: u32) -> (Vec<u8>, Vec<u8>) {
+  let rt = data::isolate_rt(instr) as u8;
+  let fs = data::isolate_rd(instr) as u8;
+  let fd = data::isolate_shamt(instr) as u8;
+  let funct = data::isolate_funct(instr);
+
+  match data::isolate_rs(instr) {
+    0x0 => (vec![F0 + fs], vec![rt]),
+    0x2 => (vec![FCSR], vec![rt]),
+    0x4 => (vec![rt], vec![F0 + fs]),
+    0x6 => (vec![rt], vec![FCSR]),
+    0x8 => (vec![FCSR], vec![]),
+    fmt @ (0x10 | 0x11) => {
+      let double = fmt == 0x11;
+
+      match funct {
+        0x0..=0x3 => ([fpr(fs, double), fpr(rt, double)].concat(), fpr(fd, double)),
+        0x4..=0x7 => (fpr(fs, double), fpr(fd, double)),
+        0xc..=0xf | 0x24 | 0x20 => (fpr(fs, double), fpr(fd, false)),
+        0x21 => (fpr(fs, double), fpr(fd, true)),
+        0x30..=0x3f => ([fpr(fs, double), fpr(rt, double)].concat(), vec![FCSR]),
+        _ => (vec![], vec![]),
+      }
+    }
+    0x14 => match funct {
+      0x20 => (fpr(fs, false), fpr(fd, false)),
+      0x21 => (fpr(fs, false), fpr(fd, true)),
+      _ => (vec![], vec![]),
+    },
+    _ => (vec![], vec![]),
+  }
+}
+
+/// Floating point register `n`, along with the next one for doubles.
+fn fpr(n: u8, double: bool) -> Vec<u8> {
+  match double {
+    true => vec![F0 + n, F0 + n + 1],
+    false => vec![F0 + n],
+  }
+}
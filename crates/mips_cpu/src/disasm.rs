@@ -0,0 +1,215 @@
+use crate::cycle::data;
+use crate::register::NAMES;
+
+/// Disassemble an instruction located at `pc`.
+///
+/// Branch and jump targets are printed as absolute addresses. Words which do
+/// not decode to a known instruction are printed as a `.word` directive.
+pub fn disassemble(instr: u32, pc: u32) -> String {
+  let rs = reg(data::isolate_rs(instr));
+  let rt = reg(data::isolate_rt(instr));
+  let rd = reg(data::isolate_rd(instr));
+  let shamt = data::isolate_shamt(instr);
+  let imm16 = data::isolate_imm16(instr);
+  let simm = data::sign_extend(16, imm16 as u32) as i32;
+  let branch = data::branch_target(pc, imm16);
+
+  match data::isolate_opcode(instr) {
+    0x0 => match data::isolate_funct(instr) {
+      0x0 if instr == 0 => "nop".to_owned(),
+      0x0 => format!("sll {rd}, {rt}, {shamt}"),
+      0x2 => format!("srl {rd}, {rt}, {shamt}"),
+      0x3 => format!("sra {rd}, {rt}, {shamt}"),
+      0x4 => format!("sllv {rd}, {rt}, {rs}"),
+      0x6 => format!("srlv {rd}, {rt}, {rs}"),
+      0x7 => format!("srav {rd}, {rt}, {rs}"),
+      0x8 => format!("jr {rs}"),
+      0x9 => format!("jalr {rd}, {rs}"),
+      0xa => format!("movz {rd}, {rs}, {rt}"),
+      0xb => format!("movn {rd}, {rs}, {rt}"),
+      0xc => "syscall".to_owned(),
+      0xd => "break".to_owned(),
+      0x10 => format!("mfhi {rd}"),
+      0x11 => format!("mthi {rs}"),
+      0x12 => format!("mflo {rd}"),
+      0x13 => format!("mtlo {rs}"),
+      0x18 => format!("mult {rs}, {rt}"),
+      0x19 => format!("multu {rs}, {rt}"),
+      0x1a => format!("div {rs}, {rt}"),
+      0x1b => format!("divu {rs}, {rt}"),
+      0x20 => format!("add {rd}, {rs}, {rt}"),
+      0x21 => format!("addu {rd}, {rs}, {rt}"),
+      0x22 => format!("sub {rd}, {rs}, {rt}"),
+      0x23 => format!("subu {rd}, {rs}, {rt}"),
+      0x24 => format!("and {rd}, {rs}, {rt}"),
+      0x25 => format!("or {rd}, {rs}, {rt}"),
+      0x26 => format!("xor {rd}, {rs}, {rt}"),
+      0x27 => format!("nor {rd}, {rs}, {rt}"),
+      0x2a => format!("slt {rd}, {rs}, {rt}"),
+      0x2b => format!("sltu {rd}, {rs}, {rt}"),
+      0x30 => format!("tge {rs}, {rt}"),
+      0x31 => format!("tgeu {rs}, {rt}"),
+      0x32 => format!("tlt {rs}, {rt}"),
+      0x33 => format!("tltu {rs}, {rt}"),
+      0x34 => format!("teq {rs}, {rt}"),
+      0x36 => format!("tne {rs}, {rt}"),
+      _ => word(instr),
+    },
+
+    0x1 => match data::isolate_rt(instr) {
+      0x0 => format!("bltz {rs}, {branch:#010x}"),
+      0x1 => format!("bgez {rs}, {branch:#010x}"),
+      0x8 => format!("tgei {rs}, {simm}"),
+      0x9 => format!("tgeiu {rs}, {simm}"),
+      0xa => format!("tlti {rs}, {simm}"),
+      0xb => format!("tltiu {rs}, {simm}"),
+      0xc => format!("teqi {rs}, {simm}"),
+      0xe => format!("tnei {rs}, {simm}"),
+      0x10 => format!("bltzal {rs}, {branch:#010x}"),
+      0x11 => format!("bgezal {rs}, {branch:#010x}"),
+      _ => word(instr),
+    },
+
+    0x2 => format!("j {:#010x}", jump(instr, pc)),
+    0x3 => format!("jal {:#010x}", jump(instr, pc)),
+    0x4 => format!("beq {rs}, {rt}, {branch:#010x}"),
+    0x5 => format!("bne {rs}, {rt}, {branch:#010x}"),
+    0x6 => format!("blez {rs}, {branch:#010x}"),
+    0x7 => format!("bgtz {rs}, {branch:#010x}"),
+    0x8 => format!("addi {rt}, {rs}, {simm}"),
+    0x9 => format!("addiu {rt}, {rs}, {simm}"),
+    0xa => format!("slti {rt}, {rs}, {simm}"),
+    0xb => format!("sltiu {rt}, {rs}, {simm}"),
+    0xc => format!("andi {rt}, {rs}, {imm16:#x}"),
+    0xd => format!("ori {rt}, {rs}, {imm16:#x}"),
+    0xe => format!("xori {rt}, {rs}, {imm16:#x}"),
+    0xf => format!("lui {rt}, {imm16:#x}"),
+
+    0x10 => match data::isolate_rs(instr) {
+      0x0 => format!("mfc0 {rt}, ${}", data::isolate_rd(instr)),
+      0x4 => format!("mtc0 {rt}, ${}", data::isolate_rd(instr)),
+      0x10 => match data::isolate_funct(instr) {
+        0x1 => "tlbr".to_owned(),
+        0x2 => "tlbwi".to_owned(),
+        0x6 => "tlbwr".to_owned(),
+        0x8 => "tlbp".to_owned(),
+        0x18 => "eret".to_owned(),
+        _ => word(instr),
+      },
+      _ => word(instr),
+    },
+
+    0x11 => cop1(instr, branch),
+
+    0x1c => match data::isolate_funct(instr) {
+      0x0 => format!("madd {rs}, {rt}"),
+      0x1 => format!("maddu {rs}, {rt}"),
+      0x2 => format!("mul {rd}, {rs}, {rt}"),
+      0x4 => format!("msub {rs}, {rt}"),
+      0x5 => format!("msubu {rs}, {rt}"),
+      0x20 => format!("clz {rd}, {rs}"),
+      0x21 => format!("clo {rd}, {rs}"),
+      _ => word(instr),
+    },
+
+    0x20 => format!("lb {rt}, {simm}({rs})"),
+    0x21 => format!("lh {rt}, {simm}({rs})"),
+    0x22 => format!("lwl {rt}, {simm}({rs})"),
+    0x23 => format!("lw {rt}, {simm}({rs})"),
+    0x24 => format!("lbu {rt}, {simm}({rs})"),
+    0x25 => format!("lhu {rt}, {simm}({rs})"),
+    0x26 => format!("lwr {rt}, {simm}({rs})"),
+    0x28 => format!("sb {rt}, {simm}({rs})"),
+    0x29 => format!("sh {rt}, {simm}({rs})"),
+    0x2a => format!("swl {rt}, {simm}({rs})"),
+    0x2b => format!("sw {rt}, {simm}({rs})"),
+    0x2e => format!("swr {rt}, {simm}({rs})"),
+    0x30 => format!("ll {rt}, {simm}({rs})"),
+    0x31 => format!("lwc1 {}, {simm}({rs})", freg(data::isolate_rt(instr))),
+    0x35 => format!("ldc1 {}, {simm}({rs})", freg(data::isolate_rt(instr))),
+    0x38 => format!("sc {rt}, {simm}({rs})"),
+    0x39 => format!("swc1 {}, {simm}({rs})", freg(data::isolate_rt(instr))),
+    0x3d => format!("sdc1 {}, {simm}({rs})", freg(data::isolate_rt(instr))),
+
+    _ => word(instr),
+  }
+}
+
+/// Coprocessor 1 instructions.
+fn cop1(instr: u32, branch: u32) -> String {
+  let rt = reg(data::isolate_rt(instr));
+  let ft = freg(data::isolate_rt(instr));
+  let fs = freg(data::isolate_rd(instr));
+  let fd = freg(data::isolate_shamt(instr));
+  let funct = data::isolate_funct(instr);
+
+  let fmt = match data::isolate_rs(instr) {
+    0x0 => return format!("mfc1 {rt}, {fs}"),
+    0x2 => return format!("cfc1 {rt}, ${}", data::isolate_rd(instr)),
+    0x4 => return format!("mtc1 {rt}, {fs}"),
+    0x6 => return format!("ctc1 {rt}, ${}", data::isolate_rd(instr)),
+    0x8 => {
+      let op = match instr & (1 << 16) {
+        0 => "bc1f",
+        _ => "bc1t",
+      };
+
+      return match (instr >> 18) & 0x7 {
+        0 => format!("{op} {branch:#010x}"),
+        cc => format!("{op} {cc}, {branch:#010x}"),
+      };
+    }
+    0x10 => "s",
+    0x11 => "d",
+    0x14 if matches!(funct, 0x20 | 0x21) => "w",
+    _ => return word(instr),
+  };
+
+  const CONDITIONS: [&str; 16] = [
+    "f", "un", "eq", "ueq", "olt", "ult", "ole", "ule", "sf", "ngle", "seq", "ngl", "lt", "nge",
+    "le", "ngt",
+  ];
+
+  match funct {
+    0x0 => format!("add.{fmt} {fd}, {fs}, {ft}"),
+    0x1 => format!("sub.{fmt} {fd}, {fs}, {ft}"),
+    0x2 => format!("mul.{fmt} {fd}, {fs}, {ft}"),
+    0x3 => format!("div.{fmt} {fd}, {fs}, {ft}"),
+    0x4 => format!("sqrt.{fmt} {fd}, {fs}"),
+    0x5 => format!("abs.{fmt} {fd}, {fs}"),
+    0x6 => format!("mov.{fmt} {fd}, {fs}"),
+    0x7 => format!("neg.{fmt} {fd}, {fs}"),
+    0xc => format!("round.w.{fmt} {fd}, {fs}"),
+    0xd => format!("trunc.w.{fmt} {fd}, {fs}"),
+    0xe => format!("ceil.w.{fmt} {fd}, {fs}"),
+    0xf => format!("floor.w.{fmt} {fd}, {fs}"),
+    0x20 => format!("cvt.s.{fmt} {fd}, {fs}"),
+    0x21 => format!("cvt.d.{fmt} {fd}, {fs}"),
+    0x24 => format!("cvt.w.{fmt} {fd}, {fs}"),
+    0x30..=0x3f => {
+      let cond = CONDITIONS[(funct & 0xf) as usize];
+
+      match (instr >> 8) & 0x7 {
+        0 => format!("c.{cond}.{fmt} {fs}, {ft}"),
+        cc => format!("c.{cond}.{fmt} {cc}, {fs}, {ft}"),
+      }
+    }
+    _ => word(instr),
+  }
+}
+
+fn reg(n: u32) -> String {
+  format!("${}", NAMES[n as usize])
+}
+
+fn freg(n: u32) -> String {
+  format!("$f{n}")
+}
+
+fn jump(instr: u32, pc: u32) -> u32 {
+  data::jump_target(pc, data::isolate_target_26(instr))
+}
+
+fn word(instr: u32) -> String {
+  format!(".word {instr:#010x}")
+}
@@ -0,0 +1,144 @@
+//! What a program sees of the world outside the CPU: the time and random
+//! numbers.
+//!
+//! By default the time is the system's and unseeded random number generators
+//! start from the time, like in MARS. In deterministic mode the time is derived
+//! from the cycle count and unseeded generators start from their id, so runs
+//! are reproducible from one machine to the next.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Cycles per millisecond of the deterministic clock, a 1 MHz CPU.
+pub const CYCLES_PER_MS: u64 = 1000;
+
+/// Random number generator with the algorithm of `java.util.Random`, which
+/// MARS uses: a seed gives the same numbers in both.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Random {
+  state: u64,
+}
+
+impl Random {
+  const MASK: u64 = (1 << 48) - 1;
+  const MULTIPLIER: u64 = 0x5_deec_e66d;
+
+  pub fn new(seed: u64) -> Random {
+    Random {
+      state: (seed ^ Random::MULTIPLIER) & Random::MASK,
+    }
+  }
+
+  /// The next `bits` random bits, at most 32.
+  fn next(&mut self, bits: u32) -> u32 {
+    self.state = self
+      .state
+      .wrapping_mul(Random::MULTIPLIER)
+      .wrapping_add(0xb)
+      & Random::MASK;
+
+    (self.state >> (48 - bits)) as u32
+  }
+
+  pub fn next_int(&mut self) -> i32 {
+    self.next(32) as i32
+  }
+
+  /// A number between 0 included and `bound` excluded, `bound` being
+  /// positive.
+  pub fn next_int_below(&mut self, bound: i32) -> i32 {
+    if bound & -bound == bound {
+      return ((bound as i64 * self.next(31) as i64) >> 31) as i32;
+    }
+
+    // rejects the last partial range of values, which would skew the result
+    loop {
+      let bits = self.next(31) as i32;
+      let value = bits % bound;
+
+      if bits
+        .checked_sub(value)
+        .and_then(|n| n.checked_add(bound - 1))
+        .is_some()
+      {
+        return value;
+      }
+    }
+  }
+
+  /// A number between 0 included and 1 excluded.
+  pub fn next_float(&mut self) -> f32 {
+    self.next(24) as f32 / (1 << 24) as f32
+  }
+
+  /// A number between 0 included and 1 excluded.
+  pub fn next_double(&mut self) -> f64 {
+    let bits = ((self.next(26) as u64) << 27) + self.next(27) as u64;
+    bits as f64 / (1u64 << 53) as f64
+  }
+}
+
+/// The clock and random number generators of a CPU.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Environment {
+  deterministic: bool,
+  /// Milliseconds slept so far, which the deterministic clock adds up.
+  slept: u64,
+  /// Random number generators by id.
+  generators: BTreeMap<u32, Random>,
+}
+
+impl Environment {
+  /// An environment whose time and random numbers only depend on the program.
+  pub fn deterministic() -> Environment {
+    Environment {
+      deterministic: true,
+      ..Environment::default()
+    }
+  }
+
+  pub fn is_deterministic(&self) -> bool {
+    self.deterministic
+  }
+
+  /// Milliseconds since the epoch, after `cycles` cycles. The deterministic
+  /// clock starts at the epoch.
+  pub fn time(&self, cycles: u64) -> u64 {
+    match self.deterministic {
+      true => cycles / CYCLES_PER_MS + self.slept,
+      false => SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as u64),
+    }
+  }
+
+  /// Let `millis` milliseconds pass. Only the deterministic clock needs to be
+  /// told, the system one goes on by itself.
+  pub fn sleep(&mut self, millis: u32) {
+    if self.deterministic {
+      self.slept += millis as u64;
+    }
+  }
+
+  /// Start generator `id` over from `seed`.
+  pub fn seed(&mut self, id: u32, seed: u64) {
+    self.generators.insert(id, Random::new(seed));
+  }
+
+  /// Generator `id`, seeded on first use.
+  pub fn generator(&mut self, id: u32) -> &mut Random {
+    let deterministic = self.deterministic;
+
+    self.generators.entry(id).or_insert_with(|| {
+      let seed = match deterministic {
+        true => id as u64,
+        false => SystemTime::now()
+          .duration_since(UNIX_EPOCH)
+          .map_or(0, |time| time.as_nanos() as u64),
+      };
+
+      Random::new(seed)
+    })
+  }
+}
@@ -1,11 +1,18 @@
 use crate::cycle;
+use serde::Serialize;
 
 /// Exception types.  
 ///
 /// Exceptions are unexpected changes in control flow.
 #[repr(u8)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum Exception {
+  /// Store to a page whose TLB entry is not dirty, that is not writable.
+  TlbMod = 0x1,
+  /// TLB miss, or invalid TLB entry, on a load or an instruction fetch.
+  TlbLoad = 0x2,
+  /// TLB miss, or invalid TLB entry, on a store.
+  TlbStore = 0x3,
   /// Address error caused by a load or an instruction fetch. Happens when reading
   /// uninitialized or unauthorized memory.
   AddrLoadFetch = 0x4,
@@ -14,11 +21,29 @@ pub enum Exception {
   AddrStore = 0x5,
   /// Exception raised by a system call.
   Syscall = 0x8,
+  /// Exception raised by `break`.
+  Breakpoint = 0x9,
+  /// Reserved instruction: the word does not encode an instruction the CPU
+  /// implements.
+  ReservedInstruction = 0xa,
   /// Arithmetic overflow error.
   Overflow = 0xb,
   /// Traps are synchronous exceptions caused by instructions constructed for this purpose,
   /// such as `teq`, `tne`, `tlt`, and more.
   Trap = 0xc,
+  /// Floating point exception, raised by the FPU when an enabled IEEE exception
+  /// occurs or an operation is unimplemented. FCSR's cause field tells which.
+  FloatingPoint = 0xf,
+}
+
+impl Exception {
+  /// Whether the exception was raised by the TLB.
+  pub fn is_tlb(self) -> bool {
+    matches!(
+      self,
+      Exception::TlbMod | Exception::TlbLoad | Exception::TlbStore
+    )
+  }
 }
 
 /// Error which can either be the of error type `T` or a VM internal error.
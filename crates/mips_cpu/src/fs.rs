@@ -0,0 +1,434 @@
+//! File systems the file system calls go through, and the table of files a
+//! program has open.
+
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+/// Descriptor of the standard input, read from the console.
+pub const STDIN: u32 = 0;
+/// Descriptor of the standard output, written to the console.
+pub const STDOUT: u32 = 1;
+/// Descriptor of the standard error, written to the console as well.
+pub const STDERR: u32 = 2;
+
+/// How a file is opened, with the flags of MARS's open system call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mode {
+  /// Read only, the file has to exist. Flags `0`.
+  Read,
+  /// Write only, creating the file or truncating it. Flags `1`.
+  Write,
+  /// Write only at the end of the file, creating it if needed. Flags `9`.
+  Append,
+}
+
+impl Mode {
+  pub fn from_flags(flags: u32) -> Option<Mode> {
+    match flags {
+      0 => Some(Mode::Read),
+      1 => Some(Mode::Write),
+      9 => Some(Mode::Append),
+      _ => None,
+    }
+  }
+}
+
+/// A file opened by a `FileSystem`.
+pub trait File: Read + Write {}
+
+impl<T: Read + Write> File for T {}
+
+/// Files a program can open by path.
+pub trait FileSystem {
+  fn open(&mut self, path: &str, mode: Mode) -> io::Result<Box<dyn File>>;
+}
+
+/// Files of the host, under a root directory programs cannot leave.
+///
+/// Paths are resolved relative to the root and may not be absolute nor go up
+/// with `..`. Symbolic links are followed, as long as they lead to a file
+/// under the root: a link pointing out of it is refused like `..` would be.
+#[derive(Debug, Clone)]
+pub struct Host {
+  root: PathBuf,
+}
+
+impl Host {
+  pub fn new(root: impl Into<PathBuf>) -> Host {
+    Host { root: root.into() }
+  }
+
+  fn resolve(&self, path: &str) -> io::Result<PathBuf> {
+    let mut resolved = self.root.clone();
+
+    for component in Path::new(path).components() {
+      match component {
+        Component::Normal(name) => resolved.push(name),
+        Component::CurDir => (),
+        Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+          return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{path} is outside of {}", self.root.display()),
+          ));
+        }
+      }
+    }
+
+    let root = self.root.canonicalize()?;
+    let canonical = canonicalize(resolved)?;
+
+    if canonical.starts_with(root) {
+      Ok(canonical)
+    } else {
+      Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{path} leads outside of {}", self.root.display()),
+      ))
+    }
+  }
+}
+
+/// Canonical form of `path`, which may not exist yet: a file about to be
+/// created is canonicalized through its parent, and a dangling link through
+/// its target, since that is what opening it creates.
+fn canonicalize(mut path: PathBuf) -> io::Result<PathBuf> {
+  // as many links as Linux follows before giving up
+  for _ in 0..40 {
+    match path.canonicalize() {
+      Err(e) if e.kind() == io::ErrorKind::NotFound => {
+        let parent = path.parent().ok_or(e)?;
+
+        match std::fs::read_link(&path) {
+          Ok(target) => path = parent.join(target),
+          Err(_) => {
+            let name = path.file_name().unwrap_or_default();
+            return Ok(parent.canonicalize()?.join(name));
+          }
+        }
+      }
+      canonical => return canonical,
+    }
+  }
+
+  Err(io::Error::new(
+    io::ErrorKind::Other,
+    format!("too many links in {}", path.display()),
+  ))
+}
+
+impl FileSystem for Host {
+  fn open(&mut self, path: &str, mode: Mode) -> io::Result<Box<dyn File>> {
+    let path = self.resolve(path)?;
+    let mut options = OpenOptions::new();
+
+    match mode {
+      Mode::Read => options.read(true),
+      Mode::Write => options.write(true).create(true).truncate(true),
+      Mode::Append => options.append(true).create(true),
+    };
+
+    Ok(Box::new(options.open(path)?))
+  }
+}
+
+/// Files held in memory, by path. Clones share their files, so the ones a
+/// program wrote can be read back from a clone kept aside.
+#[derive(Debug, Default, Clone)]
+pub struct Memory {
+  files: Rc<RefCell<BTreeMap<String, Vec<u8>>>>,
+}
+
+impl Memory {
+  /// Create or replace the file at `path`.
+  pub fn insert(&mut self, path: impl Into<String>, contents: impl Into<Vec<u8>>) {
+    self.files.borrow_mut().insert(path.into(), contents.into());
+  }
+
+  /// Builder flavour of `insert`.
+  pub fn with_file(mut self, path: impl Into<String>, contents: impl Into<Vec<u8>>) -> Memory {
+    self.insert(path, contents);
+    self
+  }
+
+  /// Contents of the file at `path`.
+  pub fn contents(&self, path: &str) -> Option<Vec<u8>> {
+    self.files.borrow().get(path).cloned()
+  }
+
+  /// Paths of all files, in order.
+  pub fn paths(&self) -> Vec<String> {
+    self.files.borrow().keys().cloned().collect()
+  }
+}
+
+impl FileSystem for Memory {
+  fn open(&mut self, path: &str, mode: Mode) -> io::Result<Box<dyn File>> {
+    let mut files = self.files.borrow_mut();
+
+    match mode {
+      Mode::Read if !files.contains_key(path) => {
+        return Err(io::Error::new(
+          io::ErrorKind::NotFound,
+          format!("no file {path}"),
+        ));
+      }
+      Mode::Read => (),
+      Mode::Write => {
+        files.insert(path.to_owned(), Vec::new());
+      }
+      Mode::Append => {
+        files.entry(path.to_owned()).or_default();
+      }
+    }
+
+    Ok(Box::new(MemoryFile {
+      files: Rc::clone(&self.files),
+      path: path.to_owned(),
+      mode,
+      position: 0,
+    }))
+  }
+}
+
+struct MemoryFile {
+  files: Rc<RefCell<BTreeMap<String, Vec<u8>>>>,
+  path: String,
+  mode: Mode,
+  position: usize,
+}
+
+impl MemoryFile {
+  fn permission(&self, action: &str) -> io::Error {
+    io::Error::new(
+      io::ErrorKind::PermissionDenied,
+      format!("{} is not open for {action}", self.path),
+    )
+  }
+}
+
+impl Read for MemoryFile {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.mode != Mode::Read {
+      return Err(self.permission("reading"));
+    }
+
+    let files = self.files.borrow();
+    let contents = files.get(&self.path).map(Vec::as_slice).unwrap_or_default();
+    let rest = contents.get(self.position..).unwrap_or_default();
+    let len = rest.len().min(buf.len());
+
+    buf[..len].copy_from_slice(&rest[..len]);
+    self.position += len;
+    Ok(len)
+  }
+}
+
+impl Write for MemoryFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let mut files = self.files.borrow_mut();
+    let contents = files.entry(self.path.clone()).or_default();
+
+    match self.mode {
+      Mode::Read => return Err(self.permission("writing")),
+      Mode::Append => contents.extend(buf),
+      Mode::Write => {
+        let end = self.position + buf.len();
+        contents.resize(contents.len().max(end), 0);
+        contents[self.position..end].copy_from_slice(buf);
+        self.position = end;
+      }
+    }
+
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// A file a program has open, with what is needed to open it again.
+struct OpenFile {
+  path: String,
+  mode: Mode,
+  /// Length of the file when it was opened for appending, 0 otherwise.
+  start: u64,
+  /// Bytes read or written so far.
+  position: u64,
+  file: Box<dyn File>,
+}
+
+impl Read for OpenFile {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let len = self.file.read(buf)?;
+    self.position += len as u64;
+    Ok(len)
+  }
+}
+
+impl Write for OpenFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let len = self.file.write(buf)?;
+    self.position += len as u64;
+    Ok(len)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
+}
+
+/// An open file as saved in a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Descriptor {
+  pub fd: u32,
+  pub path: String,
+  pub mode: Mode,
+  /// Length of the file when it was opened for appending, 0 otherwise.
+  pub start: u64,
+  /// Bytes read or written so far.
+  pub position: u64,
+}
+
+/// Files a program has open, by descriptor, and the file system it opens them
+/// from. Descriptors 0 to 2 stand for the console and are never in the table.
+pub struct Files {
+  system: Box<dyn FileSystem>,
+  open: BTreeMap<u32, OpenFile>,
+  /// Rest of the line of standard input the last read did not take.
+  pub(crate) stdin: Vec<u8>,
+}
+
+impl Files {
+  pub fn new(system: impl FileSystem + 'static) -> Files {
+    Files {
+      system: Box::new(system),
+      open: BTreeMap::new(),
+      stdin: Vec::new(),
+    }
+  }
+
+  /// Open `path`, returning the lowest free descriptor.
+  pub fn open(&mut self, path: &str, mode: Mode) -> io::Result<u32> {
+    // a file which cannot be read is taken as empty, restoring it then fails
+    // rather than cutting it short
+    let start = match mode {
+      Mode::Append => self.length(path).unwrap_or(0),
+      Mode::Read | Mode::Write => 0,
+    };
+
+    let file = self.system.open(path, mode)?;
+    let fd = (STDERR + 1..)
+      .find(|fd| !self.open.contains_key(fd))
+      .unwrap_or(u32::MAX);
+
+    let file = OpenFile {
+      path: path.to_owned(),
+      mode,
+      start,
+      position: 0,
+      file,
+    };
+
+    self.open.insert(fd, file);
+    Ok(fd)
+  }
+
+  /// The file open as `fd`, `None` for the console and unknown descriptors.
+  pub fn get(&mut self, fd: u32) -> Option<&mut (dyn File + 'static)> {
+    self.open.get_mut(&fd).map(|file| file as &mut dyn File)
+  }
+
+  /// Close `fd`. Returns whether it was open.
+  pub fn close(&mut self, fd: u32) -> bool {
+    self.open.remove(&fd).is_some()
+  }
+
+  /// Descriptors of the open files, in order.
+  pub fn descriptors(&self) -> impl Iterator<Item = u32> + '_ {
+    self.open.keys().copied()
+  }
+
+  /// The open files, to be saved in a snapshot.
+  pub fn save(&self) -> Vec<Descriptor> {
+    self
+      .open
+      .iter()
+      .map(|(fd, file)| Descriptor {
+        fd: *fd,
+        path: file.path.clone(),
+        mode: file.mode,
+        start: file.start,
+        position: file.position,
+      })
+      .collect()
+  }
+
+  /// Close every file, then open the files of `descriptors` again from the
+  /// file system.
+  ///
+  /// File contents are not part of snapshots. Files read from skip to their
+  /// position again. Files written to are cut back to where writing got to,
+  /// dropping what was written after the snapshot. Files which cannot be
+  /// opened anymore, or are now shorter than that, are left closed and
+  /// returned.
+  pub fn restore(&mut self, descriptors: Vec<Descriptor>) -> Vec<Descriptor> {
+    self.open.clear();
+    let mut lost = Vec::new();
+
+    for descriptor in descriptors {
+      match self.reopen(&descriptor) {
+        Ok(file) => {
+          self.open.insert(descriptor.fd, file);
+        }
+        Err(_) => lost.push(descriptor),
+      }
+    }
+
+    lost
+  }
+
+  fn reopen(&mut self, descriptor: &Descriptor) -> io::Result<OpenFile> {
+    let path = &descriptor.path;
+    let end = descriptor.start + descriptor.position;
+    let mut file = self.system.open(path, Mode::Read)?;
+    let mut kept = Vec::new();
+    (&mut file).take(end).read_to_end(&mut kept)?;
+
+    if kept.len() as u64 != end {
+      return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    // the file systems cannot truncate, written files are written again
+    // with what they held at the snapshot
+    if descriptor.mode != Mode::Read {
+      file = self.system.open(path, Mode::Write)?;
+      file.write_all(&kept)?;
+    }
+
+    Ok(OpenFile {
+      path: path.clone(),
+      mode: descriptor.mode,
+      start: descriptor.start,
+      position: descriptor.position,
+      file,
+    })
+  }
+
+  /// Length of the file at `path`, by reading it.
+  fn length(&mut self, path: &str) -> io::Result<u64> {
+    io::copy(&mut self.system.open(path, Mode::Read)?, &mut io::sink())
+  }
+}
+
+impl Default for Files {
+  /// No file open, on an empty in-memory file system.
+  fn default() -> Files {
+    Files::new(Memory::default())
+  }
+}
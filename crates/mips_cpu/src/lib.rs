@@ -1,6 +1,9 @@
 #![feature(bigint_helper_methods)]
 
 use cycle::Next;
+use exception::Exception;
+use mem::AccessKind;
+use std::cell::RefCell;
 use std::fmt;
 use std::rc::Rc;
 
@@ -8,7 +11,14 @@ use std::rc::Rc;
 pub struct Cpu {
   memory: mem::MemoryMap,
   registers: register::Registers,
-  _program: Rc<mips_program::ProgramData>,
+  program: Rc<mips_program::ProgramData>,
+  observers: observer::Observers,
+  /// Files opened by system calls.
+  files: fs::Files,
+  /// Time and random numbers of system calls.
+  environment: environment::Environment,
+  /// Number of cycles run so far.
+  cycles: u64,
 }
 
 impl Cpu {
@@ -18,32 +28,281 @@ impl Cpu {
 
     Cpu {
       memory: mem::MemoryMap::from_program(Rc::clone(&program)),
-      _program: program,
+      program,
       registers,
+      observers: observer::Observers::default(),
+      files: fs::Files::default(),
+      environment: environment::Environment::default(),
+      cycles: 0,
     }
   }
 
-  /// Run one CPU cycle
-  pub fn cycle(&mut self) {
-    let result = cycle::perform_cycle(&mut self.memory, &mut self.registers);
+  /// Run one CPU cycle.
+  ///
+  /// Returns `Err` with the raised exception if the instruction could not
+  /// complete. The PC is left on the faulting instruction.
+  ///
+  /// Exceptions other than system calls are delivered to the kernel instead
+  /// when `.ktext` holds a handler at the exception vector: the PC is moved to
+  /// the vector and `Ok` is returned.
+  pub fn cycle(&mut self) -> Result<(), Exception> {
+    let pc = self.registers.pc;
+    self.registers.clear_written();
+    self.memory.clear_accesses();
+    self.memory.mmu_mut().sync(&self.registers.cop0);
 
-    match result {
+    let (word, result) = match self.memory.fetch(pc) {
+      Ok(instr) => (
+        Some(instr),
+        cycle::execute(instr, &mut self.memory, &mut self.registers),
+      ),
+      Err(e) => (None, Next::Exception(e)),
+    };
+
+    let taken = matches!(result, Next::Branch(_));
+    let mut raised = None;
+
+    let outcome = match result {
       Next::Forward => {
         self.registers.pc += 4;
+        Ok(())
       }
 
       Next::Branch(value) => {
         self.registers.pc = value;
+        Ok(())
       }
 
-      Next::Exception(_excpt) => {
-        todo!("exception handling");
+      Next::Exception(excpt) => {
+        raised = Some(excpt);
+
+        match self.record_exception(excpt, pc, word.is_none()) {
+          Some(vector) => {
+            self.registers.pc = vector;
+            Ok(())
+          }
+          None => Err(excpt),
+        }
       }
 
       Next::VmError(reason) => {
         panic!("internal VM error: {reason}");
       }
+    };
+
+    if !self.observers.is_empty() {
+      self.report(pc, word, taken, raised);
+    }
+
+    self.registers.cop0.tick();
+    self.cycles += 1;
+    outcome
+  }
+
+  /// Capture the complete machine state.
+  pub fn snapshot(&self) -> snapshot::Snapshot {
+    snapshot::Snapshot {
+      registers: self.registers.clone(),
+      memory: self.memory.data().clone(),
+      mmu: self.memory.mmu().clone(),
+      program_break: self.memory.program_break(),
+      heap_limit: self.memory.heap_limit(),
+      program: mips_program::ProgramData::clone(&self.program),
+      cycles: self.cycles,
+      files: self.files.save(),
+      stdin: self.files.stdin.clone(),
+      environment: self.environment.clone(),
+      console: None,
+    }
+  }
+
+  /// Bring the machine back to the state captured in `snapshot`. Observers
+  /// stay registered, strict memory mode stays enabled with everything the
+  /// snapshot holds considered initialised.
+  ///
+  /// Open files are opened again from the current file system, see
+  /// `Files::restore`. Returns the ones which could not be.
+  pub fn restore(&mut self, snapshot: snapshot::Snapshot) -> Vec<fs::Descriptor> {
+    self.registers = snapshot.registers;
+    let strict = self.memory.is_strict();
+    self.memory = mem::MemoryMap::from_data(
+      snapshot.memory,
+      snapshot.mmu,
+      snapshot.program_break,
+      snapshot.heap_limit,
+    );
+    self.memory.set_strict(strict);
+    self.program = Rc::new(snapshot.program);
+    self.cycles = snapshot.cycles;
+    self.environment = snapshot.environment;
+    self.files.stdin = snapshot.stdin;
+    self.files.restore(snapshot.files)
+  }
+
+  /// Create a CPU from a snapshot, typically one read back from disk. It has
+  /// the empty in-memory file system, use `restore` on a CPU with its file
+  /// system set to get open files back.
+  pub fn from_snapshot(snapshot: snapshot::Snapshot) -> Cpu {
+    let mut cpu = Cpu::new(Rc::new(mips_program::ProgramData::builder().build()));
+    cpu.restore(snapshot);
+    cpu
+  }
+
+  /// Number of cycles run so far, including cycles which raised an exception.
+  pub fn cycles(&self) -> u64 {
+    self.cycles
+  }
+
+  /// Register an observer, notified of everything happening during each
+  /// cycle. Keep a clone of the `Rc` to read the observer back.
+  pub fn add_observer(
+    &mut self,
+    observer: Rc<RefCell<dyn observer::CpuObserver>>,
+  ) -> observer::ObserverId {
+    self.observers.add(observer)
+  }
+
+  /// Unregister an observer. Returns `None` if it was not registered.
+  pub fn remove_observer(
+    &mut self,
+    id: observer::ObserverId,
+  ) -> Option<Rc<RefCell<dyn observer::CpuObserver>>> {
+    self.observers.remove(id)
+  }
+
+  /// The program this CPU was created from.
+  pub fn program(&self) -> &Rc<mips_program::ProgramData> {
+    &self.program
+  }
+
+  /// Serve file system calls from `system` rather than the empty in-memory
+  /// file system a CPU starts with. Files open so far are closed.
+  pub fn set_file_system(&mut self, system: impl fs::FileSystem + 'static) {
+    self.files = fs::Files::new(system);
+  }
+
+  pub fn files(&self) -> &fs::Files {
+    &self.files
+  }
+
+  pub fn files_mut(&mut self) -> &mut fs::Files {
+    &mut self.files
+  }
+
+  pub fn environment(&self) -> &environment::Environment {
+    &self.environment
+  }
+
+  /// The environment of system calls, replace it with
+  /// `Environment::deterministic` for reproducible runs.
+  pub fn environment_mut(&mut self) -> &mut environment::Environment {
+    &mut self.environment
+  }
+
+  pub fn registers(&self) -> &register::Registers {
+    &self.registers
+  }
+
+  pub fn registers_mut(&mut self) -> &mut register::Registers {
+    &mut self.registers
+  }
+
+  pub fn memory(&self) -> &mem::MemoryMap {
+    &self.memory
+  }
+
+  pub fn memory_mut(&mut self) -> &mut mem::MemoryMap {
+    &mut self.memory
+  }
+}
+
+impl Cpu {
+  /// Record an exception raised by the instruction at `pc` in coprocessor 0,
+  /// if the kernel has a handler for it. `fetch` tells whether the
+  /// instruction could not even be fetched.
+  ///
+  /// Returns the exception vector of the handler.
+  fn record_exception(&mut self, exception: Exception, pc: u32, fetch: bool) -> Option<u32> {
+    // system calls are serviced by the simulator, not by an exception handler
+    if exception == Exception::Syscall {
+      return None;
+    }
+
+    let bad_addr = match exception {
+      Exception::AddrLoadFetch | Exception::AddrStore if fetch => Some(pc),
+      e if e.is_tlb() && fetch => Some(pc),
+      Exception::AddrLoadFetch | Exception::AddrStore => self.memory.fault_addr(),
+      e if e.is_tlb() => self.memory.fault_addr(),
+      _ => None,
+    };
+
+    // misses in kuseg have their own vector, unless already handling an
+    // exception
+    let refill = self.registers.cop0.status & cop0::STATUS_EXL == 0
+      && exception != Exception::TlbMod
+      && bad_addr.is_some_and(|addr| self.memory.mmu().is_refill(addr));
+
+    let vector = match refill {
+      true => mem::REFILL_VECTOR,
+      false => mem::EXCEPTION_VECTOR,
+    };
+
+    // without a handler the exception stops the simulation, and the program
+    // is left as it was
+    if !self.memory.has_kernel_code(vector) {
+      return None;
     }
+
+    self.registers.cop0.raise(exception, pc, bad_addr);
+    Some(vector)
+  }
+
+  /// Notify observers of what happened during the cycle which just ran.
+  fn report(&mut self, pc: u32, word: Option<u32>, taken: bool, exception: Option<Exception>) {
+    let after = self.registers.numbered_values();
+    let cycle = self.cycles;
+    let v0 = after[2];
+
+    self.observers.notify(|o| {
+      o.on_cycle_start(cycle, pc);
+
+      if let Some(word) = word {
+        o.on_fetch(pc, word);
+      }
+
+      for access in self.memory.accesses() {
+        match access.kind {
+          AccessKind::Load => o.on_memory_read(pc, access),
+          AccessKind::Store => o.on_memory_write(pc, access),
+        }
+      }
+
+      for access in self.memory.uninit_reads() {
+        o.on_uninit_read(pc, access);
+      }
+
+      for n in self.registers.written() {
+        o.on_register_write(pc, n, after[n as usize]);
+      }
+
+      if let Some(word) = word.filter(|w| decode::classify(*w).is_control_transfer()) {
+        let target = match taken {
+          true => self.registers.pc,
+          // not taken, so this has to be a PC-relative branch
+          false => decode::static_target(word, pc).unwrap_or(pc + 4),
+        };
+
+        o.on_branch(pc, target, taken);
+      }
+
+      match exception {
+        Some(Exception::Syscall) => o.on_syscall(pc, v0),
+        Some(e) => o.on_exception(pc, e),
+        None => (),
+      }
+
+      o.on_cycle_end(pc);
+    });
   }
 }
 
@@ -66,7 +325,26 @@ impl fmt::Debug for Cpu {
   }
 }
 
+pub mod cache;
+pub mod console;
+pub mod convention;
+pub mod cop0;
+pub mod cop1;
 pub mod cycle;
+pub mod decode;
+pub mod disasm;
+pub mod environment;
 pub mod exception;
+pub mod fs;
 pub mod mem;
+pub mod mmu;
+pub mod observer;
+pub mod pipeline;
+pub mod predict;
+pub mod profile;
 pub mod register;
+pub mod snapshot;
+pub mod symbols;
+pub mod syscall;
+pub mod trace;
+pub mod uninit;
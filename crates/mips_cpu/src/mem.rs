@@ -1,7 +1,12 @@
 use crate::exception::Exception;
-use mips_program::interface::IoInterface;
+use crate::mmu::{self, Mmu};
+use crate::uninit::Shadow;
+use mips_program::interface::{IoInterface, IoInterfaceMut};
 use mips_program::{Context, ProgramData, Section};
+use serde::Serialize;
+use std::ops::{Range, RangeInclusive};
 use std::rc::Rc;
+use std::{error, fmt};
 
 /// Start of `.text`.
 ///
@@ -32,6 +37,14 @@ pub const DATA_END: u32 = HEAP_START - 1;
 pub const HEAP_START: u32 = 0x10040000;
 /// End of `.heap`, inclusive.
 pub const HEAP_END: u32 = KTEXT_START - 1;
+/// Default maximum size of the heap, see `MemoryMap::set_heap_size`.
+pub const DEFAULT_HEAP_SIZE: u32 = 0x00400000;
+/// Initial value of `$sp`. The stack grows down from the top of `.heap`, as
+/// in MARS.
+pub const STACK_POINTER: u32 = 0x7fffeffc;
+/// Initial value of `$gp`, in the middle of `.extern` so that 16-bit offsets
+/// reach all of it.
+pub const GLOBAL_POINTER: u32 = EXTERN_START + 0x8000;
 /// Start of `.ktext`.
 ///
 /// The `.ktext` section contains kernel code, like the exception handler.
@@ -42,62 +55,594 @@ pub const KTEXT_END: u32 = KDATA_START - 1;
 ///
 /// The `.kdata` section contains kernel static data.
 pub const KDATA_START: u32 = 0x90000000;
+/// End of `.kdata`, inclusive. Addresses above are not backed by memory.
+pub const KDATA_END: u32 = mmu::KSEG1_START - 1;
+
+/// Address the CPU jumps to on a TLB miss in kuseg.
+pub const REFILL_VECTOR: u32 = KTEXT_START;
+/// Address the CPU jumps to on other exceptions.
+pub const EXCEPTION_VECTOR: u32 = KTEXT_START + 0x180;
+
+/// Size of the chunks range accesses are split in. Sections and TLB pages
+/// never start in the middle of one.
+const PAGE_SIZE: u64 = 0x1000;
+
+/// Kind of a data memory access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessKind {
+  Load,
+  Store,
+}
+
+/// A data memory access issued by the CPU. Instruction fetches are not data
+/// accesses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Access {
+  pub kind: AccessKind,
+  pub addr: u32,
+  /// Access width in bytes: 1, 2 or 4.
+  pub size: u8,
+  /// The value loaded or stored, zero-extended.
+  pub value: u32,
+}
 
 /// An interface used for mapping addresses in the MIPS memory layout
 /// to sections of memory.
+///
+/// Addresses are translated by the `Mmu` first, when it is enabled.
 pub struct MemoryMap {
-  program: Rc<ProgramData>,
+  program: ProgramData,
+  mmu: Mmu,
+  /// Data accesses issued since the last call to `clear_accesses`.
+  accesses: Vec<Access>,
+  /// Address of the last access which raised an address error.
+  fault: Option<u32>,
+  /// End of the heap allocated through `sbrk`.
+  program_break: u32,
+  /// Address the program break cannot go past.
+  heap_limit: u32,
+  /// Number of stores to `.text` or `.ktext` so far.
+  code_version: u64,
+  /// Initialised bytes, in strict mode.
+  shadow: Option<Shadow>,
+  /// Loads issued since the last call to `clear_accesses` which read
+  /// uninitialised bytes, in strict mode.
+  uninit: Vec<Access>,
 }
 
 impl MemoryMap {
   /// Create a `MemoryMap` instance from a shared pointer to `ProgramData`. More
   /// parameters might be required in the future.
+  ///
+  /// The program data is copied, writes issued through the `MemoryMap` never
+  /// reach the shared instance.
   pub fn from_program(program: Rc<ProgramData>) -> MemoryMap {
-    MemoryMap { program }
+    MemoryMap {
+      program: ProgramData::clone(&program),
+      mmu: Mmu::default(),
+      accesses: Vec::new(),
+      fault: None,
+      program_break: HEAP_START,
+      heap_limit: HEAP_START + DEFAULT_HEAP_SIZE,
+      code_version: 0,
+      shadow: None,
+      uninit: Vec::new(),
+    }
+  }
+
+  /// Create a `MemoryMap` over program data which is already owned, such as a
+  /// restored snapshot.
+  pub(crate) fn from_data(
+    program: ProgramData,
+    mmu: Mmu,
+    program_break: u32,
+    heap_limit: u32,
+  ) -> MemoryMap {
+    MemoryMap {
+      program,
+      mmu,
+      accesses: Vec::new(),
+      fault: None,
+      program_break,
+      heap_limit: heap_limit.max(program_break),
+      code_version: 0,
+      shadow: None,
+      uninit: Vec::new(),
+    }
+  }
+
+  /// The program data as currently stored in memory.
+  pub(crate) fn data(&self) -> &ProgramData {
+    &self.program
+  }
+
+  pub fn mmu(&self) -> &Mmu {
+    &self.mmu
+  }
+
+  /// Access the MMU, for example to enable address translation.
+  pub fn mmu_mut(&mut self) -> &mut Mmu {
+    &mut self.mmu
+  }
+
+  /// Enable or disable strict mode, which reports loads from memory never
+  /// written, see `uninit`. Enabling it considers the bytes loaded from the
+  /// program or written so far as initialised, not the rest of their
+  /// segments.
+  pub fn set_strict(&mut self, strict: bool) {
+    self.shadow = strict.then(|| {
+      let mut shadow = Shadow::default();
+      for (_, region) in self.iter_mapped_regions() {
+        shadow.mark(region.start, region.end - region.start);
+      }
+      shadow
+    });
+  }
+
+  pub fn is_strict(&self) -> bool {
+    self.shadow.is_some()
+  }
+
+  /// Loads issued since the last call to `clear_accesses` which read memory
+  /// never written. Always empty outside of strict mode.
+  pub fn uninit_reads(&self) -> &[Access] {
+    &self.uninit
+  }
+
+  /// Allow or forbid stores to `.text` and `.ktext`, for self-modifying code.
+  /// Forbidden stores raise an address error.
+  pub fn set_writable_text(&mut self, writable: bool) {
+    self.program.set_writable_text(writable);
+  }
+
+  /// Counter incremented by every store to `.text` or `.ktext`. Anything
+  /// keeping decoded instructions around must discard them when it changes.
+  pub fn code_version(&self) -> u64 {
+    self.code_version
+  }
+
+  /// End of the heap, the address `sbrk` returns next.
+  pub fn program_break(&self) -> u32 {
+    self.program_break
+  }
+
+  /// Address the program break cannot go past, see `set_heap_size`.
+  pub fn heap_limit(&self) -> u32 {
+    self.heap_limit
+  }
+
+  /// Limit the heap to `size` bytes. The heap can never go past `HEAP_END`,
+  /// nor shrink below the current program break.
+  pub fn set_heap_size(&mut self, size: u32) {
+    let limit = HEAP_START.saturating_add(size).min(HEAP_END + 1);
+    self.heap_limit = limit.max(self.program_break);
+  }
+
+  /// Move the program break by `increment` bytes, rounded up to a multiple of
+  /// 4 so that allocations stay word aligned. Returns the previous break, that
+  /// is the start of the allocated block.
+  ///
+  /// The heap is always accessible, the program break only bounds what `sbrk`
+  /// hands out.
+  pub fn sbrk(&mut self, increment: i32) -> Result<u32, SbrkError> {
+    let increment = (increment as i64 + 3) & !3;
+    let start = self.program_break;
+    let end = start as i64 + increment;
+
+    if end > self.heap_limit as i64 {
+      return Err(SbrkError::Exhausted {
+        requested: increment,
+        available: self.heap_limit - start,
+      });
+    }
+
+    if end < HEAP_START as i64 {
+      return Err(SbrkError::BelowStart {
+        requested: increment,
+      });
+    }
+
+    self.program_break = end as u32;
+    Ok(start)
+  }
+
+  /// Fetch the instruction at `addr`. Addresses where nothing was assembled
+  /// or stored raise an address error, as when the program runs past its
+  /// last instruction.
+  pub fn fetch(&self, addr: u32) -> Result<u32, Exception> {
+    if addr % 4 != 0 {
+      return Err(Exception::AddrLoadFetch);
+    }
+
+    self
+      .core_load(addr, Context::User)
+      .and_then(|(index, io)| io.read_word(index).ok_or(Exception::AddrLoadFetch))
   }
 
   /// Load a word (`u32`).
   pub fn load_word(&mut self, addr: u32) -> Result<u32, Exception> {
-    self
-      .core_load(addr)
-      .map(|(sub, io)| io.read_word((addr - sub) as usize).unwrap_or(0))
+    if addr % 4 != 0 {
+      return Err(self.fault(addr, Exception::AddrLoadFetch));
+    }
+
+    let value = self
+      .core_load(addr, Context::User)
+      .map(|(index, io)| io.read_word(index).unwrap_or(0))
+      .map_err(|e| self.fault(addr, e))?;
+
+    self.log(AccessKind::Load, addr, 4, value);
+    Ok(value)
   }
 
   /// Load a half word (`u16`).
   pub fn load_halfword(&mut self, addr: u32) -> Result<u16, Exception> {
-    self
-      .core_load(addr)
-      .map(|(sub, io)| io.read_halfword((addr - sub) as usize).unwrap_or(0))
+    if addr % 2 != 0 {
+      return Err(self.fault(addr, Exception::AddrLoadFetch));
+    }
+
+    let value = self
+      .core_load(addr, Context::User)
+      .map(|(index, io)| io.read_halfword(index).unwrap_or(0))
+      .map_err(|e| self.fault(addr, e))?;
+
+    self.log(AccessKind::Load, addr, 2, value as u32);
+    Ok(value)
   }
 
   /// Load a byte (`u8`).
   pub fn load_byte(&mut self, addr: u32) -> Result<u8, Exception> {
+    let value = self
+      .core_load(addr, Context::User)
+      .map(|(index, io)| io.read_byte(index).unwrap_or(0))
+      .map_err(|e| self.fault(addr, e))?;
+
+    self.log(AccessKind::Load, addr, 1, value as u32);
+    Ok(value)
+  }
+
+  /// Store a word (`u32`).
+  pub fn store_word(&mut self, addr: u32, value: u32) -> Result<(), Exception> {
+    if addr % 4 != 0 {
+      return Err(self.fault(addr, Exception::AddrStore));
+    }
+
     self
-      .core_load(addr)
-      .map(|(sub, io)| io.read_byte((addr - sub) as usize).unwrap_or(0))
+      .core_store(addr, Context::User, |index, io| io.write_word(index, value))
+      .map_err(|e| self.fault(addr, e))?;
+
+    self.log(AccessKind::Store, addr, 4, value);
+    Ok(())
   }
 
-  fn core_load(&mut self, addr: u32) -> Result<(u32, IoInterface), Exception> {
-    match addr {
-      TEXT_START..=TEXT_END => self
-        .program
-        .read(Section::Text, Context::User)
-        .ok_or(Exception::AddrLoadFetch)
-        .map(|e| (TEXT_START, e)),
+  /// Store a double word as two words, `low` at `addr` and `high` after it.
+  /// Nothing is stored if either word cannot be.
+  pub fn store_doubleword(&mut self, addr: u32, low: u32, high: u32) -> Result<(), Exception> {
+    if addr % 8 != 0 {
+      return Err(self.fault(addr, Exception::AddrStore));
+    }
 
-      EXTERN_START..=EXTERN_END => self
-        .program
-        .read(Section::Extern, Context::User)
-        .ok_or(Exception::AddrLoadFetch)
-        .map(|e| (EXTERN_START, e)),
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&low.to_le_bytes());
+    bytes[4..].copy_from_slice(&high.to_le_bytes());
 
-      DATA_START..=DATA_END => self
-        .program
-        .read(Section::Data, Context::User)
-        .ok_or(Exception::AddrLoadFetch)
-        .map(|e| (DATA_START, e)),
+    // an aligned double word never spans two pages nor two sections
+    self
+      .core_store(addr, Context::User, |index, io| {
+        io.write_range(index, &bytes)
+      })
+      .map_err(|e| self.fault(addr, e))?;
 
-      addr => todo!("mem fetch @ {addr:#10x}"),
+    self.log(AccessKind::Store, addr, 4, low);
+    self.log(AccessKind::Store, addr + 4, 4, high);
+    Ok(())
+  }
+
+  /// Store a half word (`u16`).
+  pub fn store_halfword(&mut self, addr: u32, value: u16) -> Result<(), Exception> {
+    if addr % 2 != 0 {
+      return Err(self.fault(addr, Exception::AddrStore));
     }
+
+    self
+      .core_store(addr, Context::User, |index, io| {
+        io.write_halfword(index, value)
+      })
+      .map_err(|e| self.fault(addr, e))?;
+
+    self.log(AccessKind::Store, addr, 2, value as u32);
+    Ok(())
+  }
+
+  /// Store a byte (`u8`).
+  pub fn store_byte(&mut self, addr: u32, value: u8) -> Result<(), Exception> {
+    self
+      .core_store(addr, Context::User, |index, io| io.write_byte(index, value))
+      .map_err(|e| self.fault(addr, e))?;
+
+    self.log(AccessKind::Store, addr, 1, value as u32);
+    Ok(())
+  }
+
+  /// Read a byte on behalf of `context`, without going through the CPU. Meant
+  /// for tooling such as debuggers.
+  pub fn read_byte(&self, addr: u32, context: Context) -> Result<u8, Exception> {
+    self
+      .core_load(addr, context)
+      .map(|(index, io)| io.read_byte(index).unwrap_or(0))
+  }
+
+  /// Write a byte on behalf of `context`, without going through the CPU. Meant
+  /// for tooling such as debuggers.
+  pub fn write_byte(&mut self, addr: u32, value: u8, context: Context) -> Result<(), Exception> {
+    self.core_store(addr, context, |index, io| io.write_byte(index, value))?;
+    self.initialise(addr, 1);
+    Ok(())
+  }
+
+  /// Read up to `len` bytes starting at `addr` on behalf of `context`, without
+  /// going through the CPU. Bytes which were never written are `None`, they
+  /// read as zero.
+  ///
+  /// Like `io::Read::read`, reading stops before the first byte which cannot
+  /// be read. Returns an error only if the very first byte cannot be read.
+  pub fn read_range(
+    &self,
+    addr: u32,
+    len: u32,
+    context: Context,
+  ) -> Result<Vec<Option<u8>>, Exception> {
+    let mut bytes = Vec::new();
+
+    for (start, len) in pages(addr, len) {
+      match self.core_load(start, context) {
+        Ok((index, io)) => bytes.extend(io.read_range(index..index + len)),
+        Err(e) if bytes.is_empty() => return Err(e),
+        Err(_) => break,
+      }
+    }
+
+    Ok(bytes)
+  }
+
+  /// Write `data` at `addr` on behalf of `context`, without going through the
+  /// CPU. Stops at the first byte which cannot be written, the bytes before it
+  /// stay written.
+  pub fn write_range(&mut self, addr: u32, data: &[u8], context: Context) -> Result<(), Exception> {
+    let mut data = data;
+
+    for (start, len) in pages(addr, data.len() as u32) {
+      let (bytes, rest) = data.split_at(len);
+      self.core_store(start, context, |index, io| io.write_range(index, bytes))?;
+      self.initialise(start, len as u32);
+      data = rest;
+    }
+
+    Ok(())
+  }
+
+  /// Address ranges holding written data, with the section they belong to, in
+  /// address order. Addresses are physical, translation is not involved.
+  pub fn iter_mapped_regions(&self) -> impl Iterator<Item = (Section, Range<u32>)> + '_ {
+    SECTIONS.into_iter().flat_map(move |section| {
+      let start = *section_range(section).start();
+      let io = self.program.read(section, Context::External);
+
+      io.into_iter().flat_map(move |io| {
+        io.iter_mapped_regions()
+          .map(move |r| (section, start + r.start as u32..start + r.end as u32))
+      })
+    })
+  }
+
+  /// Whether `.ktext` holds code at `addr`.
+  pub(crate) fn has_kernel_code(&self, addr: u32) -> bool {
+    self
+      .program
+      .read(Section::KText, Context::Kernel)
+      .zip(addr.checked_sub(KTEXT_START))
+      .and_then(|(io, index)| io.read_word(index as usize))
+      .is_some()
+  }
+
+  /// Data accesses issued since the last call to `clear_accesses`, in order.
+  pub fn accesses(&self) -> &[Access] {
+    &self.accesses
+  }
+
+  /// Clear the access log, as well as the last faulting address.
+  pub fn clear_accesses(&mut self) {
+    self.accesses.clear();
+    self.uninit.clear();
+    self.fault = None;
+  }
+
+  /// Address of the data access which raised an address error since the last
+  /// call to `clear_accesses`, if any.
+  pub fn fault_addr(&self) -> Option<u32> {
+    self.fault
+  }
+
+  /// Record `addr` as the faulting address of `exception`.
+  pub(crate) fn fault(&mut self, addr: u32, exception: Exception) -> Exception {
+    self.fault = Some(addr);
+    exception
+  }
+
+  fn log(&mut self, kind: AccessKind, addr: u32, size: u8, value: u32) {
+    let access = Access {
+      kind,
+      addr,
+      size,
+      value,
+    };
+
+    match kind {
+      AccessKind::Store => self.initialise(addr, size as u32),
+      AccessKind::Load => {
+        let initialised = self
+          .shadow
+          .as_ref()
+          .map(|s| s.is_initialised(addr, size as u32));
+
+        if initialised == Some(false) {
+          self.uninit.push(access);
+        }
+      }
+    }
+
+    self.accesses.push(access);
+  }
+
+  /// Record `len` bytes starting at `addr` as initialised, in strict mode.
+  fn initialise(&mut self, addr: u32, len: u32) {
+    if let Some(shadow) = &mut self.shadow {
+      shadow.mark(addr, len);
+    }
+  }
+
+  /// Context an access on behalf of `context` is performed with. Accesses
+  /// issued by the CPU have kernel privileges in kernel mode.
+  fn privileges(&self, context: Context) -> Context {
+    match context {
+      Context::User if self.mmu.is_kernel() => Context::Kernel,
+      _ => context,
+    }
+  }
+
+  /// Find the section `addr` is mapped to, as well as its index in said
+  /// section.
+  fn core_load(&self, addr: u32, context: Context) -> Result<(usize, IoInterface), Exception> {
+    let context = self.privileges(context);
+    let phys = self.mmu.translate(addr, context != Context::User, false)?;
+    let (section, sub) = section_of(phys).ok_or(Exception::AddrLoadFetch)?;
+
+    self
+      .program
+      .read(section, context)
+      .ok_or(Exception::AddrLoadFetch)
+      .map(|e| ((phys - sub) as usize, e))
+  }
+
+  fn core_store<F>(&mut self, addr: u32, context: Context, f: F) -> Result<(), Exception>
+  where
+    F: FnOnce(usize, &mut IoInterfaceMut) -> Option<()>,
+  {
+    let context = self.privileges(context);
+    let phys = self.mmu.translate(addr, context != Context::User, true)?;
+    let (section, sub) = section_of(phys).ok_or(Exception::AddrStore)?;
+
+    let mut io = self
+      .program
+      .write(section, context)
+      .ok_or(Exception::AddrStore)?;
+
+    f((phys - sub) as usize, &mut io).ok_or(Exception::AddrStore)?;
+
+    if matches!(section, Section::Text | Section::KText) {
+      self.code_version += 1;
+    }
+
+    Ok(())
+  }
+}
+
+/// Split `len` bytes starting at `addr` in `(start, len)` chunks which do not
+/// cross a page. Stops at the end of the address space.
+fn pages(addr: u32, len: u32) -> impl Iterator<Item = (u32, usize)> {
+  let end = (addr as u64 + len as u64).min(1 << 32);
+  let mut current = addr as u64;
+
+  std::iter::from_fn(move || {
+    if current >= end {
+      return None;
+    }
+
+    let chunk_end = ((current / PAGE_SIZE + 1) * PAGE_SIZE).min(end);
+    let chunk = (current as u32, (chunk_end - current) as usize);
+    current = chunk_end;
+
+    Some(chunk)
+  })
+}
+
+/// Error returned by `MemoryMap::sbrk`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbrkError {
+  /// The heap would grow past its limit.
+  Exhausted { requested: i64, available: u32 },
+  /// The heap would shrink below `HEAP_START`.
+  BelowStart { requested: i64 },
+}
+
+impl fmt::Display for SbrkError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SbrkError::Exhausted {
+        requested,
+        available,
+      } => write!(
+        f,
+        "out of heap memory: requested {requested} bytes, {available} available"
+      ),
+      SbrkError::BelowStart { requested } => {
+        write!(f, "cannot shrink the heap by {} bytes", -requested)
+      }
+    }
+  }
+}
+
+impl error::Error for SbrkError {}
+
+/// Every section, in address order.
+pub const SECTIONS: [Section; 6] = [
+  Section::Text,
+  Section::Extern,
+  Section::Data,
+  Section::Heap,
+  Section::KText,
+  Section::KData,
+];
+
+/// Addresses spanned by `section`.
+pub fn section_range(section: Section) -> RangeInclusive<u32> {
+  match section {
+    Section::Text => TEXT_START..=TEXT_END,
+    Section::Extern => EXTERN_START..=EXTERN_END,
+    Section::Data => DATA_START..=DATA_END,
+    Section::Heap => HEAP_START..=HEAP_END,
+    Section::KText => KTEXT_START..=KTEXT_END,
+    Section::KData => KDATA_START..=KDATA_END,
+  }
+}
+
+/// Name of `section` as written in assembly, like `.text`.
+pub fn section_name(section: Section) -> &'static str {
+  match section {
+    Section::Text => ".text",
+    Section::Extern => ".extern",
+    Section::Data => ".data",
+    Section::Heap => ".heap",
+    Section::KText => ".ktext",
+    Section::KData => ".kdata",
+  }
+}
+
+/// The section `addr` belongs to, if any.
+pub fn section_at(addr: u32) -> Option<Section> {
+  section_of(addr).map(|(section, _)| section)
+}
+
+/// Find the section `addr` belongs to, as well as the start address of said
+/// section.
+fn section_of(addr: u32) -> Option<(Section, u32)> {
+  match addr {
+    TEXT_START..=TEXT_END => Some((Section::Text, TEXT_START)),
+    EXTERN_START..=EXTERN_END => Some((Section::Extern, EXTERN_START)),
+    DATA_START..=DATA_END => Some((Section::Data, DATA_START)),
+    HEAP_START..=HEAP_END => Some((Section::Heap, HEAP_START)),
+    KTEXT_START..=KTEXT_END => Some((Section::KText, KTEXT_START)),
+    KDATA_START..=KDATA_END => Some((Section::KData, KDATA_START)),
+    _ => None,
   }
 }
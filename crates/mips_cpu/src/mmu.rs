@@ -0,0 +1,201 @@
+//! R2000 memory management unit.
+//!
+//! The virtual address space is split in four segments:
+//!
+//! - kuseg, `0x00000000..0x80000000`, mapped through the TLB, accessible in
+//!   user mode;
+//! - kseg0, `0x80000000..0xa0000000`, unmapped and cached;
+//! - kseg1, `0xa0000000..0xc0000000`, unmapped and uncached, an alias of
+//!   kseg0;
+//! - kseg2, `0xc0000000..`, mapped through the TLB.
+//!
+//! Physical addresses use the simulator's memory layout, where `.ktext` and
+//! `.kdata` live at `0x80000000` and `0x90000000`. kseg0 is thereby mapped
+//! onto the same physical addresses rather than onto `0x00000000`, and a
+//! mapping with identical virtual and physical page numbers reaches the user
+//! sections.
+//!
+//! Translation is disabled by default: addresses are then physical, like in
+//! MARS.
+
+use crate::cop0::Cop0;
+use crate::exception::Exception;
+use serde::{Deserialize, Serialize};
+
+/// Number of TLB entries.
+pub const TLB_ENTRIES: usize = 64;
+/// Lowest index `tlbwr` writes to. Entries below are never replaced randomly.
+pub const TLB_WIRED: usize = 8;
+
+/// Start of kseg0.
+pub const KSEG0_START: u32 = 0x80000000;
+/// Start of kseg1.
+pub const KSEG1_START: u32 = 0xa0000000;
+/// Start of kseg2.
+pub const KSEG2_START: u32 = 0xc0000000;
+
+/// `EntryHi` virtual page number.
+pub const ENTRY_HI_VPN: u32 = 0xfffff000;
+/// `EntryHi` address space identifier.
+pub const ENTRY_HI_ASID: u32 = 0x3f << 6;
+/// `EntryLo` physical frame number.
+pub const ENTRY_LO_PFN: u32 = 0xfffff000;
+/// `EntryLo` non-cacheable bit.
+pub const ENTRY_LO_N: u32 = 1 << 11;
+/// `EntryLo` dirty bit, the page is writable when set.
+pub const ENTRY_LO_D: u32 = 1 << 10;
+/// `EntryLo` valid bit.
+pub const ENTRY_LO_V: u32 = 1 << 9;
+/// `EntryLo` global bit, the entry matches any ASID.
+pub const ENTRY_LO_G: u32 = 1 << 8;
+
+const PAGE_OFFSET: u32 = 0xfff;
+
+/// A virtual memory segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+  Kuseg,
+  Kseg0,
+  Kseg1,
+  Kseg2,
+}
+
+impl Segment {
+  pub fn of(addr: u32) -> Segment {
+    if addr < KSEG0_START {
+      Segment::Kuseg
+    } else if addr < KSEG1_START {
+      Segment::Kseg0
+    } else if addr < KSEG2_START {
+      Segment::Kseg1
+    } else {
+      Segment::Kseg2
+    }
+  }
+
+  /// Whether addresses of the segment go through the TLB.
+  pub fn is_mapped(self) -> bool {
+    matches!(self, Segment::Kuseg | Segment::Kseg2)
+  }
+}
+
+/// A TLB entry, in the format of the `EntryHi` and `EntryLo` registers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlbEntry {
+  pub hi: u32,
+  pub lo: u32,
+}
+
+impl TlbEntry {
+  /// Whether the entry maps `addr` in the address space `asid`.
+  pub fn matches(&self, addr: u32, asid: u32) -> bool {
+    (self.hi & ENTRY_HI_VPN) == (addr & ENTRY_HI_VPN)
+      && (self.lo & ENTRY_LO_G != 0 || (self.hi & ENTRY_HI_ASID) == (asid & ENTRY_HI_ASID))
+  }
+}
+
+/// The memory management unit: the TLB and the translation state.
+///
+/// `EntryHi`, `EntryLo` and the other TLB registers are part of `Cop0`. The
+/// address space identifier and the processor mode are sampled from it at the
+/// start of every cycle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mmu {
+  /// Whether virtual addresses are translated.
+  pub enabled: bool,
+  /// `TLB_ENTRIES` entries, serde does not handle arrays that large.
+  tlb: Vec<TlbEntry>,
+  /// ASID of `EntryHi`.
+  asid: u32,
+  /// Whether the processor runs in kernel mode.
+  kernel: bool,
+}
+
+impl Default for Mmu {
+  fn default() -> Self {
+    Mmu {
+      enabled: false,
+      tlb: vec![TlbEntry::default(); TLB_ENTRIES],
+      asid: 0,
+      kernel: false,
+    }
+  }
+}
+
+impl Mmu {
+  pub fn entries(&self) -> &[TlbEntry] {
+    &self.tlb
+  }
+
+  pub fn entry(&self, index: usize) -> Option<TlbEntry> {
+    self.tlb.get(index).copied()
+  }
+
+  /// Write entry `index`, as done by `tlbwi` and `tlbwr`.
+  pub fn set_entry(&mut self, index: usize, entry: TlbEntry) {
+    if let Some(e) = self.tlb.get_mut(index) {
+      *e = entry;
+    }
+  }
+
+  /// Index of the entry mapping `addr` in the address space `asid`, as done by
+  /// `tlbp`.
+  pub fn probe(&self, addr: u32, asid: u32) -> Option<usize> {
+    self.tlb.iter().position(|e| e.matches(addr, asid))
+  }
+
+  /// Whether the processor runs in kernel mode, as of the start of the cycle.
+  pub fn is_kernel(&self) -> bool {
+    self.kernel
+  }
+
+  /// Sample the address space identifier and the processor mode.
+  pub(crate) fn sync(&mut self, cop0: &Cop0) {
+    self.asid = cop0.entry_hi & ENTRY_HI_ASID;
+    self.kernel = cop0.is_kernel();
+  }
+
+  /// Whether a TLB exception raised for `addr` is a miss in kuseg, which is
+  /// handled by the refill vector.
+  pub fn is_refill(&self, addr: u32) -> bool {
+    self.enabled && Segment::of(addr) == Segment::Kuseg && self.probe(addr, self.asid).is_none()
+  }
+
+  /// Translate `addr` to a physical address. `kernel` tells whether the access
+  /// has kernel privileges, `store` whether it is a write.
+  pub fn translate(&self, addr: u32, kernel: bool, store: bool) -> Result<u32, Exception> {
+    let (address_error, miss) = match store {
+      true => (Exception::AddrStore, Exception::TlbStore),
+      false => (Exception::AddrLoadFetch, Exception::TlbLoad),
+    };
+
+    if !self.enabled {
+      return Ok(addr);
+    }
+
+    let segment = Segment::of(addr);
+
+    if segment != Segment::Kuseg && !kernel {
+      return Err(address_error);
+    }
+
+    match segment {
+      Segment::Kseg0 => return Ok(addr),
+      Segment::Kseg1 => return Ok(addr - (KSEG1_START - KSEG0_START)),
+      Segment::Kuseg | Segment::Kseg2 => (),
+    }
+
+    let entry = self
+      .tlb
+      .iter()
+      .find(|e| e.matches(addr, self.asid))
+      .filter(|e| e.lo & ENTRY_LO_V != 0)
+      .ok_or(miss)?;
+
+    if store && entry.lo & ENTRY_LO_D == 0 {
+      return Err(Exception::TlbMod);
+    }
+
+    Ok((entry.lo & ENTRY_LO_PFN) | (addr & PAGE_OFFSET))
+  }
+}
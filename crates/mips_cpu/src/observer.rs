@@ -0,0 +1,95 @@
+use crate::exception::Exception;
+use crate::mem::Access;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Hooks into the execution of a `Cpu`.
+///
+/// Every method has an empty default implementation, observers only implement
+/// what they care about.
+///
+/// Callbacks are not issued while the instruction executes. They are all
+/// issued once the cycle completed, in this order:
+///
+/// 1. `on_cycle_start`
+/// 2. `on_fetch`, unless the fetch failed
+/// 3. `on_memory_read` and `on_memory_write`, in program order
+/// 4. `on_uninit_read`, in strict memory mode
+/// 5. `on_register_write`, by register number
+/// 6. `on_branch`, for branches and jumps
+/// 7. `on_syscall` or `on_exception`
+/// 8. `on_cycle_end`
+///
+/// The CPU is not reachable from the callbacks. Values passed to them are
+/// the state at the end of the cycle. A register written twice during the
+/// cycle is reported once, with its final value. A system call has not been
+/// serviced yet when `on_syscall` is called.
+///
+/// `pc` is always the address of the instruction being executed.
+pub trait CpuObserver {
+  /// A new cycle starts. `cycle` is the number of cycles run before it.
+  fn on_cycle_start(&mut self, _cycle: u64, _pc: u32) {}
+
+  fn on_fetch(&mut self, _pc: u32, _word: u32) {}
+
+  /// The instruction wrote a register, `value` is its value at the end of the
+  /// cycle. Writes which leave the value unchanged are reported too. `reg` is
+  /// `0..32` for regular registers, or one of the other numbers defined in
+  /// `register` (`HI`, `LO`, `F0`.., `FCSR`).
+  fn on_register_write(&mut self, _pc: u32, _reg: u8, _value: u32) {}
+
+  fn on_memory_read(&mut self, _pc: u32, _access: &Access) {}
+
+  fn on_memory_write(&mut self, _pc: u32, _access: &Access) {}
+
+  /// A load read bytes which were never written. Only reported when the
+  /// memory is in strict mode, see `MemoryMap::set_strict`.
+  fn on_uninit_read(&mut self, _pc: u32, _access: &Access) {}
+
+  /// A branch or a jump was executed. For branches which were not taken,
+  /// `target` is where the branch would have gone.
+  fn on_branch(&mut self, _pc: u32, _target: u32, _taken: bool) {}
+
+  /// A `syscall` instruction was executed, `code` is the value of `$v0`.
+  fn on_syscall(&mut self, _pc: u32, _code: u32) {}
+
+  /// An exception other than a system call was raised.
+  fn on_exception(&mut self, _pc: u32, _exception: Exception) {}
+
+  fn on_cycle_end(&mut self, _pc: u32) {}
+}
+
+/// Handle returned when registering an observer, used to remove it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(pub(crate) u64);
+
+/// Observers registered on a `Cpu`.
+#[derive(Default)]
+pub(crate) struct Observers {
+  observers: Vec<(ObserverId, Rc<RefCell<dyn CpuObserver>>)>,
+  next_id: u64,
+}
+
+impl Observers {
+  pub fn add(&mut self, observer: Rc<RefCell<dyn CpuObserver>>) -> ObserverId {
+    let id = ObserverId(self.next_id);
+    self.next_id += 1;
+    self.observers.push((id, observer));
+    id
+  }
+
+  pub fn remove(&mut self, id: ObserverId) -> Option<Rc<RefCell<dyn CpuObserver>>> {
+    let i = self.observers.iter().position(|(i, _)| *i == id)?;
+    Some(self.observers.remove(i).1)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.observers.is_empty()
+  }
+
+  pub fn notify(&self, mut f: impl FnMut(&mut dyn CpuObserver)) {
+    for (_, observer) in &self.observers {
+      f(&mut *observer.borrow_mut());
+    }
+  }
+}
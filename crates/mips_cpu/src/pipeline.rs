@@ -0,0 +1,400 @@
+//! Timing model of the classic five stage pipeline (IF, ID, EX, MEM, WB).
+//!
+//! The model drives a regular `Cpu`: every instruction is executed by
+//! `Cpu::cycle` as it enters EX, in program order, so architectural results
+//! are those of the functional core. The pipeline only decides *when* that
+//! happens:
+//!
+//! - instructions are fetched sequentially, branches are predicted not taken;
+//! - direct jumps (`j`, `jal`) are resolved in ID, costing one bubble;
+//! - other control transfers are resolved in EX (two bubbles when taken), or
+//!   in ID with `PipelineConfig::early_branches` (one bubble);
+//! - with forwarding, results are forwarded to EX (or to ID for early
+//!   branches), so only load-use hazards stall. Without forwarding, consumers
+//!   wait in ID until their producers reach WB, the register file being
+//!   written in the first half of a cycle and read in the second half;
+//! - a store to `.text` or `.ktext` refetches the instructions in IF and ID.
+
+use crate::decode::{self, Class};
+use crate::exception::Exception;
+use crate::{disasm, Cpu};
+use std::io::{self, Write};
+
+/// Stage indexes.
+const IF: usize = 0;
+const ID: usize = 1;
+const EX: usize = 2;
+const MEM: usize = 3;
+const WB: usize = 4;
+
+const STAGE_NAMES: [&str; 5] = ["IF", "ID", "EX", "MEM", "WB"];
+
+/// Pipeline organization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineConfig {
+  /// Whether forwarding paths are implemented.
+  pub forwarding: bool,
+  /// Resolve branches and register jumps in ID rather than in EX.
+  pub early_branches: bool,
+  /// Keep the stage occupancy of every cycle, for `write_diagram`.
+  pub diagram: bool,
+}
+
+impl Default for PipelineConfig {
+  fn default() -> Self {
+    PipelineConfig {
+      forwarding: true,
+      early_branches: false,
+      diagram: false,
+    }
+  }
+}
+
+/// Cycle accounting of a pipelined run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+  pub cycles: u64,
+  /// Instructions which went through WB.
+  pub instructions: u64,
+  /// Cycles ID was stalled by a data hazard.
+  pub data_stalls: u64,
+  /// Data stalls caused by a load followed by a consumer of its result.
+  pub load_use_stalls: u64,
+  /// Instructions squashed after a taken branch or a jump.
+  pub flushes: u64,
+}
+
+impl PipelineStats {
+  /// Cycles per instruction, zero if no instruction completed.
+  pub fn cpi(&self) -> f64 {
+    match self.instructions {
+      0 => 0.0,
+      n => self.cycles as f64 / n as f64,
+    }
+  }
+}
+
+/// An instruction flowing through the pipeline.
+#[derive(Debug, Clone)]
+struct Slot {
+  pc: u32,
+  /// `None` if the fetch failed, the exception is raised when it reaches EX.
+  word: Option<u32>,
+  reads: Vec<u8>,
+  writes: Vec<u8>,
+  load: bool,
+  /// Resolved in ID rather than EX.
+  early: bool,
+  /// Address of the next instruction, once executed, if it is not `pc + 4`.
+  redirect: Option<u32>,
+}
+
+/// Stage occupancy during one cycle: `(pc, word)` of each stage.
+#[derive(Debug, Clone, Copy)]
+struct DiagramRow {
+  stages: [Option<(u32, Option<u32>)>; 5],
+  stalled: bool,
+}
+
+/// A `Cpu` run through the five stage pipeline model.
+pub struct Pipeline {
+  cpu: Cpu,
+  config: PipelineConfig,
+  stages: [Option<Slot>; 5],
+  fetch_pc: u32,
+  stats: PipelineStats,
+  diagram: Vec<DiagramRow>,
+  /// `MemoryMap::code_version` when IF and ID were fetched.
+  code_version: u64,
+}
+
+impl Pipeline {
+  pub fn new(cpu: Cpu, config: PipelineConfig) -> Pipeline {
+    Pipeline {
+      fetch_pc: cpu.registers().pc,
+      code_version: cpu.memory().code_version(),
+      cpu,
+      config,
+      stages: Default::default(),
+      stats: PipelineStats::default(),
+      diagram: Vec::new(),
+    }
+  }
+
+  pub fn cpu(&self) -> &Cpu {
+    &self.cpu
+  }
+
+  /// Access the CPU, for example to service a system call. If the PC is moved,
+  /// the pipeline is flushed on the next clock.
+  pub fn cpu_mut(&mut self) -> &mut Cpu {
+    &mut self.cpu
+  }
+
+  pub fn into_cpu(self) -> Cpu {
+    self.cpu
+  }
+
+  pub fn stats(&self) -> &PipelineStats {
+    &self.stats
+  }
+
+  /// Advance the pipeline by one clock cycle.
+  ///
+  /// Returns `Err` if the instruction entering EX raised an exception, as
+  /// `Cpu::cycle` does. The faulting instruction and the ones fetched after it
+  /// are discarded, fetching resumes from the CPU's PC on the next clock.
+  pub fn clock(&mut self) -> Result<(), Exception> {
+    let result = self.advance();
+    self.end_cycle(matches!(result, Ok(true)));
+    result.map(|_| ())
+  }
+
+  /// Move instructions to their next stage. Returns whether ID stalled.
+  fn advance(&mut self) -> Result<bool, Exception> {
+    // instructions resolved in EX redirect the fetch once there
+    let ex_redirect = self.stages[EX]
+      .as_ref()
+      .filter(|s| !s.early)
+      .and_then(|s| s.redirect);
+
+    let stall = ex_redirect.is_none() && self.hazard();
+
+    self.stages[WB] = self.stages[MEM].take();
+    self.stages[MEM] = self.stages[EX].take();
+
+    if let Some(target) = ex_redirect {
+      self.squash(target);
+      return Ok(false);
+    }
+
+    if stall {
+      self.stats.data_stalls += 1;
+      return Ok(true);
+    }
+
+    let mut entering = self.stages[ID].take();
+
+    if let Some(slot) = &mut entering {
+      let pc = self.cpu.registers().pc;
+
+      if slot.pc != pc {
+        // the PC was moved from outside
+        self.restart(pc);
+        return Ok(false);
+      }
+
+      if let Err(e) = self.cpu.cycle() {
+        let pc = self.cpu.registers().pc;
+        self.restart(pc);
+        return Err(e);
+      }
+
+      let next = self.cpu.registers().pc;
+      slot.redirect = (next != slot.pc.wrapping_add(4)).then_some(next);
+    }
+
+    let early_redirect = entering
+      .as_ref()
+      .filter(|s| s.early)
+      .and_then(|s| s.redirect);
+
+    self.stages[EX] = entering;
+
+    match early_redirect {
+      Some(target) => self.squash(target),
+      // IF and ID may hold stale instructions
+      None if self.code_modified() => {
+        let pc = self.cpu.registers().pc;
+        self.restart(pc);
+      }
+      None => {
+        self.stages[ID] = self.stages[IF].take();
+        self.fetch();
+      }
+    }
+
+    Ok(false)
+  }
+
+  /// Clock the pipeline until every instruction already executed went through
+  /// WB, without fetching anything new.
+  pub fn drain(&mut self) {
+    self.stages[IF] = None;
+    self.stages[ID] = None;
+
+    while self.stages[EX..WB].iter().any(Option::is_some) {
+      self.stages[WB] = self.stages[MEM].take();
+      self.stages[MEM] = self.stages[EX].take();
+      self.end_cycle(false);
+    }
+
+    self.stages[WB] = None;
+    self.fetch_pc = self.cpu.registers().pc;
+  }
+
+  /// Write the stage occupancy of every recorded cycle, one line per cycle.
+  /// Requires `PipelineConfig::diagram`.
+  pub fn write_diagram(&self, mut out: impl Write) -> io::Result<()> {
+    write!(out, "{:>8}", "cycle")?;
+    for name in STAGE_NAMES {
+      write!(out, "  {name:<24}")?;
+    }
+    writeln!(out)?;
+
+    for (n, row) in self.diagram.iter().enumerate() {
+      write!(out, "{:>8}", n + 1)?;
+
+      for (stage, slot) in row.stages.iter().enumerate() {
+        let cell = match slot {
+          Some((pc, Some(word))) => disasm::disassemble(*word, *pc),
+          Some((pc, None)) => format!("{pc:#010x} ??"),
+          None => "-".to_owned(),
+        };
+
+        let marker = match row.stalled && stage <= ID && slot.is_some() {
+          true => "*",
+          false => "",
+        };
+
+        write!(out, "  {:<24}", format!("{cell}{marker}"))?;
+      }
+
+      writeln!(out)?;
+    }
+
+    Ok(())
+  }
+
+  /// Write a summary of the run.
+  pub fn write_report(&self, mut out: impl Write) -> io::Result<()> {
+    let stats = &self.stats;
+
+    writeln!(
+      out,
+      "cycles {}, instructions {}, CPI {:.3}",
+      stats.cycles,
+      stats.instructions,
+      stats.cpi()
+    )?;
+    writeln!(
+      out,
+      "data stalls {} (load-use {}), flushed instructions {}",
+      stats.data_stalls, stats.load_use_stalls, stats.flushes
+    )
+  }
+
+  /// Whether the instruction in ID must wait for a result. Counts load-use
+  /// stalls.
+  fn hazard(&mut self) -> bool {
+    let Some(consumer) = &self.stages[ID] else {
+      return false;
+    };
+
+    // stage at the start of which the consumer needs its operands
+    let needed = match consumer.early {
+      true => ID,
+      false => EX,
+    };
+
+    for stage in [EX, MEM] {
+      let Some(producer) = &self.stages[stage] else {
+        continue;
+      };
+
+      if !producer.writes.iter().any(|r| consumer.reads.contains(r)) {
+        continue;
+      }
+
+      let stall = match self.config.forwarding {
+        // the result is forwarded at the end of the stage producing it
+        true => {
+          let ready = match producer.load {
+            true => MEM,
+            false => EX,
+          };
+
+          stage + needed < ready + 2
+        }
+        false => true,
+      };
+
+      if stall {
+        if producer.load {
+          self.stats.load_use_stalls += 1;
+        }
+
+        return true;
+      }
+    }
+
+    false
+  }
+
+  /// Whether instructions were written since the last call.
+  fn code_modified(&mut self) -> bool {
+    let version = self.cpu.memory().code_version();
+    let modified = version != self.code_version;
+    self.code_version = version;
+    modified
+  }
+
+  /// Discard IF and ID after a taken branch or a jump, and fetch from `target`
+  /// on this cycle.
+  fn squash(&mut self, target: u32) {
+    self.stats.flushes += self.stages[IF..=ID].iter().flatten().count() as u64;
+    self.restart(target);
+  }
+
+  /// Discard IF and ID without accounting for it, and fetch from `target` on
+  /// this cycle.
+  fn restart(&mut self, target: u32) {
+    self.stages[ID] = None;
+    self.fetch_pc = target;
+    self.fetch();
+  }
+
+  fn fetch(&mut self) {
+    let pc = self.fetch_pc;
+    let word = self.cpu.memory().fetch(pc).ok();
+    let class = word.map(decode::classify);
+
+    let early = match (word, class) {
+      // direct jumps only need the instruction word
+      (Some(w), Some(Class::Jump)) => {
+        decode::static_target(w, pc).is_some() || self.config.early_branches
+      }
+      (_, Some(Class::Branch)) => self.config.early_branches,
+      _ => false,
+    };
+
+    self.stages[IF] = Some(Slot {
+      pc,
+      word,
+      reads: word.map(decode::registers_read).unwrap_or_default(),
+      writes: word.map(decode::registers_written).unwrap_or_default(),
+      load: class == Some(Class::Load),
+      early,
+      redirect: None,
+    });
+
+    self.fetch_pc = pc.wrapping_add(4);
+  }
+
+  /// Account for the cycle which just ran, `stalled` telling whether ID was
+  /// held by a data hazard.
+  fn end_cycle(&mut self, stalled: bool) {
+    self.stats.cycles += 1;
+
+    if self.stages[WB].is_some() {
+      self.stats.instructions += 1;
+    }
+
+    if !self.config.diagram {
+      return;
+    }
+
+    let stages = std::array::from_fn(|n| self.stages[n].as_ref().map(|s| (s.pc, s.word)));
+    self.diagram.push(DiagramRow { stages, stalled });
+  }
+}
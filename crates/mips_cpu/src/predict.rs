@@ -0,0 +1,476 @@
+//! Branch prediction, simulated over the conditional branches a program
+//! executes.
+//!
+//! A `BranchSimulator` registered as an observer asks its `Predictor` about
+//! every conditional branch before telling it the actual outcome. Direction
+//! predictors assume the target of a PC-relative branch is known once decoded.
+//! The BTB must also supply the target, it only predicts taken branches it has
+//! seen before.
+
+use crate::decode::{self, Class};
+use crate::disasm;
+use crate::observer::CpuObserver;
+use crate::symbols::Symbols;
+use mips_program::ProgramData;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::{error, fmt};
+
+/// Predicted outcome of a branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prediction {
+  pub taken: bool,
+  /// Target of a predicted taken branch, `None` if the predictor relies on
+  /// the decoded instruction.
+  pub target: Option<u32>,
+}
+
+impl Prediction {
+  fn direction(taken: bool) -> Prediction {
+    Prediction {
+      taken,
+      target: None,
+    }
+  }
+
+  /// Whether the prediction matches the actual outcome.
+  pub fn matches(&self, taken: bool, target: u32) -> bool {
+    self.taken == taken && (!taken || self.target.map_or(true, |t| t == target))
+  }
+}
+
+/// A branch predictor.
+pub trait Predictor {
+  fn name(&self) -> String;
+
+  /// Predict the outcome of the branch `word` located at `pc`.
+  fn predict(&mut self, pc: u32, word: u32) -> Prediction;
+
+  /// Learn the actual outcome of the branch at `pc`, after `predict`.
+  fn update(&mut self, pc: u32, taken: bool, target: u32);
+}
+
+/// Always predicts the same direction.
+pub struct Static {
+  taken: bool,
+}
+
+impl Static {
+  pub fn new(taken: bool) -> Static {
+    Static { taken }
+  }
+}
+
+impl Predictor for Static {
+  fn name(&self) -> String {
+    match self.taken {
+      true => "static taken".to_owned(),
+      false => "static not taken".to_owned(),
+    }
+  }
+
+  fn predict(&mut self, _pc: u32, _word: u32) -> Prediction {
+    Prediction::direction(self.taken)
+  }
+
+  fn update(&mut self, _pc: u32, _taken: bool, _target: u32) {}
+}
+
+/// Table of `2 ^ bits` entries, indexed by the low bits of the word address.
+fn index(pc: u32, bits: u32) -> usize {
+  ((pc >> 2) & ((1 << bits) - 1)) as usize
+}
+
+/// Remembers the last outcome of each branch.
+pub struct OneBit {
+  bits: u32,
+  table: Vec<bool>,
+}
+
+impl OneBit {
+  /// A predictor with a `2 ^ bits` entry history table.
+  pub fn new(bits: u32) -> OneBit {
+    OneBit {
+      bits,
+      table: vec![false; 1 << bits],
+    }
+  }
+}
+
+impl Predictor for OneBit {
+  fn name(&self) -> String {
+    format!("1-bit, {} entries", self.table.len())
+  }
+
+  fn predict(&mut self, pc: u32, _word: u32) -> Prediction {
+    Prediction::direction(self.table[index(pc, self.bits)])
+  }
+
+  fn update(&mut self, pc: u32, taken: bool, _target: u32) {
+    self.table[index(pc, self.bits)] = taken;
+  }
+}
+
+/// 2-bit saturating counter: `0..2` predict not taken, `2..4` taken.
+fn saturate(counter: &mut u8, taken: bool) {
+  *counter = match taken {
+    true => (*counter + 1).min(3),
+    false => counter.saturating_sub(1),
+  };
+}
+
+/// A 2-bit saturating counter per branch.
+pub struct TwoBit {
+  bits: u32,
+  table: Vec<u8>,
+}
+
+impl TwoBit {
+  /// A predictor with a `2 ^ bits` entry counter table, initially weakly not
+  /// taken.
+  pub fn new(bits: u32) -> TwoBit {
+    TwoBit {
+      bits,
+      table: vec![1; 1 << bits],
+    }
+  }
+}
+
+impl Predictor for TwoBit {
+  fn name(&self) -> String {
+    format!("2-bit saturating, {} entries", self.table.len())
+  }
+
+  fn predict(&mut self, pc: u32, _word: u32) -> Prediction {
+    Prediction::direction(self.table[index(pc, self.bits)] >= 2)
+  }
+
+  fn update(&mut self, pc: u32, taken: bool, _target: u32) {
+    saturate(&mut self.table[index(pc, self.bits)], taken);
+  }
+}
+
+/// 2-bit counters indexed by the branch address XORed with the global history
+/// of the last outcomes.
+pub struct Gshare {
+  bits: u32,
+  history: u32,
+  table: Vec<u8>,
+}
+
+impl Gshare {
+  /// A predictor with `bits` of global history and a `2 ^ bits` entry counter
+  /// table.
+  pub fn new(bits: u32) -> Gshare {
+    Gshare {
+      bits,
+      history: 0,
+      table: vec![1; 1 << bits],
+    }
+  }
+
+  fn slot(&self, pc: u32) -> usize {
+    index(pc, self.bits) ^ self.history as usize
+  }
+}
+
+impl Predictor for Gshare {
+  fn name(&self) -> String {
+    format!("gshare, {} bits of history", self.bits)
+  }
+
+  fn predict(&mut self, pc: u32, _word: u32) -> Prediction {
+    Prediction::direction(self.table[self.slot(pc)] >= 2)
+  }
+
+  fn update(&mut self, pc: u32, taken: bool, _target: u32) {
+    let slot = self.slot(pc);
+    saturate(&mut self.table[slot], taken);
+
+    self.history = ((self.history << 1) | taken as u32) & ((1 << self.bits) - 1);
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BtbEntry {
+  pc: u32,
+  target: u32,
+  counter: u8,
+}
+
+/// Direct mapped branch target buffer, holding a target and a 2-bit counter
+/// per branch. Branches missing from the buffer are predicted not taken, and
+/// only enter it once taken.
+pub struct Btb {
+  bits: u32,
+  entries: Vec<Option<BtbEntry>>,
+}
+
+impl Btb {
+  /// A buffer of `2 ^ bits` entries.
+  pub fn new(bits: u32) -> Btb {
+    Btb {
+      bits,
+      entries: vec![None; 1 << bits],
+    }
+  }
+}
+
+impl Predictor for Btb {
+  fn name(&self) -> String {
+    format!("BTB, {} entries", self.entries.len())
+  }
+
+  fn predict(&mut self, pc: u32, _word: u32) -> Prediction {
+    match self.entries[index(pc, self.bits)] {
+      Some(entry) if entry.pc == pc => Prediction {
+        taken: entry.counter >= 2,
+        target: Some(entry.target),
+      },
+      _ => Prediction::direction(false),
+    }
+  }
+
+  fn update(&mut self, pc: u32, taken: bool, target: u32) {
+    let entry = &mut self.entries[index(pc, self.bits)];
+
+    match entry {
+      Some(e) if e.pc == pc => {
+        saturate(&mut e.counter, taken);
+
+        if taken {
+          e.target = target;
+        }
+      }
+      _ if taken => {
+        *entry = Some(BtbEntry {
+          pc,
+          target,
+          counter: 2,
+        })
+      }
+      _ => (),
+    }
+  }
+}
+
+/// The available predictors, as named on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PredictorKind {
+  Taken,
+  NotTaken,
+  OneBit,
+  TwoBit,
+  Gshare,
+  Btb,
+}
+
+impl PredictorKind {
+  pub const NAMES: [&'static str; 6] = ["taken", "not-taken", "1bit", "2bit", "gshare", "btb"];
+
+  /// Build the predictor, with tables of `2 ^ bits` entries.
+  pub fn build(self, bits: u32) -> Box<dyn Predictor> {
+    match self {
+      PredictorKind::Taken => Box::new(Static::new(true)),
+      PredictorKind::NotTaken => Box::new(Static::new(false)),
+      PredictorKind::OneBit => Box::new(OneBit::new(bits)),
+      PredictorKind::TwoBit => Box::new(TwoBit::new(bits)),
+      PredictorKind::Gshare => Box::new(Gshare::new(bits)),
+      PredictorKind::Btb => Box::new(Btb::new(bits)),
+    }
+  }
+}
+
+impl FromStr for PredictorKind {
+  type Err = UnknownPredictor;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "taken" => Ok(PredictorKind::Taken),
+      "not-taken" => Ok(PredictorKind::NotTaken),
+      "1bit" => Ok(PredictorKind::OneBit),
+      "2bit" => Ok(PredictorKind::TwoBit),
+      "gshare" => Ok(PredictorKind::Gshare),
+      "btb" => Ok(PredictorKind::Btb),
+      _ => Err(UnknownPredictor(s.to_owned())),
+    }
+  }
+}
+
+/// Name which does not match any `PredictorKind`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPredictor(pub String);
+
+impl fmt::Display for UnknownPredictor {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "unknown predictor {:?}, expected one of: {}",
+      self.0,
+      PredictorKind::NAMES.join(", ")
+    )
+  }
+}
+
+impl error::Error for UnknownPredictor {}
+
+/// Outcomes of the branches at a site, or under a label.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BranchStats {
+  pub executed: u64,
+  pub taken: u64,
+  pub correct: u64,
+}
+
+impl BranchStats {
+  /// Prediction accuracy in percent, zero if nothing was executed.
+  pub fn accuracy(&self) -> f64 {
+    match self.executed {
+      0 => 0.0,
+      n => self.correct as f64 * 100.0 / n as f64,
+    }
+  }
+
+  fn record(&mut self, taken: bool, correct: bool) {
+    self.executed += 1;
+    self.taken += taken as u64;
+    self.correct += correct as u64;
+  }
+}
+
+/// Runs a predictor over the conditional branches of a program, to be
+/// registered as an observer.
+pub struct BranchSimulator {
+  predictor: Box<dyn Predictor>,
+  symbols: Symbols,
+  /// Word fetched during the current cycle.
+  word: Option<u32>,
+  sites: HashMap<u32, (u32, BranchStats)>,
+}
+
+impl BranchSimulator {
+  pub fn new(program: &ProgramData, predictor: Box<dyn Predictor>) -> BranchSimulator {
+    BranchSimulator {
+      predictor,
+      symbols: Symbols::from_program(program),
+      word: None,
+      sites: HashMap::new(),
+    }
+  }
+
+  pub fn predictor(&self) -> &dyn Predictor {
+    self.predictor.as_ref()
+  }
+
+  /// Outcomes of every branch executed.
+  pub fn total(&self) -> BranchStats {
+    self
+      .sites
+      .values()
+      .fold(BranchStats::default(), |acc, (_, s)| BranchStats {
+        executed: acc.executed + s.executed,
+        taken: acc.taken + s.taken,
+        correct: acc.correct + s.correct,
+      })
+  }
+
+  /// Outcomes per branch site, by address.
+  pub fn by_site(&self) -> Vec<(u32, BranchStats)> {
+    let mut sites = self
+      .sites
+      .iter()
+      .map(|(pc, (_, stats))| (*pc, *stats))
+      .collect::<Vec<_>>();
+
+    sites.sort_by_key(|(pc, _)| *pc);
+    sites
+  }
+
+  /// Outcomes per label of the branch sites, by label name.
+  pub fn by_label(&self) -> Vec<(String, BranchStats)> {
+    let mut labels = HashMap::<String, BranchStats>::new();
+
+    for (pc, (_, stats)) in &self.sites {
+      let label = labels
+        .entry(self.symbols.label_name(*pc).to_owned())
+        .or_default();
+      label.executed += stats.executed;
+      label.taken += stats.taken;
+      label.correct += stats.correct;
+    }
+
+    let mut labels = labels.into_iter().collect::<Vec<_>>();
+    labels.sort_by(|a, b| a.0.cmp(&b.0));
+    labels
+  }
+
+  /// Write a human readable report.
+  pub fn write_report(&self, mut out: impl Write) -> io::Result<()> {
+    let total = self.total();
+
+    writeln!(out, "predictor: {}", self.predictor.name())?;
+    writeln!(
+      out,
+      "branches {}, taken {}, correctly predicted {} ({:.2}% accuracy)",
+      total.executed,
+      total.taken,
+      total.correct,
+      total.accuracy()
+    )?;
+
+    writeln!(out, "\nby site:")?;
+    for (pc, stats) in self.by_site() {
+      let word = self.sites[&pc].0;
+
+      writeln!(
+        out,
+        "  {pc:#010x} <{}>  {:<32} executed {:>10}, taken {:>10}, {:>6.2}% accuracy",
+        self.symbols.location(pc),
+        disasm::disassemble(word, pc),
+        stats.executed,
+        stats.taken,
+        stats.accuracy(),
+      )?;
+    }
+
+    writeln!(out, "\nby label:")?;
+    for (label, stats) in self.by_label() {
+      writeln!(
+        out,
+        "  {label:<24} executed {:>10}, {:>6.2}% accuracy",
+        stats.executed,
+        stats.accuracy(),
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
+impl CpuObserver for BranchSimulator {
+  fn on_cycle_start(&mut self, _cycle: u64, _pc: u32) {
+    self.word = None;
+  }
+
+  fn on_fetch(&mut self, _pc: u32, word: u32) {
+    self.word = Some(word);
+  }
+
+  fn on_branch(&mut self, pc: u32, target: u32, taken: bool) {
+    let Some(word) = self.word.filter(|w| decode::classify(*w) == Class::Branch) else {
+      return;
+    };
+
+    let prediction = self.predictor.predict(pc, word);
+    let correct = prediction.matches(taken, target);
+    self.predictor.update(pc, taken, target);
+
+    self
+      .sites
+      .entry(pc)
+      .or_insert((word, BranchStats::default()))
+      .1
+      .record(taken, correct);
+  }
+}
@@ -0,0 +1,281 @@
+use crate::decode::{self, Class};
+use crate::disasm;
+use crate::mem::{self, Access};
+use crate::observer::CpuObserver;
+use crate::symbols::Symbols;
+use mips_program::{ProgramData, Section};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// How many addresses the text report lists.
+const HOT_ADDRESSES: usize = 20;
+
+/// Execution count of a single instruction.
+#[derive(Debug, Clone, Copy)]
+struct Hit {
+  word: u32,
+  count: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BranchStats {
+  taken: u64,
+  not_taken: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SectionAccesses {
+  loads: u64,
+  stores: u64,
+}
+
+/// Instruction-level profiler, to be registered as an observer.
+///
+/// Counts executed instructions per address and per label, the instruction
+/// mix, conditional branch outcomes per branch site and data memory accesses
+/// per section. Calls (`jal`, `jalr`, `bgezal`, `bltzal`) and returns (`jr $ra`) are
+/// followed to attribute instructions to call stacks, which can be written out
+/// in the collapsed stack format understood by flamegraph tools.
+pub struct Profiler {
+  symbols: Symbols,
+  instructions: u64,
+  hits: HashMap<u32, Hit>,
+  mix: HashMap<Class, u64>,
+  branches: HashMap<u32, BranchStats>,
+  accesses: HashMap<Section, SectionAccesses>,
+  /// Labels of the functions being executed, outermost first.
+  stack: Vec<String>,
+  /// Instruction counts per call stack, joined with `;`.
+  stacks: HashMap<String, u64>,
+  /// The instruction of the current cycle is a conditional branch, a call or
+  /// a return.
+  conditional: bool,
+  call: bool,
+  ret: bool,
+}
+
+impl Profiler {
+  pub fn new(program: &ProgramData) -> Profiler {
+    Profiler {
+      symbols: Symbols::from_program(program),
+      instructions: 0,
+      hits: HashMap::new(),
+      mix: HashMap::new(),
+      branches: HashMap::new(),
+      accesses: HashMap::new(),
+      stack: Vec::new(),
+      stacks: HashMap::new(),
+      conditional: false,
+      call: false,
+      ret: false,
+    }
+  }
+
+  /// Number of instructions executed.
+  pub fn instructions(&self) -> u64 {
+    self.instructions
+  }
+
+  /// Number of times the instruction at `addr` was executed.
+  pub fn count(&self, addr: u32) -> u64 {
+    self.hits.get(&addr).map_or(0, |h| h.count)
+  }
+
+  /// Instructions executed under each label, most executed first.
+  pub fn by_label(&self) -> Vec<(String, u64)> {
+    let mut labels = HashMap::<String, u64>::new();
+
+    for (addr, hit) in &self.hits {
+      *labels
+        .entry(self.symbols.label_name(*addr).to_owned())
+        .or_default() += hit.count;
+    }
+
+    let mut labels = labels.into_iter().collect::<Vec<_>>();
+    labels.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    labels
+  }
+
+  /// Write a human readable report.
+  pub fn write_report(&self, mut out: impl Write) -> io::Result<()> {
+    writeln!(out, "instructions executed: {}", self.instructions)?;
+
+    writeln!(out, "\nhot spots by label:")?;
+    for (label, count) in self.by_label() {
+      writeln!(
+        out,
+        "  {:>12} {:>6.2}%  {label}",
+        count,
+        self.percent(count)
+      )?;
+    }
+
+    writeln!(out, "\nhot spots by address:")?;
+    let mut hits = self.hits.iter().collect::<Vec<_>>();
+    hits.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(b.0)));
+
+    for (addr, hit) in hits.into_iter().take(HOT_ADDRESSES) {
+      writeln!(
+        out,
+        "  {:>12} {:>6.2}%  {addr:#010x} <{}>  {}",
+        hit.count,
+        self.percent(hit.count),
+        self.symbols.location(*addr),
+        disasm::disassemble(hit.word, *addr),
+      )?;
+    }
+
+    writeln!(out, "\ninstruction mix:")?;
+    let mut mix = self.mix.iter().collect::<Vec<_>>();
+    mix.sort_by(|a, b| {
+      b.1
+        .cmp(a.1)
+        .then_with(|| class_name(*a.0).cmp(class_name(*b.0)))
+    });
+
+    for (class, count) in mix {
+      writeln!(
+        out,
+        "  {:>12} {:>6.2}%  {}",
+        count,
+        self.percent(*count),
+        class_name(*class)
+      )?;
+    }
+
+    writeln!(out, "\nbranches:")?;
+    let total = self
+      .branches
+      .values()
+      .fold(BranchStats::default(), |acc, b| BranchStats {
+        taken: acc.taken + b.taken,
+        not_taken: acc.not_taken + b.not_taken,
+      });
+    writeln!(
+      out,
+      "  taken {}, not taken {} ({:.2}% taken)",
+      total.taken,
+      total.not_taken,
+      taken_ratio(total),
+    )?;
+
+    let mut sites = self.branches.iter().collect::<Vec<_>>();
+    sites.sort_by_key(|(addr, _)| **addr);
+
+    for (addr, stats) in sites {
+      writeln!(
+        out,
+        "  {addr:#010x} <{}>  taken {}, not taken {} ({:.2}% taken)",
+        self.symbols.location(*addr),
+        stats.taken,
+        stats.not_taken,
+        taken_ratio(*stats),
+      )?;
+    }
+
+    writeln!(out, "\nmemory accesses:")?;
+    for section in mem::SECTIONS {
+      let accesses = self.accesses.get(&section).copied().unwrap_or_default();
+
+      writeln!(
+        out,
+        "  {:<8} loads {}, stores {}",
+        mem::section_name(section),
+        accesses.loads,
+        accesses.stores,
+      )?;
+    }
+
+    Ok(())
+  }
+
+  /// Write instruction counts per call stack in the collapsed stack format:
+  /// one `outer;inner count` line per stack.
+  pub fn write_collapsed(&self, mut out: impl Write) -> io::Result<()> {
+    let mut stacks = self.stacks.iter().collect::<Vec<_>>();
+    stacks.sort();
+
+    for (stack, count) in stacks {
+      writeln!(out, "{stack} {count}")?;
+    }
+
+    Ok(())
+  }
+
+  fn percent(&self, count: u64) -> f64 {
+    match self.instructions {
+      0 => 0.0,
+      total => count as f64 * 100.0 / total as f64,
+    }
+  }
+}
+
+impl CpuObserver for Profiler {
+  fn on_fetch(&mut self, pc: u32, word: u32) {
+    self.instructions += 1;
+    self.hits.entry(pc).or_insert(Hit { word, count: 0 }).count += 1;
+
+    let class = decode::classify(word);
+    *self.mix.entry(class).or_default() += 1;
+
+    if self.stack.is_empty() {
+      self.stack.push(self.symbols.label_name(pc).to_owned());
+    }
+    *self.stacks.entry(self.stack.join(";")).or_default() += 1;
+
+    self.conditional = class == Class::Branch;
+    self.call = decode::is_call(word);
+    self.ret = decode::is_return(word);
+  }
+
+  fn on_memory_read(&mut self, _pc: u32, access: &Access) {
+    if let Some(section) = mem::section_at(access.addr) {
+      self.accesses.entry(section).or_default().loads += 1;
+    }
+  }
+
+  fn on_memory_write(&mut self, _pc: u32, access: &Access) {
+    if let Some(section) = mem::section_at(access.addr) {
+      self.accesses.entry(section).or_default().stores += 1;
+    }
+  }
+
+  fn on_branch(&mut self, pc: u32, target: u32, taken: bool) {
+    if self.conditional {
+      let stats = self.branches.entry(pc).or_default();
+
+      if taken {
+        stats.taken += 1;
+      } else {
+        stats.not_taken += 1;
+      }
+    }
+
+    if taken && self.call {
+      self.stack.push(self.symbols.label_name(target).to_owned());
+    } else if taken && self.ret && self.stack.len() > 1 {
+      self.stack.pop();
+    }
+  }
+}
+
+fn taken_ratio(stats: BranchStats) -> f64 {
+  match stats.taken + stats.not_taken {
+    0 => 0.0,
+    total => stats.taken as f64 * 100.0 / total as f64,
+  }
+}
+
+fn class_name(class: Class) -> &'static str {
+  match class {
+    Class::Alu => "alu",
+    Class::Float => "float",
+    Class::Branch => "branch",
+    Class::Jump => "jump",
+    Class::Load => "load",
+    Class::Store => "store",
+    Class::Syscall => "syscall",
+    Class::Trap => "trap",
+    Class::Other => "other",
+  }
+}
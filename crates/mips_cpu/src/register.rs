@@ -1,41 +1,116 @@
+use crate::cop0::Cop0;
+use crate::cop1::Cop1;
 use crate::exception::{Exception, Unstable};
-use crate::mem::TEXT_START;
-use std::cell::{BorrowError, Ref, RefCell, RefMut};
+use crate::mem::{GLOBAL_POINTER, STACK_POINTER, TEXT_START};
+use serde::{Deserialize, Serialize};
+use std::cell::{BorrowError, Cell, Ref, RefCell, RefMut};
+use std::ops::{Deref, DerefMut};
+
+/// ABI names of the regular registers, without the `$` prefix.
+pub const NAMES: [&str; 32] = [
+  "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+  "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
+
+/// Register number standing for `HI`, after the regular registers.
+pub const HI: u8 = 32;
+/// Register number standing for `LO`, after the regular registers.
+pub const LO: u8 = 33;
+/// Register number standing for `$f0`, followed by the other floating point
+/// registers.
+pub const F0: u8 = 34;
+/// Register number standing for FCSR, after the floating point registers.
+pub const FCSR: u8 = F0 + 32;
+/// Number of numbered registers.
+pub const COUNT: usize = FCSR as usize + 1;
+
+/// Display name of register number `n`: `$` followed by the ABI name for
+/// regular registers, `$f0`..`$f31` for floating point registers, `hi`, `lo`
+/// or `fcsr` otherwise.
+pub fn name(n: u8) -> String {
+  match n {
+    HI => "hi".to_owned(),
+    LO => "lo".to_owned(),
+    FCSR => "fcsr".to_owned(),
+    F0.. => format!("$f{}", n - F0),
+    n => format!("${}", NAMES[n as usize]),
+  }
+}
 
 /// A collection of registers present in a MIPS32 CPU. Contains the regular
-/// 32 registers, the PC and the HI/LO registers.  
+/// 32 registers, the PC, the HI/LO registers and coprocessors 0 and 1.  
 ///
 /// All registers span 4 bytes (a word).  
 ///
 /// For ease of use, regular registers are protected in a `RefCell`. This might
 /// change in the future.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Registers {
   regular: [RefCell<u32>; 32],
   pub pc: u32,
   pub hi: u32,
   pub lo: u32,
+  pub cop0: Cop0,
+  pub cop1: Cop1,
+  /// Regular registers, `HI` and `LO` written since the last call to
+  /// `clear_written`, one bit per register number.
+  #[serde(skip)]
+  written: Cell<u128>,
+}
+
+/// Mutable access to a regular register. Assigning through it records the
+/// register as written, even if its value stays the same. `$zero` is
+/// hardwired: assignments to it are discarded and not recorded.
+pub struct RegisterMut<'a> {
+  value: RefMut<'a, u32>,
+  n: usize,
+  written: &'a Cell<u128>,
+  /// Where assignments to `$zero` go.
+  discarded: u32,
+}
+
+impl Deref for RegisterMut<'_> {
+  type Target = u32;
+
+  fn deref(&self) -> &u32 {
+    &self.value
+  }
+}
+
+impl DerefMut for RegisterMut<'_> {
+  fn deref_mut(&mut self) -> &mut u32 {
+    if self.n == 0 {
+      return &mut self.discarded;
+    }
+
+    self.written.set(self.written.get() | 1 << self.n);
+    &mut self.value
+  }
 }
 
 impl Registers {
-  /// Initialize the registers with sensible initial values. Subject to change.
+  /// Initialize the registers as MARS does: `$sp` and `$gp` point to the
+  /// stack and to `.extern`, everything else is zero.
   pub fn init() -> Registers {
     let regular = <[RefCell<u32>; 32]>::default();
-    *regular[8].borrow_mut() = 3;
-    *regular[9].borrow_mut() = 4;
+    *regular[28].borrow_mut() = GLOBAL_POINTER;
+    *regular[29].borrow_mut() = STACK_POINTER;
 
     Registers {
       regular,
       pc: TEXT_START,
       hi: 0,
       lo: 0,
+      cop0: Cop0::init(),
+      cop1: Cop1::init(),
+      written: Cell::new(0),
     }
   }
 
   /// Tries to get mutable access to a regular register. Returns `Result::Err` if
   /// the given register is not in range `0..32` or if the register is already mutably
   /// borrowed.
-  pub fn r(&self, n: usize) -> Result<RefMut<u32>, Unstable<Exception>> {
+  pub fn r(&self, n: usize) -> Result<RegisterMut, Unstable<Exception>> {
     self
       .regular
       .get(n)
@@ -44,6 +119,36 @@ impl Registers {
         r.try_borrow_mut()
           .map_err(|_| Unstable::VmError("race condition while borrowing register".to_owned()))
       })
+      .map(|value| RegisterMut {
+        value,
+        n,
+        written: &self.written,
+        discarded: 0,
+      })
+  }
+
+  pub fn set_hi(&mut self, value: u32) {
+    self.hi = value;
+    self.written.set(self.written.get() | 1 << HI);
+  }
+
+  pub fn set_lo(&mut self, value: u32) {
+    self.lo = value;
+    self.written.set(self.written.get() | 1 << LO);
+  }
+
+  /// Numbers of the registers written since the last call to `clear_written`,
+  /// in increasing order. Direct assignments to the public fields are not
+  /// tracked, instructions go through `r`, `set_hi`, `set_lo` and the `Cop1`
+  /// setters.
+  pub fn written(&self) -> impl Iterator<Item = u8> {
+    let written = self.written.get() | (self.cop1.written() as u128) << F0;
+    (0..COUNT as u8).filter(move |n| written & 1 << n != 0)
+  }
+
+  pub fn clear_written(&mut self) {
+    self.written.set(0);
+    self.cop1.clear_written();
   }
 
   /// Load the next instruction address (PC+4) into the given register. Typical operation of
@@ -54,6 +159,29 @@ impl Registers {
     Ok(())
   }
 
+  /// Copy out the value of every regular register.
+  ///
+  /// Registers are only borrowed while an instruction executes, this must not be
+  /// called from within a cycle.
+  pub fn values(&self) -> [u32; 32] {
+    std::array::from_fn(|n| *self.regular[n].borrow())
+  }
+
+  /// Copy out the value of every regular register followed by `HI`, `LO`, the
+  /// floating point registers and FCSR, indexed by register number. Same
+  /// restrictions as `values`.
+  pub fn numbered_values(&self) -> [u32; COUNT] {
+    let regular = self.values();
+
+    std::array::from_fn(|n| match n as u8 {
+      HI => self.hi,
+      LO => self.lo,
+      FCSR => self.cop1.fcsr,
+      n @ F0.. => self.cop1.word((n - F0) as usize),
+      n => regular[n as usize],
+    })
+  }
+
   /// Tries to borrow every value in regular registers, for debugging purposes.
   pub fn regular_values(&self) -> [Result<Ref<u32>, BorrowError>; 32] {
     // collected vector cannot not be length 32
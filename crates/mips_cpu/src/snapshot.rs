@@ -0,0 +1,116 @@
+use crate::console::Buffered;
+use crate::environment::Environment;
+use crate::fs::Descriptor;
+use crate::mmu::Mmu;
+use crate::register::Registers;
+use mips_program::ProgramData;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::{error, fmt};
+
+/// Header of snapshot files. The last two bytes are the format version.
+pub const MAGIC: &[u8; 8] = b"MIPSSN10";
+
+/// Complete machine state of a `Cpu`, as returned by `Cpu::snapshot`.
+///
+/// Observers are not part of the machine state, they are left untouched when
+/// restoring a snapshot. Neither are the contents of files, only which files
+/// are open and where reading or writing them got to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+  pub(crate) registers: Registers,
+  /// Memory as seen by the CPU.
+  pub(crate) memory: ProgramData,
+  /// The TLB and translation state.
+  pub(crate) mmu: Mmu,
+  /// End of the heap allocated through `sbrk`.
+  pub(crate) program_break: u32,
+  /// Address the program break cannot go past, set by `set_heap_size`.
+  pub(crate) heap_limit: u32,
+  /// The program the CPU was created from, before it ran.
+  pub(crate) program: ProgramData,
+  pub(crate) cycles: u64,
+  /// Files opened by system calls.
+  pub(crate) files: Vec<Descriptor>,
+  /// Rest of the line of standard input the program did not read yet.
+  pub(crate) stdin: Vec<u8>,
+  pub(crate) environment: Environment,
+  /// The console of the embedder, which the CPU does not hold.
+  pub(crate) console: Option<Buffered>,
+}
+
+impl Snapshot {
+  /// Number of cycles the CPU had run when the snapshot was taken.
+  pub fn cycles(&self) -> u64 {
+    self.cycles
+  }
+
+  /// Save `console` along with the machine state.
+  pub fn with_console(mut self, console: &Buffered) -> Snapshot {
+    self.console = Some(console.clone());
+    self
+  }
+
+  /// The console saved with `with_console`, to be put back in place when
+  /// restoring the snapshot.
+  pub fn console(&self) -> Option<&Buffered> {
+    self.console.as_ref()
+  }
+
+  /// Serialize the snapshot, prefixed by `MAGIC`.
+  pub fn write_to(&self, mut out: impl Write) -> Result<(), SnapshotError> {
+    out.write_all(MAGIC)?;
+    bincode::serialize_into(&mut out, self)?;
+    Ok(())
+  }
+
+  /// Deserialize a snapshot written by `write_to`.
+  pub fn read_from(mut input: impl Read) -> Result<Snapshot, SnapshotError> {
+    let mut magic = [0; MAGIC.len()];
+    input.read_exact(&mut magic)?;
+
+    if &magic != MAGIC {
+      return Err(SnapshotError::BadMagic);
+    }
+
+    Ok(bincode::deserialize_from(input)?)
+  }
+}
+
+/// Error while reading or writing a snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+  Io(io::Error),
+  /// The data is not a valid snapshot.
+  Format(bincode::Error),
+  /// The data does not start with `MAGIC`: not a snapshot, or a snapshot from
+  /// an incompatible version.
+  BadMagic,
+}
+
+impl fmt::Display for SnapshotError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SnapshotError::Io(e) => write!(f, "snapshot IO error: {e}"),
+      SnapshotError::Format(e) => write!(f, "malformed snapshot: {e}"),
+      SnapshotError::BadMagic => write!(f, "not a snapshot, or unsupported snapshot version"),
+    }
+  }
+}
+
+impl error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+  fn from(value: io::Error) -> Self {
+    SnapshotError::Io(value)
+  }
+}
+
+impl From<bincode::Error> for SnapshotError {
+  fn from(value: bincode::Error) -> Self {
+    match *value {
+      bincode::ErrorKind::Io(e) => SnapshotError::Io(e),
+      _ => SnapshotError::Format(value),
+    }
+  }
+}
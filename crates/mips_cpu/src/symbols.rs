@@ -0,0 +1,85 @@
+use crate::mem;
+use mips_program::{ProgramData, Section};
+use std::ops::Range;
+
+/// Stands for the label of addresses no label covers.
+pub const UNLABELED: &str = "??";
+
+/// A label resolved to its address in the MIPS memory layout.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+  pub addr: u32,
+  pub name: String,
+  pub section: Section,
+}
+
+/// Every label of a program, sorted by address.
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+  symbols: Vec<Symbol>,
+}
+
+impl Symbols {
+  pub fn from_program(program: &ProgramData) -> Symbols {
+    let mut symbols = Vec::new();
+
+    for section in mem::SECTIONS {
+      let start = *mem::section_range(section).start();
+
+      symbols.extend(program.labels(section).iter().map(|label| Symbol {
+        addr: start + label.position as u32,
+        name: label.name.clone(),
+        section,
+      }));
+    }
+
+    symbols.sort_by_key(|s| s.addr);
+    Symbols { symbols }
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+    self.symbols.iter()
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Symbol> {
+    self.symbols.iter().find(|s| s.name == name)
+  }
+
+  /// Addresses covered by the label `name`: from the label up to the next
+  /// label of the same section, or the end of the section.
+  pub fn range(&self, name: &str) -> Option<Range<u32>> {
+    let i = self.symbols.iter().position(|s| s.name == name)?;
+    let symbol = &self.symbols[i];
+
+    let end = match self.symbols.get(i + 1) {
+      Some(next) if next.section == symbol.section => next.addr,
+      _ => mem::section_range(symbol.section).end() + 1,
+    };
+
+    Some(symbol.addr..end)
+  }
+
+  /// The label `addr` falls under, that is the closest label at or before
+  /// `addr` in the same section.
+  pub fn label_at(&self, addr: u32) -> Option<&Symbol> {
+    let i = self.symbols.partition_point(|s| s.addr <= addr);
+    let symbol = self.symbols.get(i.checked_sub(1)?)?;
+
+    mem::section_range(symbol.section)
+      .contains(&addr)
+      .then_some(symbol)
+  }
+
+  /// Name of the label `addr` falls under, `UNLABELED` if none.
+  pub fn label_name(&self, addr: u32) -> &str {
+    self.label_at(addr).map_or(UNLABELED, |s| s.name.as_str())
+  }
+
+  /// `label+offset` of an address, `UNLABELED` if no label covers it.
+  pub fn location(&self, addr: u32) -> String {
+    match self.label_at(addr) {
+      Some(symbol) => format!("{}+{:#x}", symbol.name, addr - symbol.addr),
+      None => UNLABELED.to_owned(),
+    }
+  }
+}
@@ -0,0 +1,493 @@
+//! System calls serviced by the simulator, with the MARS numbering.
+//!
+//! `Cpu::cycle` stops on `syscall` instructions with `Exception::Syscall`,
+//! leaving the PC on the instruction. Runners call `service` to perform the
+//! call and move past it.
+
+use crate::console::Console;
+use crate::fs::{Mode, STDERR, STDIN, STDOUT};
+use crate::mem::SbrkError;
+use crate::Cpu;
+use mips_program::Context;
+use std::{error, fmt};
+
+/// `$v0`, which holds the system call number and the result.
+const V0: usize = 2;
+/// `$a0`, the first argument.
+const A0: usize = 4;
+/// `$a1`, the second argument.
+const A1: usize = 5;
+/// `$a2`, the third argument.
+const A2: usize = 6;
+/// `$f0`, which holds floating point results.
+const F0: usize = 0;
+/// `$f12`, the floating point argument.
+const F12: usize = 12;
+
+/// Print the integer in `$a0`.
+pub const PRINT_INT: u32 = 1;
+/// Print the single precision value in `$f12`.
+pub const PRINT_FLOAT: u32 = 2;
+/// Print the double precision value in `$f12`.
+pub const PRINT_DOUBLE: u32 = 3;
+/// Print the null-terminated string at `$a0`.
+pub const PRINT_STRING: u32 = 4;
+/// Read an integer into `$v0`.
+pub const READ_INT: u32 = 5;
+/// Read a single precision value into `$f0`.
+pub const READ_FLOAT: u32 = 6;
+/// Read a double precision value into `$f0`.
+pub const READ_DOUBLE: u32 = 7;
+/// Read a line into the buffer at `$a0` of `$a1` bytes. The line is cut to
+/// fit, keeping its newline when there is room, and null-terminated.
+pub const READ_STRING: u32 = 8;
+/// Allocate `$a0` bytes of heap, returns the address of the block in `$v0`.
+pub const SBRK: u32 = 9;
+/// Terminate the program.
+pub const EXIT: u32 = 10;
+/// Print the character in the low byte of `$a0`.
+pub const PRINT_CHAR: u32 = 11;
+/// Read a character into `$v0`.
+pub const READ_CHAR: u32 = 12;
+/// Open the file named by the null-terminated string at `$a0`, with the flags
+/// in `$a1` (see `fs::Mode`). Returns the descriptor in `$v0`, -1 on error.
+pub const OPEN_FILE: u32 = 13;
+/// Read up to `$a2` bytes, at most 64 KiB, from descriptor `$a0` into the
+/// buffer at `$a1`. Returns the number of bytes read in `$v0`, 0 at the end of
+/// the file and -1 on error. Reading the standard input takes from a line of
+/// the console.
+pub const READ_FILE: u32 = 14;
+/// Write the `$a2` bytes at `$a1` to descriptor `$a0`, at most 64 KiB at a
+/// time. Returns the number of bytes written in `$v0`, -1 on error.
+pub const WRITE_FILE: u32 = 15;
+/// Close descriptor `$a0`.
+pub const CLOSE_FILE: u32 = 16;
+/// Terminate the program with exit code `$a0`.
+pub const EXIT2: u32 = 17;
+/// Milliseconds since the epoch, low word in `$a0` and high word in `$a1`.
+pub const TIME: u32 = 30;
+/// Pause for `$a0` milliseconds.
+pub const SLEEP: u32 = 32;
+/// Print `$a0` as 8 hexadecimal digits.
+pub const PRINT_HEX: u32 = 34;
+/// Print `$a0` as 32 binary digits.
+pub const PRINT_BINARY: u32 = 35;
+/// Print `$a0` as an unsigned integer.
+pub const PRINT_UNSIGNED: u32 = 36;
+/// Seed random number generator `$a0` with `$a1`.
+pub const SET_SEED: u32 = 40;
+/// Random integer from generator `$a0`, into `$a0`.
+pub const RANDOM_INT: u32 = 41;
+/// Random integer from generator `$a0` between 0 included and `$a1` excluded,
+/// into `$a0`.
+pub const RANDOM_INT_RANGE: u32 = 42;
+/// Random single precision value from generator `$a0` between 0 included and
+/// 1 excluded, into `$f0`.
+pub const RANDOM_FLOAT: u32 = 43;
+/// Random double precision value from generator `$a0` between 0 included and
+/// 1 excluded, into `$f0`.
+pub const RANDOM_DOUBLE: u32 = 44;
+
+/// Longest string `PRINT_STRING` prints, guarding against missing
+/// terminators.
+const MAX_STRING: u32 = 0x10000;
+
+/// Most bytes a single `READ_FILE` or `WRITE_FILE` transfers, reads and writes
+/// may do less than asked anyway.
+const MAX_TRANSFER: u32 = 0x10000;
+
+/// What the program does after a serviced system call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+  /// Resume with the instruction following the `syscall`.
+  Continue,
+  /// The program terminated with an exit code.
+  Exit(i32),
+  /// The system call waits for input the console does not have yet. Nothing
+  /// happened and the PC is left on the `syscall`, service it again once
+  /// there is input.
+  AwaitInput,
+  /// The program asks to pause for this many milliseconds, then resume with
+  /// the instruction following the `syscall`. Runners may skip the wait in
+  /// deterministic mode, where the clock accounts for it.
+  Sleep(u32),
+}
+
+/// Error servicing a system call. The PC is left on the `syscall`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyscallError {
+  /// No system call has this number.
+  Unsupported(u32),
+  Sbrk(SbrkError),
+  /// A string or buffer argument is not readable or writable memory.
+  Address(u32),
+  /// Input which does not parse as the number requested.
+  InvalidInput(String),
+  /// Upper bound of a random range which is not positive.
+  Bound(i32),
+}
+
+impl fmt::Display for SyscallError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SyscallError::Unsupported(n) => write!(f, "unsupported system call {n}"),
+      SyscallError::Sbrk(e) => write!(f, "sbrk: {e}"),
+      SyscallError::Address(addr) => write!(f, "bad address {addr:#010x}"),
+      SyscallError::InvalidInput(input) => write!(f, "invalid input `{input}`"),
+      SyscallError::Bound(bound) => write!(f, "random range up to {bound} is empty"),
+    }
+  }
+}
+
+impl error::Error for SyscallError {}
+
+impl From<SbrkError> for SyscallError {
+  fn from(value: SbrkError) -> Self {
+    SyscallError::Sbrk(value)
+  }
+}
+
+/// Perform the system call requested by `$v0`, after `Cpu::cycle` returned
+/// `Exception::Syscall`. Input and output go through `console`.
+pub fn service(cpu: &mut Cpu, console: &mut dyn Console) -> Result<Outcome, SyscallError> {
+  let code = read(cpu, V0);
+  let a0 = read(cpu, A0);
+
+  let outcome = match code {
+    PRINT_INT => print(console, (a0 as i32).to_string()),
+
+    PRINT_FLOAT => print(
+      console,
+      java(&format!("{:e}", cpu.registers().cop1.single(F12))),
+    ),
+
+    PRINT_DOUBLE => print(
+      console,
+      java(&format!("{:e}", cpu.registers().cop1.double(F12))),
+    ),
+
+    PRINT_STRING => {
+      let text = read_string(cpu, a0)?;
+      print(console, String::from_utf8_lossy(&text).into_owned())
+    }
+
+    READ_INT => match console.read_line() {
+      Some(line) => {
+        let value = parse::<i32>(&line)?;
+        write(cpu, V0, value as u32);
+        Outcome::Continue
+      }
+      None => Outcome::AwaitInput,
+    },
+
+    READ_FLOAT => match console.read_line() {
+      Some(line) => {
+        let value = parse::<f32>(&line)?;
+        cpu.registers_mut().cop1.set_single(F0, value);
+        Outcome::Continue
+      }
+      None => Outcome::AwaitInput,
+    },
+
+    READ_DOUBLE => match console.read_line() {
+      Some(line) => {
+        let value = parse::<f64>(&line)?;
+        cpu.registers_mut().cop1.set_double(F0, value);
+        Outcome::Continue
+      }
+      None => Outcome::AwaitInput,
+    },
+
+    READ_STRING => match console.read_line() {
+      Some(line) => {
+        let size = read(cpu, A1) as usize;
+        let mut bytes = line.into_bytes();
+        bytes.push(b'\n');
+        bytes.truncate(size.saturating_sub(1));
+
+        if size > 0 {
+          bytes.push(0);
+        }
+
+        cpu
+          .memory_mut()
+          .write_range(a0, &bytes, Context::User)
+          .map_err(|_| SyscallError::Address(a0))?;
+        Outcome::Continue
+      }
+      None => Outcome::AwaitInput,
+    },
+
+    SBRK => {
+      let block = cpu.memory_mut().sbrk(a0 as i32)?;
+      write(cpu, V0, block);
+      Outcome::Continue
+    }
+
+    EXIT => Outcome::Exit(0),
+
+    PRINT_CHAR => print(console, (a0 as u8 as char).to_string()),
+
+    READ_CHAR => match console.read_line() {
+      Some(line) => {
+        let c = line.chars().next().unwrap_or('\n');
+        write(cpu, V0, c as u32);
+        Outcome::Continue
+      }
+      None => Outcome::AwaitInput,
+    },
+
+    OPEN_FILE => {
+      let path = read_string(cpu, a0)?;
+      let path = String::from_utf8_lossy(&path);
+      let fd = Mode::from_flags(read(cpu, A1))
+        .and_then(|mode| cpu.files_mut().open(&path, mode).ok())
+        .unwrap_or(u32::MAX);
+
+      write(cpu, V0, fd);
+      Outcome::Continue
+    }
+
+    READ_FILE => {
+      let (buffer, len) = (read(cpu, A1), read(cpu, A2).min(MAX_TRANSFER));
+
+      let bytes = match a0 {
+        STDIN => {
+          if cpu.files().stdin.is_empty() {
+            match console.read_line() {
+              Some(line) => {
+                let stdin = &mut cpu.files_mut().stdin;
+                stdin.extend(line.into_bytes());
+                stdin.push(b'\n');
+              }
+              None => return Ok(Outcome::AwaitInput),
+            }
+          }
+
+          let stdin = &mut cpu.files_mut().stdin;
+          let len = stdin.len().min(len as usize);
+          Some(stdin.drain(..len).collect())
+        }
+        fd => {
+          let mut bytes = vec![0; len as usize];
+          let read = cpu.files_mut().get(fd).map(|file| file.read(&mut bytes));
+
+          match read {
+            Some(Ok(n)) => {
+              bytes.truncate(n);
+              Some(bytes)
+            }
+            _ => None,
+          }
+        }
+      };
+
+      let result = match bytes {
+        Some(bytes) => {
+          cpu
+            .memory_mut()
+            .write_range(buffer, &bytes, Context::User)
+            .map_err(|_| SyscallError::Address(buffer))?;
+          bytes.len() as u32
+        }
+        None => u32::MAX,
+      };
+
+      write(cpu, V0, result);
+      Outcome::Continue
+    }
+
+    WRITE_FILE => {
+      let (buffer, len) = (read(cpu, A1), read(cpu, A2).min(MAX_TRANSFER));
+      let bytes = read_buffer(cpu, buffer, len)?;
+
+      let written = match a0 {
+        STDOUT | STDERR => {
+          console.write(&String::from_utf8_lossy(&bytes));
+          Some(len)
+        }
+        fd => cpu
+          .files_mut()
+          .get(fd)
+          .and_then(|file| file.write_all(&bytes).ok())
+          .map(|()| len),
+      };
+
+      write(cpu, V0, written.unwrap_or(u32::MAX));
+      Outcome::Continue
+    }
+
+    CLOSE_FILE => {
+      cpu.files_mut().close(a0);
+      Outcome::Continue
+    }
+
+    EXIT2 => Outcome::Exit(a0 as i32),
+
+    TIME => {
+      let time = cpu.environment().time(cpu.cycles());
+      write(cpu, A0, time as u32);
+      write(cpu, A1, (time >> 32) as u32);
+      Outcome::Continue
+    }
+
+    SLEEP => {
+      cpu.environment_mut().sleep(a0);
+      Outcome::Sleep(a0)
+    }
+
+    PRINT_HEX => print(console, format!("{a0:#010x}")),
+
+    PRINT_BINARY => print(console, format!("{a0:032b}")),
+
+    PRINT_UNSIGNED => print(console, a0.to_string()),
+
+    SET_SEED => {
+      // sign extended, like the long seeds of MARS
+      let seed = read(cpu, A1) as i32 as i64 as u64;
+      cpu.environment_mut().seed(a0, seed);
+      Outcome::Continue
+    }
+
+    RANDOM_INT => {
+      let value = cpu.environment_mut().generator(a0).next_int();
+      write(cpu, A0, value as u32);
+      Outcome::Continue
+    }
+
+    RANDOM_INT_RANGE => {
+      let bound = read(cpu, A1) as i32;
+
+      if bound <= 0 {
+        return Err(SyscallError::Bound(bound));
+      }
+
+      let value = cpu.environment_mut().generator(a0).next_int_below(bound);
+      write(cpu, A0, value as u32);
+      Outcome::Continue
+    }
+
+    RANDOM_FLOAT => {
+      let value = cpu.environment_mut().generator(a0).next_float();
+      cpu.registers_mut().cop1.set_single(F0, value);
+      Outcome::Continue
+    }
+
+    RANDOM_DOUBLE => {
+      let value = cpu.environment_mut().generator(a0).next_double();
+      cpu.registers_mut().cop1.set_double(F0, value);
+      Outcome::Continue
+    }
+
+    n => return Err(SyscallError::Unsupported(n)),
+  };
+
+  if matches!(outcome, Outcome::Continue | Outcome::Sleep(_)) {
+    cpu.registers_mut().pc += 4;
+  }
+
+  Ok(outcome)
+}
+
+fn print(console: &mut dyn Console, text: String) -> Outcome {
+  console.write(&text);
+  Outcome::Continue
+}
+
+/// A float or double the way Java's `toString` prints it, as MARS does:
+/// `1.5`, `0.001`, `1.0E10`, `Infinity`. `scientific` is the value printed by
+/// `{:e}`, which has the shortest digits that read back as the same value,
+/// like Java's.
+fn java(scientific: &str) -> String {
+  let (sign, unsigned) = match scientific.strip_prefix('-') {
+    Some(unsigned) => ("-", unsigned),
+    None => ("", scientific),
+  };
+
+  let Some((mantissa, exponent)) = unsigned.split_once('e') else {
+    return match unsigned {
+      "inf" => format!("{sign}Infinity"),
+      nan => nan.to_owned(),
+    };
+  };
+
+  let exponent: i32 = exponent.parse().unwrap_or(0);
+  let digits = mantissa.replace('.', "");
+  let or_zero = |digits: &str| match digits {
+    "" => "0".to_owned(),
+    digits => digits.to_owned(),
+  };
+
+  // plain notation from 10^-3 included to 10^7 excluded
+  let text = match exponent {
+    0..=6 => {
+      let point = exponent as usize + 1;
+      let digits = format!("{digits:0<point$}");
+      let (integer, fraction) = digits.split_at(point);
+      format!("{integer}.{}", or_zero(fraction))
+    }
+    -3..=-1 => format!("0.{}{digits}", "0".repeat(-exponent as usize - 1)),
+    _ => {
+      let (first, rest) = digits.split_at(1);
+      format!("{first}.{}E{exponent}", or_zero(rest))
+    }
+  };
+
+  format!("{sign}{text}")
+}
+
+fn parse<T: std::str::FromStr>(line: &str) -> Result<T, SyscallError> {
+  line
+    .trim()
+    .parse()
+    .map_err(|_| SyscallError::InvalidInput(line.to_owned()))
+}
+
+/// The null-terminated string at `addr`, without its terminator.
+fn read_string(cpu: &Cpu, addr: u32) -> Result<Vec<u8>, SyscallError> {
+  let mut text = Vec::new();
+
+  for i in 0..MAX_STRING {
+    let addr = addr.wrapping_add(i);
+    let byte = cpu
+      .memory()
+      .read_byte(addr, Context::User)
+      .map_err(|_| SyscallError::Address(addr))?;
+
+    if byte == 0 {
+      break;
+    }
+
+    text.push(byte);
+  }
+
+  Ok(text)
+}
+
+/// The `len` bytes at `addr`.
+fn read_buffer(cpu: &Cpu, addr: u32, len: u32) -> Result<Vec<u8>, SyscallError> {
+  let bytes = cpu
+    .memory()
+    .read_range(addr, len, Context::User)
+    .map_err(|_| SyscallError::Address(addr))?;
+
+  if bytes.len() < len as usize {
+    return Err(SyscallError::Address(addr.wrapping_add(bytes.len() as u32)));
+  }
+
+  Ok(bytes.into_iter().map(Option::unwrap_or_default).collect())
+}
+
+fn read(cpu: &Cpu, n: usize) -> u32 {
+  // n is one of the constants above
+  #[allow(clippy::unwrap_used)]
+  let register = cpu.registers().r(n).unwrap();
+  *register
+}
+
+fn write(cpu: &mut Cpu, n: usize, value: u32) {
+  // n is one of the constants above
+  #[allow(clippy::unwrap_used)]
+  let mut register = cpu.registers().r(n).unwrap();
+  *register = value;
+}
@@ -0,0 +1,234 @@
+//! Instruction traces, for grading and diffing runs against other simulators.
+//!
+//! A `Tracer` registered as an observer on a `Cpu` builds one `Record` per
+//! cycle and writes it out in one of two formats:
+//!
+//! - `Format::JsonLines`: one JSON object per line, as serialized from `Record`.
+//!   Registers are named (`$t0`, `hi`, `$f2`), everything else is numeric.
+//! - `Format::Binary`: the `MAGIC` header, then per record, little endian:
+//!
+//!   | field      | type  | notes                                        |
+//!   |------------|-------|----------------------------------------------|
+//!   | cycle      | `u64` |                                              |
+//!   | pc         | `u32` |                                              |
+//!   | word       | `u32` | zero if the fetch failed                     |
+//!   | flags      | `u8`  | bit 0: word fetched, bit 1: exception raised |
+//!   | exception  | `u8`  | exception code, zero if none                 |
+//!   | reg count  | `u8`  | followed by `(u8 register, u32 value)` pairs |
+//!   | mem count  | `u8`  | followed by `(u8 kind, u32 addr, u32 value)` |
+//!
+//!   Register numbers 32 and 33 stand for `HI` and `LO`, 34 to 65 for `$f0` to
+//!   `$f31` and 66 for FCSR. The access kind byte holds the access size, with
+//!   bit 7 set for stores. The disassembly is not part of the binary format,
+//!   it can be recovered from the word.
+
+use crate::exception::Exception;
+use crate::mem::{Access, AccessKind};
+use crate::observer::CpuObserver;
+use crate::symbols::Symbols;
+use crate::{disasm, register};
+use mips_program::ProgramData;
+use serde::{Serialize, Serializer};
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// Header of binary traces.
+pub const MAGIC: &[u8; 8] = b"MIPSTRC1";
+
+/// Output format of a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  JsonLines,
+  Binary,
+}
+
+/// A register written during a cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RegisterWrite {
+  /// Register number, as defined in `register`.
+  #[serde(serialize_with = "serialize_register")]
+  pub reg: u8,
+  pub value: u32,
+}
+
+/// Everything that happened during one cycle.
+#[derive(Debug, Clone, Serialize)]
+pub struct Record {
+  pub cycle: u64,
+  pub pc: u32,
+  /// The instruction word, `None` if it could not be fetched.
+  pub word: Option<u32>,
+  pub asm: Option<String>,
+  pub regs: Vec<RegisterWrite>,
+  pub mem: Vec<Access>,
+  pub exception: Option<Exception>,
+}
+
+/// Writes trace records for the instructions it is interested in.
+pub struct Tracer {
+  out: Box<dyn Write>,
+  format: Format,
+  /// Only instructions within these ranges are traced. Everything is traced if
+  /// empty.
+  ranges: Vec<Range<u32>>,
+  started: bool,
+  /// Record of the cycle being executed, if it is traced.
+  current: Option<Record>,
+  /// First IO error encountered, tracing stops after it.
+  error: Option<io::Error>,
+}
+
+impl Tracer {
+  pub fn new(out: impl Write + 'static, format: Format) -> Tracer {
+    Tracer {
+      out: Box::new(out),
+      format,
+      ranges: Vec::new(),
+      started: false,
+      current: None,
+      error: None,
+    }
+  }
+
+  /// Trace instructions located within `range`. Can be called several times,
+  /// ranges add up.
+  pub fn with_range(mut self, range: Range<u32>) -> Tracer {
+    self.ranges.push(range);
+    self
+  }
+
+  /// Trace instructions located under the label `name`, up to the next label.
+  /// Returns `None` if the program has no such label.
+  pub fn with_label(self, program: &ProgramData, name: &str) -> Option<Tracer> {
+    let range = Symbols::from_program(program).range(name)?;
+    Some(self.with_range(range))
+  }
+
+  /// Whether the instruction at `pc` should be traced.
+  pub fn wants(&self, pc: u32) -> bool {
+    self.error.is_none() && (self.ranges.is_empty() || self.ranges.iter().any(|r| r.contains(&pc)))
+  }
+
+  /// Write out a record.
+  pub fn record(&mut self, record: &Record) {
+    if self.error.is_some() {
+      return;
+    }
+
+    let result = match self.format {
+      Format::JsonLines => serde_json::to_writer(&mut self.out, record)
+        .map_err(io::Error::from)
+        .and_then(|()| self.out.write_all(b"\n")),
+      Format::Binary => self.write_binary(record),
+    };
+
+    if let Err(e) = result {
+      self.error = Some(e);
+    }
+  }
+
+  /// Flush the output, and report the first error encountered while tracing.
+  pub fn finish(&mut self) -> io::Result<()> {
+    match self.error.take() {
+      Some(e) => Err(e),
+      None => self.out.flush(),
+    }
+  }
+
+  fn write_binary(&mut self, record: &Record) -> io::Result<()> {
+    if !self.started {
+      self.out.write_all(MAGIC)?;
+      self.started = true;
+    }
+
+    let flags = record.word.is_some() as u8 | (record.exception.is_some() as u8) << 1;
+
+    let mut buf = Vec::with_capacity(32);
+    buf.extend_from_slice(&record.cycle.to_le_bytes());
+    buf.extend_from_slice(&record.pc.to_le_bytes());
+    buf.extend_from_slice(&record.word.unwrap_or(0).to_le_bytes());
+    buf.push(flags);
+    buf.push(record.exception.map_or(0, |e| e as u8));
+
+    buf.push(record.regs.len() as u8);
+    for write in &record.regs {
+      buf.push(write.reg);
+      buf.extend_from_slice(&write.value.to_le_bytes());
+    }
+
+    buf.push(record.mem.len() as u8);
+    for access in &record.mem {
+      let store = match access.kind {
+        AccessKind::Load => 0,
+        AccessKind::Store => 0x80,
+      };
+
+      buf.push(store | access.size);
+      buf.extend_from_slice(&access.addr.to_le_bytes());
+      buf.extend_from_slice(&access.value.to_le_bytes());
+    }
+
+    self.out.write_all(&buf)
+  }
+}
+
+impl CpuObserver for Tracer {
+  fn on_cycle_start(&mut self, cycle: u64, pc: u32) {
+    self.current = self.wants(pc).then(|| Record {
+      cycle,
+      pc,
+      word: None,
+      asm: None,
+      regs: Vec::new(),
+      mem: Vec::new(),
+      exception: None,
+    });
+  }
+
+  fn on_fetch(&mut self, pc: u32, word: u32) {
+    if let Some(record) = &mut self.current {
+      record.word = Some(word);
+      record.asm = Some(disasm::disassemble(word, pc));
+    }
+  }
+
+  fn on_register_write(&mut self, _pc: u32, reg: u8, value: u32) {
+    if let Some(record) = &mut self.current {
+      record.regs.push(RegisterWrite { reg, value });
+    }
+  }
+
+  fn on_memory_read(&mut self, _pc: u32, access: &Access) {
+    if let Some(record) = &mut self.current {
+      record.mem.push(*access);
+    }
+  }
+
+  fn on_memory_write(&mut self, _pc: u32, access: &Access) {
+    if let Some(record) = &mut self.current {
+      record.mem.push(*access);
+    }
+  }
+
+  fn on_syscall(&mut self, _pc: u32, _code: u32) {
+    if let Some(record) = &mut self.current {
+      record.exception = Some(Exception::Syscall);
+    }
+  }
+
+  fn on_exception(&mut self, _pc: u32, exception: Exception) {
+    if let Some(record) = &mut self.current {
+      record.exception = Some(exception);
+    }
+  }
+
+  fn on_cycle_end(&mut self, _pc: u32) {
+    if let Some(record) = self.current.take() {
+      self.record(&record);
+    }
+  }
+}
+
+fn serialize_register<S: Serializer>(reg: &u8, serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_str(&register::name(*reg))
+}
@@ -0,0 +1,130 @@
+//! Detection of loads from memory which was never written, a tiny Valgrind.
+//!
+//! In strict mode (`MemoryMap::set_strict`), the memory keeps a shadow bitmap
+//! of the bytes holding a value: the program image, then every store. Loads
+//! touching other bytes still read zero, but are reported to observers through
+//! `CpuObserver::on_uninit_read`. `UninitChecker` collects these reports.
+
+use crate::disasm;
+use crate::mem::Access;
+use crate::observer::CpuObserver;
+use crate::symbols::Symbols;
+use mips_program::ProgramData;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Bytes tracked by each page of the shadow bitmap.
+const PAGE_SIZE: u32 = 0x1000;
+
+/// One bit per byte of memory, set once the byte holds a value. Addresses are
+/// tracked as issued, before translation.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Shadow {
+  pages: HashMap<u32, Box<[u64; PAGE_SIZE as usize / 64]>>,
+}
+
+impl Shadow {
+  /// Mark `len` bytes starting at `addr` as initialised.
+  pub fn mark(&mut self, addr: u32, len: u32) {
+    for addr in (0..len).map(|i| addr.wrapping_add(i)) {
+      let page = self
+        .pages
+        .entry(addr / PAGE_SIZE)
+        .or_insert_with(|| Box::new([0; PAGE_SIZE as usize / 64]));
+
+      let bit = addr % PAGE_SIZE;
+      page[bit as usize / 64] |= 1 << (bit % 64);
+    }
+  }
+
+  /// Whether every byte of the `len` bytes starting at `addr` is initialised.
+  pub fn is_initialised(&self, addr: u32, len: u32) -> bool {
+    (0..len).map(|i| addr.wrapping_add(i)).all(|addr| {
+      let bit = addr % PAGE_SIZE;
+
+      self
+        .pages
+        .get(&(addr / PAGE_SIZE))
+        .is_some_and(|page| page[bit as usize / 64] & (1 << (bit % 64)) != 0)
+    })
+  }
+}
+
+/// A load which read memory never written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitRead {
+  pub pc: u32,
+  /// The instruction issuing the load.
+  pub word: u32,
+  pub access: Access,
+}
+
+/// Collects the loads from uninitialised memory, to be registered as an
+/// observer of a CPU whose memory is in strict mode.
+pub struct UninitChecker {
+  symbols: Symbols,
+  /// Word fetched during the current cycle.
+  word: u32,
+  reads: Vec<UninitRead>,
+}
+
+impl UninitChecker {
+  pub fn new(program: &ProgramData) -> UninitChecker {
+    UninitChecker {
+      symbols: Symbols::from_program(program),
+      word: 0,
+      reads: Vec::new(),
+    }
+  }
+
+  /// Every load from uninitialised memory, in program order.
+  pub fn reads(&self) -> &[UninitRead] {
+    &self.reads
+  }
+
+  /// Write the loads from uninitialised memory, grouped by instruction.
+  pub fn write_report(&self, mut out: impl Write) -> io::Result<()> {
+    writeln!(
+      out,
+      "{} load(s) from uninitialised memory",
+      self.reads.len()
+    )?;
+
+    let mut sites: HashMap<u32, (&UninitRead, u64)> = HashMap::new();
+    for read in &self.reads {
+      sites.entry(read.pc).or_insert((read, 0)).1 += 1;
+    }
+
+    let mut sites = sites.into_iter().collect::<Vec<_>>();
+    sites.sort_by_key(|(pc, _)| *pc);
+
+    for (pc, (first, count)) in sites {
+      let addr = first.access.addr;
+
+      writeln!(
+        out,
+        "  {pc:#010x} <{}>  {:<24} {} byte(s) at {addr:#010x} <{}>, {count} time(s)",
+        self.symbols.location(pc),
+        disasm::disassemble(first.word, pc),
+        first.access.size,
+        self.symbols.location(addr),
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
+impl CpuObserver for UninitChecker {
+  fn on_fetch(&mut self, _pc: u32, word: u32) {
+    self.word = word;
+  }
+
+  fn on_uninit_read(&mut self, pc: u32, access: &Access) {
+    self.reads.push(UninitRead {
+      pc,
+      word: self.word,
+      access: *access,
+    });
+  }
+}
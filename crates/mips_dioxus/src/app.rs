@@ -0,0 +1,348 @@
+use crate::bitmap::BitmapDisplay;
+use crate::console::ConsolePanel;
+use crate::editor::Editor;
+use crate::memory::MemoryPanel;
+use crate::registers::RegisterPanel;
+use crate::session::{Session, Status};
+use crate::text::TextView;
+use dioxus::prelude::*;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Instructions run between two renders while the program runs at full
+/// speed.
+const SLICE: u64 = 10_000;
+
+/// Speeds of the slider in instructions per second, `None` standing for full
+/// speed.
+const SPEEDS: [Option<u32>; 10] = [
+  Some(1),
+  Some(2),
+  Some(5),
+  Some(10),
+  Some(25),
+  Some(50),
+  Some(100),
+  Some(1000),
+  Some(10_000),
+  None,
+];
+
+/// Shortest pause between two batches of instructions below full speed.
+const FRAME: Duration = Duration::from_millis(20);
+
+const SAMPLE: &str = "# Sum the numbers in `array`.
+        .data
+array:  .word 3, 1, 4, 1, 5, 9, 2, 6
+end:
+msg:    .asciiz \"sum = \"
+
+        .text
+main:   la $t0, array
+        la $t1, end
+        li $s0, 0
+loop:   bge $t0, $t1, done
+        lw $t2, ($t0)
+        add $s0, $s0, $t2
+        addi $t0, $t0, 4
+        b loop
+done:   la $a0, msg
+        li $v0, 4
+        syscall
+        move $a0, $s0
+        li $v0, 1
+        syscall
+        li $v0, 10
+        syscall
+";
+
+/// What the main pane shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+  /// The source editor.
+  Edit,
+  /// The assembled instructions.
+  Execute,
+  /// The bitmap display tool.
+  Bitmap,
+}
+
+pub fn App(cx: Scope) -> Element {
+  let tab = use_state(cx, || Tab::Edit);
+  let source = use_state(cx, || SAMPLE.to_owned());
+  let errors = use_state(cx, Vec::<mips_asm::Error>::new);
+  let session = use_ref(cx, || None::<Session>);
+  let task = use_ref(cx, || None::<TaskId>);
+  let speed = use_ref(cx, || SPEEDS.len() - 1);
+
+  let stop = move || {
+    if let Some(task) = task.write_silent().take() {
+      cx.remove_future(task);
+    }
+  };
+
+  let assemble = move |_| {
+    stop();
+
+    match mips_asm::assemble(source.get()) {
+      Ok(assembly) => {
+        errors.set(Vec::new());
+        session.set(Some(Session::new(source.get().clone(), assembly)));
+        tab.set(Tab::Execute);
+      }
+      Err(e) => {
+        errors.set(e);
+        session.set(None);
+        tab.set(Tab::Edit);
+      }
+    }
+  };
+
+  // runs in slices, yielding to the renderer in between
+  let spawn = move || {
+    stop();
+
+    let session = session.clone();
+    let speed = speed.clone();
+    let id = cx.push_future(async move {
+      loop {
+        let (cycles, delay) = match SPEEDS[*speed.read()] {
+          Some(ips) => {
+            let delay = FRAME.max(Duration::from_secs(1) / ips);
+            let cycles = (ips as u128 * delay.as_millis() / 1000).max(1);
+            (cycles as u64, Some(delay))
+          }
+          None => (SLICE, None),
+        };
+
+        let (running, sleep) = session.with_mut(|s| match s {
+          Some(s) if s.status == Status::Running => {
+            s.run(cycles);
+            (s.status == Status::Running, s.take_sleep())
+          }
+          _ => (false, Duration::ZERO),
+        });
+
+        match delay.map_or(sleep, |delay| delay.max(sleep)) {
+          _ if !running => break,
+          Duration::ZERO => yield_now().await,
+          delay => crate::time::sleep(delay).await,
+        }
+      }
+    });
+    task.set(Some(id));
+  };
+
+  let run = move |_| {
+    session.with_mut(|s| {
+      if let Some(s) = s {
+        s.start();
+      }
+    });
+    spawn();
+  };
+
+  let run_to = move |addr: u32| {
+    session.with_mut(|s| {
+      if let Some(s) = s.as_mut().filter(|s| s.status.is_runnable()) {
+        s.run_to(addr);
+      }
+    });
+    spawn();
+  };
+
+  let input = move |line: String| {
+    session.with_mut(|s| {
+      if let Some(s) = s {
+        s.input(line);
+      }
+    });
+
+    if session
+      .read()
+      .as_ref()
+      .is_some_and(|s| s.status == Status::Running)
+    {
+      spawn();
+    }
+  };
+
+  let pause = move |_| {
+    stop();
+    session.with_mut(|s| {
+      if let Some(s) = s {
+        s.pause();
+      }
+    });
+  };
+
+  let step = move |_| {
+    stop();
+    session.with_mut(|s| {
+      if let Some(s) = s {
+        s.step();
+      }
+    });
+  };
+
+  let reset = move |_| {
+    stop();
+    session.with_mut(|s| {
+      if let Some(s) = s {
+        s.reset();
+      }
+    });
+  };
+
+  let current = session.read();
+  let status = match current.as_ref().map(|s| &s.status) {
+    None if errors.is_empty() => "Not assembled".to_owned(),
+    None => format!("{} errors", errors.len()),
+    Some(Status::Ready) => "Ready".to_owned(),
+    Some(Status::Running) => "Running".to_owned(),
+    Some(Status::Paused) => "Paused".to_owned(),
+    Some(Status::AwaitingInput { .. }) => "Waiting for input".to_owned(),
+    Some(Status::Exited(code)) => format!("Exited with code {code}"),
+    Some(Status::Stopped(reason)) => format!("Stopped: {reason}"),
+  };
+
+  let runnable = current.as_ref().is_some_and(|s| s.status.is_runnable());
+  let running = current
+    .as_ref()
+    .is_some_and(|s| s.status == Status::Running);
+
+  let reason = current
+    .as_ref()
+    .filter(|s| s.status == Status::Paused)
+    .and_then(|s| s.reason.clone());
+  let (pc, cycles) = current
+    .as_ref()
+    .map_or((0, 0), |s| (s.cpu.registers().pc, s.cpu.cycles()));
+
+  let speed_index = *speed.read();
+  let speed_label = match SPEEDS[speed_index] {
+    Some(ips) => format!("{ips} instr/s"),
+    None => "Full speed".to_owned(),
+  };
+
+  cx.render(rsx!(
+    div {
+      class: "flex flex-col h-screen bg-gray-100",
+      div {
+        class: "flex items-center gap-2 p-2 border-b border-gray-300",
+        Button { onclick: assemble, "Assemble" }
+        Button { onclick: run, disabled: !runnable || running, "Run" }
+        Button { onclick: step, disabled: !runnable || running, "Step" }
+        Button { onclick: pause, disabled: !running, "Pause" }
+        Button { onclick: reset, disabled: current.is_none(), "Reset" }
+        input {
+          class: "ml-4 w-40",
+          r#type: "range",
+          min: "0",
+          max: "{SPEEDS.len() - 1}",
+          value: "{speed_index}",
+          oninput: move |e| {
+            if let Ok(index) = e.value.parse::<usize>() {
+              *speed.write() = index.min(SPEEDS.len() - 1);
+            }
+          },
+        }
+        span {
+          class: "w-24 text-sm text-gray-700",
+          "{speed_label}"
+        }
+      }
+      div {
+        class: "flex flex-grow gap-2 p-2 min-h-0",
+        div {
+          class: "flex flex-col flex-grow gap-2 min-w-0",
+          div {
+            class: "flex gap-1",
+            for (name, t) in [("Edit", Tab::Edit), ("Execute", Tab::Execute), ("Bitmap", Tab::Bitmap)] {
+              button {
+                key: "{name}",
+                class: if **tab == t { "px-3 text-sm rounded bg-white border border-gray-300" } else { "px-3 text-sm rounded hover:bg-gray-50" },
+                onclick: move |_| tab.set(t),
+                "{name}"
+              }
+            }
+          }
+          div {
+            class: "flex-grow min-h-0",
+            match **tab {
+              Tab::Edit => rsx!(Editor { source: source, errors: errors.get() }),
+              Tab::Execute => rsx!(TextView { session: session, onrunto: run_to }),
+              Tab::Bitmap => rsx!(BitmapDisplay { session: session }),
+            }
+          }
+          div {
+            class: "flex h-72 gap-2",
+            div {
+              class: "flex-grow min-w-0 bg-white border border-gray-300 rounded",
+              MemoryPanel { session: session }
+            }
+            div {
+              class: "w-80 bg-white border border-gray-300 rounded",
+              ConsolePanel { session: session, oninput: input }
+            }
+          }
+        }
+        div {
+          class: "w-96 bg-white border border-gray-300 rounded",
+          RegisterPanel { session: session }
+        }
+      }
+      div {
+        class: "flex gap-4 px-2 py-1 text-xs text-gray-700 border-t border-gray-300",
+        span { "{status}" }
+        if let Some(reason) = reason {
+          rsx!(span { class: "text-gray-500", "{reason}" })
+        }
+        span { class: "ml-auto font-mono", "pc {pc:#010x}" }
+        span { class: "font-mono", "{cycles} cycles" }
+      }
+    }
+  ))
+}
+
+#[derive(Props)]
+struct ButtonProps<'a> {
+  onclick: EventHandler<'a, MouseEvent>,
+  #[props(default)]
+  disabled: bool,
+  children: Element<'a>,
+}
+
+fn Button<'a>(cx: Scope<'a, ButtonProps<'a>>) -> Element<'a> {
+  cx.render(rsx!(
+    button {
+      class: "px-3 py-1 text-sm bg-white border border-gray-300 rounded hover:bg-gray-50 disabled:opacity-50",
+      disabled: cx.props.disabled,
+      onclick: move |e| cx.props.onclick.call(e),
+      &cx.props.children
+    }
+  ))
+}
+
+/// Give the renderer a turn between two slices of a running program.
+fn yield_now() -> impl Future<Output = ()> {
+  struct YieldNow(bool);
+
+  impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+      if self.0 {
+        return Poll::Ready(());
+      }
+
+      self.0 = true;
+      cx.waker().wake_by_ref();
+      Poll::Pending
+    }
+  }
+
+  YieldNow(false)
+}
@@ -0,0 +1,199 @@
+use crate::session::Session;
+use dioxus::prelude::*;
+use mips_cpu::mem::{MemoryMap, DATA_START, EXTERN_START, HEAP_START};
+use mips_program::Context;
+use std::time::Duration;
+
+/// Time between two refreshes of the display.
+const REFRESH: Duration = Duration::from_millis(100);
+
+/// Pixel sizes a unit can have.
+const UNITS: [u32; 6] = [1, 2, 4, 8, 16, 32];
+/// Sizes the display can have, in pixels.
+const SIZES: [u32; 5] = [64, 128, 256, 512, 1024];
+/// Framebuffer addresses to choose from, like MARS.
+const BASES: [(&str, u32); 4] = [
+  (".extern", EXTERN_START),
+  ("$gp", EXTERN_START + 0x8000),
+  (".data", DATA_START),
+  ("heap", HEAP_START),
+];
+
+/// Layout of the framebuffer: a word per unit, `0x00RRGGBB`, row by row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Config {
+  unit_width: u32,
+  unit_height: u32,
+  width: u32,
+  height: u32,
+  base: u32,
+}
+
+impl Config {
+  fn columns(&self) -> u32 {
+    (self.width / self.unit_width).max(1)
+  }
+
+  fn rows(&self) -> u32 {
+    (self.height / self.unit_height).max(1)
+  }
+}
+
+/// The framebuffer as a BMP image, as a data URL.
+///
+/// Words stored little endian are laid out like the BGRX pixels of a 32 bits
+/// BMP, so memory is copied as is.
+fn image(memory: &MemoryMap, config: &Config) -> String {
+  let (columns, rows) = (config.columns(), config.rows());
+  let len = columns * rows * 4;
+
+  let mut pixels = memory
+    .read_range(config.base, len, Context::External)
+    .unwrap_or_default()
+    .into_iter()
+    .map(Option::unwrap_or_default)
+    .collect::<Vec<_>>();
+  pixels.resize(len as usize, 0);
+
+  let mut bmp = Vec::with_capacity(54 + pixels.len());
+  // file header
+  bmp.extend(b"BM");
+  bmp.extend((54 + len).to_le_bytes());
+  bmp.extend([0; 4]);
+  bmp.extend(54u32.to_le_bytes());
+  // info header, a negative height stores rows top to bottom
+  bmp.extend(40u32.to_le_bytes());
+  bmp.extend((columns as i32).to_le_bytes());
+  bmp.extend((-(rows as i32)).to_le_bytes());
+  bmp.extend(1u16.to_le_bytes());
+  bmp.extend(32u16.to_le_bytes());
+  bmp.extend([0; 24]);
+  bmp.extend(pixels);
+
+  format!("data:image/bmp;base64,{}", base64(&bmp))
+}
+
+fn base64(bytes: &[u8]) -> String {
+  const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+  let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+  for chunk in bytes.chunks(3) {
+    let n = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+
+    for i in 0..4 {
+      if i <= chunk.len() {
+        text.push(DIGITS[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+      } else {
+        text.push('=');
+      }
+    }
+  }
+
+  text
+}
+
+#[derive(Props)]
+pub struct BitmapDisplayProps<'a> {
+  session: &'a UseRef<Option<Session>>,
+}
+
+/// Pixels drawn by the program in a framebuffer, like MARS's Bitmap Display.
+/// Memory is read again at a fixed rate rather than on every render.
+pub fn BitmapDisplay<'a>(cx: Scope<'a, BitmapDisplayProps<'a>>) -> Element<'a> {
+  let config = use_ref(cx, || Config {
+    unit_width: 8,
+    unit_height: 8,
+    width: 512,
+    height: 256,
+    base: DATA_START,
+  });
+  let src = use_state(cx, String::new);
+
+  use_future(cx, (), |_| {
+    let session = cx.props.session.clone();
+    let config = config.clone();
+    let src = src.clone();
+
+    async move {
+      loop {
+        let image = session
+          .read()
+          .as_ref()
+          .map(|s| image(s.cpu.memory(), &config.read()))
+          .unwrap_or_default();
+
+        if *src.current() != image {
+          src.set(image);
+        }
+
+        crate::time::sleep(REFRESH).await;
+      }
+    }
+  });
+
+  let current = *config.read();
+
+  let setting =
+    |label: &'static str, options: Vec<(String, u32)>, value: u32, set: fn(&mut Config, u32)| {
+      rsx!(
+        label {
+          class: "flex items-center gap-1",
+          "{label}"
+          select {
+            class: "px-1 border border-gray-300 rounded",
+            value: "{value}",
+            onchange: move |e| {
+              if let Ok(value) = e.value.parse() {
+                set(&mut config.write(), value);
+              }
+            },
+            for (name, option) in options {
+              option { key: "{option}", value: "{option}", "{name}" }
+            }
+          }
+        }
+      )
+    };
+
+  let units = UNITS
+    .iter()
+    .map(|&u| (u.to_string(), u))
+    .collect::<Vec<_>>();
+  let sizes = SIZES
+    .iter()
+    .map(|&s| (s.to_string(), s))
+    .collect::<Vec<_>>();
+  let bases = BASES
+    .iter()
+    .map(|&(name, addr)| (format!("{addr:#010x} ({name})"), addr))
+    .collect::<Vec<_>>();
+
+  cx.render(rsx!(
+    div {
+      class: "flex flex-col h-full bg-white border border-gray-300 rounded",
+      div {
+        class: "flex flex-wrap items-center gap-4 p-2 text-xs border-b border-gray-300",
+        setting("Unit width", units.clone(), current.unit_width, |c, v| c.unit_width = v),
+        setting("Unit height", units, current.unit_height, |c, v| c.unit_height = v),
+        setting("Display width", sizes.clone(), current.width, |c, v| c.width = v),
+        setting("Display height", sizes, current.height, |c, v| c.height = v),
+        setting("Base address", bases, current.base, |c, v| c.base = v)
+      }
+      div {
+        class: "flex-grow min-h-0 overflow-auto p-2",
+        if !src.is_empty() {
+          rsx!(img {
+            src: "{src}",
+            width: "{current.width}",
+            height: "{current.height}",
+            style: "image-rendering: pixelated",
+          })
+        }
+      }
+    }
+  ))
+}
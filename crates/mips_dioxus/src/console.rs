@@ -0,0 +1,56 @@
+use crate::session::{Session, Status};
+use dioxus::html::input_data::keyboard_types::Key;
+use dioxus::prelude::*;
+
+#[derive(Props)]
+pub struct ConsolePanelProps<'a> {
+  session: &'a UseRef<Option<Session>>,
+  /// Called with a line of input the program waits for.
+  oninput: EventHandler<'a, String>,
+}
+
+/// Output of the running program, and a line to type the input it waits for.
+pub fn ConsolePanel<'a>(cx: Scope<'a, ConsolePanelProps<'a>>) -> Element<'a> {
+  let line = use_state(cx, String::new);
+
+  let current = cx.props.session.read();
+  let output = current
+    .as_ref()
+    .map(|s| s.console.output().to_owned())
+    .unwrap_or_default();
+  let awaiting = current
+    .as_ref()
+    .is_some_and(|s| matches!(s.status, Status::AwaitingInput { .. }));
+
+  cx.render(rsx!(
+    div {
+      class: "flex flex-col h-full",
+      div {
+        class: "px-2 py-1 text-xs font-semibold text-gray-500 uppercase border-b border-gray-300",
+        "Console"
+      }
+      // reversed so the view sticks to the end of the output
+      div {
+        class: "flex flex-col-reverse flex-grow min-h-0 overflow-auto",
+        pre {
+          class: "p-2 font-mono text-xs whitespace-pre-wrap",
+          "{output}"
+        }
+      }
+      input {
+        class: if awaiting { "m-1 px-1 font-mono text-xs border border-blue-500 rounded" } else { "m-1 px-1 font-mono text-xs border border-gray-300 rounded" },
+        placeholder: if awaiting { "Waiting for input" } else { "" },
+        disabled: !awaiting,
+        autofocus: awaiting,
+        value: "{line}",
+        oninput: move |e| line.set(e.value.clone()),
+        onkeydown: move |e| {
+          if e.key() == Key::Enter {
+            cx.props.oninput.call(line.get().clone());
+            line.set(String::new());
+          }
+        },
+      }
+    }
+  ))
+}
@@ -0,0 +1,71 @@
+use crate::highlight::{highlight, Kind};
+use dioxus::prelude::*;
+use mips_asm::Error;
+
+/// Classes shared by the text area and the highlighted copy under it, so the
+/// two line up.
+const TEXT: &str = "font-mono text-sm leading-5 p-2 whitespace-pre";
+
+#[derive(Props)]
+pub struct EditorProps<'a> {
+  source: &'a UseState<String>,
+  errors: &'a [Error],
+}
+
+/// Source editor: a transparent text area over a highlighted copy of its
+/// contents, with line numbers and the assembler errors next to their line.
+pub fn Editor<'a>(cx: Scope<'a, EditorProps<'a>>) -> Element<'a> {
+  let source = cx.props.source;
+  let lines = source.get().split('\n').collect::<Vec<_>>();
+  let rows = lines.len();
+
+  let error_at = |line: usize| cx.props.errors.iter().find(|e| e.line == line);
+
+  cx.render(rsx!(
+    div {
+      class: "flex overflow-auto h-full bg-white border border-gray-300 rounded",
+      div {
+        class: "{TEXT} text-right text-gray-400 bg-gray-50 border-r border-gray-300 select-none",
+        for (i, _) in lines.iter().enumerate() {
+          div {
+            key: "{i}",
+            class: if error_at(i + 1).is_some() { "text-red-600 font-bold" } else { "" },
+            "{i + 1}"
+          }
+        }
+      }
+      div {
+        class: "relative flex-grow",
+        div {
+          class: "{TEXT} absolute inset-0 pointer-events-none",
+          for (i, line) in lines.iter().enumerate() {
+            div {
+              key: "{i}",
+              class: if error_at(i + 1).is_some() { "h-5 bg-red-100" } else { "h-5" },
+              for (kind, piece) in highlight(line) {
+                span {
+                  class: Kind::class(kind),
+                  "{piece}"
+                }
+              }
+              if let Some(error) = error_at(i + 1) {
+                rsx!(span {
+                  class: "ml-8 text-red-600 italic",
+                  "{error.message}"
+                })
+              }
+            }
+          }
+        }
+        textarea {
+          class: "{TEXT} relative block w-full bg-transparent text-transparent caret-black resize-none outline-none",
+          rows: "{rows}",
+          wrap: "off",
+          spellcheck: "false",
+          value: "{source}",
+          oninput: move |e| source.set(e.value.clone()),
+        }
+      }
+    }
+  ))
+}
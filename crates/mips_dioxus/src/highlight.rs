@@ -0,0 +1,104 @@
+//! Syntax highlighting of MIPS assembly, one line at a time.
+
+use std::ops::Range;
+
+/// What a piece of a line is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+  /// Whitespace, punctuation and label references.
+  Plain,
+  Comment,
+  Directive,
+  Instruction,
+  /// A label being defined.
+  Label,
+  Register,
+  Number,
+  Str,
+}
+
+impl Kind {
+  /// Tailwind classes of the kind.
+  pub fn class(self) -> &'static str {
+    match self {
+      Kind::Plain => "",
+      Kind::Comment => "text-gray-400 italic",
+      Kind::Directive => "text-purple-700",
+      Kind::Instruction => "text-blue-800 font-semibold",
+      Kind::Label => "text-red-700 font-semibold",
+      Kind::Register => "text-teal-700",
+      Kind::Number => "text-orange-600",
+      Kind::Str => "text-green-700",
+    }
+  }
+}
+
+/// Split `line` into highlighted pieces, which put together give back the
+/// line.
+pub fn highlight(line: &str) -> Vec<(Kind, &str)> {
+  let mut pieces: Vec<(Kind, Range<usize>)> = Vec::new();
+  let mut instruction = false;
+  let mut rest = line;
+
+  while let Some(c) = rest.chars().next() {
+    let (kind, len) = match c {
+      '#' => (Kind::Comment, rest.len()),
+      '"' | '\'' => (Kind::Str, quoted(rest, c)),
+      '$' => (Kind::Register, 1 + word(&rest[1..])),
+      '.' => (Kind::Directive, word(rest)),
+      '-' if rest[1..].starts_with(|c: char| c.is_ascii_digit()) => {
+        (Kind::Number, 1 + word(&rest[1..]))
+      }
+      c if c.is_ascii_digit() => (Kind::Number, word(rest)),
+      c if c.is_alphabetic() || c == '_' => {
+        let len = word(rest);
+
+        if rest[len..].trim_start().starts_with(':') {
+          (Kind::Label, len)
+        } else if !instruction {
+          instruction = true;
+          (Kind::Instruction, len)
+        } else {
+          (Kind::Plain, len)
+        }
+      }
+      c => (Kind::Plain, c.len_utf8()),
+    };
+
+    let start = line.len() - rest.len();
+    match pieces.last_mut() {
+      Some((Kind::Plain, range)) if kind == Kind::Plain => range.end = start + len,
+      _ => pieces.push((kind, start..start + len)),
+    }
+
+    rest = &rest[len..];
+  }
+
+  pieces
+    .into_iter()
+    .map(|(kind, range)| (kind, &line[range]))
+    .collect()
+}
+
+/// Length of the identifier or number starting `s`.
+fn word(s: &str) -> usize {
+  s.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+    .unwrap_or(s.len())
+}
+
+/// Length of the string or character literal starting `s`, up to the closing
+/// quote or the end of the line.
+fn quoted(s: &str, quote: char) -> usize {
+  let mut escaped = false;
+
+  for (i, c) in s.char_indices().skip(1) {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' => escaped = true,
+      c if c == quote => return i + 1,
+      _ => (),
+    }
+  }
+
+  s.len()
+}
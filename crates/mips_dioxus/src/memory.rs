@@ -0,0 +1,182 @@
+use crate::session::{Session, Status};
+use dioxus::prelude::*;
+use mips_cpu::mem::{AccessKind, DATA_START, EXTERN_START, HEAP_START, KDATA_START, TEXT_START};
+use mips_program::Context;
+
+/// Bytes shown on a row.
+const ROW: u32 = 16;
+/// Bytes shown on a page.
+const PAGE: u32 = ROW * 16;
+/// `$sp`.
+const SP: usize = 29;
+
+/// Places the viewer can jump to, `None` standing for the stack pointer.
+const JUMPS: [(&str, Option<u32>); 6] = [
+  (".text", Some(TEXT_START)),
+  (".data", Some(DATA_START)),
+  (".extern", Some(EXTERN_START)),
+  ("heap", Some(HEAP_START)),
+  ("stack", None),
+  (".kdata", Some(KDATA_START)),
+];
+
+fn parse_hex(text: &str) -> Option<u32> {
+  let text = text.trim();
+  let digits = text
+    .strip_prefix("0x")
+    .or_else(|| text.strip_prefix("0X"))
+    .unwrap_or(text);
+
+  u32::from_str_radix(digits, 16).ok()
+}
+
+/// A word of the page. Bytes past the end of `bytes` could not be read.
+fn word(bytes: &[Option<u8>], offset: usize) -> Option<(u32, bool)> {
+  let bytes = bytes.get(offset..offset + 4)?;
+  let value = u32::from_le_bytes(std::array::from_fn(|i| bytes[i].unwrap_or(0)));
+
+  Some((value, bytes.iter().any(Option::is_some)))
+}
+
+#[derive(Props)]
+pub struct MemoryPanelProps<'a> {
+  session: &'a UseRef<Option<Session>>,
+}
+
+/// A page of memory as words and ASCII, highlighting the bytes written by the
+/// last instruction. Words can be edited while the program is not running.
+pub fn MemoryPanel<'a>(cx: Scope<'a, MemoryPanelProps<'a>>) -> Element<'a> {
+  let start = use_state(cx, || DATA_START);
+  let invalid = use_state(cx, || None::<u32>);
+  let session = cx.props.session;
+
+  let current = session.read();
+  let Some(current) = current.as_ref() else {
+    return cx.render(rsx!(
+      div {
+        class: "p-2 text-sm text-gray-400",
+        "Assemble a program to see its memory."
+      }
+    ));
+  };
+
+  let editable = matches!(current.status, Status::Ready | Status::Paused);
+  let sp = current.cpu.registers().values()[SP];
+  let memory = current.cpu.memory();
+
+  let bytes = memory
+    .read_range(**start, PAGE, Context::External)
+    .unwrap_or_default();
+
+  let written = |addr: u32| {
+    memory
+      .accesses()
+      .iter()
+      .any(|a| a.kind == AccessKind::Store && (a.addr..a.addr + a.size as u32).contains(&addr))
+  };
+
+  let go = move |addr: u32| {
+    invalid.set(None);
+    start.set(addr & !(ROW - 1));
+  };
+
+  let edit = move |addr: u32, text: &str| {
+    let stored = parse_hex(text).is_some_and(|value| {
+      session.with_mut(|s| {
+        s.as_mut().is_some_and(|s| {
+          s.cpu
+            .memory_mut()
+            .write_range(addr, &value.to_le_bytes(), Context::External)
+            .is_ok()
+        })
+      })
+    });
+
+    invalid.set((!stored).then_some(addr));
+  };
+
+  let rows = (0..PAGE / ROW).map(|row| {
+    let addr = start.wrapping_add(row * ROW);
+    let offset = (row * ROW) as usize;
+
+    let ascii = (0..ROW as usize)
+      .map(|i| match bytes.get(offset + i) {
+        Some(Some(b @ 0x20..=0x7e)) => *b as char,
+        Some(_) => '.',
+        None => ' ',
+      })
+      .collect::<String>();
+
+    let words = (0..ROW)
+      .step_by(4)
+      .map(|i| (i, word(&bytes, offset + i as usize)))
+      .collect::<Vec<_>>();
+
+    (addr, words, ascii)
+  });
+
+  cx.render(rsx!(
+    div {
+      class: "flex flex-col h-full",
+      div {
+        class: "flex items-center gap-1 p-1 border-b border-gray-300",
+        for (name, target) in JUMPS {
+          button {
+            key: "{name}",
+            class: "px-2 text-xs rounded hover:bg-gray-100",
+            onclick: move |_| go(target.unwrap_or(sp)),
+            "{name}"
+          }
+        }
+        button {
+          class: "ml-4 px-2 text-xs rounded hover:bg-gray-100",
+          onclick: move |_| go(start.wrapping_sub(PAGE)),
+          "◀"
+        }
+        input {
+          class: "w-24 px-1 font-mono text-xs border border-gray-300 rounded",
+          value: "{start.get():#010x}",
+          onchange: move |e| {
+            if let Some(addr) = parse_hex(&e.value) {
+              go(addr);
+            }
+          },
+        }
+        button {
+          class: "px-2 text-xs rounded hover:bg-gray-100",
+          onclick: move |_| go(start.wrapping_add(PAGE)),
+          "▶"
+        }
+      }
+      div {
+        class: "overflow-auto",
+        table {
+          class: "font-mono text-xs",
+          for (addr, words, ascii) in rows {
+            tr {
+              key: "{addr}",
+              td { class: "px-2 text-gray-400", "{addr:#010x}" }
+              for (i, word) in words {
+                td {
+                  key: "{i}",
+                  class: if (0..4).any(|b| written(addr + i + b)) { "px-1 bg-yellow-200" } else { "px-1" },
+                  match word {
+                    Some((value, _)) if editable => rsx!(input {
+                      class: if **invalid == Some(addr + i) { "w-20 px-1 bg-red-100" } else { "w-20 px-1 bg-transparent hover:bg-white" },
+                      value: "{value:08x}",
+                      onchange: move |e| edit(addr + i, &e.value),
+                    }),
+                    Some((value, true)) => rsx!(span { class: "px-1", "{value:08x}" }),
+                    Some((value, false)) => rsx!(span { class: "px-1 text-gray-400", "{value:08x}" }),
+                    None => rsx!(span { class: "px-1 text-gray-300", "--------" }),
+                  }
+                }
+              }
+              td { class: "px-2 whitespace-pre text-gray-600", "{ascii}" }
+            }
+          }
+        }
+      }
+    }
+  ))
+}
@@ -0,0 +1,280 @@
+use crate::session::{Session, Status};
+use dioxus::prelude::*;
+use mips_cpu::cop0;
+use mips_cpu::register::{Registers, NAMES};
+
+/// How register values are shown and edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+  Hex,
+  Decimal,
+  Unsigned,
+  Ascii,
+}
+
+impl Format {
+  const ALL: [Format; 4] = [
+    Format::Hex,
+    Format::Decimal,
+    Format::Unsigned,
+    Format::Ascii,
+  ];
+
+  fn label(self) -> &'static str {
+    match self {
+      Format::Hex => "Hex",
+      Format::Decimal => "Dec",
+      Format::Unsigned => "Unsigned",
+      Format::Ascii => "ASCII",
+    }
+  }
+
+  fn show(self, value: u32) -> String {
+    match self {
+      Format::Hex => format!("{value:#010x}"),
+      Format::Decimal => (value as i32).to_string(),
+      Format::Unsigned => value.to_string(),
+      Format::Ascii => value
+        .to_be_bytes()
+        .iter()
+        .map(|&b| match b {
+          0x20..=0x7e => b as char,
+          _ => '.',
+        })
+        .collect(),
+    }
+  }
+
+  /// Parse an edited value. Hexadecimal values may omit the `0x` prefix and
+  /// ASCII values are right-aligned like numbers.
+  fn parse(self, text: &str) -> Option<u32> {
+    let text = text.trim();
+
+    match self {
+      Format::Hex => {
+        let digits = text
+          .strip_prefix("0x")
+          .or_else(|| text.strip_prefix("0X"))
+          .unwrap_or(text);
+        u32::from_str_radix(digits, 16).ok()
+      }
+      Format::Decimal => text
+        .parse::<i32>()
+        .map(|v| v as u32)
+        .or_else(|_| text.parse::<u32>())
+        .ok(),
+      Format::Unsigned => text.parse().ok(),
+      Format::Ascii if text.len() <= 4 && text.is_ascii() => {
+        Some(text.bytes().fold(0, |acc, b| acc << 8 | b as u32))
+      }
+      Format::Ascii => None,
+    }
+  }
+}
+
+/// A register shown by the panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+  Regular(usize),
+  Pc,
+  Hi,
+  Lo,
+  Cop0(u32),
+  Float(usize),
+  Fcsr,
+}
+
+/// Implemented coprocessor 0 registers.
+const COP0: [(&str, u32); 9] = [
+  ("Index", cop0::INDEX),
+  ("Random", cop0::RANDOM),
+  ("EntryLo", cop0::ENTRY_LO),
+  ("Context", cop0::CONTEXT),
+  ("BadVAddr", cop0::BAD_VADDR),
+  ("EntryHi", cop0::ENTRY_HI),
+  ("Status", cop0::STATUS),
+  ("Cause", cop0::CAUSE),
+  ("EPC", cop0::EPC),
+];
+
+impl Register {
+  fn name(self) -> String {
+    match self {
+      Register::Regular(n) => format!("${}", NAMES[n]),
+      Register::Pc => "pc".to_owned(),
+      Register::Hi => "hi".to_owned(),
+      Register::Lo => "lo".to_owned(),
+      Register::Cop0(n) => COP0
+        .iter()
+        .find(|(_, number)| *number == n)
+        .map_or_else(|| format!("${n}"), |(name, _)| (*name).to_owned()),
+      Register::Float(n) => format!("$f{n}"),
+      Register::Fcsr => "fcsr".to_owned(),
+    }
+  }
+
+  /// Register number, for the registers which have one.
+  fn number(self) -> Option<String> {
+    match self {
+      Register::Regular(n) | Register::Float(n) => Some(n.to_string()),
+      Register::Cop0(n) => Some(n.to_string()),
+      Register::Fcsr => Some("31".to_owned()),
+      Register::Pc | Register::Hi | Register::Lo => None,
+    }
+  }
+
+  fn read(self, registers: &Registers) -> u32 {
+    match self {
+      Register::Regular(n) => registers.values()[n],
+      Register::Pc => registers.pc,
+      Register::Hi => registers.hi,
+      Register::Lo => registers.lo,
+      Register::Cop0(n) => registers.cop0.read(n).unwrap_or_default(),
+      Register::Float(n) => registers.cop1.word(n),
+      Register::Fcsr => registers.cop1.fcsr,
+    }
+  }
+
+  /// Whether the register may be edited. `$zero` is hardwired, and some
+  /// coprocessor 0 registers are only written by the processor.
+  fn is_writable(self) -> bool {
+    match self {
+      Register::Regular(n) => n != 0,
+      Register::Cop0(n) => !matches!(n, cop0::RANDOM | cop0::BAD_VADDR),
+      _ => true,
+    }
+  }
+
+  fn write(self, registers: &mut Registers, value: u32) {
+    match self {
+      Register::Regular(n) => {
+        if let Ok(mut r) = registers.r(n) {
+          *r = value;
+        }
+      }
+      Register::Pc => registers.pc = value,
+      Register::Hi => registers.hi = value,
+      Register::Lo => registers.lo = value,
+      Register::Cop0(n) => {
+        registers.cop0.write(n, value);
+      }
+      Register::Float(n) => registers.cop1.set_word(n, value),
+      Register::Fcsr => registers.cop1.fcsr = value,
+    }
+  }
+}
+
+/// Groups of registers, in display order.
+fn groups() -> [(&'static str, Vec<Register>); 4] {
+  [
+    ("Registers", (0..32).map(Register::Regular).collect()),
+    ("Special", vec![Register::Pc, Register::Hi, Register::Lo]),
+    (
+      "Coprocessor 0",
+      COP0.iter().map(|&(_, n)| Register::Cop0(n)).collect(),
+    ),
+    (
+      "Coprocessor 1",
+      (0..32)
+        .map(Register::Float)
+        .chain([Register::Fcsr])
+        .collect(),
+    ),
+  ]
+}
+
+#[derive(Props)]
+pub struct RegisterPanelProps<'a> {
+  session: &'a UseRef<Option<Session>>,
+}
+
+/// Register values, highlighting the ones changed by the last step. Values
+/// can be edited while the program is not running.
+pub fn RegisterPanel<'a>(cx: Scope<'a, RegisterPanelProps<'a>>) -> Element<'a> {
+  let format = use_state(cx, || Format::Hex);
+  let invalid = use_state(cx, || None::<Register>);
+  let session = cx.props.session;
+
+  let current = session.read();
+  let Some(current) = current.as_ref() else {
+    return cx.render(rsx!(
+      div {
+        class: "p-2 text-sm text-gray-400",
+        "Assemble a program to see its registers."
+      }
+    ));
+  };
+
+  let editable = matches!(current.status, Status::Ready | Status::Paused);
+  let registers = current.cpu.registers();
+  let previous = &current.previous;
+
+  let edit = move |register: Register, text: &str| match format.parse(text) {
+    Some(value) => {
+      invalid.set(None);
+      session.with_mut(|s| {
+        if let Some(s) = s {
+          register.write(s.cpu.registers_mut(), value);
+          register.write(&mut s.previous, value);
+        }
+      });
+    }
+    None => invalid.set(Some(register)),
+  };
+
+  cx.render(rsx!(
+    div {
+      class: "flex flex-col h-full",
+      div {
+        class: "flex gap-1 p-1 border-b border-gray-300",
+        for f in Format::ALL {
+          button {
+            key: "{f.label()}",
+            class: if f == **format { "px-2 text-xs rounded bg-gray-300" } else { "px-2 text-xs rounded hover:bg-gray-100" },
+            onclick: move |_| format.set(f),
+            f.label()
+          }
+        }
+      }
+      div {
+        class: "overflow-auto",
+        for (title, group) in groups() {
+          div {
+            key: "{title}",
+            class: "px-2 pt-2 text-xs font-semibold text-gray-500 uppercase",
+            "{title}"
+          }
+          table {
+            class: "w-full font-mono text-xs",
+            for register in group {
+              tr {
+                key: "{register.name()}",
+                class: if register.read(registers) != register.read(previous) { "bg-yellow-200" } else { "" },
+                td { class: "px-2", "{register.name()}" }
+                td { class: "px-2 text-right text-gray-400", register.number() }
+                td {
+                  class: "px-2",
+                  if editable && register.is_writable() {
+                    rsx!(input {
+                      class: if *invalid.get() == Some(register) { "w-full px-1 bg-red-100" } else { "w-full px-1 bg-transparent hover:bg-white" },
+                      value: "{format.show(register.read(registers))}",
+                      onchange: move |e| edit(register, &e.value),
+                    })
+                  } else {
+                    rsx!(span { class: "px-1", "{format.show(register.read(registers))}" })
+                  }
+                }
+                td {
+                  class: "px-2 text-gray-500",
+                  if let Register::Float(n) = register {
+                    rsx!("{registers.cop1.single(n)}")
+                  }
+                }
+              }
+            }
+          }
+        }
+      }
+    }
+  ))
+}
@@ -0,0 +1,249 @@
+use mips_asm::Assembly;
+use mips_cpu::console::{Buffered, Console};
+use mips_cpu::exception::Exception;
+use mips_cpu::register::Registers;
+use mips_cpu::syscall::{self, Outcome};
+use mips_cpu::Cpu;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Where an assembled program stands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+  /// Assembled or reset, nothing ran yet.
+  Ready,
+  Running,
+  Paused,
+  /// A system call waits for a line of input. Running resumes once it is
+  /// entered if `resume` is set.
+  AwaitingInput {
+    resume: bool,
+  },
+  /// The program exited with this code.
+  Exited(i32),
+  /// Stopped by an exception or an unsupported system call.
+  Stopped(String),
+}
+
+impl Status {
+  /// Whether the program may run further.
+  pub fn is_runnable(&self) -> bool {
+    matches!(self, Status::Ready | Status::Running | Status::Paused)
+  }
+}
+
+/// An assembled program, and the CPU running it.
+pub struct Session {
+  /// The source the program was assembled from.
+  pub source: String,
+  pub assembly: Assembly,
+  pub cpu: Cpu,
+  pub status: Status,
+  /// Registers before the last step or slice, to tell which ones it wrote.
+  pub previous: Registers,
+  /// Addresses running stops at, kept across resets.
+  pub breakpoints: BTreeSet<u32>,
+  /// Output of the program, and the input entered so far.
+  pub console: Buffered,
+  /// Address running stops at once, for running to the cursor.
+  run_to: Option<u32>,
+  /// Why running last paused, if not stepping.
+  pub reason: Option<String>,
+  /// Milliseconds the program asked to sleep during the last slice.
+  sleep: u32,
+}
+
+impl Session {
+  pub fn new(source: String, assembly: Assembly) -> Session {
+    let cpu = cpu(&assembly);
+    let previous = cpu.registers().clone();
+
+    Session {
+      source,
+      assembly,
+      cpu,
+      status: Status::Ready,
+      previous,
+      breakpoints: BTreeSet::new(),
+      console: Buffered::default(),
+      run_to: None,
+      reason: None,
+      sleep: 0,
+    }
+  }
+
+  /// Source line `line`, counting from 1.
+  pub fn source_line(&self, line: usize) -> Option<&str> {
+    self.source.lines().nth(line.checked_sub(1)?)
+  }
+
+  pub fn toggle_breakpoint(&mut self, addr: u32) {
+    if !self.breakpoints.remove(&addr) {
+      self.breakpoints.insert(addr);
+    }
+  }
+
+  /// Start over from the assembled program.
+  pub fn reset(&mut self) {
+    self.cpu = cpu(&self.assembly);
+    self.status = Status::Ready;
+    self.previous = self.cpu.registers().clone();
+    self.console = Buffered::default();
+    self.run_to = None;
+    self.reason = None;
+    self.sleep = 0;
+  }
+
+  /// How long to wait before the next slice, for the sleeps of the last one.
+  pub fn take_sleep(&mut self) -> Duration {
+    Duration::from_millis(std::mem::take(&mut self.sleep) as u64)
+  }
+
+  /// Execute one instruction, servicing system calls. Leaves the session
+  /// paused unless the program ended. Sleeps are not waited for.
+  pub fn step(&mut self) {
+    self.previous = self.cpu.registers().clone();
+    self.reason = None;
+    self.advance();
+    self.sleep = 0;
+  }
+
+  /// Start running, stepping over the breakpoint the PC is on, if any.
+  pub fn start(&mut self) {
+    self.reason = None;
+
+    if self.breakpoints.contains(&self.cpu.registers().pc) {
+      self.step();
+    }
+
+    if self.status.is_runnable() {
+      self.status = Status::Running;
+    }
+  }
+
+  /// Start running until the PC reaches `addr`, or something else stops the
+  /// program first.
+  pub fn run_to(&mut self, addr: u32) {
+    self.start();
+    self.run_to = Some(addr);
+  }
+
+  pub fn pause(&mut self) {
+    if self.status == Status::Running {
+      self.status = Status::Paused;
+      self.run_to = None;
+      self.reason = Some("Paused".to_owned());
+    }
+  }
+
+  /// Run at most `cycles` instructions. The session stays running unless the
+  /// program ended or reached a breakpoint. A sleep ends the slice early.
+  pub fn run(&mut self, cycles: u64) {
+    self.previous = self.cpu.registers().clone();
+
+    for _ in 0..cycles {
+      let pc = self.cpu.registers().pc;
+
+      if self.run_to == Some(pc) {
+        self.pause_at(format!("Reached {pc:#010x}"));
+        return;
+      }
+
+      if self.breakpoints.contains(&pc) {
+        self.pause_at(format!("Breakpoint at {pc:#010x}"));
+        return;
+      }
+
+      self.advance();
+
+      if let Status::AwaitingInput { resume } = &mut self.status {
+        *resume = true;
+      }
+
+      if self.status != Status::Paused {
+        return;
+      }
+
+      if self.sleep > 0 {
+        break;
+      }
+    }
+
+    self.status = Status::Running;
+  }
+
+  /// Enter a line of input the program waits for, echoing it to the console.
+  pub fn input(&mut self, line: String) {
+    let Status::AwaitingInput { resume } = self.status else {
+      return;
+    };
+
+    self.console.write(&format!("{line}\n"));
+    self.console.push_input(line);
+    self.status = self.service();
+
+    if resume && self.status == Status::Paused {
+      self.status = Status::Running;
+    }
+  }
+
+  fn pause_at(&mut self, reason: String) {
+    self.status = Status::Paused;
+    self.run_to = None;
+    self.reason = Some(reason);
+  }
+
+  fn advance(&mut self) {
+    if !self.status.is_runnable() {
+      return;
+    }
+
+    let pc = self.cpu.registers().pc;
+
+    self.status = match self.cpu.cycle() {
+      Ok(()) => Status::Paused,
+      Err(Exception::Syscall) => self.service(),
+      Err(e) => {
+        let mut reason = format!("{e:?} exception (code {}) at {pc:#010x}", e as u8);
+
+        if let Some(addr) = self.cpu.memory().fault_addr() {
+          reason.push_str(&format!(", bad address {addr:#010x}"));
+        }
+
+        Status::Stopped(reason)
+      }
+    };
+  }
+
+  fn service(&mut self) -> Status {
+    let pc = self.cpu.registers().pc;
+
+    match syscall::service(&mut self.cpu, &mut self.console) {
+      Ok(Outcome::Continue) => Status::Paused,
+      Ok(Outcome::Exit(code)) => Status::Exited(code),
+      Ok(Outcome::AwaitInput) => Status::AwaitingInput { resume: false },
+      Ok(Outcome::Sleep(millis)) => {
+        self.sleep = self.sleep.saturating_add(millis);
+        Status::Paused
+      }
+      Err(e) => Status::Stopped(format!("{e} at {pc:#010x}")),
+    }
+  }
+}
+
+/// A CPU for `assembly`. File system calls reach the working directory on the
+/// desktop, and stay in memory in the browser. The browser has no system
+/// clock, so time is derived from cycles there.
+fn cpu(assembly: &Assembly) -> Cpu {
+  let mut cpu = Cpu::new(Rc::new(assembly.program.clone()));
+
+  #[cfg(not(target_arch = "wasm32"))]
+  cpu.set_file_system(mips_cpu::fs::Host::new("."));
+  #[cfg(target_arch = "wasm32")]
+  {
+    *cpu.environment_mut() = mips_cpu::environment::Environment::deterministic();
+  }
+
+  cpu
+}
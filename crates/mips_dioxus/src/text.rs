@@ -0,0 +1,140 @@
+use crate::session::Session;
+use dioxus::prelude::*;
+use mips_cpu::disasm;
+use mips_cpu::mem::section_range;
+use mips_program::{Context, Section};
+
+/// An instruction of `.text` or `.ktext`.
+struct Row {
+  addr: u32,
+  word: u32,
+  labels: String,
+  /// Source line, on the first instruction it was assembled into.
+  line: Option<usize>,
+}
+
+/// Instructions held in the text sections, with the labels pointing at them
+/// and the source line they were assembled from.
+fn rows(session: &Session) -> Vec<Row> {
+  let memory = session.cpu.memory();
+  let program = &session.assembly.program;
+
+  memory
+    .iter_mapped_regions()
+    .filter(|(section, _)| matches!(section, Section::Text | Section::KText))
+    .flat_map(|(section, range)| {
+      let start = *section_range(section).start();
+      let bytes = memory
+        .read_range(range.start, range.len() as u32, Context::External)
+        .unwrap_or_default();
+
+      bytes
+        .chunks_exact(4)
+        .zip((range.start..).step_by(4))
+        .map(|(word, addr)| {
+          let labels = program
+            .labels(section)
+            .iter()
+            .filter(|l| start + l.position as u32 == addr)
+            .map(|l| l.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+          Row {
+            addr,
+            word: u32::from_le_bytes(std::array::from_fn(|i| word[i].unwrap_or(0))),
+            labels,
+            line: session
+              .assembly
+              .line_at(addr)
+              .filter(|&line| session.assembly.addr_of_line(line) == Some(addr)),
+          }
+        })
+        .collect::<Vec<_>>()
+    })
+    .collect()
+}
+
+#[derive(Props)]
+pub struct TextViewProps<'a> {
+  session: &'a UseRef<Option<Session>>,
+  /// Called with the address of the instruction to run to.
+  onrunto: EventHandler<'a, u32>,
+}
+
+/// The text segment as addresses, machine code, disassembly and source, with
+/// the PC highlighted. Clicking the first column toggles a breakpoint, the
+/// arrow next to it runs up to the instruction.
+pub fn TextView<'a>(cx: Scope<'a, TextViewProps<'a>>) -> Element<'a> {
+  let session = cx.props.session;
+
+  let current = session.read();
+  let Some(current) = current.as_ref() else {
+    return cx.render(rsx!(
+      div {
+        class: "p-2 text-sm text-gray-400",
+        "Assemble a program to see its instructions."
+      }
+    ));
+  };
+
+  let pc = current.cpu.registers().pc;
+  let rows = rows(current);
+
+  let toggle = move |addr: u32| {
+    session.with_mut(|s| {
+      if let Some(s) = s {
+        s.toggle_breakpoint(addr);
+      }
+    })
+  };
+
+  cx.render(rsx!(
+    div {
+      class: "h-full overflow-auto bg-white border border-gray-300 rounded",
+      table {
+        class: "w-full font-mono text-xs",
+        tr {
+          class: "text-left text-gray-500",
+          th { class: "w-6" }
+          th { class: "w-6" }
+          th { class: "px-2", "Address" }
+          th { class: "px-2", "Code" }
+          th { class: "px-2", "Label" }
+          th { class: "px-2", "Instruction" }
+          th { class: "px-2", "Source" }
+        }
+        for row in rows {
+          tr {
+            key: "{row.addr}",
+            class: if row.addr == pc { "bg-yellow-200" } else { "hover:bg-gray-50" },
+            td {
+              class: "w-6 text-center text-red-600 cursor-pointer select-none",
+              onclick: move |_| toggle(row.addr),
+              if current.breakpoints.contains(&row.addr) { "●" } else { "" }
+            }
+            td {
+              class: "w-6 text-center text-gray-300 hover:text-blue-600 cursor-pointer select-none",
+              title: "Run to here",
+              onclick: move |_| cx.props.onrunto.call(row.addr),
+              "▸"
+            }
+            td { class: "px-2 text-gray-400", "{row.addr:#010x}" }
+            td { class: "px-2 text-gray-500", "{row.word:08x}" }
+            td { class: "px-2 text-red-700", "{row.labels}" }
+            td { class: "px-2 whitespace-pre", disasm::disassemble(row.word, row.addr) }
+            td {
+              class: "px-2 whitespace-pre text-gray-600",
+              if let Some(line) = row.line {
+                rsx!(
+                  span { class: "text-gray-400", "{line}: " }
+                  current.source_line(line).unwrap_or_default().trim()
+                )
+              }
+            }
+          }
+        }
+      }
+    }
+  ))
+}
@@ -0,0 +1,22 @@
+//! Timers for the platform the app runs on: tokio's on the desktop, the
+//! browser's `setTimeout` on the web.
+
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sleep(duration: Duration) {
+  tokio::time::sleep(duration).await
+}
+
+#[cfg(target_arch = "wasm32")]
+pub async fn sleep(duration: Duration) {
+  let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+  let promise = js_sys::Promise::new(&mut |resolve, _| {
+    if let Some(window) = web_sys::window() {
+      // the promise never resolving only stops the loop awaiting it
+      let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, millis);
+    }
+  });
+
+  let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
@@ -0,0 +1,435 @@
+//! GDB remote serial protocol stub.
+//!
+//! Lets `gdb-multiarch` (or any IDE speaking RSP) drive a simulated `Cpu`:
+//!
+//! ```text
+//! (gdb) set architecture mips
+//! (gdb) set endian little
+//! (gdb) target remote localhost:1234
+//! ```
+
+use mips_cpu::console::{Console, Stdio};
+use mips_cpu::exception::Exception;
+use mips_cpu::syscall::{self, Outcome};
+use mips_cpu::Cpu;
+use mips_program::Context;
+use packet::{Connection, Incoming, Transport};
+use std::collections::BTreeSet;
+use std::io;
+
+/// How many cycles to run between two checks for an interrupt request while
+/// continuing.
+const INTERRUPT_POLL_INTERVAL: usize = 4096;
+
+/// Largest packet GDB may send us, and the largest reply we send back.
+const PACKET_SIZE: usize = 0x4000;
+
+/// Why the target stopped, reported to GDB as a signal.
+#[derive(Debug, Clone, Copy)]
+enum Stop {
+  /// Hit a software breakpoint, or finished a single step.
+  Trap,
+  /// Interrupted by GDB.
+  Interrupt,
+  /// The CPU raised an exception, or a system call failed.
+  Exception(Exception),
+  /// The program exited with this code.
+  Exited(i32),
+}
+
+impl Stop {
+  /// GDB signal number. An exited program has none, which is signal 0.
+  fn signal(self) -> u8 {
+    const SIGINT: u8 = 2;
+    const SIGILL: u8 = 4;
+    const SIGTRAP: u8 = 5;
+    const SIGFPE: u8 = 8;
+    const SIGSEGV: u8 = 11;
+    const SIGSYS: u8 = 12;
+
+    match self {
+      Stop::Trap => SIGTRAP,
+      Stop::Interrupt => SIGINT,
+      Stop::Exited(_) => 0,
+      Stop::Exception(e) => match e {
+        Exception::AddrLoadFetch | Exception::AddrStore => SIGSEGV,
+        Exception::TlbMod | Exception::TlbLoad | Exception::TlbStore => SIGSEGV,
+        Exception::Syscall => SIGSYS,
+        Exception::ReservedInstruction => SIGILL,
+        Exception::Overflow | Exception::FloatingPoint => SIGFPE,
+        Exception::Trap | Exception::Breakpoint => SIGTRAP,
+      },
+    }
+  }
+}
+
+/// What to do after handling a packet.
+enum Flow {
+  Reply(Vec<u8>),
+  /// Reply then close the connection.
+  Close(Vec<u8>),
+  /// Close the connection without replying.
+  Kill,
+}
+
+/// A GDB server controlling one `Cpu`. System calls are serviced as the
+/// program runs, with the standard streams as console unless another one is
+/// given.
+pub struct GdbStub {
+  cpu: Cpu,
+  console: Box<dyn Console>,
+  breakpoints: BTreeSet<u32>,
+  last_stop: Stop,
+}
+
+impl GdbStub {
+  pub fn new(cpu: Cpu) -> Self {
+    Self {
+      cpu,
+      console: Box::new(Stdio),
+      breakpoints: BTreeSet::new(),
+      last_stop: Stop::Trap,
+    }
+  }
+
+  /// Use `console` for the input and output of system calls.
+  pub fn with_console(mut self, console: impl Console + 'static) -> Self {
+    self.console = Box::new(console);
+    self
+  }
+
+  pub fn cpu(&self) -> &Cpu {
+    &self.cpu
+  }
+
+  /// Serve a single GDB session over `stream`, until GDB detaches, kills the
+  /// target or hangs up.
+  pub fn serve<T: Transport>(&mut self, stream: T) -> io::Result<()> {
+    let mut conn = Connection::new(stream);
+
+    while let Some(incoming) = conn.receive()? {
+      let packet = match incoming {
+        Incoming::Packet(packet) => packet,
+        // the target is not running, nothing to interrupt
+        Incoming::Interrupt => continue,
+      };
+
+      match self.handle(&packet, &mut conn)? {
+        Flow::Reply(reply) => conn.send(&reply)?,
+        Flow::Close(reply) => {
+          conn.send(&reply)?;
+          break;
+        }
+        Flow::Kill => break,
+      }
+
+      if packet == b"QStartNoAckMode" {
+        conn.disable_acks();
+      }
+    }
+
+    Ok(())
+  }
+
+  fn handle<T: Transport>(&mut self, packet: &[u8], conn: &mut Connection<T>) -> io::Result<Flow> {
+    let Some((&kind, args)) = packet.split_first() else {
+      return Ok(Flow::Reply(Vec::new()));
+    };
+
+    let reply = match kind {
+      b'?' => self.stop_reply(),
+      b'g' => self.read_registers(),
+      b'G' => self.write_registers(args),
+      b'p' => self.read_register(args),
+      b'P' => self.write_register(args),
+      b'm' => self.read_memory(args),
+      b'M' => self.write_memory_hex(args),
+      b'X' => self.write_memory_binary(args),
+      b'Z' => self.breakpoint(args, true),
+      b'z' => self.breakpoint(args, false),
+      b's' => {
+        self.resume_at(args);
+        self.last_stop = self.step();
+        self.stop_reply()
+      }
+      b'c' => {
+        self.resume_at(args);
+        self.last_stop = self.run(conn)?;
+        self.stop_reply()
+      }
+      b'H' | b'T' => b"OK".to_vec(),
+      b'q' => self.query(args),
+      b'Q' if args == b"StartNoAckMode" => b"OK".to_vec(),
+      b'D' => return Ok(Flow::Close(b"OK".to_vec())),
+      b'k' => return Ok(Flow::Kill),
+      // unsupported packets get an empty reply
+      _ => Vec::new(),
+    };
+
+    Ok(Flow::Reply(reply))
+  }
+
+  fn stop_reply(&self) -> Vec<u8> {
+    let reply = match self.last_stop {
+      // GDB only knows the low byte of exit codes
+      Stop::Exited(code) => format!("W{:02x}", code as u8),
+      stop => format!("S{:02x}", stop.signal()),
+    };
+
+    reply.into_bytes()
+  }
+
+  fn query(&self, args: &[u8]) -> Vec<u8> {
+    let args = String::from_utf8_lossy(args);
+
+    if args.starts_with("Supported") {
+      format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+").into_bytes()
+    } else if let Some(annex) = args.strip_prefix("Xfer:features:read:") {
+      xfer_target_xml(annex)
+    } else if args == "Attached" {
+      b"1".to_vec()
+    } else if args == "C" {
+      b"QC1".to_vec()
+    } else if args == "fThreadInfo" {
+      b"m1".to_vec()
+    } else if args == "sThreadInfo" {
+      b"l".to_vec()
+    } else {
+      Vec::new()
+    }
+  }
+
+  fn read_registers(&self) -> Vec<u8> {
+    (0..target::REGISTER_COUNT)
+      .flat_map(|n| encode_register(target::read_register(&self.cpu, n)))
+      .collect()
+  }
+
+  fn write_registers(&mut self, args: &[u8]) -> Vec<u8> {
+    for (n, chunk) in args.chunks(8).enumerate() {
+      // unavailable registers are sent back as `x`s, skip them
+      if let Some(value) = decode_register(chunk) {
+        target::write_register(&mut self.cpu, n, value);
+      }
+    }
+
+    b"OK".to_vec()
+  }
+
+  fn read_register(&self, args: &[u8]) -> Vec<u8> {
+    match parse_hex(args) {
+      Some(n) => encode_register(target::read_register(&self.cpu, n as usize)),
+      None => b"E01".to_vec(),
+    }
+  }
+
+  fn write_register(&mut self, args: &[u8]) -> Vec<u8> {
+    let written = split_once(args, b'=')
+      .and_then(|(n, value)| Some((parse_hex(n)?, decode_register(value)?)))
+      .map(|(n, value)| target::write_register(&mut self.cpu, n as usize, value));
+
+    match written {
+      Some(true) => b"OK".to_vec(),
+      _ => b"E01".to_vec(),
+    }
+  }
+
+  fn read_memory(&self, args: &[u8]) -> Vec<u8> {
+    let Some((addr, len)) = parse_addr_len(args) else {
+      return b"E01".to_vec();
+    };
+
+    // two hex digits per byte, GDB asks again for the rest of a short read
+    let len = len.min(PACKET_SIZE as u32 / 2);
+    let bytes = self.cpu.memory().read_range(addr, len, Context::External);
+
+    match bytes {
+      Ok(bytes) if !bytes.is_empty() || len == 0 => bytes
+        .into_iter()
+        .flat_map(|byte| format!("{:02x}", byte.unwrap_or(0)).into_bytes())
+        .collect(),
+      _ => b"E01".to_vec(),
+    }
+  }
+
+  fn write_memory_hex(&mut self, args: &[u8]) -> Vec<u8> {
+    let bytes = split_once(args, b':').and_then(|(header, data)| {
+      let (addr, len) = parse_addr_len(header)?;
+      let bytes = decode_hex_bytes(data)?;
+      (bytes.len() == len as usize).then_some((addr, bytes))
+    });
+
+    match bytes {
+      Some((addr, bytes)) => self.write_memory(addr, &bytes),
+      None => b"E01".to_vec(),
+    }
+  }
+
+  fn write_memory_binary(&mut self, args: &[u8]) -> Vec<u8> {
+    let bytes = split_once(args, b':').and_then(|(header, data)| {
+      let (addr, len) = parse_addr_len(header)?;
+      (data.len() == len as usize).then_some((addr, data))
+    });
+
+    match bytes {
+      Some((addr, bytes)) => self.write_memory(addr, bytes),
+      None => b"E01".to_vec(),
+    }
+  }
+
+  fn write_memory(&mut self, addr: u32, bytes: &[u8]) -> Vec<u8> {
+    let memory = self.cpu.memory_mut();
+
+    match memory.write_range(addr, bytes, Context::External) {
+      Ok(()) => b"OK".to_vec(),
+      Err(_) => b"E01".to_vec(),
+    }
+  }
+
+  /// Handle `Z`/`z` packets. Software and hardware breakpoints are treated the
+  /// same way, watchpoints are not supported.
+  fn breakpoint(&mut self, args: &[u8], insert: bool) -> Vec<u8> {
+    let mut fields = args.split(|b| *b == b',');
+
+    let kind = fields.next();
+    let addr = fields.next().and_then(parse_hex);
+
+    match (kind, addr) {
+      (Some(b"0" | b"1"), Some(addr)) => {
+        if insert {
+          self.breakpoints.insert(addr);
+        } else {
+          self.breakpoints.remove(&addr);
+        }
+
+        b"OK".to_vec()
+      }
+      (Some(b"0" | b"1"), None) => b"E01".to_vec(),
+      _ => Vec::new(),
+    }
+  }
+
+  /// `s` and `c` can carry an address to resume at.
+  fn resume_at(&mut self, args: &[u8]) {
+    if let Some(addr) = parse_hex(args) {
+      self.cpu.registers_mut().pc = addr;
+    }
+  }
+
+  /// Run one cycle, servicing system calls. Returns why the target stops, if
+  /// it does.
+  fn cycle(&mut self) -> Option<Stop> {
+    match self.cpu.cycle() {
+      Ok(()) => None,
+      Err(Exception::Syscall) => match syscall::service(&mut self.cpu, &mut *self.console) {
+        // the debugger is in charge of time, sleeps are not waited for
+        Ok(Outcome::Continue | Outcome::Sleep(_)) => None,
+        Ok(Outcome::Exit(code)) => Some(Stop::Exited(code)),
+        // the PC stays on the `syscall`, resuming services it again
+        Ok(Outcome::AwaitInput) => Some(Stop::Interrupt),
+        Err(_) => Some(Stop::Exception(Exception::Syscall)),
+      },
+      Err(e) => Some(Stop::Exception(e)),
+    }
+  }
+
+  fn step(&mut self) -> Stop {
+    if let Stop::Exited(_) = self.last_stop {
+      return self.last_stop;
+    }
+
+    self.cycle().unwrap_or(Stop::Trap)
+  }
+
+  /// Run until a breakpoint is hit, an exception is raised, the program exits
+  /// or GDB interrupts. A breakpoint on the instruction the CPU resumes at is
+  /// stepped over.
+  fn run<T: Transport>(&mut self, conn: &mut Connection<T>) -> io::Result<Stop> {
+    if let Stop::Exited(_) = self.last_stop {
+      return Ok(self.last_stop);
+    }
+
+    let mut cycles = 0;
+
+    loop {
+      if let Some(stop) = self.cycle() {
+        return Ok(stop);
+      }
+
+      if self.breakpoints.contains(&self.cpu.registers().pc) {
+        return Ok(Stop::Trap);
+      }
+
+      cycles += 1;
+      if cycles % INTERRUPT_POLL_INTERVAL == 0 && conn.poll_interrupt()? {
+        return Ok(Stop::Interrupt);
+      }
+    }
+  }
+}
+
+/// Serve `qXfer:features:read:<annex>:<offset>,<length>`.
+fn xfer_target_xml(annex: &str) -> Vec<u8> {
+  let Some(("target.xml", range)) = annex.split_once(':') else {
+    return b"E00".to_vec();
+  };
+  let Some((offset, len)) = parse_addr_len(range.as_bytes()) else {
+    return b"E01".to_vec();
+  };
+
+  let xml = target::target_xml().into_bytes();
+  let start = (offset as usize).min(xml.len());
+  let end = (start + len as usize).min(xml.len());
+
+  let mut reply = vec![if end == xml.len() { b'l' } else { b'm' }];
+  reply.extend_from_slice(&xml[start..end]);
+  reply
+}
+
+/// Registers are sent in target byte order, which is little endian.
+fn encode_register(value: Option<u32>) -> Vec<u8> {
+  match value {
+    Some(value) => value
+      .to_le_bytes()
+      .iter()
+      .flat_map(|b| format!("{b:02x}").into_bytes())
+      .collect(),
+    None => b"xxxxxxxx".to_vec(),
+  }
+}
+
+fn decode_register(hex: &[u8]) -> Option<u32> {
+  let bytes: [u8; 4] = decode_hex_bytes(hex)?.try_into().ok()?;
+  Some(u32::from_le_bytes(bytes))
+}
+
+fn decode_hex_bytes(hex: &[u8]) -> Option<Vec<u8>> {
+  if hex.len() % 2 != 0 {
+    return None;
+  }
+
+  hex
+    .chunks(2)
+    .map(parse_hex)
+    .map(|b| b.map(|b| b as u8))
+    .collect()
+}
+
+fn parse_hex(hex: &[u8]) -> Option<u32> {
+  u32::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+}
+
+fn parse_addr_len(args: &[u8]) -> Option<(u32, u32)> {
+  let (addr, len) = split_once(args, b',')?;
+  Some((parse_hex(addr)?, parse_hex(len)?))
+}
+
+fn split_once(bytes: &[u8], delimiter: u8) -> Option<(&[u8], &[u8])> {
+  let i = bytes.iter().position(|b| *b == delimiter)?;
+  Some((&bytes[..i], &bytes[i + 1..]))
+}
+
+/// Packet framing.
+pub mod packet;
+/// Register layout and target description.
+mod target;
@@ -0,0 +1,79 @@
+use mips_asm::assemble;
+use mips_cpu::Cpu;
+use mips_gdb::GdbStub;
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+const USAGE: &str = "usage: mips_gdb <program.s> [--tcp <addr> | --unix <path>]";
+
+/// Where to listen for GDB.
+enum Listen {
+  Tcp(String),
+  #[cfg(unix)]
+  Unix(String),
+}
+
+fn main() -> ExitCode {
+  let mut args = std::env::args().skip(1);
+
+  let Some(source_path) = args.next() else {
+    eprintln!("{USAGE}");
+    return ExitCode::FAILURE;
+  };
+
+  let listen = match (args.next().as_deref(), args.next()) {
+    (None, _) => Listen::Tcp("127.0.0.1:1234".to_owned()),
+    (Some("--tcp"), Some(addr)) => Listen::Tcp(addr),
+    #[cfg(unix)]
+    (Some("--unix"), Some(path)) => Listen::Unix(path),
+    _ => {
+      eprintln!("{USAGE}");
+      return ExitCode::FAILURE;
+    }
+  };
+
+  let source = match std::fs::read_to_string(&source_path) {
+    Ok(source) => source,
+    Err(e) => {
+      eprintln!("cannot read {source_path}: {e}");
+      return ExitCode::FAILURE;
+    }
+  };
+
+  let program = match assemble(&source) {
+    Ok(assembly) => assembly.program,
+    Err(errors) => {
+      for error in errors {
+        eprintln!("{source_path}: {error}");
+      }
+      return ExitCode::FAILURE;
+    }
+  };
+
+  let mut stub = GdbStub::new(Cpu::new(Rc::new(program)));
+
+  let result = match listen {
+    Listen::Tcp(addr) => TcpListener::bind(&addr).and_then(|listener| {
+      eprintln!("waiting for GDB on {addr}");
+      let (stream, _) = listener.accept()?;
+      stub.serve(stream)
+    }),
+    #[cfg(unix)]
+    Listen::Unix(path) => UnixListener::bind(&path).and_then(|listener| {
+      eprintln!("waiting for GDB on {path}");
+      let (stream, _) = listener.accept()?;
+      stub.serve(stream)
+    }),
+  };
+
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("{e}");
+      ExitCode::FAILURE
+    }
+  }
+}
@@ -0,0 +1,200 @@
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+/// Byte sent by GDB to interrupt a running target (Ctrl-C).
+const INTERRUPT: u8 = 0x03;
+
+/// A byte stream GDB is connected through.
+pub trait Transport: Read + Write {
+  fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Transport for TcpStream {
+  fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+    TcpStream::set_nonblocking(self, nonblocking)
+  }
+}
+
+#[cfg(unix)]
+impl Transport for UnixStream {
+  fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+    UnixStream::set_nonblocking(self, nonblocking)
+  }
+}
+
+/// Something received from GDB.
+pub enum Incoming {
+  /// A packet, checksum verified and with escapes removed.
+  Packet(Vec<u8>),
+  /// Out-of-band interrupt request.
+  Interrupt,
+}
+
+/// Packet-level view of a `Transport`. Takes care of framing, checksums and
+/// acknowledgments.
+pub struct Connection<T> {
+  stream: T,
+  no_ack: bool,
+  /// Bytes read while polling for an interrupt, to be received next.
+  pending: VecDeque<u8>,
+}
+
+impl<T: Transport> Connection<T> {
+  pub fn new(stream: T) -> Self {
+    Self {
+      stream,
+      no_ack: false,
+      pending: VecDeque::new(),
+    }
+  }
+
+  /// Stop sending and expecting acknowledgments, as negotiated by
+  /// `QStartNoAckMode`.
+  pub fn disable_acks(&mut self) {
+    self.no_ack = true;
+  }
+
+  /// Block until a packet or an interrupt is received. Returns `None` once GDB
+  /// hangs up.
+  pub fn receive(&mut self) -> io::Result<Option<Incoming>> {
+    loop {
+      let Some(byte) = self.read_byte()? else {
+        return Ok(None);
+      };
+
+      match byte {
+        b'$' => {
+          if let Some(packet) = self.read_packet_body()? {
+            return Ok(Some(Incoming::Packet(packet)));
+          }
+        }
+        INTERRUPT => return Ok(Some(Incoming::Interrupt)),
+        // stray acknowledgments
+        _ => (),
+      }
+    }
+  }
+
+  /// Send a packet, retransmitting it until GDB acknowledges it.
+  pub fn send(&mut self, data: &[u8]) -> io::Result<()> {
+    let checksum = data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+
+    let mut frame = Vec::with_capacity(data.len() + 4);
+    frame.push(b'$');
+    frame.extend_from_slice(data);
+    frame.extend_from_slice(format!("#{checksum:02x}").as_bytes());
+
+    loop {
+      self.stream.write_all(&frame)?;
+      self.stream.flush()?;
+
+      if self.no_ack {
+        return Ok(());
+      }
+
+      match self.read_byte()? {
+        Some(b'+') | None => return Ok(()),
+        Some(b'-') => continue,
+        // anything else means GDB moved on, consider the packet delivered
+        Some(_) => return Ok(()),
+      }
+    }
+  }
+
+  /// Check whether GDB requested an interrupt, without blocking. Anything
+  /// else GDB sent in the meantime is kept for `receive`.
+  pub fn poll_interrupt(&mut self) -> io::Result<bool> {
+    self.stream.set_nonblocking(true)?;
+
+    let mut buf = [0; 64];
+    let result = loop {
+      match self.stream.read(&mut buf) {
+        Ok(0) => break Ok(false),
+        Ok(n) => match buf[..n].iter().position(|b| *b == INTERRUPT) {
+          Some(i) => {
+            self.pending.extend(&buf[..i]);
+            self.pending.extend(&buf[i + 1..n]);
+            break Ok(true);
+          }
+          None => self.pending.extend(&buf[..n]),
+        },
+        Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(false),
+        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+        Err(e) => break Err(e),
+      }
+    };
+
+    self.stream.set_nonblocking(false)?;
+    result
+  }
+
+  /// Read the rest of a packet after `$`. Returns `None` if the checksum
+  /// did not match, after asking for a retransmission.
+  fn read_packet_body(&mut self) -> io::Result<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+    let mut checksum = 0u8;
+    let mut escaped = false;
+
+    loop {
+      let byte = self.expect_byte()?;
+
+      if byte == b'#' {
+        break;
+      }
+
+      checksum = checksum.wrapping_add(byte);
+
+      if escaped {
+        data.push(byte ^ 0x20);
+        escaped = false;
+      } else if byte == b'}' {
+        escaped = true;
+      } else {
+        data.push(byte);
+      }
+    }
+
+    let expected = [self.expect_byte()?, self.expect_byte()?];
+    let expected = std::str::from_utf8(&expected)
+      .ok()
+      .and_then(|s| u8::from_str_radix(s, 16).ok());
+
+    if self.no_ack {
+      return Ok(Some(data));
+    }
+
+    if expected == Some(checksum) {
+      self.stream.write_all(b"+")?;
+      Ok(Some(data))
+    } else {
+      self.stream.write_all(b"-")?;
+      Ok(None)
+    }
+  }
+
+  fn read_byte(&mut self) -> io::Result<Option<u8>> {
+    if let Some(byte) = self.pending.pop_front() {
+      return Ok(Some(byte));
+    }
+
+    let mut buf = [0];
+
+    loop {
+      match self.stream.read(&mut buf) {
+        Ok(0) => return Ok(None),
+        Ok(_) => return Ok(Some(buf[0])),
+        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+        Err(e) => return Err(e),
+      }
+    }
+  }
+
+  fn expect_byte(&mut self) -> io::Result<u8> {
+    self
+      .read_byte()?
+      .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))
+  }
+}
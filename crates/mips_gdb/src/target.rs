@@ -0,0 +1,130 @@
+use mips_cpu::{cop0, cop1, Cpu};
+use std::fmt::Write;
+
+/// Number of registers in GDB's MIPS register file.
+pub const REGISTER_COUNT: usize = 72;
+
+/// GDB register numbers, as laid out by GDB for 32-bit MIPS targets.
+mod regnum {
+  pub const STATUS: usize = 32;
+  pub const LO: usize = 33;
+  pub const HI: usize = 34;
+  pub const BADVADDR: usize = 35;
+  pub const CAUSE: usize = 36;
+  pub const PC: usize = 37;
+  pub const F0: usize = 38;
+  pub const F31: usize = F0 + 31;
+  pub const FCSR: usize = 70;
+  pub const FIR: usize = 71;
+}
+
+/// Read register number `n` in GDB order. Returns `None` for registers the
+/// simulator does not implement (yet), which are reported as unavailable.
+pub fn read_register(cpu: &Cpu, n: usize) -> Option<u32> {
+  let registers = cpu.registers();
+
+  match n {
+    0..=31 => registers.r(n).ok().map(|r| *r),
+    regnum::STATUS => Some(registers.cop0.status),
+    regnum::LO => Some(registers.lo),
+    regnum::HI => Some(registers.hi),
+    regnum::BADVADDR => Some(registers.cop0.bad_vaddr),
+    regnum::CAUSE => Some(registers.cop0.cause),
+    regnum::PC => Some(registers.pc),
+    regnum::F0..=regnum::F31 => Some(registers.cop1.word(n - regnum::F0)),
+    regnum::FCSR => Some(registers.cop1.fcsr),
+    regnum::FIR => Some(cop1::FIR),
+    _ => None,
+  }
+}
+
+/// Write register number `n` in GDB order. Returns `false` if the register
+/// cannot be written.
+pub fn write_register(cpu: &mut Cpu, n: usize, value: u32) -> bool {
+  let registers = cpu.registers_mut();
+
+  match n {
+    0..=31 => match registers.r(n) {
+      Ok(mut r) => {
+        *r = value;
+        true
+      }
+      Err(_) => false,
+    },
+    regnum::LO => {
+      registers.lo = value;
+      true
+    }
+    regnum::HI => {
+      registers.hi = value;
+      true
+    }
+    regnum::PC => {
+      registers.pc = value;
+      true
+    }
+    regnum::STATUS => registers.cop0.write(cop0::STATUS, value),
+    regnum::CAUSE => registers.cop0.write(cop0::CAUSE, value),
+    regnum::F0..=regnum::F31 => {
+      registers.cop1.set_word(n - regnum::F0, value);
+      true
+    }
+    regnum::FCSR => {
+      registers.cop1.fcsr = value;
+      true
+    }
+    _ => false,
+  }
+}
+
+/// Target description handed to GDB through `qXfer:features:read`, so it knows
+/// the register layout without guessing.
+pub fn target_xml() -> String {
+  let mut xml = String::from(concat!(
+    r#"<?xml version="1.0"?>"#,
+    r#"<!DOCTYPE target SYSTEM "gdb-target.dtd">"#,
+    r#"<target version="1.0">"#,
+    r#"<architecture>mips</architecture>"#,
+    r#"<feature name="org.gnu.gdb.mips.cpu">"#,
+  ));
+
+  for n in 0..32 {
+    reg(&mut xml, &format!("r{n}"), n, None);
+  }
+  reg(&mut xml, "lo", regnum::LO, None);
+  reg(&mut xml, "hi", regnum::HI, None);
+  reg(&mut xml, "pc", regnum::PC, Some("code_ptr"));
+
+  xml.push_str(r#"</feature><feature name="org.gnu.gdb.mips.cp0">"#);
+  reg(&mut xml, "status", regnum::STATUS, None);
+  reg(&mut xml, "badvaddr", regnum::BADVADDR, None);
+  reg(&mut xml, "cause", regnum::CAUSE, None);
+
+  xml.push_str(r#"</feature><feature name="org.gnu.gdb.mips.fpu">"#);
+  for n in 0..32 {
+    reg(
+      &mut xml,
+      &format!("f{n}"),
+      regnum::F0 + n,
+      Some("ieee_single"),
+    );
+  }
+  reg(&mut xml, "fcsr", regnum::FCSR, None);
+  reg(&mut xml, "fir", regnum::FIR, None);
+
+  xml.push_str("</feature></target>");
+  xml
+}
+
+fn reg(xml: &mut String, name: &str, regnum: usize, ty: Option<&str>) {
+  // writing to a `String` cannot fail
+  #[allow(clippy::unwrap_used)]
+  write!(xml, r#"<reg name="{name}" bitsize="32" regnum="{regnum}""#).unwrap();
+
+  if let Some(ty) = ty {
+    #[allow(clippy::unwrap_used)]
+    write!(xml, r#" type="{ty}""#).unwrap();
+  }
+
+  xml.push_str("/>");
+}
@@ -1,4 +1,5 @@
 use super::{Continuous, HybridStore, SegmentedStore};
+use std::ops::Range;
 
 /// Interface with encapsulates read operations with different storage
 /// solutions.
@@ -38,4 +39,105 @@ impl IoInterface<'_> {
       Segmented(s) => s.read_word(index),
     }
   }
+
+  /// Read the bytes of `range`. Bytes which were never written are `None`,
+  /// they read as zero.
+  pub fn read_range(&self, range: Range<usize>) -> Vec<Option<u8>> {
+    use IoInterface::*;
+
+    match self {
+      Continuous(c) => c.read_range(range),
+      Hybrid(h) => h.read_range(range),
+      Segmented(s) => s.read_range(range),
+    }
+  }
+
+  /// Sorted, disjoint index ranges holding written data.
+  pub fn iter_mapped_regions(&self) -> impl Iterator<Item = Range<usize>> {
+    use IoInterface::*;
+
+    let regions = match self {
+      Continuous(c) => c.mapped_regions(),
+      Hybrid(h) => h.mapped_regions(),
+      Segmented(s) => s.mapped_regions(),
+    };
+
+    regions.into_iter()
+  }
+}
+
+/// Interface which encapsulates write operations with different storage
+/// solutions.
+pub enum IoInterfaceMut<'a> {
+  Continuous(&'a mut Continuous),
+  Hybrid(&'a mut HybridStore),
+  Segmented(&'a mut SegmentedStore),
+}
+
+impl IoInterfaceMut<'_> {
+  pub fn write_byte(&mut self, index: usize, value: u8) -> Option<()> {
+    use IoInterfaceMut::*;
+
+    match self {
+      Continuous(c) => c.write_byte(index, value),
+      Hybrid(h) => {
+        h.write(index, &[value]);
+        Some(())
+      }
+      Segmented(s) => {
+        s.write(index, &[value]);
+        Some(())
+      }
+    }
+  }
+
+  pub fn write_halfword(&mut self, index: usize, value: u16) -> Option<()> {
+    use IoInterfaceMut::*;
+
+    match self {
+      Continuous(c) => c.write_halfword(index, value),
+      Hybrid(h) => {
+        h.write(index, &value.to_le_bytes());
+        Some(())
+      }
+      Segmented(s) => {
+        s.write(index, &value.to_le_bytes());
+        Some(())
+      }
+    }
+  }
+
+  pub fn write_word(&mut self, index: usize, value: u32) -> Option<()> {
+    use IoInterfaceMut::*;
+
+    match self {
+      Continuous(c) => c.write_word(index, value),
+      Hybrid(h) => {
+        h.write(index, &value.to_le_bytes());
+        Some(())
+      }
+      Segmented(s) => {
+        s.write(index, &value.to_le_bytes());
+        Some(())
+      }
+    }
+  }
+
+  /// Write `data` at `index`. Returns `None` if it does not fit in the store,
+  /// in which case nothing is written.
+  pub fn write_range(&mut self, index: usize, data: &[u8]) -> Option<()> {
+    use IoInterfaceMut::*;
+
+    match self {
+      Continuous(c) => c.write(index, data),
+      Hybrid(h) => {
+        h.write(index, data);
+        Some(())
+      }
+      Segmented(s) => {
+        s.write(index, data);
+        Some(())
+      }
+    }
+  }
 }
@@ -1,12 +1,13 @@
 #![feature(is_sorted)]
 
 use derive_more::Deref;
-use interface::IoInterface;
+use interface::{IoInterface, IoInterfaceMut};
+use serde::{Deserialize, Serialize};
 use storage::continuous::Continuous;
 use storage::hybrid_store::HybridStore;
 use storage::segmented_store::SegmentedStore;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// A label, like `msg`, `main` and `loop` in:
 ///
 /// ```asm
@@ -31,7 +32,7 @@ pub struct Label {
   pub name: String,
 }
 
-#[derive(Debug, Deref)]
+#[derive(Debug, Clone, Deref, Serialize, Deserialize)]
 pub struct Labeled<S> {
   #[deref]
   storage: S,
@@ -47,7 +48,7 @@ impl<S> Labeled<S> {
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// Contains all data needed to run a MIPS program. Implements read/write restrictions.
 pub struct ProgramData {
   /// `.text` block, contains user program code.  
@@ -83,18 +84,18 @@ pub struct ProgramData {
   ///
   /// A con is a that this level of flexibility is completely useless to almost
   /// anyone.
-  #[allow(dead_code)]
   heap: Labeled<SegmentedStore>,
   /// `.ktext` block, contains kernel code
   ///
   /// The kernel text is the same story as `.text`.
-  #[allow(dead_code)]
   ktext: Labeled<HybridStore>,
   /// `.kdata` block, contains kernel static data.
   ///
   /// Same story as the heap.
-  #[allow(dead_code)]
   kdata: Labeled<SegmentedStore>,
+  /// Whether `.text` and `.ktext` accept writes from user code, for
+  /// self-modifying code.
+  writable_text: bool,
 }
 
 impl ProgramData {
@@ -108,14 +109,28 @@ impl ProgramData {
       Text => &self.text.labels,
       Extern => &self.r#extern.labels,
       Data => &self.data.labels,
+      Heap => &self.heap.labels,
+      KText => &self.ktext.labels,
+      KData => &self.kdata.labels,
     }
   }
 
+  /// Whether `.text` and `.ktext` accept writes from user code. The kernel
+  /// and debuggers can always write them.
+  pub fn writable_text(&self) -> bool {
+    self.writable_text
+  }
+
+  /// Allow or forbid writes to `.text` and `.ktext` from user code.
+  pub fn set_writable_text(&mut self, writable: bool) {
+    self.writable_text = writable;
+  }
+
   /// Request to read into a memory section.  
   ///
   /// Returns `None` if reading is unauthorized considering the `Context`.  
   /// Returns `Some(interface)` if reading is authorized.  
-  pub fn read(&self, section: Section, _context: Context) -> Option<IoInterface<'_>> {
+  pub fn read(&self, section: Section, context: Context) -> Option<IoInterface<'_>> {
     use Section::*;
 
     match section {
@@ -133,18 +148,71 @@ impl ProgramData {
         // whatever context is allowed to read .extern
         Some(IoInterface::Continuous(&self.data.storage))
       }
+
+      Heap => {
+        // whatever context is allowed to read the heap
+        Some(IoInterface::Segmented(&self.heap.storage))
+      }
+
+      KText | KData if context == Context::User => None,
+
+      KText => Some(IoInterface::Hybrid(&self.ktext.storage)),
+
+      KData => Some(IoInterface::Segmented(&self.kdata.storage)),
+    }
+  }
+
+  /// Request to write into a memory section.
+  ///
+  /// Returns `None` if writing is unauthorized considering the `Context`.
+  /// Returns `Some(interface)` if writing is authorized.
+  pub fn write(&mut self, section: Section, context: Context) -> Option<IoInterfaceMut<'_>> {
+    use Section::*;
+
+    match section {
+      Text | KText if !self.writable_text && context == Context::User => None,
+
+      Text => {
+        // whatever context is allowed to write .text, when writable for user
+        // code
+        Some(IoInterfaceMut::Hybrid(&mut self.text.storage))
+      }
+
+      Extern => {
+        // whatever context is allowed to write .extern
+        Some(IoInterfaceMut::Continuous(&mut self.r#extern.storage))
+      }
+
+      Data => {
+        // whatever context is allowed to write .data
+        Some(IoInterfaceMut::Continuous(&mut self.data.storage))
+      }
+
+      Heap => {
+        // whatever context is allowed to write the heap
+        Some(IoInterfaceMut::Segmented(&mut self.heap.storage))
+      }
+
+      KText | KData if context == Context::User => None,
+
+      KText => Some(IoInterfaceMut::Hybrid(&mut self.ktext.storage)),
+
+      KData => Some(IoInterfaceMut::Segmented(&mut self.kdata.storage)),
     }
   }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Section {
   Text,
   Extern,
   Data,
+  Heap,
+  KText,
+  KData,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Context {
   User,
   Kernel,
@@ -157,10 +225,22 @@ pub enum Context {
 #[derive(Debug, Default)]
 pub struct ProgramDataBuilder {
   text: Option<Vec<u8>>,
+  data: Option<Vec<u8>>,
+  ktext: Option<Vec<u8>>,
+  kdata: Option<Vec<u8>>,
+  writable_text: bool,
+  labels: Vec<(Section, Label)>,
 }
 impl ProgramDataBuilder {
   pub fn new() -> Self {
-    ProgramDataBuilder { text: None }
+    ProgramDataBuilder {
+      text: None,
+      data: None,
+      ktext: None,
+      kdata: None,
+      writable_text: false,
+      labels: Vec::new(),
+    }
   }
 
   pub fn text(mut self, text: Vec<u8>) -> Self {
@@ -168,20 +248,89 @@ impl ProgramDataBuilder {
     self
   }
 
+  /// Static data, at the start of `.data`. Bytes past the end of the section
+  /// are dropped.
+  pub fn data(mut self, data: Vec<u8>) -> Self {
+    self.data = Some(data);
+    self
+  }
+
+  /// Kernel code, such as an exception handler.
+  pub fn ktext(mut self, ktext: Vec<u8>) -> Self {
+    self.ktext = Some(ktext);
+    self
+  }
+
+  /// Kernel static data, at the start of `.kdata`.
+  pub fn kdata(mut self, kdata: Vec<u8>) -> Self {
+    self.kdata = Some(kdata);
+    self
+  }
+
+  /// Allow writes to `.text` and `.ktext`, for self-modifying code.
+  pub fn writable_text(mut self, writable: bool) -> Self {
+    self.writable_text = writable;
+    self
+  }
+
+  /// Add a label named `name` at `position` in `section`.
+  pub fn label(mut self, section: Section, name: impl Into<String>, position: usize) -> Self {
+    let label = Label {
+      position,
+      name: name.into(),
+    };
+
+    self.labels.push((section, label));
+    self
+  }
+
   pub fn build(self) -> ProgramData {
     let mut text_store = HybridStore::new();
     if let Some(text) = self.text {
       text_store.insert_continuous(0, text);
     }
 
-    ProgramData {
+    let mut ktext_store = HybridStore::new();
+    if let Some(ktext) = self.ktext {
+      ktext_store.insert_continuous(0, ktext);
+    }
+
+    // 0x10010000..0x10040000, up to the heap
+    let mut data_store = Continuous::init(0x30000);
+    for (i, byte) in self.data.into_iter().flatten().enumerate() {
+      data_store.write_byte(i, byte);
+    }
+
+    let mut kdata_store = SegmentedStore::new();
+    if let Some(kdata) = self.kdata {
+      kdata_store.write(0, &kdata);
+    }
+
+    let mut program = ProgramData {
       text: Labeled::with_no_labels(text_store),
-      r#extern: Labeled::with_no_labels(Continuous::init(0)),
-      data: Labeled::with_no_labels(Continuous::init(0)),
+      // 0x10000000..0x10010000
+      r#extern: Labeled::with_no_labels(Continuous::init(0x10000)),
+      data: Labeled::with_no_labels(data_store),
       heap: Labeled::with_no_labels(SegmentedStore::new()),
-      ktext: Labeled::with_no_labels(HybridStore::new()),
-      kdata: Labeled::with_no_labels(SegmentedStore::new()),
+      ktext: Labeled::with_no_labels(ktext_store),
+      kdata: Labeled::with_no_labels(kdata_store),
+      writable_text: self.writable_text,
+    };
+
+    for (section, label) in self.labels {
+      let labels = match section {
+        Section::Text => &mut program.text.labels,
+        Section::Extern => &mut program.r#extern.labels,
+        Section::Data => &mut program.data.labels,
+        Section::Heap => &mut program.heap.labels,
+        Section::KText => &mut program.ktext.labels,
+        Section::KData => &mut program.kdata.labels,
+      };
+
+      labels.push(label);
     }
+
+    program
   }
 }
 
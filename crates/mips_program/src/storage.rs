@@ -1,3 +1,69 @@
 pub mod continuous;
 pub mod hybrid_store;
 pub mod segmented_store;
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Indexes a store actually holds data for, written or loaded from the
+/// program, as sorted ranges which neither overlap nor touch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Extents(Vec<Range<usize>>);
+
+impl Extents {
+  /// Add `range`, merging it with the ranges it overlaps or touches.
+  fn insert(&mut self, range: Range<usize>) {
+    if range.is_empty() {
+      return;
+    }
+
+    let first = self.0.partition_point(|r| r.end < range.start);
+    let last = self.0.partition_point(|r| r.start <= range.end);
+
+    let mut merged = range;
+    if first < last {
+      merged.start = merged.start.min(self.0[first].start);
+      merged.end = merged.end.max(self.0[last - 1].end);
+    }
+
+    self.0.splice(first..last, [merged]);
+  }
+
+  fn ranges(&self) -> Vec<Range<usize>> {
+    self.0.clone()
+  }
+
+  /// Replace by `None` the bytes of `bytes`, which hold the indexes of
+  /// `range`, outside of every extent.
+  fn mask(&self, range: Range<usize>, bytes: &mut [Option<u8>]) {
+    let mut held = vec![false; range.len()];
+
+    let first = self.0.partition_point(|r| r.end <= range.start);
+    for extent in self.0[first..].iter().take_while(|r| r.start < range.end) {
+      let start = extent.start.max(range.start) - range.start;
+      let end = extent.end.min(range.end) - range.start;
+      held[start..end].fill(true);
+    }
+
+    for (byte, held) in bytes.iter_mut().zip(held) {
+      if !held {
+        *byte = None;
+      }
+    }
+  }
+}
+
+/// Sort `regions` and merge the ones which overlap or touch.
+fn merge_regions(mut regions: Vec<Range<usize>>) -> Vec<Range<usize>> {
+  regions.sort_by_key(|r| r.start);
+
+  let mut merged: Vec<Range<usize>> = Vec::with_capacity(regions.len());
+  for region in regions.into_iter().filter(|r| !r.is_empty()) {
+    match merged.last_mut() {
+      Some(last) if region.start <= last.end => last.end = last.end.max(region.end),
+      _ => merged.push(region),
+    }
+  }
+
+  merged
+}
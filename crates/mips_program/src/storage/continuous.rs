@@ -1,9 +1,15 @@
+use super::Extents;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
 /// A size-bound continuous data store. It's nothing more than a
 /// wrapper around a `Vec`.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Continuous {
   data: Vec<u8>,
   max_size: usize,
+  /// Bytes written, the others up to the end of `data` are zero filled.
+  written: Extents,
 }
 
 impl Continuous {
@@ -13,6 +19,7 @@ impl Continuous {
       // skip a good 8 small relocations
       data: Vec::with_capacity(512),
       max_size,
+      written: Extents::default(),
     }
   }
 
@@ -48,4 +55,57 @@ impl Continuous {
 
     Some(u32::from_le_bytes(bytes))
   }
+
+  /// Write a single byte into the data store. Bytes between the end of the
+  /// stored data and `index` are zeroed.
+  ///
+  /// Returns `None` if `index` is over the size limit.
+  pub fn write_byte(&mut self, index: usize, value: u8) -> Option<()> {
+    self.write(index, &[value])
+  }
+
+  /// Write a half word (2 bytes) into the data store.
+  pub fn write_halfword(&mut self, index: usize, value: u16) -> Option<()> {
+    self.write(index, &value.to_le_bytes())
+  }
+
+  /// Write a whole word (4 bytes) into the data store.
+  pub fn write_word(&mut self, index: usize, value: u32) -> Option<()> {
+    self.write(index, &value.to_le_bytes())
+  }
+
+  /// Read the bytes of `range`, `None` for those never written.
+  pub fn read_range(&self, range: Range<usize>) -> Vec<Option<u8>> {
+    let mut bytes = range
+      .clone()
+      .map(|i| self.data.get(i).copied())
+      .collect::<Vec<_>>();
+    self.written.mask(range, &mut bytes);
+    bytes
+  }
+
+  /// Indexes of the bytes written.
+  pub fn mapped_regions(&self) -> Vec<Range<usize>> {
+    self.written.ranges()
+  }
+
+  /// Write `bytes` at `index`. Bytes between the end of the stored data and
+  /// `index` are zeroed.
+  ///
+  /// Returns `None`, writing nothing, if the bytes go over the size limit.
+  pub fn write(&mut self, index: usize, bytes: &[u8]) -> Option<()> {
+    let end = index + bytes.len();
+
+    if end > self.max_size {
+      return None;
+    }
+
+    if end > self.data.len() {
+      self.data.resize(end, 0);
+    }
+
+    self.data[index..end].copy_from_slice(bytes);
+    self.written.insert(index..end);
+    Some(())
+  }
 }
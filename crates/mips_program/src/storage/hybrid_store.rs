@@ -1,7 +1,8 @@
 use super::segmented_store::SegmentedStore;
+use serde::{Deserialize, Serialize};
 use std::ops::Range;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ContinuousRegion {
   index: usize,
   data: Vec<u8>,
@@ -13,7 +14,7 @@ impl ContinuousRegion {
   }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HybridStore {
   regions: Vec<ContinuousRegion>,
   fallback: SegmentedStore,
@@ -70,6 +71,43 @@ impl HybridStore {
     ]))
   }
 
+  /// Write `data` at `index`. Bytes falling in a continuous region are
+  /// updated in place, the others go to the fallback store.
+  pub fn write(&mut self, index: usize, data: &[u8]) {
+    for (i, byte) in data.iter().enumerate() {
+      let index = index + i;
+
+      match self.regions.iter_mut().find(|r| r.range().contains(&index)) {
+        Some(region) => region.data[index - region.index] = *byte,
+        None => self.fallback.write(index, &[*byte]),
+      }
+    }
+  }
+
+  /// Read the bytes of `range`, `None` for those neither in a continuous
+  /// region nor in the fallback store.
+  pub fn read_range(&self, range: Range<usize>) -> Vec<Option<u8>> {
+    let mut bytes = self.fallback.read_range(range.clone());
+
+    for region in &self.regions {
+      let start = region.index.max(range.start);
+      let end = region.range().end.min(range.end);
+
+      for i in start..end {
+        bytes[i - range.start] = Some(region.data[i - region.index]);
+      }
+    }
+
+    bytes
+  }
+
+  /// Indexes covered by continuous regions or by the fallback store.
+  pub fn mapped_regions(&self) -> Vec<Range<usize>> {
+    let mut regions = self.fallback.mapped_regions();
+    regions.extend(self.regions.iter().map(ContinuousRegion::range));
+    super::merge_regions(regions)
+  }
+
   fn try_read_continuous(&self, index: usize) -> Option<&[u8]> {
     self
       .regions
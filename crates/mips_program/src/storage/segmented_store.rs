@@ -1,11 +1,15 @@
+use super::Extents;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::io::Read;
+use std::ops::Range;
 
 const SIZE: usize = 2048;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Segment {
   index: usize,
+  #[serde(with = "segment_data")]
   data: Box<[u8; SIZE]>,
 }
 
@@ -49,15 +53,18 @@ impl Segment {
 }
 
 /// Segmented storage, in blocks of 2048 bytes.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SegmentedStore {
   segments: VecDeque<Segment>,
+  /// Bytes written, the others of their segments are zero filled.
+  written: Extents,
 }
 
 impl SegmentedStore {
   pub fn new() -> Self {
     Self {
       segments: VecDeque::new(),
+      written: Extents::default(),
     }
   }
 
@@ -109,7 +116,7 @@ impl SegmentedStore {
   /// Read a word which might cross segment boundaries.
   #[inline]
   pub fn read_word(&self, index: usize) -> Option<u32> {
-    if index.div_ceil(SIZE) == (index + 4).div_ceil(SIZE) {
+    if index / SIZE == (index + 3) / SIZE {
       // does NOT cross segment boundaries
       self
         .read_continuous(index)
@@ -128,7 +135,39 @@ impl SegmentedStore {
     }
   }
 
+  /// Read the bytes of `range`, `None` for those never written.
+  pub fn read_range(&self, range: Range<usize>) -> Vec<Option<u8>> {
+    let mut bytes = vec![None; range.len()];
+    let first = self.segments.partition_point(|s| s.end() <= range.start);
+
+    for segment in self.segments.range(first..) {
+      if segment.start() >= range.end {
+        break;
+      }
+
+      let start = segment.start().max(range.start);
+      let end = segment.end().min(range.end);
+
+      for (i, byte) in segment.data[start - segment.index..end - segment.index]
+        .iter()
+        .enumerate()
+      {
+        bytes[start - range.start + i] = Some(*byte);
+      }
+    }
+
+    self.written.mask(range, &mut bytes);
+    bytes
+  }
+
+  /// Indexes of the bytes written.
+  pub fn mapped_regions(&self) -> Vec<Range<usize>> {
+    self.written.ranges()
+  }
+
   pub fn write(&mut self, index: usize, mut data: &[u8]) {
+    self.written.insert(index..index + data.len());
+
     let mut start = index - (index / SIZE) * SIZE;
     let mut blocks_traversed = 0;
 
@@ -142,3 +181,27 @@ impl SegmentedStore {
     }
   }
 }
+
+/// Serde does not handle arrays as large as a segment, (de)serialize them as
+/// byte strings.
+mod segment_data {
+  use super::SIZE;
+  use serde::de::Error;
+  use serde::{Deserialize, Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(data: &[u8; SIZE], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bytes(data)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Box<[u8; SIZE]>, D::Error> {
+    let bytes = Vec::<u8>::deserialize(deserializer)?;
+    let len = bytes.len();
+
+    bytes
+      .into_boxed_slice()
+      .try_into()
+      .map_err(|_| D::Error::invalid_length(len, &"a whole segment"))
+  }
+}
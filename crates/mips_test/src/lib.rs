@@ -1 +1,254 @@
+//! Helpers for tests running small programs, hand-encoded or assembled.
 
+use mips_asm::assemble;
+use mips_cpu::console::Buffered;
+use mips_cpu::exception::Exception;
+use mips_cpu::syscall::{self, Outcome, SyscallError};
+use mips_cpu::Cpu;
+use mips_program::ProgramData;
+use std::rc::Rc;
+
+/// Register numbers, named as in assembly.
+pub mod reg {
+  pub const ZERO: u32 = 0;
+  pub const AT: u32 = 1;
+  pub const V0: u32 = 2;
+  pub const V1: u32 = 3;
+  pub const A0: u32 = 4;
+  pub const A1: u32 = 5;
+  pub const A2: u32 = 6;
+  pub const A3: u32 = 7;
+  pub const T0: u32 = 8;
+  pub const T1: u32 = 9;
+  pub const T2: u32 = 10;
+  pub const T3: u32 = 11;
+  pub const T4: u32 = 12;
+  pub const T5: u32 = 13;
+  pub const T6: u32 = 14;
+  pub const T7: u32 = 15;
+  pub const S0: u32 = 16;
+  pub const S1: u32 = 17;
+  pub const S2: u32 = 18;
+  pub const S3: u32 = 19;
+  pub const S4: u32 = 20;
+  pub const S5: u32 = 21;
+  pub const S6: u32 = 22;
+  pub const S7: u32 = 23;
+  pub const T8: u32 = 24;
+  pub const T9: u32 = 25;
+  pub const K0: u32 = 26;
+  pub const K1: u32 = 27;
+  pub const GP: u32 = 28;
+  pub const SP: u32 = 29;
+  pub const FP: u32 = 30;
+  pub const RA: u32 = 31;
+}
+
+/// Instruction encoders, named after the mnemonics. Registers are numbers,
+/// branch offsets are counted in instructions from the next one, jump targets
+/// are absolute addresses.
+pub mod asm {
+  fn r(rs: u32, rt: u32, rd: u32, shamt: u32, funct: u32) -> u32 {
+    (rs << 21) | (rt << 16) | (rd << 11) | (shamt << 6) | funct
+  }
+
+  fn i(opcode: u32, rs: u32, rt: u32, imm: i32) -> u32 {
+    (opcode << 26) | (rs << 21) | (rt << 16) | (imm as u32 & 0xffff)
+  }
+
+  fn cop0(rs: u32, rt: u32, rd: u32, funct: u32) -> u32 {
+    (0x10 << 26) | r(rs, rt, rd, 0, funct)
+  }
+
+  fn cop1(fmt: u32, ft: u32, fs: u32, fd: u32, funct: u32) -> u32 {
+    (0x11 << 26) | r(fmt, ft, fs, fd, funct)
+  }
+
+  pub fn add(rd: u32, rs: u32, rt: u32) -> u32 {
+    r(rs, rt, rd, 0, 0x20)
+  }
+
+  pub fn addu(rd: u32, rs: u32, rt: u32) -> u32 {
+    r(rs, rt, rd, 0, 0x21)
+  }
+
+  pub fn sub(rd: u32, rs: u32, rt: u32) -> u32 {
+    r(rs, rt, rd, 0, 0x22)
+  }
+
+  pub fn sll(rd: u32, rt: u32, shamt: u32) -> u32 {
+    r(0, rt, rd, shamt, 0x0)
+  }
+
+  pub fn multu(rs: u32, rt: u32) -> u32 {
+    r(rs, rt, 0, 0, 0x19)
+  }
+
+  pub fn mflo(rd: u32) -> u32 {
+    r(0, 0, rd, 0, 0x12)
+  }
+
+  pub fn jr(rs: u32) -> u32 {
+    r(rs, 0, 0, 0, 0x8)
+  }
+
+  pub fn syscall() -> u32 {
+    0xc
+  }
+
+  pub fn addi(rt: u32, rs: u32, imm: i32) -> u32 {
+    i(0x8, rs, rt, imm)
+  }
+
+  pub fn ori(rt: u32, rs: u32, imm: i32) -> u32 {
+    i(0xd, rs, rt, imm)
+  }
+
+  pub fn lui(rt: u32, imm: i32) -> u32 {
+    i(0xf, 0, rt, imm)
+  }
+
+  pub fn lw(rt: u32, offset: i32, base: u32) -> u32 {
+    i(0x23, base, rt, offset)
+  }
+
+  pub fn sw(rt: u32, offset: i32, base: u32) -> u32 {
+    i(0x2b, base, rt, offset)
+  }
+
+  pub fn beq(rs: u32, rt: u32, offset: i32) -> u32 {
+    i(0x4, rs, rt, offset)
+  }
+
+  pub fn bne(rs: u32, rt: u32, offset: i32) -> u32 {
+    i(0x5, rs, rt, offset)
+  }
+
+  pub fn j(target: u32) -> u32 {
+    (0x2 << 26) | ((target >> 2) & 0x3ffffff)
+  }
+
+  pub fn jal(target: u32) -> u32 {
+    (0x3 << 26) | ((target >> 2) & 0x3ffffff)
+  }
+
+  pub fn mfc0(rt: u32, rd: u32) -> u32 {
+    cop0(0x0, rt, rd, 0)
+  }
+
+  pub fn mtc0(rt: u32, rd: u32) -> u32 {
+    cop0(0x4, rt, rd, 0)
+  }
+
+  pub fn tlbr() -> u32 {
+    cop0(0x10, 0, 0, 0x1)
+  }
+
+  pub fn tlbwi() -> u32 {
+    cop0(0x10, 0, 0, 0x2)
+  }
+
+  pub fn tlbwr() -> u32 {
+    cop0(0x10, 0, 0, 0x6)
+  }
+
+  pub fn tlbp() -> u32 {
+    cop0(0x10, 0, 0, 0x8)
+  }
+
+  pub fn eret() -> u32 {
+    cop0(0x10, 0, 0, 0x18)
+  }
+
+  pub fn mtc1(rt: u32, fs: u32) -> u32 {
+    cop1(0x4, rt, fs, 0, 0)
+  }
+
+  pub fn cvt_s_w(fd: u32, fs: u32) -> u32 {
+    cop1(0x14, 0, fs, fd, 0x20)
+  }
+
+  pub fn add_s(fd: u32, fs: u32, ft: u32) -> u32 {
+    cop1(0x10, ft, fs, fd, 0x0)
+  }
+
+  pub fn mul_s(fd: u32, fs: u32, ft: u32) -> u32 {
+    cop1(0x10, ft, fs, fd, 0x2)
+  }
+
+  pub fn swc1(ft: u32, offset: i32, base: u32) -> u32 {
+    i(0x39, base, ft, offset)
+  }
+}
+
+fn bytes(words: &[u32]) -> Vec<u8> {
+  words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+/// A program made of the given `.text` words.
+pub fn program(words: &[u32]) -> Rc<ProgramData> {
+  Rc::new(ProgramData::builder().text(bytes(words)).build())
+}
+
+/// A program made of the given `.text` and `.ktext` words.
+pub fn kernel_program(words: &[u32], kernel: &[u32]) -> Rc<ProgramData> {
+  let builder = ProgramData::builder()
+    .text(bytes(words))
+    .ktext(bytes(kernel));
+
+  Rc::new(builder.build())
+}
+
+/// Run `cpu` until it raises an exception, at most `max_cycles` cycles.
+pub fn run(cpu: &mut Cpu, max_cycles: u64) -> Option<Exception> {
+  (0..max_cycles).find_map(|_| cpu.cycle().err())
+}
+
+/// A machine loaded with the assembled `source`.
+pub fn cpu(source: &str) -> Cpu {
+  Cpu::new(Rc::new(assemble(source).unwrap().program))
+}
+
+/// Run `cpu` until a system call exits, sleeps or awaits input, servicing
+/// the others.
+pub fn run_syscalls(cpu: &mut Cpu, console: &mut Buffered) -> Result<Outcome, SyscallError> {
+  service_until(cpu, console, None)
+}
+
+/// Like [`run_syscalls`], but also stop with [`Outcome::Continue`] when the
+/// PC reaches `until`.
+pub fn run_syscalls_to(
+  cpu: &mut Cpu,
+  console: &mut Buffered,
+  until: u32,
+) -> Result<Outcome, SyscallError> {
+  service_until(cpu, console, Some(until))
+}
+
+fn service_until(
+  cpu: &mut Cpu,
+  console: &mut Buffered,
+  until: Option<u32>,
+) -> Result<Outcome, SyscallError> {
+  for _ in 0..100_000 {
+    if Some(cpu.registers().pc) == until {
+      return Ok(Outcome::Continue);
+    }
+
+    match cpu.cycle() {
+      Ok(()) => (),
+      Err(Exception::Syscall) => match syscall::service(cpu, console)? {
+        Outcome::Continue => (),
+        outcome => return Ok(outcome),
+      },
+      Err(e) => panic!("unexpected {e:?}"),
+    }
+  }
+
+  panic!("the program did not stop");
+}
+
+/// The value of general purpose register `n`.
+pub fn register(cpu: &Cpu, n: u32) -> u32 {
+  *cpu.registers().r(n as usize).unwrap()
+}
@@ -0,0 +1,142 @@
+use mips_asm::{assemble, Error};
+use mips_cpu::exception::Exception;
+use mips_cpu::mem::{DATA_START, TEXT_START};
+use mips_cpu::{disasm, Cpu};
+use mips_program::{Context, Section};
+use mips_test::reg::{S0, S1, S2};
+use mips_test::register;
+use std::rc::Rc;
+
+fn disassembly(source: &str) -> Vec<String> {
+  let assembly = assemble(source).unwrap();
+  let mut cpu = Cpu::new(Rc::new(assembly.program.clone()));
+
+  assembly
+    .lines()
+    .map(|(addr, _)| disasm::disassemble(cpu.memory_mut().fetch(addr).unwrap(), addr))
+    .collect()
+}
+
+#[test]
+fn instructions_round_trip() {
+  let source = "
+    add $t0, $t1, $t2
+    sll $s0, $s1, 4
+    addiu $sp, $sp, -8
+    ori $a0, $zero, 0xff
+    lw $ra, 4($sp)
+    sb $t0, ($a0)
+  loop:
+    bne $t0, $zero, loop
+    jal loop
+    jr $ra
+    mfc0 $k0, $14
+    add.s $f0, $f2, $f4
+    cvt.d.w $f2, $f0
+    c.lt.d 2, $f0, $f2
+    bc1t 2, loop
+    syscall
+  ";
+
+  k9::assert_equal!(
+    disassembly(source),
+    vec![
+      "add $t0, $t1, $t2",
+      "sll $s0, $s1, 4",
+      "addiu $sp, $sp, -8",
+      "ori $a0, $zero, 0xff",
+      "lw $ra, 4($sp)",
+      "sb $t0, 0($a0)",
+      "bne $t0, $zero, 0x00400018",
+      "jal 0x00400018",
+      "jr $ra",
+      "mfc0 $k0, $14",
+      "add.s $f0, $f2, $f4",
+      "cvt.d.w $f2, $f0",
+      "c.lt.d 2, $f0, $f2",
+      "bc1t 2, 0x00400018",
+      "syscall",
+    ]
+  );
+}
+
+#[test]
+fn pseudo_instructions_and_data_run() {
+  let source = "
+    .data
+    bytes: .byte 1, 2
+    array: .word 3, 4, 5   # aligned past the bytes
+    end:
+    msg: .asciiz \"hi\"
+
+    .text
+    main:
+      la $t0, array
+      la $t1, end
+      li $s0, 0
+    loop:
+      bge $t0, $t1, done
+      lw $t2, ($t0)
+      add $s0, $s0, $t2
+      addi $t0, $t0, 4
+      b loop
+    done:
+      lw $s1, array+8
+      li $s2, 0x12345678
+      li $v0, 10
+      syscall
+  ";
+
+  let assembly = assemble(source).unwrap();
+  let labels = assembly.program.labels(Section::Data);
+  let array = labels.iter().find(|l| l.name == "array").unwrap();
+  k9::assert_equal!(array.position, 4);
+
+  let mut cpu = Cpu::new(Rc::new(assembly.program));
+  k9::assert_equal!(mips_test::run(&mut cpu, 1000), Some(Exception::Syscall));
+
+  k9::assert_equal!(register(&cpu, S0), 12);
+  k9::assert_equal!(register(&cpu, S1), 5);
+  k9::assert_equal!(register(&cpu, S2), 0x12345678);
+
+  let msg = cpu
+    .memory()
+    .read_range(DATA_START + 16, 3, Context::External);
+  k9::assert_equal!(msg, Ok(vec![Some(b'h'), Some(b'i'), Some(0)]));
+}
+
+#[test]
+fn errors_are_reported_by_line() {
+  let source = "
+    add $t0, $t1
+    foo $t0
+    lw $t0, missing
+    addi $t0, $t0, 70000
+    j
+  ";
+
+  let errors = assemble(source).unwrap_err();
+  let lines = errors.iter().map(|e| e.line).collect::<Vec<_>>();
+  k9::assert_equal!(lines, vec![2, 3, 4, 5, 6]);
+
+  k9::assert_equal!(
+    errors[2],
+    Error {
+      line: 4,
+      message: "undefined label `missing`".to_owned()
+    }
+  );
+  k9::assert_equal!(errors[1].to_string(), "line 3: unknown instruction `foo`");
+}
+
+#[test]
+fn instructions_map_to_source_lines() {
+  let source = ".text\nmain:\n  li $t0, 0x10000\n\n  nop\n";
+  let assembly = assemble(source).unwrap();
+
+  k9::assert_equal!(assembly.line_at(TEXT_START), Some(3));
+  k9::assert_equal!(assembly.line_at(TEXT_START + 4), Some(3));
+  k9::assert_equal!(assembly.line_at(TEXT_START + 8), Some(5));
+  k9::assert_equal!(assembly.addr_of_line(5), Some(TEXT_START + 8));
+  k9::assert_equal!(assembly.addr_of_line(4), None);
+}
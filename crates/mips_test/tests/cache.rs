@@ -0,0 +1,212 @@
+use mips_asm::assemble;
+use mips_cpu::cache::{
+  Cache,
+  CacheConfig,
+  CacheSimulator,
+  CacheStats,
+  ConfigError,
+  Replacement,
+  WritePolicy,
+};
+use mips_cpu::exception::Exception;
+use mips_cpu::mem::AccessKind;
+use mips_cpu::Cpu;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Load from every address of `addrs`, returning which accesses hit.
+fn loads(cache: &mut Cache, addrs: &[u32]) -> Vec<bool> {
+  addrs
+    .iter()
+    .map(|addr| cache.access(*addr, AccessKind::Load))
+    .collect()
+}
+
+fn two_way(replacement: Replacement) -> Cache {
+  Cache::new(CacheConfig {
+    size: 64,
+    block_size: 16,
+    associativity: 2,
+    replacement,
+    ..CacheConfig::default()
+  })
+  .unwrap()
+}
+
+#[test]
+fn direct_mapped_blocks_conflict_on_the_same_set() {
+  // 8 blocks of 16 bytes, 0x80 maps to the set of 0x0
+  let mut cache = Cache::new(CacheConfig::default()).unwrap();
+
+  k9::assert_equal!(
+    loads(&mut cache, &[0x0, 0x4, 0xc, 0x80, 0x0, 0x10, 0x14]),
+    vec![false, true, true, false, false, false, true]
+  );
+  k9::assert_equal!(cache.stats().hits, 3);
+  k9::assert_equal!(cache.stats().misses, 4);
+  k9::assert_equal!(cache.stats().block_reads, 4);
+}
+
+#[test]
+fn sweeps_larger_than_the_cache_miss_once_per_block() {
+  let mut cache = Cache::new(CacheConfig::default()).unwrap();
+  let words = (0..256).step_by(4).collect::<Vec<_>>();
+
+  // twice the capacity: the second sweep finds none of the blocks
+  loads(&mut cache, &words);
+  loads(&mut cache, &words);
+
+  k9::assert_equal!(cache.stats().misses, 32);
+  k9::assert_equal!(cache.stats().hits, 96);
+  k9::assert_equal!(cache.stats().hit_rate(), 75.0);
+}
+
+#[test]
+fn lru_evicts_the_least_recently_used_block() {
+  // 2 sets of 2 blocks, 0x0, 0x20 and 0x40 share set 0
+  let mut cache = two_way(Replacement::Lru);
+
+  k9::assert_equal!(
+    loads(&mut cache, &[0x0, 0x20, 0x0, 0x40, 0x0, 0x20]),
+    vec![false, false, true, false, true, false]
+  );
+  k9::assert_equal!(cache.stats().hits, 2);
+  k9::assert_equal!(cache.stats().misses, 4);
+
+  // the other set is untouched
+  k9::assert_equal!(
+    loads(&mut cache, &[0x10, 0x30, 0x14]),
+    vec![false, false, true]
+  );
+}
+
+#[test]
+fn fifo_evicts_the_oldest_block() {
+  let mut cache = two_way(Replacement::Fifo);
+
+  // same pattern as with LRU, 0x0 goes first although it was just used
+  k9::assert_equal!(
+    loads(&mut cache, &[0x0, 0x20, 0x0, 0x40, 0x0, 0x20]),
+    vec![false, false, true, false, false, false]
+  );
+  k9::assert_equal!(cache.stats().hits, 1);
+  k9::assert_equal!(cache.stats().misses, 5);
+}
+
+#[test]
+fn write_policies_count_memory_traffic() {
+  let mut cache = Cache::new(CacheConfig::default()).unwrap();
+
+  cache.access(0x0, AccessKind::Store);
+  cache.access(0x4, AccessKind::Store);
+  cache.access(0x80, AccessKind::Load);
+  cache.access(0x0, AccessKind::Load);
+
+  k9::assert_equal!(
+    *cache.stats(),
+    CacheStats {
+      hits: 1,
+      misses: 3,
+      block_reads: 3,
+      // 0x0 when 0x80 replaced it, 0x80 was clean
+      writebacks: 1,
+      memory_writes: 0,
+    }
+  );
+
+  let mut cache = Cache::new(CacheConfig {
+    write_policy: WritePolicy::WriteThrough,
+    write_allocate: false,
+    ..CacheConfig::default()
+  })
+  .unwrap();
+
+  cache.access(0x0, AccessKind::Store);
+  cache.access(0x0, AccessKind::Load);
+  cache.access(0x4, AccessKind::Store);
+
+  k9::assert_equal!(
+    *cache.stats(),
+    CacheStats {
+      hits: 1,
+      misses: 2,
+      block_reads: 1,
+      writebacks: 0,
+      memory_writes: 2,
+    }
+  );
+}
+
+#[test]
+fn inconsistent_geometries_are_rejected() {
+  let config = CacheConfig::default();
+
+  for (config, error) in [
+    (
+      CacheConfig {
+        block_size: 12,
+        ..config
+      },
+      ConfigError::BlockSize(12),
+    ),
+    (
+      CacheConfig {
+        associativity: 0,
+        ..config
+      },
+      ConfigError::Associativity(0),
+    ),
+    (CacheConfig { size: 96, ..config }, ConfigError::Size(96)),
+  ] {
+    k9::assert_equal!(Cache::new(config).err(), Some(error));
+  }
+}
+
+#[test]
+fn the_simulator_feeds_fetches_and_data_accesses() {
+  let program = Rc::new(
+    assemble(
+      "
+      main:
+      lui $t1, 0x1001
+      li $t0, 10
+      loop:
+      lw $t2, 0($t1)
+      addi $t0, $t0, -1
+      bne $t0, $zero, loop
+      syscall
+    ",
+    )
+    .unwrap()
+    .program,
+  );
+
+  let config = Some(CacheConfig::default());
+  let simulator = Rc::new(RefCell::new(
+    CacheSimulator::new(&program, config, config).unwrap(),
+  ));
+
+  let mut cpu = Cpu::new(program);
+  cpu.add_observer(simulator.clone());
+  k9::assert_equal!(mips_test::run(&mut cpu, 1000), Some(Exception::Syscall));
+
+  let simulator = simulator.borrow();
+
+  // 33 fetches over two blocks, the loop crossing from the first to the
+  // second
+  let icache = simulator.icache().unwrap().stats();
+  k9::assert_equal!(icache.misses, 2);
+  k9::assert_equal!(icache.hits, 31);
+
+  // the same word loaded 10 times
+  let dcache = simulator.dcache().unwrap().stats();
+  k9::assert_equal!(dcache.misses, 1);
+  k9::assert_equal!(dcache.hits, 9);
+
+  let labels = simulator.by_label();
+  k9::assert_equal!(labels.len(), 2);
+  k9::assert_equal!(labels[0].0, "loop");
+  k9::assert_equal!(labels[0].1.dcache.accesses(), 10);
+  k9::assert_equal!(labels[1].0, "main");
+  k9::assert_equal!(labels[1].1.icache.accesses(), 2);
+}
@@ -0,0 +1,165 @@
+use mips_cpu::console::Buffered;
+use mips_cpu::syscall::{self, Outcome};
+use mips_test::reg::V0;
+use mips_test::{cpu, register, run_syscalls};
+
+#[test]
+fn output_goes_to_the_console() {
+  let mut cpu = cpu(
+    "
+    .data
+    msg: .asciiz \"hi\\n\"
+
+    .text
+    li $a0, -5
+    li $v0, 1
+    syscall
+    la $a0, msg
+    li $v0, 4
+    syscall
+    li $a0, 'x'
+    li $v0, 11
+    syscall
+    li $a0, 255
+    li $v0, 34
+    syscall
+    li $a0, -1
+    li $v0, 36
+    syscall
+    li $v0, 10
+    syscall
+  ",
+  );
+
+  let mut console = Buffered::default();
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut console).unwrap(),
+    Outcome::Exit(0)
+  );
+  k9::assert_equal!(console.output(), "-5hi\nx0x000000ff4294967295");
+}
+
+#[test]
+fn floats_print_like_java() {
+  let mut cpu = cpu(
+    "
+    .data
+    floats: .float 1.0, 0.1, 1e10, 1234567.0, 12345678.0, 0.001, 0.0001, -2.5
+    .word 0x7f800000, 0xff800000, 0x7fc00000, 0x80000000
+    doubles: .double 1.0, 0.1, 1e10, 100.0, 0.00000015, 123.456, 1e300
+
+    .text
+    la $t0, floats
+    li $t1, 12
+    li $v0, 2
+    floats_loop:
+    lwc1 $f12, ($t0)
+    syscall
+    li $a0, ' '
+    li $v0, 11
+    syscall
+    li $v0, 2
+    addi $t0, $t0, 4
+    addi $t1, $t1, -1
+    bnez $t1, floats_loop
+
+    la $t0, doubles
+    li $t1, 7
+    doubles_loop:
+    ldc1 $f12, ($t0)
+    li $v0, 3
+    syscall
+    li $a0, ' '
+    li $v0, 11
+    syscall
+    addi $t0, $t0, 8
+    addi $t1, $t1, -1
+    bnez $t1, doubles_loop
+
+    li $v0, 10
+    syscall
+  ",
+  );
+
+  let mut console = Buffered::default();
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut console).unwrap(),
+    Outcome::Exit(0)
+  );
+  k9::assert_equal!(
+    console.output(),
+    "1.0 0.1 1.0E10 1234567.0 1.2345678E7 0.001 1.0E-4 -2.5 Infinity -Infinity NaN -0.0 \
+     1.0 0.1 1.0E10 100.0 1.5E-7 123.456 1.0E300 "
+  );
+}
+
+#[test]
+fn input_is_read_from_the_console() {
+  let mut cpu = cpu(
+    "
+    .data
+    buffer: .space 8
+
+    .text
+    li $v0, 5
+    syscall
+    add $a0, $v0, $v0
+    li $v0, 1
+    syscall
+    la $a0, buffer
+    li $a1, 8
+    li $v0, 8
+    syscall
+    li $v0, 4
+    syscall
+    li $v0, 10
+    syscall
+  ",
+  );
+
+  let mut console = Buffered::default();
+  console.push_input("21");
+  console.push_input("abc");
+
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut console).unwrap(),
+    Outcome::Exit(0)
+  );
+  k9::assert_equal!(console.output(), "42abc\n");
+}
+
+#[test]
+fn reads_await_input() {
+  let mut cpu = cpu(
+    "
+    li $v0, 12
+    syscall
+    li $v0, 10
+    syscall
+  ",
+  );
+
+  let mut console = Buffered::default();
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut console).unwrap(),
+    Outcome::AwaitInput
+  );
+
+  let pc = cpu.registers().pc;
+  k9::assert_equal!(
+    syscall::service(&mut cpu, &mut console),
+    Ok(Outcome::AwaitInput)
+  );
+  k9::assert_equal!(cpu.registers().pc, pc);
+
+  console.push_input("yes");
+  k9::assert_equal!(
+    syscall::service(&mut cpu, &mut console),
+    Ok(Outcome::Continue)
+  );
+  k9::assert_equal!(register(&cpu, V0), 'y' as u32);
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut console).unwrap(),
+    Outcome::Exit(0)
+  );
+}
@@ -0,0 +1,168 @@
+use mips_cpu::convention::{ConventionChecker, ViolationKind};
+use mips_cpu::exception::Exception;
+use mips_cpu::mem::{STACK_POINTER, TEXT_START};
+use mips_cpu::register::Registers;
+use mips_cpu::Cpu;
+use mips_program::{ProgramData, Section};
+use mips_test::asm::*;
+use mips_test::reg::{K0, RA, S0, SP, T0, T1, T2, T3};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Runs `main` until its system call, with a function `f` starting at the
+/// instruction `f`.
+fn check(main: &[u32], f: usize) -> (Vec<(u32, ViolationKind)>, String) {
+  let text = main.iter().flat_map(|w| w.to_le_bytes()).collect();
+  let program = ProgramData::builder()
+    .text(text)
+    .label(Section::Text, "main", 0)
+    .label(Section::Text, "f", f * 4)
+    .build();
+  let program = Rc::new(program);
+
+  let mut cpu = Cpu::new(Rc::clone(&program));
+  let checker = ConventionChecker::new(&program, cpu.registers());
+  let checker = Rc::new(RefCell::new(checker));
+  cpu.add_observer(checker.clone());
+
+  k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::Syscall));
+
+  let checker = checker.borrow();
+  k9::assert_equal!(checker.depth(), 0);
+
+  let mut out = Vec::new();
+  checker.write_report(&mut out).unwrap();
+
+  let violations = checker.violations().iter().map(|v| (v.pc, v.kind));
+  (violations.collect(), String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn clobbered_registers_are_reported() {
+  let (violations, report) = check(
+    &[
+      addi(SP, SP, -8),
+      addi(S0, 0, 5),
+      addi(T0, 0, 1),
+      jal(TEXT_START + 0x24),
+      add(T1, T0, 0),
+      add(T1, T0, 0),
+      // reloads the value it already holds
+      addi(T2, 0, 0),
+      add(T3, T2, 0),
+      syscall(),
+      // f
+      addi(S0, 0, 7),
+      jr(RA),
+    ],
+    9,
+  );
+
+  let not_restored = ViolationKind::NotRestored {
+    reg: S0 as u8,
+    function: TEXT_START + 0x24,
+    expected: 5,
+    found: 7,
+  };
+  let clobbered = ViolationKind::ClobberedRead {
+    reg: T0 as u8,
+    call: TEXT_START + 0xc,
+  };
+  k9::assert_equal!(
+    violations,
+    vec![
+      (TEXT_START + 0x28, not_restored),
+      (TEXT_START + 0x10, clobbered),
+    ]
+  );
+
+  assert!(report.starts_with("2 calling convention violation(s)"));
+  assert!(report.contains("$s0 not restored by <f+0x0>: 0x5 became 0x7"));
+  assert!(report.contains("$t0 read after the call at <main+0xc> without being reloaded"));
+}
+
+#[test]
+fn saved_registers_pass() {
+  let (violations, _) = check(
+    &[
+      addi(SP, SP, -8),
+      addi(S0, 0, 5),
+      jal(TEXT_START + 0x14),
+      add(T0, S0, 0),
+      syscall(),
+      // f
+      addi(SP, SP, -8),
+      sw(S0, 0, SP),
+      addi(S0, 0, 9),
+      lw(S0, 0, SP),
+      addi(SP, SP, 8),
+      jr(RA),
+    ],
+    5,
+  );
+
+  k9::assert_equal!(violations, vec![]);
+}
+
+#[test]
+fn stack_alignment_and_kernel_registers() {
+  let (violations, _) = check(
+    &[
+      addi(SP, SP, -8),
+      // 8-byte aligned, but not a multiple of 8 bytes from the initial $sp
+      addi(SP, SP, 4),
+      jal(TEXT_START + 0x14),
+      addi(SP, SP, 2),
+      syscall(),
+      // f
+      addi(K0, 0, 1),
+      jr(RA),
+    ],
+    5,
+  );
+
+  k9::assert_equal!(
+    violations,
+    vec![
+      (
+        TEXT_START + 0x8,
+        ViolationKind::MisalignedStack {
+          sp: STACK_POINTER - 4,
+          alignment: 8
+        }
+      ),
+      (
+        TEXT_START + 0x14,
+        ViolationKind::KernelRegister { reg: K0 as u8 }
+      ),
+      (
+        TEXT_START + 0xc,
+        ViolationKind::MisalignedStack {
+          sp: STACK_POINTER - 2,
+          alignment: 4
+        }
+      ),
+    ]
+  );
+}
+
+#[test]
+fn frames_are_aligned_from_the_initial_stack_pointer() {
+  // MARS only aligns the initial $sp on a word
+  k9::assert_equal!(*Registers::init().r(SP as usize).unwrap(), STACK_POINTER);
+
+  let (violations, _) = check(
+    &[
+      jal(TEXT_START + 0x14),
+      addi(SP, SP, -8),
+      jal(TEXT_START + 0x14),
+      addi(SP, SP, 8),
+      syscall(),
+      // f
+      jr(RA),
+    ],
+    5,
+  );
+
+  k9::assert_equal!(violations, vec![]);
+}
@@ -0,0 +1,136 @@
+use mips_cpu::console::Buffered;
+use mips_cpu::environment::{Environment, Random, CYCLES_PER_MS};
+use mips_cpu::syscall::{Outcome, SyscallError};
+use mips_cpu::Cpu;
+use mips_test::reg::{S0, S1, S2};
+use mips_test::{register, run_syscalls};
+
+fn cpu(source: &str) -> Cpu {
+  let mut cpu = mips_test::cpu(source);
+  *cpu.environment_mut() = Environment::deterministic();
+  cpu
+}
+
+/// Run `cpu` until it exits, servicing system calls and collecting sleeps.
+fn run(cpu: &mut Cpu) -> Result<Vec<u32>, SyscallError> {
+  let mut sleeps = Vec::new();
+
+  loop {
+    match run_syscalls(cpu, &mut Buffered::default())? {
+      Outcome::Sleep(millis) => sleeps.push(millis),
+      Outcome::Exit(_) => return Ok(sleeps),
+      outcome => panic!("unexpected {outcome:?}"),
+    }
+  }
+}
+
+#[test]
+fn deterministic_time_follows_cycles_and_sleeps() {
+  let mut cpu = cpu(
+    "
+    li $t0, 2000
+    loop:
+    addi $t0, $t0, -1
+    bne $t0, $zero, loop
+    li $v0, 30
+    syscall
+    add $s0, $a0, $zero
+    li $a0, 250
+    li $v0, 32
+    syscall
+    li $v0, 30
+    syscall
+    add $s1, $a0, $zero
+    li $v0, 10
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(run(&mut cpu).unwrap(), vec![250]);
+
+  let before = register(&cpu, S0);
+  k9::assert_equal!(before as u64, 4003 / CYCLES_PER_MS);
+  k9::assert_equal!(register(&cpu, S1), before + 250);
+}
+
+#[test]
+fn generators_follow_java_random() {
+  let mut random = Random::new(42);
+  k9::assert_equal!(random.next_int(), -1170105035);
+
+  let mut random = Random::new(42);
+  k9::assert_equal!(random.next_double(), 0.7275636800328681);
+
+  let mut random = Random::new(42);
+  let values = (0..1000)
+    .map(|_| random.next_int_below(7))
+    .collect::<Vec<_>>();
+  assert!(values.iter().all(|v| (0..7).contains(v)));
+}
+
+const RANDOM: &str = "
+  li $a0, 1
+  li $a1, 100
+  li $v0, 42
+  syscall
+  add $s0, $a0, $zero
+  li $a0, 2
+  li $a1, 100
+  li $v0, 42
+  syscall
+  add $s1, $a0, $zero
+  li $a0, 3
+  li $a1, 42
+  li $v0, 40
+  syscall
+  li $a0, 3
+  li $v0, 41
+  syscall
+  add $s2, $a0, $zero
+  li $a0, 3
+  li $v0, 44
+  syscall
+  li $v0, 10
+  syscall
+";
+
+#[test]
+fn deterministic_runs_are_reproducible() {
+  let mut first = cpu(RANDOM);
+  let mut second = cpu(RANDOM);
+  run(&mut first).unwrap();
+  run(&mut second).unwrap();
+
+  for n in [16, 17, 18] {
+    k9::assert_equal!(register(&first, n), register(&second, n));
+  }
+
+  // unseeded generators start from their id
+  k9::assert_equal!(
+    register(&first, S0) as i32,
+    Random::new(1).next_int_below(100)
+  );
+  k9::assert_equal!(
+    register(&first, S1) as i32,
+    Random::new(2).next_int_below(100)
+  );
+
+  // the seeded one continues after its first number
+  let mut seeded = Random::new(42);
+  k9::assert_equal!(register(&first, S2) as i32, seeded.next_int());
+  k9::assert_equal!(first.registers().cop1.double(0), seeded.next_double());
+}
+
+#[test]
+fn empty_ranges_are_errors() {
+  let mut cpu = cpu(
+    "
+    li $a0, 0
+    li $a1, 0
+    li $v0, 42
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(run(&mut cpu), Err(SyscallError::Bound(0)));
+}
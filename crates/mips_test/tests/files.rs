@@ -0,0 +1,262 @@
+use mips_cpu::console::Buffered;
+use mips_cpu::fs::{FileSystem, Host, Memory, Mode};
+use mips_cpu::syscall::Outcome;
+use mips_test::reg::{S0, S1, S2};
+use mips_test::{cpu, register, run_syscalls};
+use std::io::Read;
+use std::path::PathBuf;
+
+/// Copy `in.txt` to `out.txt`, keeping descriptors in `$s0`, `$s1`, and the
+/// number of bytes copied in `$s2`.
+const COPY: &str = "
+  .data
+  input:  .asciiz \"in.txt\"
+  output: .asciiz \"out.txt\"
+  buffer: .space 64
+
+  .text
+  la $a0, input
+  li $a1, 0
+  li $v0, 13
+  syscall
+  add $s0, $v0, $zero
+  la $a0, output
+  li $a1, 1
+  li $v0, 13
+  syscall
+  add $s1, $v0, $zero
+  add $a0, $s0, $zero
+  la $a1, buffer
+  li $a2, 64
+  li $v0, 14
+  syscall
+  add $s2, $v0, $zero
+  add $a0, $s1, $zero
+  la $a1, buffer
+  add $a2, $s2, $zero
+  li $v0, 15
+  syscall
+  add $a0, $s0, $zero
+  li $v0, 16
+  syscall
+  add $a0, $s1, $zero
+  li $v0, 16
+  syscall
+  li $v0, 10
+  syscall
+";
+
+#[test]
+fn files_are_copied_in_memory() {
+  let files = Memory::default().with_file("in.txt", "hello files");
+  let mut cpu = cpu(COPY);
+  cpu.set_file_system(files.clone());
+
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut Buffered::default()).unwrap(),
+    Outcome::Exit(0)
+  );
+  k9::assert_equal!((register(&cpu, S0), register(&cpu, S1)), (3, 4));
+  k9::assert_equal!(register(&cpu, S2), 11);
+  k9::assert_equal!(files.contents("out.txt"), Some(b"hello files".to_vec()));
+  k9::assert_equal!(cpu.files().descriptors().count(), 0);
+}
+
+#[test]
+fn missing_files_and_descriptors_fail() {
+  let mut cpu = cpu(
+    "
+    .data
+    name: .asciiz \"missing.txt\"
+
+    .text
+    la $a0, name
+    li $a1, 0
+    li $v0, 13
+    syscall
+    add $s0, $v0, $zero
+    li $a0, 7
+    la $a1, name
+    li $a2, 4
+    li $v0, 15
+    syscall
+    add $s1, $v0, $zero
+    li $v0, 10
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut Buffered::default()).unwrap(),
+    Outcome::Exit(0)
+  );
+  k9::assert_equal!(register(&cpu, S0), u32::MAX);
+  k9::assert_equal!(register(&cpu, S1), u32::MAX);
+}
+
+#[test]
+fn writes_are_cut_to_64_kib() {
+  let files = Memory::default();
+  let mut cpu = cpu(
+    "
+    .data
+    name: .asciiz \"big.bin\"
+
+    .text
+    la $a0, name
+    li $a1, 1
+    li $v0, 13
+    syscall
+    add $a0, $v0, $zero
+    lui $a1, 0x1001
+    lui $a2, 0x2
+    li $v0, 15
+    syscall
+    add $s0, $v0, $zero
+    li $v0, 10
+    syscall
+  ",
+  );
+  cpu.set_file_system(files.clone());
+
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut Buffered::default()).unwrap(),
+    Outcome::Exit(0)
+  );
+  k9::assert_equal!(register(&cpu, S0), 0x10000);
+  k9::assert_equal!(files.contents("big.bin").map(|c| c.len()), Some(0x10000));
+}
+
+#[test]
+fn standard_descriptors_use_the_console() {
+  let mut cpu = cpu(
+    "
+    .data
+    buffer: .space 8
+
+    .text
+    li $a0, 0
+    la $a1, buffer
+    li $a2, 3
+    li $v0, 14
+    syscall
+    add $s0, $v0, $zero
+    li $a0, 1
+    la $a1, buffer
+    add $a2, $s0, $zero
+    li $v0, 15
+    syscall
+    li $a0, 0
+    la $a1, buffer
+    li $a2, 8
+    li $v0, 14
+    syscall
+    add $a2, $v0, $zero
+    li $a0, 2
+    la $a1, buffer
+    li $v0, 15
+    syscall
+    li $v0, 10
+    syscall
+  ",
+  );
+
+  let mut console = Buffered::default();
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut console).unwrap(),
+    Outcome::AwaitInput
+  );
+
+  console.push_input("abcde");
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut console).unwrap(),
+    Outcome::Exit(0)
+  );
+  k9::assert_equal!(console.output(), "abcde\n");
+}
+
+/// Directory under the system temporary directory, removed with everything it
+/// holds when dropped, even when the test fails.
+struct TempDir(PathBuf);
+
+impl TempDir {
+  fn new(name: &str) -> TempDir {
+    let path = std::env::temp_dir().join(format!("mips-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&path).unwrap();
+    TempDir(path)
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.0);
+  }
+}
+
+#[test]
+fn host_files_stay_under_the_root() {
+  let root = TempDir::new("fs");
+  std::fs::write(root.0.join("in.txt"), "from the host").unwrap();
+
+  let mut host = Host::new(&root.0);
+  for path in ["../in.txt", "/etc/passwd", "a/../../in.txt"] {
+    let error = host.open(path, Mode::Read).err().unwrap();
+    k9::assert_equal!(error.kind(), std::io::ErrorKind::PermissionDenied);
+  }
+
+  let mut cpu = cpu(COPY);
+  cpu.set_file_system(host);
+
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut Buffered::default()).unwrap(),
+    Outcome::Exit(0)
+  );
+  let copied = std::fs::read_to_string(root.0.join("out.txt"));
+  k9::assert_equal!(copied.unwrap(), "from the host");
+}
+
+#[cfg(unix)]
+#[test]
+fn host_links_stay_under_the_root() {
+  let base = TempDir::new("links");
+  let root = base.0.join("root");
+  std::fs::create_dir_all(root.join("dir")).unwrap();
+  std::fs::write(base.0.join("secret.txt"), "outside").unwrap();
+  std::fs::write(root.join("in.txt"), "inside").unwrap();
+
+  let link =
+    |target: &str, name: &str| std::os::unix::fs::symlink(target, root.join(name)).unwrap();
+  link("in.txt", "inner.txt");
+  link("dir", "inner");
+  link("../secret.txt", "secret.txt");
+  link("..", "up");
+  link("../missing.txt", "dangling.txt");
+  link("dir/created.txt", "created.txt");
+
+  let mut host = Host::new(&root);
+  let mut read = |path: &str, mode: Mode| {
+    host.open(path, mode).map(|mut file| {
+      let mut contents = String::new();
+      file.read_to_string(&mut contents).unwrap_or_default();
+      contents
+    })
+  };
+
+  k9::assert_equal!(read("inner.txt", Mode::Read).unwrap(), "inside");
+  assert!(read("inner/new.txt", Mode::Write).is_ok());
+  assert!(read("created.txt", Mode::Write).is_ok());
+  assert!(root.join("dir/created.txt").exists());
+
+  for (path, mode) in [
+    ("secret.txt", Mode::Read),
+    ("up/secret.txt", Mode::Read),
+    ("up/new.txt", Mode::Write),
+    ("dangling.txt", Mode::Write),
+  ] {
+    let error = read(path, mode).err().map(|e| e.kind());
+    k9::assert_equal!(error, Some(std::io::ErrorKind::PermissionDenied), "{path}");
+  }
+
+  assert!(!base.0.join("new.txt").exists());
+  assert!(!base.0.join("missing.txt").exists());
+}
@@ -0,0 +1,338 @@
+use mips_cpu::exception::Exception;
+use mips_cpu::Cpu;
+
+/// FCSR fields, as `cop1::flag` bits shifted in place.
+const FLAGS: u32 = 2;
+const ENABLES: u32 = 7;
+const CAUSE: u32 = 12;
+
+const INEXACT: u32 = 1 << 0;
+const UNDERFLOW: u32 = 1 << 1;
+const OVERFLOW: u32 = 1 << 2;
+const DIV_BY_ZERO: u32 = 1 << 3;
+const INVALID: u32 = 1 << 4;
+
+/// Run `source` until it raises an exception, and return it.
+fn run(source: &str) -> (Cpu, Exception) {
+  let mut cpu = mips_test::cpu(source);
+  let exception = mips_test::run(&mut cpu, 1000).unwrap();
+  (cpu, exception)
+}
+
+/// Run `source` with rounding mode `rm` until the `syscall` ending it.
+fn run_with(rm: u32, source: &str) -> Cpu {
+  let source = format!("li $t0, {rm}\nctc1 $t0, $31\n{source}\nsyscall");
+  let (cpu, exception) = run(&source);
+  k9::assert_equal!(exception, Exception::Syscall);
+  cpu
+}
+
+fn cause(cpu: &Cpu) -> u32 {
+  (cpu.registers().cop1.fcsr >> CAUSE) & 0x3f
+}
+
+fn flags(cpu: &Cpu) -> u32 {
+  (cpu.registers().cop1.fcsr >> FLAGS) & 0x1f
+}
+
+#[test]
+fn arithmetic() {
+  let cpu = run_with(
+    0,
+    "
+    .data
+    a: .double 6.0
+    b: .double 1.5
+    x: .float 2.5
+    y: .float -4.0
+    .text
+    la $t1, a
+    ldc1 $f0, 0($t1)
+    ldc1 $f2, 8($t1)
+    lwc1 $f4, 16($t1)
+    lwc1 $f5, 20($t1)
+    add.d $f6, $f0, $f2
+    sub.d $f8, $f2, $f0
+    mul.s $f10, $f4, $f5
+    div.s $f11, $f5, $f4
+    sqrt.d $f12, $f0
+    abs.s $f14, $f5
+    neg.d $f16, $f2
+    cvt.d.s $f18, $f4
+    cvt.s.d $f20, $f0
+  ",
+  );
+
+  let cop1 = &cpu.registers().cop1;
+  k9::assert_equal!(cop1.double(6), 7.5);
+  k9::assert_equal!(cop1.double(8), -4.5);
+  k9::assert_equal!(cop1.single(10), -10.0);
+  k9::assert_equal!(cop1.single(11), -1.6);
+  k9::assert_equal!(cop1.double(12), 6f64.sqrt());
+  k9::assert_equal!(cop1.single(14), 4.0);
+  k9::assert_equal!(cop1.double(16), -1.5);
+  k9::assert_equal!(cop1.double(18), 2.5);
+  k9::assert_equal!(cop1.single(20), 6.0);
+}
+
+#[test]
+fn every_rounding_mode_applies_to_arithmetic() {
+  let source = "
+    li $t1, 1
+    li $t2, 3
+    li $t3, -1
+    li $t4, 2
+    mtc1 $t1, $f0
+    mtc1 $t2, $f1
+    mtc1 $t3, $f2
+    mtc1 $t4, $f3
+    cvt.s.w $f0, $f0
+    cvt.s.w $f1, $f1
+    cvt.s.w $f2, $f2
+    cvt.s.w $f3, $f3
+    div.s $f4, $f0, $f1
+    div.s $f5, $f2, $f1
+    cvt.d.s $f6, $f0
+    cvt.d.s $f8, $f1
+    div.d $f10, $f6, $f8
+    cvt.d.s $f12, $f3
+    sqrt.d $f14, $f12
+  ";
+
+  // 1/3 and -1/3 in single precision, 1/3 and sqrt(2) in double precision
+  for (rm, third, minus_third, double_third, root) in [
+    (
+      0,
+      0x3eaa_aaab,
+      0xbeaa_aaab,
+      0x3fd5_5555_5555_5555,
+      0x3ff6_a09e_667f_3bcd,
+    ),
+    (
+      1,
+      0x3eaa_aaaa,
+      0xbeaa_aaaa,
+      0x3fd5_5555_5555_5555,
+      0x3ff6_a09e_667f_3bcc,
+    ),
+    (
+      2,
+      0x3eaa_aaab,
+      0xbeaa_aaaa,
+      0x3fd5_5555_5555_5556,
+      0x3ff6_a09e_667f_3bcd,
+    ),
+    (
+      3,
+      0x3eaa_aaaa,
+      0xbeaa_aaab,
+      0x3fd5_5555_5555_5555,
+      0x3ff6_a09e_667f_3bcc,
+    ),
+  ] {
+    let cpu = run_with(rm, source);
+    let cop1 = &cpu.registers().cop1;
+
+    k9::assert_equal!(cop1.word(4), third, "rm {rm}");
+    k9::assert_equal!(cop1.word(5), minus_third, "rm {rm}");
+    k9::assert_equal!(cop1.double(10).to_bits(), double_third, "rm {rm}");
+    k9::assert_equal!(cop1.double(14).to_bits(), root, "rm {rm}");
+  }
+}
+
+#[test]
+fn every_rounding_mode_applies_to_conversions() {
+  let source = "
+    .data
+    tenth: .double 0.1
+    .text
+    la $t1, tenth
+    ldc1 $f0, 0($t1)
+    cvt.s.d $f2, $f0
+    neg.d $f0, $f0
+    cvt.s.d $f3, $f0
+    lui $t2, 0x0100
+    addiu $t2, $t2, 1
+    mtc1 $t2, $f4
+    cvt.s.w $f5, $f4
+  ";
+
+  // 0.1 and -0.1 to single precision, 2^24 + 1 to single precision
+  for (rm, tenth, minus_tenth, word) in [
+    (0, 0x3dcc_cccd, 0xbdcc_cccd, 0x4b80_0000),
+    (1, 0x3dcc_cccc, 0xbdcc_cccc, 0x4b80_0000),
+    (2, 0x3dcc_cccd, 0xbdcc_cccc, 0x4b80_0001),
+    (3, 0x3dcc_cccc, 0xbdcc_cccd, 0x4b80_0000),
+  ] {
+    let cpu = run_with(rm, source);
+    let cop1 = &cpu.registers().cop1;
+
+    k9::assert_equal!(cop1.word(2), tenth, "rm {rm}");
+    k9::assert_equal!(cop1.word(3), minus_tenth, "rm {rm}");
+    k9::assert_equal!(cop1.word(5), word, "rm {rm}");
+  }
+}
+
+#[test]
+fn overflow_rounds_to_infinity_or_the_largest_value() {
+  let source = "
+    .data
+    max: .float 3.4028235e38
+    .text
+    la $t1, max
+    lwc1 $f0, 0($t1)
+    add.s $f1, $f0, $f0
+    neg.s $f2, $f0
+    add.s $f3, $f2, $f2
+  ";
+
+  for (rm, positive, negative) in [
+    (0, f32::INFINITY, f32::NEG_INFINITY),
+    (1, f32::MAX, f32::MIN),
+    (2, f32::INFINITY, f32::MIN),
+    (3, f32::MAX, f32::NEG_INFINITY),
+  ] {
+    let cpu = run_with(rm, source);
+    let cop1 = &cpu.registers().cop1;
+
+    k9::assert_equal!(cop1.single(1), positive, "rm {rm}");
+    k9::assert_equal!(cop1.single(3), negative, "rm {rm}");
+    k9::assert_equal!(cause(&cpu), OVERFLOW | INEXACT);
+  }
+}
+
+#[test]
+fn causes_are_per_operation_and_flags_are_sticky() {
+  let source = "
+    .data
+    values: .float 1.0, 3.0, 0.0
+    # 1e-30
+    .word 0x0da24260
+    .text
+    la $t1, values
+    lwc1 $f0, 0($t1)
+    lwc1 $f1, 4($t1)
+    lwc1 $f2, 8($t1)
+    lwc1 $f3, 12($t1)
+  ";
+
+  for (operation, expected) in [
+    ("add.s $f4, $f0, $f1", 0),
+    ("div.s $f4, $f0, $f1", INEXACT),
+    ("div.s $f4, $f0, $f2", DIV_BY_ZERO),
+    ("div.s $f4, $f2, $f2", INVALID),
+    ("sqrt.s $f4, $f2", 0),
+    ("mul.s $f4, $f3, $f3", UNDERFLOW | INEXACT),
+  ] {
+    let cpu = run_with(0, &format!("{source}\n{operation}"));
+    k9::assert_equal!(cause(&cpu), expected, "{operation}");
+    k9::assert_equal!(flags(&cpu), expected, "{operation}");
+  }
+
+  // an exact operation clears the cause, not the flags
+  let cpu = run_with(
+    0,
+    &format!("{source}\ndiv.s $f4, $f0, $f1\nmul.s $f5, $f0, $f1\ndiv.s $f6, $f0, $f2"),
+  );
+  k9::assert_equal!(cause(&cpu), DIV_BY_ZERO);
+  k9::assert_equal!(flags(&cpu), INEXACT | DIV_BY_ZERO);
+  k9::assert_equal!(cpu.registers().cop1.single(5), 3.0);
+}
+
+#[test]
+fn converting_a_nan_to_single_is_invalid() {
+  // a quiet double NaN in $f2 and $f3
+  let cpu = run_with(
+    0,
+    "
+    lui $t0, 0x7ff8
+    mtc1 $zero, $f2
+    mtc1 $t0, $f3
+    cvt.s.d $f4, $f2
+  ",
+  );
+
+  assert!(cpu.registers().cop1.single(4).is_nan());
+  k9::assert_equal!(cause(&cpu), INVALID);
+  k9::assert_equal!(flags(&cpu), INVALID);
+}
+
+#[test]
+fn enabled_exceptions_trap_without_writing() {
+  for (enables, operation, expected) in [
+    (DIV_BY_ZERO, "div.s $f4, $f0, $f2", DIV_BY_ZERO),
+    (INEXACT, "div.s $f4, $f0, $f1", INEXACT),
+    (INVALID, "sqrt.s $f4, $f5", INVALID),
+  ] {
+    let source = format!(
+      "
+      li $t0, {}
+      ctc1 $t0, $31
+      li $t1, 1
+      li $t2, 3
+      li $t3, -1
+      li $t4, 42
+      mtc1 $t1, $f0
+      mtc1 $t2, $f1
+      mtc1 $t3, $f5
+      mtc1 $t4, $f4
+      cvt.s.w $f0, $f0
+      cvt.s.w $f1, $f1
+      cvt.s.w $f5, $f5
+      {operation}
+      syscall
+    ",
+      enables << ENABLES,
+    );
+    let (cpu, exception) = run(&source);
+
+    k9::assert_equal!(exception, Exception::FloatingPoint, "{operation}");
+    k9::assert_equal!(cpu.registers().pc, 0x0040_0034, "{operation}");
+    k9::assert_equal!(cpu.registers().cop1.word(4), 42, "{operation}");
+    k9::assert_equal!(cause(&cpu), expected, "{operation}");
+    // trapping exceptions are not accumulated
+    k9::assert_equal!(flags(&cpu), 0, "{operation}");
+  }
+
+  // disabled exceptions still write their result
+  let cpu = run_with(
+    INEXACT << ENABLES,
+    "
+    li $t1, 1
+    mtc1 $t1, $f0
+    mtc1 $zero, $f2
+    cvt.s.w $f0, $f0
+    div.s $f4, $f0, $f2
+  ",
+  );
+  k9::assert_equal!(cpu.registers().cop1.single(4), f32::INFINITY);
+  k9::assert_equal!(flags(&cpu), DIV_BY_ZERO);
+}
+
+#[test]
+fn sdc1_stores_both_words_or_none() {
+  let source = "
+    lui $t0, 0x1001
+    li $t1, -1
+    sw $t1, 0($t0)
+    sw $t1, 4($t0)
+    li $t2, 0x1234
+    li $t3, 0x5678
+    mtc1 $t2, $f0
+    mtc1 $t3, $f1
+  ";
+
+  let mut cpu = run_with(0, &format!("{source}\nsdc1 $f0, 0($t0)"));
+  let memory = cpu.memory_mut();
+  k9::assert_equal!(memory.load_word(0x1001_0000), Ok(0x1234));
+  k9::assert_equal!(memory.load_word(0x1001_0004), Ok(0x5678));
+
+  for offset in [4, 2] {
+    let (mut cpu, exception) = run(&format!("{source}\nsdc1 $f0, {offset}($t0)"));
+    k9::assert_equal!(exception, Exception::AddrStore);
+
+    let memory = cpu.memory_mut();
+    k9::assert_equal!(memory.load_word(0x1001_0000), Ok(0xffff_ffff));
+    k9::assert_equal!(memory.load_word(0x1001_0004), Ok(0xffff_ffff));
+  }
+}
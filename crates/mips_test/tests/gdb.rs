@@ -0,0 +1,309 @@
+use mips_asm::assemble;
+use mips_cpu::console::{Buffered, Console};
+use mips_cpu::Cpu;
+use mips_gdb::packet::{Connection, Incoming, Transport};
+use mips_gdb::GdbStub;
+use mips_program::Context;
+use std::cell::RefCell;
+use std::io::{self, Cursor, Read, Write};
+use std::rc::Rc;
+
+/// In-memory transport: GDB's side is written up front, our side is kept.
+struct Pipe {
+  input: Cursor<Vec<u8>>,
+  output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Pipe {
+  fn new(input: &[u8]) -> (Pipe, Rc<RefCell<Vec<u8>>>) {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let pipe = Pipe {
+      input: Cursor::new(input.to_vec()),
+      output: output.clone(),
+    };
+
+    (pipe, output)
+  }
+}
+
+impl Read for Pipe {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    self.input.read(buf)
+  }
+}
+
+impl Write for Pipe {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.output.borrow_mut().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Transport for Pipe {
+  fn set_nonblocking(&self, _: bool) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+fn frame(data: &str) -> String {
+  let checksum = data.bytes().fold(0u8, |acc, b| acc.wrapping_add(b));
+  format!("${data}#{checksum:02x}")
+}
+
+/// Split `output` into the packets it holds, checking their checksums.
+fn packets(output: &[u8]) -> Vec<String> {
+  let output = String::from_utf8_lossy(output);
+
+  output
+    .split('$')
+    .skip(1)
+    .map(|frame| {
+      let (data, checksum) = frame.split_once('#').unwrap();
+      let expected = data.bytes().fold(0u8, |acc, b| acc.wrapping_add(b));
+      k9::assert_equal!(&checksum[..2], format!("{expected:02x}"));
+      data.to_owned()
+    })
+    .collect()
+}
+
+fn receive(conn: &mut Connection<Pipe>) -> Vec<u8> {
+  match conn.receive().unwrap() {
+    Some(Incoming::Packet(packet)) => packet,
+    Some(Incoming::Interrupt) => panic!("unexpected interrupt"),
+    None => panic!("unexpected end of stream"),
+  }
+}
+
+#[test]
+fn packets_are_acknowledged_and_unescaped() {
+  // `}` escapes the next byte, xored with 0x20
+  let input = format!("+{}{}", frame("m0,4"), frame("X0,1:}]"));
+  let (pipe, output) = Pipe::new(input.as_bytes());
+  let mut conn = Connection::new(pipe);
+
+  k9::assert_equal!(receive(&mut conn), b"m0,4".to_vec());
+  k9::assert_equal!(receive(&mut conn), b"X0,1:}".to_vec());
+  k9::assert_equal!(output.borrow().clone(), b"++".to_vec());
+}
+
+#[test]
+fn bad_checksums_are_retransmitted() {
+  let input = format!("$g#00{}", frame("g"));
+  let (pipe, output) = Pipe::new(input.as_bytes());
+  let mut conn = Connection::new(pipe);
+
+  k9::assert_equal!(receive(&mut conn), b"g".to_vec());
+  k9::assert_equal!(output.borrow().clone(), b"-+".to_vec());
+}
+
+#[test]
+fn sent_packets_wait_for_acknowledgment() {
+  let (pipe, output) = Pipe::new(b"-+");
+  let mut conn = Connection::new(pipe);
+  conn.send(b"OK").unwrap();
+
+  k9::assert_equal!(output.borrow().clone(), b"$OK#9a$OK#9a".to_vec());
+}
+
+#[test]
+fn interrupts_keep_other_bytes() {
+  let input = format!("\x03{}\x03{}", frame("g"), frame("?"));
+  let (pipe, _) = Pipe::new(input.as_bytes());
+  let mut conn = Connection::new(pipe);
+  conn.disable_acks();
+
+  assert!(matches!(conn.receive().unwrap(), Some(Incoming::Interrupt)));
+  assert!(conn.poll_interrupt().unwrap());
+  k9::assert_equal!(receive(&mut conn), b"g".to_vec());
+  k9::assert_equal!(receive(&mut conn), b"?".to_vec());
+  assert!(!conn.poll_interrupt().unwrap());
+}
+
+/// Counts `$t0` down from 3000, long enough to poll for interrupts.
+const COUNTDOWN: &str = "
+  li $t0, 3000
+  loop:
+  addi $t0, $t0, -1
+  bne $t0, $zero, loop
+  li $v0, 10
+  syscall
+";
+
+/// Console whose contents stay readable once the stub owns it.
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Buffered>>);
+
+impl Console for Shared {
+  fn write(&mut self, text: &str) {
+    self.0.borrow_mut().write(text);
+  }
+
+  fn read_line(&mut self) -> Option<String> {
+    self.0.borrow_mut().read_line()
+  }
+}
+
+/// Run a GDB session made of `commands` against `source`. Returns the replies
+/// and the stub.
+fn session(source: &str, commands: &[&str]) -> (Vec<String>, GdbStub) {
+  session_with_console(source, commands, Shared::default())
+}
+
+fn session_with_console(
+  source: &str,
+  commands: &[&str],
+  console: Shared,
+) -> (Vec<String>, GdbStub) {
+  let program = assemble(source).unwrap().program;
+  let mut stub = GdbStub::new(Cpu::new(Rc::new(program))).with_console(console);
+
+  let mut input = frame("QStartNoAckMode") + "+";
+  for command in commands {
+    input += &frame(command);
+  }
+
+  let (pipe, output) = Pipe::new(input.as_bytes());
+  stub.serve(pipe).unwrap();
+
+  let output = output.borrow();
+  k9::assert_equal!(output.first(), Some(&b'+'));
+  let mut replies = packets(&output);
+  k9::assert_equal!(replies.remove(0), "OK");
+
+  (replies, stub)
+}
+
+#[test]
+fn registers_are_read_and_written() {
+  let (replies, stub) = session(COUNTDOWN, &["g"]);
+  let registers = &replies[0];
+
+  // 72 registers, the PC is number 37
+  k9::assert_equal!(registers.len(), 72 * 8);
+  k9::assert_equal!(&registers[37 * 8..38 * 8], "00004000");
+
+  let mut written = registers.clone();
+  written.replace_range(8 * 8..9 * 8, "2a000000");
+  let (replies, _) = session(COUNTDOWN, &[&format!("G{written}"), "p8", "g"]);
+
+  k9::assert_equal!(replies[0], "OK");
+  k9::assert_equal!(replies[1], "2a000000");
+  k9::assert_equal!(replies[2], written);
+  k9::assert_equal!(stub.cpu().registers().pc, 0x0040_0000);
+}
+
+#[test]
+fn memory_is_read_and_written() {
+  let (replies, stub) = session(
+    COUNTDOWN,
+    &[
+      "m400000,4",
+      "M10010000,4:78563412",
+      "m10010000,4",
+      "M10010000,4:78",
+    ],
+  );
+
+  // li $t0, 3000 is addiu $t0, $zero, 0xbb8
+  k9::assert_equal!(replies[0], "b80b0824");
+  k9::assert_equal!(replies[1], "OK");
+  k9::assert_equal!(replies[2], "78563412");
+  k9::assert_equal!(replies[3], "E01");
+
+  let memory = stub.cpu().memory();
+  let word = memory.read_range(0x1001_0000, 4, Context::External);
+  k9::assert_equal!(
+    word.unwrap(),
+    vec![Some(0x78), Some(0x56), Some(0x34), Some(0x12)]
+  );
+}
+
+#[test]
+fn memory_reads_fit_in_a_packet() {
+  let (replies, _) = session(COUNTDOWN, &["M10010000,4:78563412", "m10010000,10000"]);
+
+  k9::assert_equal!(replies[1].len(), 0x4000);
+  k9::assert_equal!(&replies[1][..8], "78563412");
+}
+
+#[test]
+fn breakpoints_stop_continue_and_steps_trap() {
+  let (replies, stub) = session(
+    COUNTDOWN,
+    &[
+      "Z0,40000c,4",
+      "c",
+      "p8",
+      "p25",
+      "s",
+      "p25",
+      "z0,40000c,4",
+      "c",
+      "p2",
+    ],
+  );
+
+  // the breakpoint is hit after the loop, which polled for interrupts and
+  // kept the packets after `c`
+  k9::assert_equal!(replies[0], "OK");
+  k9::assert_equal!(replies[1], "S05");
+  k9::assert_equal!(replies[2], "00000000");
+  k9::assert_equal!(replies[3], "0c004000");
+  k9::assert_equal!(replies[4], "S05");
+  k9::assert_equal!(replies[5], "10004000");
+  k9::assert_equal!(replies[6], "OK");
+  // the exit system call ends the program
+  k9::assert_equal!(replies[7], "W00");
+  k9::assert_equal!(replies[8], "0a000000");
+  k9::assert_equal!(replies.len(), 9);
+  k9::assert_equal!(stub.cpu().registers().pc, 0x0040_0010);
+}
+
+#[test]
+fn system_calls_are_serviced_until_the_program_exits() {
+  let console = Shared::default();
+  console.0.borrow_mut().push_input("41");
+
+  let (replies, _) = session_with_console(
+    "
+    li $v0, 5
+    syscall
+    addi $a0, $v0, 1
+    li $v0, 1
+    syscall
+    li $v0, 17
+    syscall
+    ",
+    &["s", "c", "s", "c", "?"],
+    console.clone(),
+  );
+
+  // stepping over a system call services it
+  k9::assert_equal!(replies[0], "S05");
+  k9::assert_equal!(replies[1], "W2a");
+  // the program is gone, it does not run again
+  k9::assert_equal!(replies[2], "W2a");
+  k9::assert_equal!(replies[3], "W2a");
+  k9::assert_equal!(replies[4], "W2a");
+  k9::assert_equal!(console.0.borrow().output(), "42");
+}
+
+#[test]
+fn failed_system_calls_stop_with_sigsys() {
+  let (replies, stub) = session("li $v0, 99\nsyscall", &["c", "c"]);
+
+  k9::assert_equal!(replies, vec!["S0c".to_string(), "S0c".to_string()]);
+  k9::assert_equal!(stub.cpu().registers().pc, 0x0040_0004);
+}
+
+#[test]
+fn waiting_for_input_stops_on_the_system_call() {
+  let console = Shared::default();
+  let (replies, stub) = session_with_console("li $v0, 5\nsyscall", &["c"], console);
+
+  k9::assert_equal!(replies, vec!["S02".to_string()]);
+  k9::assert_equal!(stub.cpu().registers().pc, 0x0040_0004);
+}
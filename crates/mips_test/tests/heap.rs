@@ -0,0 +1,66 @@
+use mips_cpu::console::Buffered;
+use mips_cpu::mem::{SbrkError, HEAP_START};
+use mips_cpu::syscall::{Outcome, SyscallError};
+use mips_cpu::Cpu;
+use mips_test::asm::*;
+use mips_test::reg::{A0, S0, S1, T0, T1, T2, T3, V0};
+use mips_test::{register, run_syscalls};
+
+#[test]
+fn sbrk_allocates_usable_memory() {
+  let program = mips_test::program(&[
+    // 10 bytes, rounded up to 12
+    addi(A0, 0, 10),
+    addi(V0, 0, 9),
+    syscall(),
+    add(S0, V0, 0),
+    addi(T0, 0, 77),
+    sw(T0, 0, S0),
+    addi(A0, 0, 4096),
+    addi(V0, 0, 9),
+    syscall(),
+    add(S1, V0, 0),
+    lw(T1, 0, S0),
+    // first word of the second segment of the store
+    lui(T2, 0x1004),
+    sw(T0, 2048, T2),
+    lw(T3, 2048, T2),
+    addi(V0, 0, 10),
+    syscall(),
+  ]);
+  let mut cpu = Cpu::new(program);
+
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut Buffered::default()),
+    Ok(Outcome::Exit(0))
+  );
+  k9::assert_equal!(register(&cpu, S0), HEAP_START);
+  k9::assert_equal!(register(&cpu, S1), HEAP_START + 12);
+  k9::assert_equal!(register(&cpu, T1), 77);
+  k9::assert_equal!(register(&cpu, T3), 77);
+  k9::assert_equal!(cpu.memory().program_break(), HEAP_START + 12 + 4096);
+}
+
+#[test]
+fn heap_growth_is_limited() {
+  let program = mips_test::program(&[addi(A0, 0, 32), addi(V0, 0, 9), syscall()]);
+  let mut cpu = Cpu::new(program);
+  cpu.memory_mut().set_heap_size(16);
+
+  let error = SbrkError::Exhausted {
+    requested: 32,
+    available: 16,
+  };
+  k9::assert_equal!(
+    run_syscalls(&mut cpu, &mut Buffered::default()),
+    Err(SyscallError::Sbrk(error))
+  );
+  // left on the syscall
+  k9::assert_equal!(cpu.registers().pc, 0x00400008);
+  k9::assert_equal!(cpu.memory().program_break(), HEAP_START);
+
+  k9::assert_equal!(
+    cpu.memory_mut().sbrk(-4),
+    Err(SbrkError::BelowStart { requested: -4 })
+  );
+}
@@ -0,0 +1,504 @@
+use mips_cpu::exception::Exception;
+use mips_cpu::mem::{GLOBAL_POINTER, STACK_POINTER, TEXT_START};
+use mips_cpu::{cop0, Cpu};
+use mips_test::asm::{addi, eret, mfc0, mtc0, syscall};
+use mips_test::reg::{RA, T0, T1, T2, T3, T4, T5};
+use mips_test::{cpu, register};
+
+/// Run `source` until it raises an exception, which is expected to be the
+/// `syscall` ending it.
+fn run(source: &str) -> Cpu {
+  let mut cpu = cpu(source);
+  k9::assert_equal!(mips_test::run(&mut cpu, 1000), Some(Exception::Syscall));
+  cpu
+}
+
+/// Run `source` until it raises an exception, and return it.
+fn fault(source: &str) -> (Cpu, Exception) {
+  let mut cpu = cpu(source);
+  let exception = mips_test::run(&mut cpu, 1000).unwrap();
+  (cpu, exception)
+}
+
+#[test]
+fn operands_may_be_the_destination() {
+  let cpu = run(
+    "
+    li $t0, 5
+    addu $t0, $t0, $t0
+    addiu $t0, $t0, 1
+    li $t1, 3
+    sub $t1, $t1, $t1
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T0), 11);
+  k9::assert_equal!(register(&cpu, T1), 0);
+}
+
+#[test]
+fn slti_compares_signed_values() {
+  let cpu = run(
+    "
+    li $t0, -5
+    slti $t1, $t0, 3
+    lui $t0, 0x8000
+    slti $t2, $t0, 1
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T1), 1);
+  // 0x80000000 - 1 overflows, the comparison must not
+  k9::assert_equal!(register(&cpu, T2), 1);
+
+  let cpu = run(
+    "
+    lui $t0, 0x7fff
+    slti $t1, $t0, -1
+    syscall
+  ",
+  );
+  k9::assert_equal!(register(&cpu, T1), 0);
+}
+
+#[test]
+fn addiu_wraps_and_addi_traps() {
+  let cpu = run(
+    "
+    li $t0, -1
+    addiu $t1, $t0, 2
+    syscall
+  ",
+  );
+  k9::assert_equal!(register(&cpu, T1), 1);
+
+  let (cpu, exception) = fault(
+    "
+    lui $t0, 0x7fff
+    ori $t0, $t0, 0xffff
+    li $t1, 7
+    addi $t1, $t0, 1
+  ",
+  );
+  k9::assert_equal!(exception, Exception::Overflow);
+  // the destination is left alone
+  k9::assert_equal!(register(&cpu, T1), 7);
+}
+
+#[test]
+fn regimm_branches_follow_the_rt_field() {
+  let cpu = run(
+    "
+    li $t1, 7
+    li $t0, -1
+    bltz $t0, negative
+    li $t1, 1
+    negative:
+    bgez $t0, positive
+    li $t2, 2
+    positive:
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T1), 7);
+  k9::assert_equal!(register(&cpu, T2), 2);
+}
+
+#[test]
+fn branches_are_relative_to_the_next_instruction() {
+  let cpu = run(
+    "
+    li $t0, 0
+    li $t1, 0
+    back:
+    addi $t0, $t0, 1
+    li $t2, 3
+    bne $t0, $t2, back
+    beq $t0, $t2, forward
+    li $t1, 1
+    forward:
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T0), 3);
+  k9::assert_equal!(register(&cpu, T1), 0);
+}
+
+#[test]
+fn jumps_stay_in_the_current_region_and_link() {
+  let cpu = run(
+    "
+    li $t0, 0
+    jal function
+    j end
+    function:
+    li $t0, 1
+    jr $ra
+    end:
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T0), 1);
+  k9::assert_equal!(register(&cpu, RA), 0x0040_0008);
+  k9::assert_equal!(cpu.registers().pc, 0x0040_0014);
+}
+
+#[test]
+fn offsets_and_loads_are_sign_extended() {
+  let mut cpu = run(
+    "
+    .data
+    bytes: .byte 0xf0, 0x7f, 0x80, 0xff
+    .text
+    la $t0, bytes
+    addi $t0, $t0, 4
+    lb $t1, -4($t0)
+    lbu $t2, -4($t0)
+    lh $t3, -2($t0)
+    lhu $t4, -2($t0)
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T1), 0xffff_fff0);
+  k9::assert_equal!(register(&cpu, T2), 0xf0);
+  k9::assert_equal!(register(&cpu, 11), 0xffff_ff80);
+  k9::assert_equal!(register(&cpu, 12), 0xff80);
+  k9::assert_equal!(cpu.memory_mut().load_word(0x1001_0000), Ok(0xff80_7ff0));
+}
+
+#[test]
+fn stores_write_their_size() {
+  let mut cpu = run(
+    "
+    lui $t0, 0x1001
+    li $t1, 0x11223344
+    sw $t1, 0($t0)
+    li $t2, -1
+    sh $t2, 4($t0)
+    sb $t2, 7($t0)
+    sb $t1, 1($t0)
+    syscall
+  ",
+  );
+
+  let memory = cpu.memory_mut();
+  k9::assert_equal!(memory.load_word(0x1001_0000), Ok(0x1122_4444));
+  k9::assert_equal!(memory.load_word(0x1001_0004), Ok(0xff00_ffff));
+}
+
+#[test]
+fn syscall_stops_on_the_instruction() {
+  let cpu = run(
+    "
+    li $v0, 10
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(cpu.registers().pc, 0x0040_0004);
+}
+
+#[test]
+fn unaligned_accesses_are_address_errors() {
+  for (access, offset, expected) in [
+    ("lw", 2, Exception::AddrLoadFetch),
+    ("lh", 1, Exception::AddrLoadFetch),
+    ("lhu", 3, Exception::AddrLoadFetch),
+    ("sw", 1, Exception::AddrStore),
+    ("sh", 3, Exception::AddrStore),
+  ] {
+    let source = format!("lui $t0, 0x1001\n{access} $t1, {offset}($t0)");
+    let (cpu, exception) = fault(&source);
+
+    k9::assert_equal!(exception, expected);
+    k9::assert_equal!(cpu.registers().pc, 0x0040_0004);
+    k9::assert_equal!(cpu.memory().fault_addr(), Some(0x1001_0000 + offset));
+  }
+
+  // bytes have no alignment
+  let cpu = run(
+    "
+    lui $t0, 0x1001
+    lb $t1, 3($t0)
+    sb $t1, 1($t0)
+    syscall
+  ",
+  );
+  k9::assert_equal!(register(&cpu, T1), 0);
+}
+
+/// Words which encode no instruction: an unused opcode, SPECIAL function,
+/// REGIMM operation, COP0 operation and COP0 function.
+const RESERVED: [u32; 5] = [
+  0xfc00_0000,
+  0x0000_0005,
+  0x041f_0000,
+  0x43e0_0000,
+  0x4200_003f,
+];
+
+#[test]
+fn reserved_instructions_stop_without_a_handler() {
+  for word in RESERVED {
+    let mut cpu = Cpu::new(mips_test::program(&[word]));
+    let before = cpu.registers().cop0.clone();
+
+    k9::assert_equal!(
+      cpu.cycle(),
+      Err(Exception::ReservedInstruction),
+      "{word:#x}"
+    );
+    k9::assert_equal!(cpu.registers().pc, 0x0040_0000);
+    k9::assert_equal!(cpu.registers().cop0.cause, before.cause);
+    k9::assert_equal!(cpu.registers().cop0.epc, before.epc);
+    k9::assert_equal!(cpu.registers().cop0.status, before.status);
+  }
+}
+
+#[test]
+fn reserved_instructions_are_delivered_to_the_handler() {
+  let handler = [
+    // skip the reserved instruction
+    mfc0(26, cop0::EPC),
+    addi(26, 26, 4),
+    mtc0(26, cop0::EPC),
+    eret(),
+  ];
+  let mut kernel = vec![0; 0x180 / 4];
+  kernel.extend(handler);
+
+  for word in RESERVED {
+    let program = mips_test::kernel_program(&[word, addi(8, 0, 1), syscall()], &kernel);
+    let mut cpu = Cpu::new(program);
+
+    k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::Syscall));
+    k9::assert_equal!(register(&cpu, T0), 1, "{word:#x}");
+    k9::assert_equal!(
+      cpu.registers().cop0.cause >> 2 & 0x1f,
+      Exception::ReservedInstruction as u32
+    );
+  }
+}
+
+#[test]
+fn shifts() {
+  let cpu = run(
+    "
+    li $t0, -16
+    li $t1, 34
+    srl $t2, $t0, 2
+    sra $t3, $t0, 2
+    srlv $t4, $t0, $t1
+    srav $t5, $t0, $t1
+    sllv $t6, $t0, $t1
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T2), 0x3fff_fffc);
+  k9::assert_equal!(register(&cpu, T3), -4i32 as u32);
+  // only the low 5 bits of the amount count
+  k9::assert_equal!(register(&cpu, T4), 0x3fff_fffc);
+  k9::assert_equal!(register(&cpu, T5), -4i32 as u32);
+  k9::assert_equal!(register(&cpu, 14), -64i32 as u32);
+}
+
+#[test]
+fn signed_multiplication_and_division() {
+  let cpu = run(
+    "
+    li $t0, -7
+    li $t1, 2
+    mult $t0, $t1
+    mfhi $t2
+    mflo $t3
+    div $t0, $t1
+    mfhi $t4
+    mflo $t5
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T2), u32::MAX);
+  k9::assert_equal!(register(&cpu, T3), -14i32 as u32);
+  // the remainder takes the sign of the dividend
+  k9::assert_equal!(register(&cpu, T4), -1i32 as u32);
+  k9::assert_equal!(register(&cpu, T5), -3i32 as u32);
+
+  let cpu = run(
+    "
+    li $t0, -7
+    li $t1, 2
+    divu $t0, $t1
+    mfhi $t2
+    mflo $t3
+    div $t0, $zero
+    divu $t0, $zero
+    mfhi $t4
+    mflo $t5
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T2), 1);
+  k9::assert_equal!(register(&cpu, T3), 0x7fff_fffc);
+  // dividing by zero leaves HI and LO alone
+  k9::assert_equal!(register(&cpu, T4), 1);
+  k9::assert_equal!(register(&cpu, T5), 0x7fff_fffc);
+}
+
+#[test]
+fn special2_instructions() {
+  let cpu = run(
+    "
+    li $t0, -3
+    li $t1, 5
+    mul $t2, $t0, $t1
+    mthi $zero
+    li $t3, 100
+    mtlo $t3
+    madd $t0, $t1
+    msub $t1, $t1
+    mflo $t3
+    mfhi $t4
+    mtlo $zero
+    maddu $t0, $t1
+    msubu $t1, $t1
+    mfhi $t5
+    clz $t6, $t1
+    clo $t7, $t0
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T2), -15i32 as u32);
+  // 100 - 15 - 25, then 5 * (2^32 - 3) - 25 over 64 bits
+  k9::assert_equal!(register(&cpu, T3), 60);
+  k9::assert_equal!(register(&cpu, T4), 0);
+  k9::assert_equal!(register(&cpu, T5), 4);
+  k9::assert_equal!(register(&cpu, 14), 29);
+  k9::assert_equal!(register(&cpu, 15), 30);
+}
+
+#[test]
+fn unaligned_words_are_loaded_and_stored_in_two_parts() {
+  let mut cpu = run(
+    "
+    .data
+    bytes: .byte 1, 2, 3, 4, 5, 6, 7, 8
+    .text
+    la $t0, bytes
+    li $t1, -1
+    li $t2, -1
+    lwr $t1, 1($t0)
+    lwl $t1, 4($t0)
+    lwl $t2, 1($t0)
+    lwr $t3, 3($t0)
+    lui $t4, 0xaabb
+    ori $t4, $t4, 0xccdd
+    swr $t4, 2($t0)
+    swl $t4, 5($t0)
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T1), 0x0504_0302);
+  // the bytes up to the address fill the high end, the rest is kept
+  k9::assert_equal!(register(&cpu, T2), 0x0201_ffff);
+  k9::assert_equal!(register(&cpu, T3) & 0xff, 4);
+
+  let memory = cpu.memory_mut();
+  k9::assert_equal!(memory.load_word(0x1001_0000), Ok(0xccdd_0201));
+  k9::assert_equal!(memory.load_word(0x1001_0004), Ok(0x0807_aabb));
+}
+
+#[test]
+fn sc_stores_and_reports_success() {
+  let mut cpu = run(
+    "
+    lui $t0, 0x1001
+    li $t1, 41
+    sw $t1, 0($t0)
+    ll $t2, 0($t0)
+    addi $t2, $t2, 1
+    sc $t2, 0($t0)
+    syscall
+  ",
+  );
+
+  k9::assert_equal!(register(&cpu, T2), 1);
+  k9::assert_equal!(cpu.memory_mut().load_word(0x1001_0000), Ok(42));
+}
+
+#[test]
+fn break_raises_a_breakpoint() {
+  let (cpu, exception) = fault("li $t0, 1\nbreak 5\nli $t0, 2");
+
+  k9::assert_equal!(exception, Exception::Breakpoint);
+  k9::assert_equal!(cpu.registers().pc, 0x0040_0004);
+  k9::assert_equal!(register(&cpu, T0), 1);
+}
+
+#[test]
+fn conditional_traps() {
+  for (trap, taken) in [
+    ("tge $t0, $t1", false),
+    ("tlt $t0, $t1", true),
+    ("tgeu $t0, $t1", true),
+    ("tltu $t0, $t1", false),
+    ("teq $t0, $t0", true),
+    ("tne $t0, $t0", false),
+    ("tgei $t0, -1", true),
+    ("tgei $t0, 0", false),
+    ("tgeiu $t0, -1", true),
+    ("tlti $t0, 0", true),
+    ("tltiu $t0, -1", false),
+    ("tltiu $t1, -1", true),
+    ("teqi $t0, -1", true),
+    ("tnei $t0, -1", false),
+  ] {
+    let (_, exception) = fault(&format!("li $t0, -1\nli $t1, 1\n{trap}\nsyscall"));
+
+    let expected = match taken {
+      true => Exception::Trap,
+      false => Exception::Syscall,
+    };
+    k9::assert_equal!(exception, expected, "{trap}");
+  }
+}
+
+#[test]
+fn registers_start_as_in_mars() {
+  let cpu = Cpu::new(mips_test::program(&[syscall()]));
+
+  for n in 0..32 {
+    let expected = match n {
+      28 => GLOBAL_POINTER,
+      29 => STACK_POINTER,
+      _ => 0,
+    };
+    k9::assert_equal!(register(&cpu, n), expected, "register {n}");
+  }
+  k9::assert_equal!(cpu.registers().pc, TEXT_START);
+
+  // the stack is backed by memory
+  let mut cpu = run(
+    "
+    addiu $sp, $sp, -8
+    li $t0, 42
+    sw $t0, 4($sp)
+    lw $t1, 4($sp)
+    sw $t0, -4($gp)
+    syscall
+  ",
+  );
+  k9::assert_equal!(register(&cpu, T1), 42);
+  k9::assert_equal!(cpu.memory_mut().load_word(STACK_POINTER - 4), Ok(42));
+  k9::assert_equal!(cpu.memory_mut().load_word(GLOBAL_POINTER - 4), Ok(42));
+}
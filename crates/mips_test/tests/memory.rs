@@ -0,0 +1,116 @@
+use mips_cpu::exception::Exception;
+use mips_cpu::mem::{DATA_START, HEAP_START, KDATA_END, TEXT_START};
+use mips_cpu::Cpu;
+use mips_program::{Context, Section};
+use mips_test::asm::*;
+
+fn cpu() -> Cpu {
+  Cpu::new(mips_test::program(&[addi(8, 0, 1), addi(9, 0, 2)]))
+}
+
+#[test]
+fn ranges_report_initialised_bytes() {
+  let cpu = cpu();
+  let text = cpu.memory().read_range(TEXT_START + 4, 8, Context::User);
+
+  let word = addi(9, 0, 2).to_le_bytes().map(Some);
+  k9::assert_equal!(text, Ok([&word[..], &[None; 4]].concat()));
+}
+
+#[test]
+fn ranges_span_sections_and_segments() {
+  let mut cpu = cpu();
+  let memory = cpu.memory_mut();
+
+  // .extern into .data
+  memory
+    .write_range(DATA_START - 2, &[1, 2, 3], Context::User)
+    .unwrap();
+  // across two heap segments
+  memory
+    .write_range(HEAP_START + 2046, &[4, 5, 6, 7], Context::User)
+    .unwrap();
+
+  // bytes never written are not reported, although loads find them zeroed
+  k9::assert_equal!(
+    memory.read_range(DATA_START - 3, 5, Context::User),
+    Ok(vec![None, Some(1), Some(2), Some(3), None])
+  );
+  k9::assert_equal!(
+    memory.read_range(HEAP_START + 2045, 6, Context::User),
+    Ok(vec![None, Some(4), Some(5), Some(6), Some(7), None])
+  );
+  k9::assert_equal!(memory.load_word(HEAP_START + 2048), Ok(0x0706));
+  k9::assert_equal!(memory.load_byte(HEAP_START + 2050), Ok(0));
+
+  let regions = memory.iter_mapped_regions().collect::<Vec<_>>();
+  k9::assert_equal!(
+    regions,
+    vec![
+      (Section::Text, TEXT_START..TEXT_START + 8),
+      (Section::Extern, DATA_START - 2..DATA_START),
+      (Section::Data, DATA_START..DATA_START + 1),
+      (Section::Heap, HEAP_START + 2046..HEAP_START + 2050),
+    ]
+  );
+}
+
+#[test]
+fn written_extents_merge() {
+  let mut cpu = cpu();
+  let memory = cpu.memory_mut();
+
+  for (offset, len) in [(8, 4), (0, 2), (4, 4), (2, 2), (32, 1)] {
+    memory
+      .write_range(HEAP_START + offset, &vec![0xff; len], Context::User)
+      .unwrap();
+  }
+
+  let regions = memory
+    .iter_mapped_regions()
+    .filter(|(section, _)| *section == Section::Heap)
+    .map(|(_, region)| region)
+    .collect::<Vec<_>>();
+  k9::assert_equal!(
+    regions,
+    vec![
+      HEAP_START..HEAP_START + 12,
+      HEAP_START + 32..HEAP_START + 33
+    ]
+  );
+}
+
+#[test]
+fn ranges_stop_at_unmapped_memory() {
+  let mut cpu = cpu();
+  let memory = cpu.memory_mut();
+
+  k9::assert_equal!(
+    memory.read_range(0, 4, Context::External),
+    Err(Exception::AddrLoadFetch)
+  );
+  k9::assert_equal!(
+    memory
+      .read_range(KDATA_END - 1, 4, Context::External)
+      .map(|bytes| bytes.len()),
+    Ok(2)
+  );
+  k9::assert_equal!(
+    memory.write_range(TEXT_START, &[0; 4], Context::User),
+    Err(Exception::AddrStore)
+  );
+}
+
+#[test]
+fn running_past_the_program_raises_an_address_error() {
+  let mut cpu = cpu();
+  cpu.cycle().unwrap();
+  cpu.cycle().unwrap();
+
+  k9::assert_equal!(cpu.cycle(), Err(Exception::AddrLoadFetch));
+  k9::assert_equal!(cpu.registers().pc, TEXT_START + 8);
+  k9::assert_equal!(
+    cpu.memory().fetch(TEXT_START + 8),
+    Err(Exception::AddrLoadFetch)
+  );
+}
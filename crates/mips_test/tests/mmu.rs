@@ -0,0 +1,196 @@
+use mips_cpu::exception::Exception;
+use mips_cpu::mem::DATA_START;
+use mips_cpu::mmu::{TlbEntry, ENTRY_LO_D, ENTRY_LO_G, ENTRY_LO_V};
+use mips_cpu::{cop0, Cpu};
+use mips_program::{Context, ProgramData};
+use mips_test::asm::*;
+use mips_test::reg::{K0, T0, T1, T2, T3, V0};
+use mips_test::register;
+use std::rc::Rc;
+
+/// Stores 42 to `.data`, loads it back, then exits.
+fn store_load() -> Vec<u32> {
+  vec![
+    lui(T1, 0x1001),
+    addi(T0, 0, 42),
+    sw(T0, 0, T1),
+    lw(T2, 0, T1),
+    addi(V0, 0, 10),
+    syscall(),
+  ]
+}
+
+/// Refill handler mapping the missing page onto the physical page with the
+/// same number. The ASID is 0, so `EntryHi` only holds the page number.
+fn identity_refill() -> Vec<u32> {
+  vec![
+    mfc0(K0, cop0::ENTRY_HI),
+    ori(K0, K0, (ENTRY_LO_V | ENTRY_LO_D | ENTRY_LO_G) as i32),
+    mtc0(K0, cop0::ENTRY_LO),
+    tlbwr(),
+    eret(),
+  ]
+}
+
+fn mapped_cpu(program: Rc<ProgramData>) -> Cpu {
+  let mut cpu = Cpu::new(program);
+  cpu.memory_mut().mmu_mut().enabled = true;
+  cpu
+}
+
+fn valid_entries(cpu: &Cpu) -> usize {
+  let entries = cpu.memory().mmu().entries();
+  entries.iter().filter(|e| e.lo & ENTRY_LO_V != 0).count()
+}
+
+#[test]
+fn refill_handler_maps_pages() {
+  let program = mips_test::kernel_program(&store_load(), &identity_refill());
+  let mut cpu = mapped_cpu(program);
+
+  k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::Syscall));
+  k9::assert_equal!(register(&cpu, T2), 42);
+  // one page of code, one page of data
+  k9::assert_equal!(valid_entries(&cpu), 2);
+  k9::assert_equal!(cpu.registers().cop0.status & cop0::STATUS_EXL, 0);
+}
+
+#[test]
+fn miss_is_recorded_when_delivered() {
+  // the refill handler stops right away
+  let program = mips_test::kernel_program(&store_load(), &[syscall()]);
+  let mut cpu = mapped_cpu(program);
+
+  k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::Syscall));
+  k9::assert_equal!(cpu.registers().pc, 0x80000000);
+
+  let cop0 = &cpu.registers().cop0;
+  k9::assert_equal!(cop0.bad_vaddr, 0x00400000);
+  k9::assert_equal!(cop0.epc, 0x00400000);
+  k9::assert_equal!(cop0.entry_hi, 0x00400000);
+  k9::assert_equal!(cop0.context, 0x00400 << 2);
+  k9::assert_equal!(cop0.cause >> 2 & 0x1f, Exception::TlbLoad as u32);
+  k9::assert_equal!(cop0.status & cop0::STATUS_EXL, cop0::STATUS_EXL);
+}
+
+#[test]
+fn miss_without_handler_leaves_cop0_alone() {
+  let mut cpu = mapped_cpu(mips_test::program(&store_load()));
+  let before = cpu.registers().cop0.clone();
+
+  k9::assert_equal!(cpu.cycle(), Err(Exception::TlbLoad));
+  k9::assert_equal!(cpu.registers().pc, 0x00400000);
+
+  let cop0 = &cpu.registers().cop0;
+  k9::assert_equal!(cop0.bad_vaddr, before.bad_vaddr);
+  k9::assert_equal!(cop0.epc, before.epc);
+  k9::assert_equal!(cop0.entry_hi, before.entry_hi);
+  k9::assert_equal!(cop0.context, before.context);
+  k9::assert_equal!(cop0.cause, before.cause);
+  k9::assert_equal!(cop0.status, before.status);
+}
+
+#[test]
+fn context_keeps_the_page_table_base_on_kernel_misses() {
+  let mut cop0 = cop0::Cop0::init();
+  cop0.write(cop0::CONTEXT, 0x1200_0000);
+  cop0.raise(Exception::TlbLoad, 0x8000_0180, Some(0xc000_1234));
+
+  // BadVPN holds bits 30 to 12 of the address, bit 31 does not reach the base
+  k9::assert_equal!(cop0.context, 0x1200_0000 | 0x4_0001 << 2);
+  k9::assert_equal!(cop0.bad_vaddr, 0xc000_1234);
+}
+
+#[test]
+fn pages_are_translated_and_protected() {
+  let program = mips_test::program(&[
+    lui(T1, 0x7fff),
+    addi(T0, 0, 7),
+    sw(T0, 4, T1),
+    lui(T1, 0x0040),
+    sw(T0, 0, T1),
+  ]);
+  let mut cpu = mapped_cpu(program);
+
+  let mmu = cpu.memory_mut().mmu_mut();
+  // read-only code page
+  mmu.set_entry(
+    0,
+    TlbEntry {
+      hi: 0x00400000,
+      lo: 0x00400000 | ENTRY_LO_V,
+    },
+  );
+  // 0x7fff0000 is backed by .data
+  mmu.set_entry(
+    1,
+    TlbEntry {
+      hi: 0x7fff0000,
+      lo: DATA_START | ENTRY_LO_V | ENTRY_LO_D,
+    },
+  );
+
+  k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::TlbMod));
+  k9::assert_equal!(cpu.memory().fault_addr(), Some(0x00400000));
+
+  cpu.memory_mut().mmu_mut().enabled = false;
+  k9::assert_equal!(cpu.memory().read_byte(DATA_START + 4, Context::User), Ok(7));
+}
+
+#[test]
+fn user_mode_cannot_reach_kernel_segments() {
+  let program = mips_test::program(&[lui(T1, 0x8000), lw(T0, 0, T1)]);
+  let mut cpu = mapped_cpu(program);
+
+  cpu.memory_mut().mmu_mut().set_entry(
+    0,
+    TlbEntry {
+      hi: 0x00400000,
+      lo: 0x00400000 | ENTRY_LO_V | ENTRY_LO_G,
+    },
+  );
+
+  k9::assert_equal!(
+    mips_test::run(&mut cpu, 100),
+    Some(Exception::AddrLoadFetch)
+  );
+  k9::assert_equal!(cpu.memory().fault_addr(), Some(0x80000000));
+}
+
+#[test]
+fn tlb_instructions() {
+  let program = mips_test::program(&[
+    // write entry 5
+    lui(T0, 0x1234),
+    mtc0(T0, cop0::ENTRY_HI),
+    lui(T1, 0x0040),
+    ori(T1, T1, ENTRY_LO_V as i32),
+    mtc0(T1, cop0::ENTRY_LO),
+    addi(T2, 0, 5 << 8),
+    mtc0(T2, cop0::INDEX),
+    tlbwi(),
+    // probe a page which is not mapped
+    lui(T0, 0x0040),
+    mtc0(T0, cop0::ENTRY_HI),
+    tlbp(),
+    mfc0(T3, cop0::INDEX),
+    // read entry 5 back
+    mtc0(T2, cop0::INDEX),
+    tlbr(),
+    addi(V0, 0, 10),
+    syscall(),
+  ]);
+  let mut cpu = Cpu::new(program);
+
+  k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::Syscall));
+
+  let entry = cpu.memory().mmu().entry(5).unwrap();
+  k9::assert_equal!(entry.hi, 0x12340000);
+  k9::assert_equal!(entry.lo, 0x00400000 | ENTRY_LO_V);
+
+  k9::assert_equal!(
+    register(&cpu, T3) & cop0::INDEX_PROBE_FAILURE,
+    cop0::INDEX_PROBE_FAILURE
+  );
+  k9::assert_equal!(cpu.registers().cop0.entry_hi, 0x12340000);
+}
@@ -0,0 +1,149 @@
+use mips_cpu::exception::Exception;
+use mips_cpu::mem::Access;
+use mips_cpu::observer::CpuObserver;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Logs every callback it gets.
+#[derive(Default)]
+struct Log(Vec<String>);
+
+impl CpuObserver for Log {
+  fn on_cycle_start(&mut self, cycle: u64, pc: u32) {
+    self.0.push(format!("start {cycle} {pc:#x}"));
+  }
+
+  fn on_fetch(&mut self, pc: u32, word: u32) {
+    self.0.push(format!("fetch {pc:#x} {word:#010x}"));
+  }
+
+  fn on_register_write(&mut self, pc: u32, reg: u8, value: u32) {
+    self.0.push(format!("register {pc:#x} {reg} {value}"));
+  }
+
+  fn on_memory_read(&mut self, pc: u32, access: &Access) {
+    self
+      .0
+      .push(format!("read {pc:#x} {:#x} {}", access.addr, access.value));
+  }
+
+  fn on_memory_write(&mut self, pc: u32, access: &Access) {
+    self
+      .0
+      .push(format!("write {pc:#x} {:#x} {}", access.addr, access.value));
+  }
+
+  fn on_branch(&mut self, pc: u32, target: u32, taken: bool) {
+    self.0.push(format!("branch {pc:#x} {target:#x} {taken}"));
+  }
+
+  fn on_syscall(&mut self, pc: u32, code: u32) {
+    self.0.push(format!("syscall {pc:#x} {code}"));
+  }
+
+  fn on_exception(&mut self, pc: u32, exception: Exception) {
+    self.0.push(format!("exception {pc:#x} {exception:?}"));
+  }
+
+  fn on_cycle_end(&mut self, pc: u32) {
+    self.0.push(format!("end {pc:#x}"));
+  }
+}
+
+/// Run `source` for `cycles` cycles and return the log of each cycle.
+fn log(source: &str, cycles: usize) -> Vec<Vec<String>> {
+  let mut cpu = mips_test::cpu(source);
+  let log = Rc::new(RefCell::new(Log::default()));
+  cpu.add_observer(log.clone());
+
+  (0..cycles)
+    .map(|_| {
+      let _ = cpu.cycle();
+      std::mem::take(&mut log.borrow_mut().0)
+    })
+    .collect()
+}
+
+#[test]
+fn hooks_follow_the_cycle() {
+  let cycles = log(
+    "
+    lui $t0, 0x1001
+    li $t1, 7
+    sw $t1, 0($t0)
+    lw $t1, 0($t0)
+    beq $t1, $zero, skip
+    multu $t1, $t1
+    skip:
+    li $v0, 10
+    syscall
+  ",
+    8,
+  );
+
+  k9::assert_equal!(
+    cycles[2],
+    vec![
+      "start 2 0x400008",
+      "fetch 0x400008 0xad090000",
+      "write 0x400008 0x10010000 7",
+      "end 0x400008",
+    ]
+  );
+  // the load rewrites the value $t1 already holds
+  k9::assert_equal!(
+    cycles[3],
+    vec![
+      "start 3 0x40000c",
+      "fetch 0x40000c 0x8d090000",
+      "read 0x40000c 0x10010000 7",
+      "register 0x40000c 9 7",
+      "end 0x40000c",
+    ]
+  );
+  // a branch which is not taken reports where it would have gone
+  k9::assert_equal!(
+    cycles[4],
+    vec![
+      "start 4 0x400010",
+      "fetch 0x400010 0x11200001",
+      "branch 0x400010 0x400018 false",
+      "end 0x400010",
+    ]
+  );
+  // registers come by number, HI is 32 and LO 33
+  k9::assert_equal!(
+    cycles[5][2..4],
+    ["register 0x400014 32 0", "register 0x400014 33 49"]
+  );
+  k9::assert_equal!(
+    cycles[7],
+    vec![
+      "start 7 0x40001c",
+      "fetch 0x40001c 0x0000000c",
+      "syscall 0x40001c 10",
+      "end 0x40001c",
+    ]
+  );
+}
+
+#[test]
+fn hooks_report_exceptions_after_writes() {
+  let cycles = log(
+    "
+    lui $t0, 0x1001
+    lw $t1, 2($t0)
+  ",
+    2,
+  );
+
+  k9::assert_equal!(
+    cycles[1],
+    vec![
+      "start 1 0x400004",
+      "fetch 0x400004 0x8d090002",
+      "exception 0x400004 AddrLoadFetch",
+      "end 0x400004",
+    ]
+  );
+}
@@ -0,0 +1,186 @@
+use mips_cpu::exception::Exception;
+use mips_cpu::mem::DATA_START;
+use mips_cpu::pipeline::{Pipeline, PipelineConfig};
+use mips_cpu::Cpu;
+use mips_program::{Context, ProgramData};
+use mips_test::asm::*;
+use mips_test::reg::{A0, RA, S0, T0, T1, T2, T3, V0};
+use mips_test::register;
+use std::rc::Rc;
+
+/// Stores 10..1 to `.data`, then sums them back.
+fn array_sum() -> Rc<ProgramData> {
+  mips_test::program(&[
+    lui(T1, 0x1001),
+    addi(T0, 0, 10),
+    addi(T3, 0, 0),
+    // store loop
+    sw(T0, 0, T1),
+    addi(T1, T1, 4),
+    addi(T0, T0, -1),
+    bne(T0, 0, -4),
+    lui(T1, 0x1001),
+    addi(T0, 0, 10),
+    // sum loop, with a load-use hazard
+    lw(T2, 0, T1),
+    add(T3, T3, T2),
+    addi(T1, T1, 4),
+    addi(T0, T0, -1),
+    bne(T0, 0, -5),
+    addi(V0, 0, 10),
+    syscall(),
+  ])
+}
+
+/// Calls a function, then does some floating point arithmetic.
+fn call_and_float() -> Rc<ProgramData> {
+  mips_test::program(&[
+    addi(A0, 0, 6),
+    jal(0x00400018),
+    add(S0, V0, 0),
+    mtc1(S0, 0),
+    cvt_s_w(1, 0),
+    j(0x00400028),
+    // 0x00400018: v0 = a0 * a0 * 2
+    multu(A0, A0),
+    mflo(V0),
+    sll(V0, V0, 1),
+    jr(RA),
+    // 0x00400028
+    mul_s(2, 1, 1),
+    add_s(3, 2, 1),
+    lui(T1, 0x1001),
+    swc1(3, 8, T1),
+    sw(S0, 0, T1),
+    beq(0, 0, 1),
+    addi(S0, 0, -1),
+    addi(V0, 0, 10),
+    syscall(),
+  ])
+}
+
+fn run_functional(program: &Rc<ProgramData>) -> (Cpu, Option<Exception>) {
+  let mut cpu = Cpu::new(Rc::clone(program));
+  let exception = mips_test::run(&mut cpu, 10_000);
+  (cpu, exception)
+}
+
+fn run_pipelined(
+  program: &Rc<ProgramData>,
+  config: PipelineConfig,
+) -> (Pipeline, Option<Exception>) {
+  let mut pipeline = Pipeline::new(Cpu::new(Rc::clone(program)), config);
+  let exception = (0..10_000).find_map(|_| pipeline.clock().err());
+  pipeline.drain();
+  (pipeline, exception)
+}
+
+fn assert_same_state(functional: &Cpu, pipelined: &Cpu) {
+  let (a, b) = (functional.registers(), pipelined.registers());
+
+  k9::assert_equal!(a.numbered_values(), b.numbered_values());
+  k9::assert_equal!(a.pc, b.pc);
+  k9::assert_equal!(functional.cycles(), pipelined.cycles());
+
+  for addr in DATA_START..DATA_START + 64 {
+    k9::assert_equal!(
+      functional.memory().read_byte(addr, Context::User),
+      pipelined.memory().read_byte(addr, Context::User)
+    );
+  }
+}
+
+fn configs() -> Vec<PipelineConfig> {
+  let mut configs = Vec::new();
+
+  for forwarding in [true, false] {
+    for early_branches in [true, false] {
+      configs.push(PipelineConfig {
+        forwarding,
+        early_branches,
+        diagram: true,
+      });
+    }
+  }
+
+  configs
+}
+
+#[test]
+fn same_results_as_functional_core() {
+  for program in [array_sum(), call_and_float()] {
+    let (functional, expected) = run_functional(&program);
+    k9::assert_equal!(expected, Some(Exception::Syscall));
+
+    for config in configs() {
+      let (pipeline, exception) = run_pipelined(&program, config);
+      let stats = pipeline.stats();
+
+      k9::assert_equal!(exception, expected);
+      assert_same_state(&functional, pipeline.cpu());
+
+      // the syscall does not complete
+      k9::assert_equal!(stats.instructions, functional.cycles() - 1);
+    }
+  }
+}
+
+#[test]
+fn array_sum_result() {
+  let (pipeline, _) = run_pipelined(&array_sum(), PipelineConfig::default());
+  k9::assert_equal!(register(pipeline.cpu(), T3), 55);
+}
+
+#[test]
+fn load_use_and_branch_penalties() {
+  let (pipeline, _) = run_pipelined(&array_sum(), PipelineConfig::default());
+  let stats = pipeline.stats();
+
+  // one load-use stall per iteration of the sum loop
+  k9::assert_equal!(stats.load_use_stalls, 10);
+  k9::assert_equal!(stats.data_stalls, 10);
+  // 9 taken branches per loop, squashing 2 instructions each
+  k9::assert_equal!(stats.flushes, 36);
+  // 96 instructions, 4 cycles to fill the pipeline
+  k9::assert_equal!(stats.cycles, 96 + 4 + 10 + 36);
+
+  let early = PipelineConfig {
+    early_branches: true,
+    ..Default::default()
+  };
+  let (pipeline, _) = run_pipelined(&array_sum(), early);
+  let stats = pipeline.stats();
+
+  // each bne now waits one cycle for the addi right before it
+  k9::assert_equal!(stats.data_stalls, 30);
+  k9::assert_equal!(stats.flushes, 18);
+}
+
+#[test]
+fn no_forwarding_stalls_until_write_back() {
+  let config = PipelineConfig {
+    forwarding: false,
+    ..Default::default()
+  };
+  let (pipeline, _) = run_pipelined(&array_sum(), config);
+
+  // lw -> add and addi -> bne, 2 cycles each; the first sw and lw wait for the
+  // setup instructions, the syscall for $v0
+  k9::assert_equal!(pipeline.stats().data_stalls, 10 * 2 + 20 * 2 + 1 + 1 + 2);
+}
+
+#[test]
+fn diagram_has_one_line_per_cycle() {
+  let config = PipelineConfig {
+    diagram: true,
+    ..Default::default()
+  };
+  let (pipeline, _) = run_pipelined(&call_and_float(), config);
+
+  let mut out = Vec::new();
+  pipeline.write_diagram(&mut out).unwrap();
+  let diagram = String::from_utf8(out).unwrap();
+
+  k9::assert_equal!(diagram.lines().count() as u64, pipeline.stats().cycles + 1);
+  assert!(diagram.contains("jal 0x00400018"));
+}
@@ -0,0 +1,160 @@
+use mips_asm::assemble;
+use mips_cpu::exception::Exception;
+use mips_cpu::predict::{
+  BranchSimulator,
+  BranchStats,
+  Btb,
+  Gshare,
+  OneBit,
+  Predictor,
+  PredictorKind,
+  Static,
+  TwoBit,
+};
+use mips_cpu::Cpu;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const PC: u32 = 0x0040_0000;
+const TARGET: u32 = 0x0040_0100;
+
+/// Feed `outcomes` of the branch at `pc` to `predictor`, returning which were
+/// predicted correctly.
+fn predict(predictor: &mut dyn Predictor, pc: u32, outcomes: &str) -> Vec<bool> {
+  outcomes
+    .chars()
+    .map(|outcome| {
+      let taken = outcome == 'T';
+      let correct = predictor.predict(pc, 0).matches(taken, TARGET);
+      predictor.update(pc, taken, TARGET);
+      correct
+    })
+    .collect()
+}
+
+#[test]
+fn static_predictors_ignore_outcomes() {
+  k9::assert_equal!(
+    predict(&mut Static::new(true), PC, "TTNT"),
+    vec![true, true, false, true]
+  );
+  k9::assert_equal!(
+    predict(&mut Static::new(false), PC, "TTNT"),
+    vec![false, false, true, false]
+  );
+}
+
+#[test]
+fn one_bit_repeats_the_last_outcome() {
+  k9::assert_equal!(
+    predict(&mut OneBit::new(4), PC, "TTNTTN"),
+    vec![false, true, false, false, true, false]
+  );
+}
+
+#[test]
+fn two_bit_tolerates_a_single_change() {
+  // the loop exit only costs one misprediction, not two
+  k9::assert_equal!(
+    predict(&mut TwoBit::new(4), PC, "TTNTTN"),
+    vec![false, true, false, true, true, false]
+  );
+
+  // alternating outcomes keep it between the weak states
+  k9::assert_equal!(predict(&mut TwoBit::new(4), PC, "TNTNTN"), vec![false; 6]);
+}
+
+#[test]
+fn tables_are_indexed_by_the_low_address_bits() {
+  // 4 entries: words 0 and 4 share one
+  let mut predictor = OneBit::new(2);
+
+  predict(&mut predictor, PC, "T");
+  k9::assert_equal!(predict(&mut predictor, PC + 16, "T"), vec![true]);
+  k9::assert_equal!(predict(&mut predictor, PC + 4, "T"), vec![false]);
+}
+
+#[test]
+fn gshare_learns_patterns_from_the_history() {
+  k9::assert_equal!(
+    predict(&mut Gshare::new(2), PC, "TNTNTNTN"),
+    vec![false, true, false, true, true, true, true, true]
+  );
+}
+
+#[test]
+fn btb_predicts_the_target_of_taken_branches() {
+  let mut btb = Btb::new(4);
+
+  // unknown branches are predicted not taken
+  k9::assert_equal!(btb.predict(PC, 0).taken, false);
+  btb.update(PC, true, TARGET);
+
+  let prediction = btb.predict(PC, 0);
+  k9::assert_equal!(prediction.taken, true);
+  k9::assert_equal!(prediction.target, Some(TARGET));
+
+  // the right direction to another target is a misprediction
+  k9::assert_equal!(prediction.matches(true, TARGET + 8), false);
+  btb.update(PC, true, TARGET + 8);
+  k9::assert_equal!(btb.predict(PC, 0).target, Some(TARGET + 8));
+
+  // not taken branches do not enter the buffer, nor evict an entry
+  btb.update(PC + 64, false, TARGET);
+  k9::assert_equal!(btb.predict(PC + 64, 0).taken, false);
+  k9::assert_equal!(btb.predict(PC, 0).taken, true);
+
+  // a taken branch sharing the entry replaces it
+  btb.update(PC + 64, true, TARGET);
+  k9::assert_equal!(btb.predict(PC, 0).taken, false);
+  k9::assert_equal!(btb.predict(PC + 64, 0).taken, true);
+}
+
+#[test]
+fn every_kind_is_named() {
+  for name in PredictorKind::NAMES {
+    k9::assert_equal!(name.parse::<PredictorKind>().is_ok(), true, "{name}");
+  }
+
+  k9::assert_equal!("3bit".parse::<PredictorKind>().is_err(), true);
+}
+
+#[test]
+fn the_simulator_follows_conditional_branches() {
+  let program = Rc::new(
+    assemble(
+      "
+      main:
+      li $t0, 10
+      loop:
+      addi $t0, $t0, -1
+      bne $t0, $zero, loop
+      j end
+      end:
+      syscall
+    ",
+    )
+    .unwrap()
+    .program,
+  );
+
+  let predictor = PredictorKind::TwoBit.build(4);
+  let simulator = Rc::new(RefCell::new(BranchSimulator::new(&program, predictor)));
+
+  let mut cpu = Cpu::new(program);
+  cpu.add_observer(simulator.clone());
+  k9::assert_equal!(mips_test::run(&mut cpu, 1000), Some(Exception::Syscall));
+
+  // the jump is not a conditional branch
+  let stats = BranchStats {
+    executed: 10,
+    taken: 9,
+    correct: 8,
+  };
+
+  let simulator = simulator.borrow();
+  k9::assert_equal!(simulator.total(), stats);
+  k9::assert_equal!(simulator.by_site(), vec![(0x0040_0008, stats)]);
+  k9::assert_equal!(simulator.by_label(), vec![("loop".to_owned(), stats)]);
+  k9::assert_equal!(stats.accuracy(), 80.0);
+}
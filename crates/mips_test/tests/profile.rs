@@ -0,0 +1,79 @@
+use mips_asm::assemble;
+use mips_cpu::exception::Exception;
+use mips_cpu::profile::Profiler;
+use mips_cpu::Cpu;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Calls `square` three times from a loop, storing each result.
+const PROGRAM: &str = "
+  main:
+  lui $s1, 0x1001
+  li $s0, 3
+  loop:
+  move $a0, $s0
+  jal square
+  sw $v0, 0($s1)
+  addi $s0, $s0, -1
+  bne $s0, $zero, loop
+  li $v0, 10
+  syscall
+  square:
+  multu $a0, $a0
+  mflo $v0
+  jr $ra
+";
+
+fn profile() -> Rc<RefCell<Profiler>> {
+  let program = Rc::new(assemble(PROGRAM).unwrap().program);
+  let profiler = Rc::new(RefCell::new(Profiler::new(&program)));
+
+  let mut cpu = Cpu::new(program);
+  cpu.add_observer(profiler.clone());
+  k9::assert_equal!(mips_test::run(&mut cpu, 1000), Some(Exception::Syscall));
+
+  profiler
+}
+
+#[test]
+fn instructions_are_counted_per_address_and_label() {
+  let profiler = profile();
+  let profiler = profiler.borrow();
+
+  k9::assert_equal!(profiler.instructions(), 28);
+  k9::assert_equal!(profiler.count(0x0040_0000), 1);
+  k9::assert_equal!(profiler.count(0x0040_0008), 3);
+  k9::assert_equal!(profiler.count(0x0040_0024), 3);
+  k9::assert_equal!(profiler.count(0x0040_0030), 0);
+  k9::assert_equal!(
+    profiler.by_label(),
+    vec![
+      ("loop".to_owned(), 17),
+      ("square".to_owned(), 9),
+      ("main".to_owned(), 2),
+    ]
+  );
+}
+
+#[test]
+fn calls_are_attributed_to_stacks() {
+  let mut collapsed = Vec::new();
+  profile().borrow().write_collapsed(&mut collapsed).unwrap();
+
+  k9::assert_equal!(
+    String::from_utf8(collapsed).unwrap(),
+    "main 19\nmain;square 9\n"
+  );
+}
+
+#[test]
+fn report_lists_branches_and_accesses() {
+  let mut report = Vec::new();
+  profile().borrow().write_report(&mut report).unwrap();
+  let report = String::from_utf8(report).unwrap();
+
+  assert!(report.starts_with("instructions executed: 28\n"));
+  assert!(report.contains("  taken 2, not taken 1 (66.67% taken)\n"));
+  assert!(report.contains("  0x00400018 <loop+0x10>  taken 2, not taken 1 (66.67% taken)\n"));
+  assert!(report.contains("  .data    loads 0, stores 3\n"));
+}
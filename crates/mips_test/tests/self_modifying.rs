@@ -0,0 +1,94 @@
+use mips_cpu::exception::Exception;
+use mips_cpu::mem::TEXT_START;
+use mips_cpu::pipeline::{Pipeline, PipelineConfig};
+use mips_cpu::{disasm, Cpu};
+use mips_program::{Context, ProgramData};
+use mips_test::asm::*;
+use mips_test::reg::{T0, T1, T2, T3, V0};
+use mips_test::register;
+use std::rc::Rc;
+
+/// Patches the instruction following the store, then writes and reads back a
+/// word past the end of the code.
+fn patching() -> Rc<ProgramData> {
+  let patch = addi(T2, 0, 99);
+
+  mips_test::program(&[
+    lui(T1, 0x0040),
+    lui(T0, (patch >> 16) as i32),
+    ori(T0, T0, (patch & 0xffff) as i32),
+    sw(T0, 0x10, T1),
+    // replaced by the patch
+    addi(T2, 0, 1),
+    addi(T3, 0, 5),
+    sw(T3, 0x100, T1),
+    lw(T3, 0x100, T1),
+    addi(V0, 0, 10),
+    syscall(),
+  ])
+}
+
+#[test]
+fn text_is_read_only_by_default() {
+  let mut cpu = Cpu::new(patching());
+
+  k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::AddrStore));
+  k9::assert_equal!(cpu.memory().code_version(), 0);
+}
+
+#[test]
+fn debuggers_write_read_only_text() {
+  let mut cpu = Cpu::new(patching());
+  let patch = addi(T2, 0, 99).to_le_bytes();
+
+  k9::assert_equal!(
+    cpu
+      .memory_mut()
+      .write_range(TEXT_START + 0x10, &patch, Context::User),
+    Err(Exception::AddrStore)
+  );
+  k9::assert_equal!(
+    cpu
+      .memory_mut()
+      .write_range(TEXT_START + 0x10, &patch, Context::External),
+    Ok(())
+  );
+  k9::assert_equal!(cpu.memory().fetch(TEXT_START + 0x10), Ok(addi(T2, 0, 99)));
+}
+
+#[test]
+fn patched_instructions_are_executed() {
+  let mut cpu = Cpu::new(patching());
+  cpu.memory_mut().set_writable_text(true);
+
+  k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::Syscall));
+  k9::assert_equal!(register(&cpu, T2), 99);
+  k9::assert_equal!(register(&cpu, T3), 5);
+  k9::assert_equal!(cpu.memory().code_version(), 2);
+  k9::assert_equal!(cpu.memory().fetch(TEXT_START + 0x100), Ok(5));
+}
+
+#[test]
+fn pipeline_refetches_patched_instructions() {
+  let mut cpu = Cpu::new(patching());
+  cpu.memory_mut().set_writable_text(true);
+
+  let config = PipelineConfig {
+    diagram: true,
+    ..Default::default()
+  };
+  let mut pipeline = Pipeline::new(cpu, config);
+  let exception = (0..100).find_map(|_| pipeline.clock().err());
+  pipeline.drain();
+
+  k9::assert_equal!(exception, Some(Exception::Syscall));
+  k9::assert_equal!(register(pipeline.cpu(), T2), 99);
+
+  let mut out = Vec::new();
+  pipeline.write_diagram(&mut out).unwrap();
+  let diagram = String::from_utf8(out).unwrap();
+
+  // without a refetch, the word fetched before the store would be kept
+  let patched = disasm::disassemble(addi(T2, 0, 99), TEXT_START + 0x10);
+  assert!(diagram.contains(&patched));
+}
@@ -0,0 +1,238 @@
+use mips_cpu::console::Buffered;
+use mips_cpu::environment::Environment;
+use mips_cpu::fs::{self, Memory};
+use mips_cpu::snapshot::{Snapshot, SnapshotError};
+use mips_cpu::symbols::Symbols;
+use mips_cpu::Cpu;
+use mips_test::reg::{S0, S5};
+use mips_test::{register, run_syscalls, run_syscalls_to};
+
+/// Reads from a file and the standard input, writes to a file and draws a
+/// random number on each side of `half`.
+const PROGRAM: &str = "
+  .data
+  input: .asciiz \"in.txt\"
+  output: .asciiz \"out.txt\"
+  buffer: .space 16
+  .text
+  la $a0, input
+  li $a1, 0
+  li $v0, 13
+  syscall
+  move $s0, $v0
+  la $a0, output
+  li $a1, 1
+  li $v0, 13
+  syscall
+  move $s1, $v0
+  move $a0, $s0
+  la $a1, buffer
+  li $a2, 3
+  li $v0, 14
+  syscall
+  move $a0, $s1
+  la $a1, buffer
+  li $a2, 3
+  li $v0, 15
+  syscall
+  li $a0, 0
+  la $a1, buffer
+  li $a2, 2
+  li $v0, 14
+  syscall
+  li $a0, 1
+  li $a1, 100
+  li $v0, 42
+  syscall
+  move $s2, $a0
+  li $v0, 30
+  syscall
+  move $s3, $a0
+  li $v0, 11
+  li $a0, 62
+  syscall
+  half:
+  move $a0, $s0
+  la $a1, buffer
+  li $a2, 3
+  li $v0, 14
+  syscall
+  move $a0, $s1
+  la $a1, buffer
+  li $a2, 3
+  li $v0, 15
+  syscall
+  li $a0, 0
+  la $a1, buffer
+  li $a2, 8
+  li $v0, 14
+  syscall
+  move $a0, $v0
+  li $v0, 1
+  syscall
+  li $a0, 1
+  li $a1, 100
+  li $v0, 42
+  syscall
+  move $s4, $a0
+  li $v0, 30
+  syscall
+  move $s5, $a0
+  li $v0, 10
+  syscall
+";
+
+fn cpu(files: &Memory) -> Cpu {
+  let mut cpu = mips_test::cpu(PROGRAM);
+  cpu.set_file_system(files.clone());
+  *cpu.environment_mut() = Environment::deterministic();
+  cpu
+}
+
+fn registers(cpu: &Cpu) -> Vec<u32> {
+  (S0..=S5).map(|n| register(cpu, n)).collect()
+}
+
+#[test]
+fn snapshots_round_trip_through_bytes() {
+  let files = Memory::default().with_file("in.txt", "abcdef");
+  let mut console = Buffered::default();
+  console.push_input("12345");
+
+  let mut cpu = cpu(&files);
+  let half = Symbols::from_program(cpu.program())
+    .get("half")
+    .unwrap()
+    .addr;
+  run_syscalls_to(&mut cpu, &mut console, half).unwrap();
+
+  let mut bytes = Vec::new();
+  cpu
+    .snapshot()
+    .with_console(&console)
+    .write_to(&mut bytes)
+    .unwrap();
+
+  run_syscalls(&mut cpu, &mut console).unwrap();
+  let expected = registers(&cpu);
+  k9::assert_equal!(console.output(), ">4");
+  k9::assert_equal!(files.contents("out.txt").unwrap(), b"abcdef".to_vec());
+
+  // a new machine on the same files, cutting what was written after the
+  // snapshot
+  let snapshot = Snapshot::read_from(bytes.as_slice()).unwrap();
+  let mut console = snapshot.console().unwrap().clone();
+  let mut restored = cpu_for_restore(&files);
+  k9::assert_equal!(restored.restore(snapshot), Vec::new());
+  k9::assert_equal!(restored.registers().pc, half);
+  k9::assert_equal!(files.contents("out.txt").unwrap(), b"abc".to_vec());
+  k9::assert_equal!(
+    restored.files().descriptors().collect::<Vec<_>>(),
+    vec![3, 4]
+  );
+
+  run_syscalls(&mut restored, &mut console).unwrap();
+  k9::assert_equal!(registers(&restored), expected);
+  k9::assert_equal!(console.output(), ">4");
+  k9::assert_equal!(files.contents("out.txt").unwrap(), b"abcdef".to_vec());
+}
+
+/// A machine running some other program, which the snapshot replaces.
+fn cpu_for_restore(files: &Memory) -> Cpu {
+  let mut cpu = mips_test::cpu("nop");
+  cpu.set_file_system(files.clone());
+  cpu
+}
+
+#[test]
+fn files_which_are_gone_stay_closed() {
+  let files = Memory::default().with_file("in.txt", "abcdef");
+  let mut console = Buffered::default();
+  console.push_input("12345");
+
+  let mut cpu = cpu(&files);
+  let half = Symbols::from_program(cpu.program())
+    .get("half")
+    .unwrap()
+    .addr;
+  run_syscalls_to(&mut cpu, &mut console, half).unwrap();
+
+  // the input file is there, the output file is gone with what it held
+  let mut restored = cpu_for_restore(&Memory::default().with_file("in.txt", "abcdef"));
+  let lost = restored.restore(cpu.snapshot());
+  let lost = lost.iter().map(|d| (d.fd, d.mode)).collect::<Vec<_>>();
+
+  k9::assert_equal!(lost, vec![(4, fs::Mode::Write)]);
+  k9::assert_equal!(restored.files().descriptors().collect::<Vec<_>>(), vec![3]);
+}
+
+#[test]
+fn appended_files_are_cut_back_to_the_snapshot() {
+  let files = Memory::default().with_file("log.txt", "old ");
+  let mut cpu = mips_test::cpu(
+    "
+    .data
+    path: .asciiz \"log.txt\"
+    text: .ascii \"new\"
+    .text
+    la $a0, path
+    li $a1, 9
+    li $v0, 13
+    syscall
+    move $a0, $v0
+    la $a1, text
+    li $a2, 3
+    li $v0, 15
+    syscall
+    half:
+    li $v0, 15
+    syscall
+    li $v0, 10
+    syscall
+  ",
+  );
+  cpu.set_file_system(files.clone());
+  let half = Symbols::from_program(cpu.program())
+    .get("half")
+    .unwrap()
+    .addr;
+
+  let mut console = Buffered::default();
+  run_syscalls_to(&mut cpu, &mut console, half).unwrap();
+  let snapshot = cpu.snapshot();
+  run_syscalls(&mut cpu, &mut console).unwrap();
+  k9::assert_equal!(files.contents("log.txt").unwrap(), b"old newnew".to_vec());
+
+  let mut restored = cpu_for_restore(&files);
+  k9::assert_equal!(restored.restore(snapshot), Vec::new());
+  k9::assert_equal!(files.contents("log.txt").unwrap(), b"old new".to_vec());
+
+  run_syscalls(&mut restored, &mut console).unwrap();
+  k9::assert_equal!(files.contents("log.txt").unwrap(), b"old newnew".to_vec());
+}
+
+#[test]
+fn the_heap_limit_is_saved() {
+  let mut cpu = cpu(&Memory::default());
+  cpu.memory_mut().set_heap_size(0x100);
+
+  let mut restored = cpu_for_restore(&Memory::default());
+  restored.restore(cpu.snapshot());
+  k9::assert_equal!(restored.memory().heap_limit(), cpu.memory().heap_limit());
+  k9::assert_equal!(restored.memory().heap_limit(), 0x1004_0100);
+}
+
+#[test]
+fn other_versions_are_rejected() {
+  let mut bytes = Vec::new();
+  cpu(&Memory::default())
+    .snapshot()
+    .write_to(&mut bytes)
+    .unwrap();
+  bytes[6..8].copy_from_slice(b"P9");
+
+  assert!(matches!(
+    Snapshot::read_from(bytes.as_slice()),
+    Err(SnapshotError::BadMagic)
+  ));
+}
@@ -0,0 +1,133 @@
+use mips_cpu::trace::{Format, Tracer, MAGIC};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// Trace output kept in memory.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.borrow_mut().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+const PROGRAM: &str = "
+  li $t1, 5
+  addu $t1, $t1, $zero
+  lui $t0, 0x1001
+  sw $t1, 4($t0)
+  lw $t2, 4($t0)
+  syscall
+";
+
+/// Trace `PROGRAM` in `format` until its system call.
+fn trace(format: Format) -> Vec<u8> {
+  let output = Output::default();
+  let tracer = Rc::new(RefCell::new(Tracer::new(output.clone(), format)));
+
+  let mut cpu = mips_test::cpu(PROGRAM);
+  cpu.add_observer(tracer.clone());
+  mips_test::run(&mut cpu, 100);
+  tracer.borrow_mut().finish().unwrap();
+
+  let bytes = output.0.borrow().clone();
+  bytes
+}
+
+#[test]
+fn json_lines_hold_one_record_per_cycle() {
+  let trace = String::from_utf8(trace(Format::JsonLines)).unwrap();
+  let lines = trace.lines().collect::<Vec<_>>();
+
+  k9::assert_equal!(lines.len(), 6);
+  // the write leaves $t1 unchanged, it is still reported
+  k9::assert_equal!(
+    lines[1],
+    r#"{"cycle":1,"pc":4194308,"word":18892833,"asm":"addu $t1, $t1, $zero","regs":[{"reg":"$t1","value":5}],"mem":[],"exception":null}"#
+  );
+  k9::assert_equal!(
+    lines[3],
+    r#"{"cycle":3,"pc":4194316,"word":2903048196,"asm":"sw $t1, 4($t0)","regs":[],"mem":[{"kind":"store","addr":268500996,"size":4,"value":5}],"exception":null}"#
+  );
+  k9::assert_equal!(
+    lines[5],
+    r#"{"cycle":5,"pc":4194324,"word":12,"asm":"syscall","regs":[],"mem":[],"exception":"Syscall"}"#
+  );
+}
+
+#[test]
+fn binary_records_follow_the_header() {
+  let trace = trace(Format::Binary);
+  let (magic, mut records) = trace.split_at(MAGIC.len());
+  k9::assert_equal!(magic, MAGIC);
+
+  let mut take = |n: usize| {
+    let (bytes, rest) = records.split_at(n);
+    records = rest;
+    bytes.to_vec()
+  };
+
+  // addiu $t1, $zero, 5
+  k9::assert_equal!(take(8), 0u64.to_le_bytes().to_vec());
+  k9::assert_equal!(take(4), 0x0040_0000u32.to_le_bytes().to_vec());
+  k9::assert_equal!(take(4), 0x2409_0005u32.to_le_bytes().to_vec());
+  // fetched, no exception, writes $t1 and no memory
+  k9::assert_equal!(take(5), vec![0x1, 0, 1, 9, 5]);
+  k9::assert_equal!(take(4), vec![0, 0, 0, 0]);
+
+  // skip addu and lui, 25 bytes each
+  take(50);
+
+  // sw $t1, 4($t0)
+  take(16);
+  k9::assert_equal!(take(4), vec![0x1, 0, 0, 1]);
+  k9::assert_equal!(take(1), vec![0x84]);
+  k9::assert_equal!(take(4), 0x1001_0004u32.to_le_bytes().to_vec());
+  k9::assert_equal!(take(4), 5u32.to_le_bytes().to_vec());
+
+  // lw writes $t2 and loads a word, then syscall raises an exception
+  take(16 + 3 + 5 + 1 + 9);
+  take(16);
+  k9::assert_equal!(take(4), vec![0x3, 0x8, 0, 0]);
+  assert!(records.is_empty());
+}
+
+#[test]
+fn ranges_limit_the_trace() {
+  let output = Output::default();
+  let tracer = Tracer::new(output.clone(), Format::JsonLines).with_range(0x0040_0008..0x0040_0010);
+  let tracer = Rc::new(RefCell::new(tracer));
+
+  let mut cpu = mips_test::cpu(PROGRAM);
+  cpu.add_observer(tracer.clone());
+  mips_test::run(&mut cpu, 100);
+  tracer.borrow_mut().finish().unwrap();
+
+  let trace = String::from_utf8(output.0.borrow().clone()).unwrap();
+  let pcs = trace
+    .lines()
+    .map(|line| line.split(',').nth(1).unwrap().to_owned())
+    .collect::<Vec<_>>();
+  k9::assert_equal!(pcs, vec![r#""pc":4194312"#, r#""pc":4194316"#]);
+}
+
+#[test]
+fn writes_to_zero_are_discarded() {
+  let output = Output::default();
+  let tracer = Rc::new(RefCell::new(Tracer::new(output.clone(), Format::JsonLines)));
+
+  let mut cpu = mips_test::cpu("addiu $zero, $zero, 7");
+  cpu.add_observer(tracer.clone());
+  cpu.cycle().unwrap();
+  tracer.borrow_mut().finish().unwrap();
+
+  k9::assert_equal!(mips_test::register(&cpu, 0), 0);
+  let trace = String::from_utf8(output.0.borrow().clone()).unwrap();
+  assert!(trace.contains(r#""regs":[]"#), "{trace}");
+}
@@ -0,0 +1,137 @@
+use mips_cpu::exception::Exception;
+use mips_cpu::mem::{DATA_START, HEAP_START, TEXT_START};
+use mips_cpu::uninit::UninitChecker;
+use mips_cpu::Cpu;
+use mips_program::{Context, ProgramData, Section};
+use mips_test::asm::*;
+use mips_test::reg::{T0, T1, T2, T3};
+use mips_test::register;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Loads from `.text`, from a stored heap word and from `.data`, which is
+/// never written.
+fn loads() -> Rc<ProgramData> {
+  let words = [
+    lui(T0, 0x1001),
+    lui(T1, 0x1004),
+    lui(T2, 0x0040),
+    lw(T3, 0, T2),
+    sw(T2, 0, T1),
+    lw(T3, 0, T1),
+    lw(T3, 4, T0),
+    lw(T3, 4, T0),
+    syscall(),
+  ];
+  let text = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+
+  let builder = ProgramData::builder()
+    .text(text)
+    .label(Section::Text, "main", 0)
+    .label(Section::Data, "buffer", 0);
+
+  Rc::new(builder.build())
+}
+
+fn strict_cpu() -> (Cpu, Rc<RefCell<UninitChecker>>) {
+  let program = loads();
+  let mut cpu = Cpu::new(Rc::clone(&program));
+  cpu.memory_mut().set_strict(true);
+
+  let checker = Rc::new(RefCell::new(UninitChecker::new(&program)));
+  cpu.add_observer(checker.clone());
+
+  (cpu, checker)
+}
+
+#[test]
+fn loads_from_unwritten_memory_are_reported() {
+  let (mut cpu, checker) = strict_cpu();
+  k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::Syscall));
+
+  let checker = checker.borrow();
+  let reads = checker
+    .reads()
+    .iter()
+    .map(|r| (r.pc, r.access.addr, r.access.size))
+    .collect::<Vec<_>>();
+  k9::assert_equal!(
+    reads,
+    vec![
+      (TEXT_START + 0x18, DATA_START + 4, 4),
+      (TEXT_START + 0x1c, DATA_START + 4, 4),
+    ]
+  );
+
+  let mut out = Vec::new();
+  checker.write_report(&mut out).unwrap();
+  let report = String::from_utf8(out).unwrap();
+  assert!(report.starts_with("2 load(s) from uninitialised memory"));
+  assert!(report.contains("<main+0x18>"));
+  assert!(report.contains("<buffer+0x4>"));
+}
+
+#[test]
+fn strict_mode_is_optional() {
+  let program = loads();
+  let mut cpu = Cpu::new(Rc::clone(&program));
+  let checker = Rc::new(RefCell::new(UninitChecker::new(&program)));
+  cpu.add_observer(checker.clone());
+
+  k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::Syscall));
+  k9::assert_equal!(checker.borrow().reads().len(), 0);
+  k9::assert_equal!(register(&cpu, T3), 0);
+}
+
+#[test]
+fn tooling_writes_and_snapshots_initialise_memory() {
+  let (mut cpu, checker) = strict_cpu();
+  cpu
+    .memory_mut()
+    .write_range(DATA_START + 4, &[1, 2, 3, 4], Context::External)
+    .unwrap();
+  k9::assert_equal!(mips_test::run(&mut cpu, 100), Some(Exception::Syscall));
+  k9::assert_equal!(checker.borrow().reads().len(), 0);
+
+  // the restored heap word is part of the snapshot
+  let snapshot = cpu.snapshot();
+  let (mut cpu, _) = strict_cpu();
+  cpu.restore(snapshot);
+  k9::assert_equal!(cpu.memory().is_strict(), true);
+  k9::assert_equal!(cpu.memory_mut().load_word(HEAP_START), Ok(TEXT_START));
+  k9::assert_equal!(cpu.memory().uninit_reads().len(), 0);
+}
+
+#[test]
+fn enabling_strict_mode_keeps_untouched_bytes_uninitialised() {
+  let program = ProgramData::builder().data(vec![1, 2]).build();
+  let mut cpu = Cpu::new(Rc::new(program));
+
+  let memory = cpu.memory_mut();
+  memory.store_word(HEAP_START, 7).unwrap();
+  memory.set_strict(true);
+
+  // written and loaded from the program
+  k9::assert_equal!(memory.load_word(HEAP_START), Ok(7));
+  k9::assert_equal!(memory.load_halfword(DATA_START), Ok(0x0201));
+  k9::assert_equal!(memory.uninit_reads().len(), 0);
+
+  // in the same segment and the same continuous store, never written
+  k9::assert_equal!(memory.load_word(HEAP_START + 4), Ok(0));
+  k9::assert_equal!(memory.load_byte(HEAP_START + 2047), Ok(0));
+  k9::assert_equal!(memory.load_halfword(DATA_START + 2), Ok(0));
+
+  let reads = memory
+    .uninit_reads()
+    .iter()
+    .map(|r| (r.addr, r.size))
+    .collect::<Vec<_>>();
+  k9::assert_equal!(
+    reads,
+    vec![
+      (HEAP_START + 4, 4),
+      (HEAP_START + 2047, 1),
+      (DATA_START + 2, 2)
+    ]
+  );
+}